@@ -3,11 +3,7 @@ use std::io::{self, Write};
 use std::time::Instant;
 
 // Import everything upfront
-use sheet::function_ext;
-use sheet::graph_ext::{self, StateSnapshot};
-
-static mut NUM_CELLS: usize = 0;
-static mut HAS_CYCLE: bool = false;
+use sheet::grid_ext::Grid;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args: Vec<String> = env::args().collect();
@@ -40,35 +36,18 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
-// ✅ Snapshot function just for extended mode
-fn create_snapshot_extended(
-    arr: &[function_ext::Cell],
-    formula_array: &[graph_ext::Formula],
-    graph: &graph_ext::Graph,
-) -> graph_ext::StateSnapshot {
-    graph_ext::StateSnapshot {
-        arr: arr.to_owned(),
-        formula_array: formula_array.to_owned(),
-        graph: graph.clone(),
-    }
-}
-
 // ✅ Standard mode logic (unchanged)
 fn run_standard(r: usize, c: usize) {
     use sheet::display::{printer, scroller};
-    use sheet::function::*;
     use sheet::graph::{Formula, Graph};
-    use sheet::parser::parser;
+    use sheet::parser::{cell_parser, parser, ParseError};
 
     let start = Instant::now();
     let num_cells = r * c;
-    unsafe {
-        NUM_CELLS = num_cells;
-    }
 
     let mut graph = Graph::new(num_cells);
-    let mut formula_array = vec![Formula::default(); num_cells];
-    let mut arr = vec![0; num_cells];
+    let mut formula_array = Grid::new(num_cells, Formula::default());
+    let mut arr = Grid::new(num_cells, 0);
 
     let mut currx = 0;
     let mut curry = 0;
@@ -95,7 +74,8 @@ fn run_standard(r: usize, c: usize) {
         }
 
         let loop_start = Instant::now();
-        let mut status = 1;
+        let mut status = Ok(());
+        let mut message = None;
 
         match trimmed {
             "disable_output" => {
@@ -109,15 +89,25 @@ fn run_standard(r: usize, c: usize) {
                 print!("[{:.6}] (ok) ", loop_start.elapsed().as_secs_f64());
                 continue;
             }
+            _ if trimmed.starts_with("formula ") => {
+                let target = trimmed["formula ".len()..].trim();
+                match cell_parser(target, c, r, 0, target.len().saturating_sub(1), &graph) {
+                    Some(cell) => {
+                        let text = formula_array[cell].to_formula_string(cell, c);
+                        message = Some(format!("{}: {}", target, text));
+                    }
+                    None => status = Err(ParseError::Unrecognized),
+                }
+            }
             _ => {
                 if ['w', 'a', 's', 'd'].contains(&trimmed.chars().next().unwrap_or(' '))
                     || trimmed.starts_with("scroll_to ")
                 {
                     if scroller(trimmed, &mut arr, &mut currx, &mut curry, c, r, &mut graph) == -1 {
-                        status = -1;
+                        status = Err(ParseError::Unrecognized);
                     }
                 } else {
-                    status = parser(trimmed, c, r, &mut arr, &mut graph, &mut formula_array[..]);
+                    status = parser(trimmed, c, r, &mut arr, &mut graph, &mut formula_array);
                 }
             }
         }
@@ -125,20 +115,14 @@ fn run_standard(r: usize, c: usize) {
         if !output_disabled {
             printer(currx, curry, &arr, c, r);
         }
+        if let Some(message) = message {
+            println!("{}", message);
+        }
 
         let elapsed = loop_start.elapsed().as_secs_f64();
-        unsafe {
-            if status > 0 {
-                print!("[{:.6}] (ok) ", elapsed);
-            } else if HAS_CYCLE {
-                print!("[{:.6}] (Circular dependency detected) ", elapsed);
-                HAS_CYCLE = false;
-            } else if INVALID_RANGE {
-                print!("[{:.6}] (Invalid range) ", elapsed);
-                INVALID_RANGE = false;
-            } else {
-                print!("[{:.6}] (unrecognized command) ", elapsed);
-            }
+        match status {
+            Ok(()) => print!("[{:.6}] (ok) ", elapsed),
+            Err(e) => print!("[{:.6}] ({}) ", elapsed, e),
         }
     }
 }
@@ -146,26 +130,15 @@ fn run_standard(r: usize, c: usize) {
 // ✅ Extended mode logic with undo/redo and cell types
 fn run_extended(r: usize, c: usize) -> Result<(), Box<dyn std::error::Error>> {
     use sheet::display_ext::{printer, scroller};
-    use sheet::function_ext::Cell;
-    use sheet::graph_ext::{Formula, Graph, State};
-    use sheet::parser_ext::parser;
+    use sheet::Sheet;
 
-    let num_cells = r * c;
-    let cols_i32 = c as i32;
-    let rows_i32 = r as i32;
-
-    let mut arr = vec![Cell::new_int(0); num_cells];
-    let mut formula_array = vec![Formula::default(); num_cells];
-    let mut graph = Graph::new(num_cells);
-    let mut state = State::new();
-    let mut undo_stack: Vec<StateSnapshot> = Vec::new();
-    let mut redo_stack: Vec<StateSnapshot> = Vec::new();
+    let mut sheet = Sheet::new(r as i32, c as i32).with_undo_limit(5);
     let mut currx = 0;
     let mut curry = 0;
     let mut output_enabled = true;
 
     if output_enabled {
-        printer(currx, curry, &arr, cols_i32, rows_i32);
+        printer(currx, curry, sheet.cells(), sheet.cols(), sheet.rows());
     }
     print!("[0.0] (ok) > ");
     loop {
@@ -181,6 +154,7 @@ fn run_extended(r: usize, c: usize) -> Result<(), Box<dyn std::error::Error>> {
             break;
         }
 
+        let mut message = None;
         let result = match input {
             "disable_output" => {
                 output_enabled = false;
@@ -190,58 +164,47 @@ fn run_extended(r: usize, c: usize) -> Result<(), Box<dyn std::error::Error>> {
                 output_enabled = true;
                 Ok(())
             }
-            "w" | "a" | "s" | "d" => {
-                scroller(input, &arr, &mut currx, &mut curry, cols_i32, rows_i32, &graph)
-            }
-            _ if input.starts_with("scroll_to ") => {
-                let cmd = input.replacen("scroll_to ", "", 1);
-                scroller(
-                    &format!("scroll_to {}", cmd),
-                    &arr,
-                    &mut currx,
-                    &mut curry,
-                    cols_i32,
-                    rows_i32,
-                    &graph,
-                )
-            }
-            "undo" => {
-                if let Some(prev) = undo_stack.pop() {
-                    redo_stack.push(create_snapshot_extended(&arr, &formula_array, &graph));
-                    arr = prev.arr;
-                    formula_array = prev.formula_array;
-                    graph = prev.graph;
-                    Ok(())
-                } else {
-                    Err("Nothing to undo")
-                }
+            "w" | "a" | "s" | "d" => scroller(
+                input,
+                sheet.cells(),
+                &mut currx,
+                &mut curry,
+                sheet.cols(),
+                sheet.rows(),
+                sheet.graph(),
+            ),
+            _ if input.starts_with("scroll_to ") => scroller(
+                input,
+                sheet.cells(),
+                &mut currx,
+                &mut curry,
+                sheet.cols(),
+                sheet.rows(),
+                sheet.graph(),
+            ),
+            _ if input.starts_with("formula ") => {
+                let target = input["formula ".len()..].trim();
+                sheet.formula(target).map(|text| {
+                    let text = text.unwrap_or_else(|| "(no formula)".to_string());
+                    message = Some(format!("{}: {}", target, text));
+                })
             }
-            "redo" => {
-                if let Some(next) = redo_stack.pop() {
-                    undo_stack.push(create_snapshot_extended(&arr, &formula_array, &graph));
-                    arr = next.arr;
-                    formula_array = next.formula_array;
-                    graph = next.graph;
-                    Ok(())
-                } else {
-                    Err("Nothing to redo")
-                }
+            _ if input.starts_with("trace ") => {
+                let target = input["trace ".len()..].trim();
+                sheet.trace(target).map(|path| {
+                    let text = match path {
+                        Some(path) => path.join(" → "),
+                        None => "not part of a cycle".to_string(),
+                    };
+                    message = Some(format!("{}: {}", target, text));
+                })
             }
             _ => {
-                undo_stack.push(create_snapshot_extended(&arr, &formula_array, &graph));
-                if undo_stack.len() > 5 {
-                    undo_stack.remove(0);
-                }
-                redo_stack.clear();
-                parser(
-                    input,
-                    cols_i32,
-                    rows_i32,
-                    &mut arr,
-                    &mut graph,
-                    &mut formula_array,
-                    &mut state,
-                )
+                let result = sheet.run(input);
+                // A resize or undo may have shrunk the sheet.
+                currx = currx.min(sheet.cols() - 1);
+                curry = curry.min(sheet.rows() - 1);
+                result
             }
         };
 
@@ -249,13 +212,16 @@ fn run_extended(r: usize, c: usize) -> Result<(), Box<dyn std::error::Error>> {
         match result {
             Ok(_) => {
                 if output_enabled {
-                    printer(currx, curry, &arr, cols_i32, rows_i32);
+                    printer(currx, curry, sheet.cells(), sheet.cols(), sheet.rows());
+                }
+                if let Some(message) = message {
+                    println!("{}", message);
                 }
                 print!("[{:.1}] (ok) > ", elapsed);
             }
             Err(e) => {
                 if output_enabled {
-                    printer(currx, curry, &arr, cols_i32, rows_i32);
+                    printer(currx, curry, sheet.cells(), sheet.cols(), sheet.rows());
                 }
                 print!("[{:.1}] ({}) > ", elapsed, e);
             }
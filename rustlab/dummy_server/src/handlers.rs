@@ -2,34 +2,35 @@
 //!
 //! This module contains handler functions for the various API endpoints exposed by
 //! the spreadsheet server. It implements the core functionality for retrieving sheet data,
-//! updating cells, processing queries, and performing undo/redo operations.
+//! updating cells, processing queries, performing undo/redo operations, copying, cutting
+//! and pasting ranges, inserting and deleting rows and columns, and resizing the sheet.
 #![deny(clippy::all)]
 
 use axum::{
     body::Bytes,
     extract::{Json as ExtractJson, State},
+    http::StatusCode,
     response::IntoResponse,
     Json,
 };
 
 use crate::server_models::Sheet;
-use crate::types::{AppState, QueryResponse, UpdateCellRequest, UpdateResponse};
-
-use sheet::graph_ext::State as State1;
-use sheet::parser_ext::*;
-
-use crate::create_snapshot;
-use crate::types::UndoRedoResponse;
+use crate::types::{
+    AppState, ClipboardRequest, LineRequest, PasteRequest, QueryResponse, ResizeRequest,
+    UndoRedoResponse, UpdateCellRequest, UpdateResponse,
+};
 
-use sheet::{
-    function_ext::{Cell, CellValue},
-    graph_ext::{Formula, Graph},
+use sheet::date_ext::parse_iso;
+use sheet::error_ext::SheetError;
+use sheet::structure_ext::{
+    delete_col, delete_row, insert_col, insert_row, StructureEdit, MAX_COLS, MAX_ROWS,
 };
+use sheet::util_ext::column_label;
 
 /// Retrieves the current spreadsheet data.
 ///
-/// This handler returns the complete sheet data in a format suitable for
-/// client-side rendering.
+/// This handler returns the size of the sheet and its non-empty cells, built from the
+/// engine on each request, in a format suitable for client-side rendering.
 ///
 /// # Arguments
 ///
@@ -39,8 +40,7 @@ use sheet::{
 ///
 /// The current spreadsheet as a JSON response
 pub async fn get_sheet(state: State<AppState>) -> Json<Sheet> {
-    let sheet = state.read().await.sheet.clone();
-    Json(sheet)
+    Json(Sheet::from_engine(&state.read().await.engine))
 }
 
 /// Handles requests to undo the last action.
@@ -57,30 +57,23 @@ pub async fn get_sheet(state: State<AppState>) -> Json<Sheet> {
 /// # Returns
 ///
 /// A JSON response indicating success or failure of the undo operation
-pub async fn undo_action(State(state): State<AppState>) -> Json<UndoRedoResponse> {
+pub async fn undo_action(State(state): State<AppState>) -> (StatusCode, Json<UndoRedoResponse>) {
     let mut app_state = state.write().await;
+    let engine = &mut app_state.engine;
 
-    if let Some(prev) = app_state.undo_stack.pop() {
-        // Create temporary copies for snapshot creation
-        let cells_copy = app_state.cells.clone();
-        let formula_array_copy = app_state.formula_array.clone();
-        let graph_copy = app_state.graph.clone();
-
-        // Save current state to redo stack before reverting
-        app_state.redo_stack.push(create_snapshot(&cells_copy, &formula_array_copy, &graph_copy));
-
-        // Restore previous state
-        app_state.cells = prev.arr;
-        app_state.formula_array = prev.formula_array;
-        app_state.graph = prev.graph;
-
-        // Also update the regular sheet model for API compatibility
-        let cells_clone = app_state.cells.clone();
-        update_simple_sheet_from_cells(&mut app_state.sheet, &cells_clone, 10, 10);
-
-        Json(UndoRedoResponse { success: true, message: "Action undone successfully".to_string() })
-    } else {
-        Json(UndoRedoResponse { success: false, message: "Nothing to undo".to_string() })
+    match engine.undo() {
+        Ok(()) => (
+            StatusCode::OK,
+            Json(UndoRedoResponse {
+                success: true,
+                message: "Action undone successfully".to_string(),
+                error: None,
+            }),
+        ),
+        Err(e) => (
+            status_code(&e),
+            Json(UndoRedoResponse { success: false, message: e.to_string(), error: Some(e) }),
+        ),
     }
 }
 
@@ -98,61 +91,51 @@ pub async fn undo_action(State(state): State<AppState>) -> Json<UndoRedoResponse
 /// # Returns
 ///
 /// A JSON response indicating success or failure of the redo operation
-pub async fn redo_action(State(state): State<AppState>) -> Json<UndoRedoResponse> {
+pub async fn redo_action(State(state): State<AppState>) -> (StatusCode, Json<UndoRedoResponse>) {
     let mut app_state = state.write().await;
+    let engine = &mut app_state.engine;
 
-    if let Some(next) = app_state.redo_stack.pop() {
-        // Create temporary copies for snapshot creation
-        let cells_copy = app_state.cells.clone();
-        let formula_array_copy = app_state.formula_array.clone();
-        let graph_copy = app_state.graph.clone();
-
-        // Save current state to undo stack before redoing
-        app_state.undo_stack.push(create_snapshot(&cells_copy, &formula_array_copy, &graph_copy));
-
-        // Restore next state
-        app_state.cells = next.arr;
-        app_state.formula_array = next.formula_array;
-        app_state.graph = next.graph;
-
-        let cells_clone = app_state.cells.clone();
-        // Also update the regular sheet model for API compatibility
-        update_simple_sheet_from_cells(&mut app_state.sheet, &cells_clone, 10, 10);
-
-        Json(UndoRedoResponse { success: true, message: "Action redone successfully".to_string() })
-    } else {
-        Json(UndoRedoResponse { success: false, message: "Nothing to redo".to_string() })
+    match engine.redo() {
+        Ok(()) => (
+            StatusCode::OK,
+            Json(UndoRedoResponse {
+                success: true,
+                message: "Action redone successfully".to_string(),
+                error: None,
+            }),
+        ),
+        Err(e) => (
+            status_code(&e),
+            Json(UndoRedoResponse { success: false, message: e.to_string(), error: Some(e) }),
+        ),
     }
 }
 
-/// Updates the simple sheet model from internal cell data.
-///
-/// This helper function synchronizes the sheet view model with the underlying cell data.
-///
-/// # Arguments
-///
-/// * `sheet` - The sheet model to update
-/// * `cells` - Source cell data
-/// * `rows` - Number of rows in the sheet
-/// * `cols` - Number of columns in the sheet
-fn update_simple_sheet_from_cells(sheet: &mut Sheet, cells: &[Cell], rows: usize, cols: usize) {
-    for r in 0..rows {
-        for c in 0..cols {
-            let idx = r * cols + c;
-            if idx < cells.len() {
-                // Convert Cell to string and update the sheet
-                match &cells[idx].value {
-                    CellValue::Int(i) => sheet.data[r][c].value = CellValue::String(i.to_string()),
-                    CellValue::Float(f) => {
-                        sheet.data[r][c].value = CellValue::String(f.to_string())
-                    }
-                    CellValue::String(s) => sheet.data[r][c].value = CellValue::String(s.clone()),
-                }
-            }
-        }
+/// Returns the HTTP status code reported for an engine error. Clients tell the kinds of
+/// error apart by the `kind` tag of the serialized error, not by the status.
+///
+/// | Error | Status |
+/// |-------|--------|
+/// | Malformed formula or command | 400 Bad Request |
+/// | Unknown function, bad reference, invalid range or type mismatch | 422 Unprocessable Entity |
+/// | Circular dependency | 409 Conflict |
+fn status_code(error: &SheetError) -> StatusCode {
+    match error {
+        SheetError::Parse { .. } | SheetError::Command { .. } => StatusCode::BAD_REQUEST,
+        SheetError::UnknownFunction { .. }
+        | SheetError::BadReference { .. }
+        | SheetError::InvalidRange { .. }
+        | SheetError::TypeMismatch { .. } => StatusCode::UNPROCESSABLE_ENTITY,
+        SheetError::Cycle { .. } => StatusCode::CONFLICT,
     }
 }
 
+/// Builds the response for a failed engine call, with the status code for the error and the
+/// error itself next to the message.
+fn failed(message: String, error: SheetError) -> (StatusCode, Json<UpdateResponse>) {
+    (status_code(&error), Json(UpdateResponse { success: false, message, error: Some(error) }))
+}
+
 /// Updates a specific cell in the spreadsheet.
 ///
 /// This handler processes cell update requests from the client,
@@ -169,283 +152,367 @@ fn update_simple_sheet_from_cells(sheet: &mut Sheet, cells: &[Cell], rows: usize
 pub async fn update_cell(
     State(state): State<AppState>,
     ExtractJson(payload): ExtractJson<UpdateCellRequest>,
-) -> Json<UpdateResponse> {
+) -> (StatusCode, Json<UpdateResponse>) {
     // Parse row and column IDs from string to usize
     let row_index = match payload.row_id.parse::<usize>() {
         Ok(index) => index,
         Err(_) => {
-            return Json(UpdateResponse {
-                success: false,
-                message: "Invalid row ID format".to_string(),
-            })
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(UpdateResponse {
+                    success: false,
+                    message: "Invalid row ID format".to_string(),
+                    error: None,
+                }),
+            )
         }
     };
 
     let col_index = match payload.column_id.parse::<usize>() {
         Ok(index) => index,
         Err(_) => {
-            return Json(UpdateResponse {
-                success: false,
-                message: "Invalid column ID format".to_string(),
-            })
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(UpdateResponse {
+                    success: false,
+                    message: "Invalid column ID format".to_string(),
+                    error: None,
+                }),
+            )
         }
     };
 
-    let mut app_state = state.write().await;
-    let cols = app_state.sheet.data[0].len();
-
-    // Calculate 1D index from row and column
-    let cell_index = row_index * cols + col_index;
-
-    // Check if the indices are valid
-    if row_index >= app_state.sheet.data.len()
-        || col_index >= app_state.sheet.data[0].len()
-        || cell_index >= app_state.cells.len()
-    {
-        return Json(UpdateResponse {
-            success: false,
-            message: "Cell indices out of bounds".to_string(),
-        });
+    // Writing past the last row or column grows the sheet, up to the size limit
+    if row_index >= MAX_ROWS as usize || col_index >= MAX_COLS as usize {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(UpdateResponse {
+                success: false,
+                message: "Cell indices out of bounds".to_string(),
+                error: None,
+            }),
+        );
     }
 
-    // Try to parse the input value and determine its type
-    if let Ok(int_val) = payload.value.parse::<i32>() {
-        // It's an integer
-        app_state.cells[cell_index] = Cell::new_int(int_val);
-        app_state.sheet.data[row_index][col_index].value = CellValue::Int(int_val);
-    } else if let Ok(float_val) = payload.value.parse::<f64>() {
-        // It's a float
-        app_state.cells[cell_index] = Cell::new_float(float_val);
-        app_state.sheet.data[row_index][col_index].value = CellValue::Float(float_val);
-    } else if payload.value.contains("=") {
-        // It's a formula - parse and evaluate it
-        let mut cells_clone = app_state.cells.clone();
-        let mut formula_array_clone = app_state.formula_array.clone();
-        let mut graph_clone = app_state.graph.clone();
-        let mut state_clone = app_state.state.clone();
-        match cell_parser(
-            &payload.value,
-            cols as i32,
-            app_state.sheet.data.len() as i32,
-            &mut cells_clone,
-            &mut graph_clone,
-            &mut formula_array_clone,
-            &mut state_clone,
-        ) {
-            Ok(_) => {
-                // Formula processed successfully
-                // Update the actual state with the modified clones
-                app_state.cells = cells_clone;
-                app_state.formula_array = formula_array_clone;
-                app_state.graph = graph_clone;
-                app_state.state = state_clone;
-
-                // Also update the display sheet
-                match &app_state.cells[cell_index].value {
-                    CellValue::Int(i) => {
-                        app_state.sheet.data[row_index][col_index].value = CellValue::Int(*i)
-                    }
-                    CellValue::Float(f) => {
-                        app_state.sheet.data[row_index][col_index].value = CellValue::Float(*f)
-                    }
-                    CellValue::String(s) => {
-                        app_state.sheet.data[row_index][col_index].value =
-                            CellValue::String(s.clone())
-                    }
-                }
-            }
-            Err(e) => {
-                return Json(UpdateResponse {
-                    success: false,
-                    message: format!("Formula error: {}", e),
-                });
-            }
-        }
+    // Every edit goes through the formula parser so the cell's formula and its
+    // dependents stay in sync. Plain text is stored as a string literal
+    // and TRUE/FALSE as booleans. ISO dates such as 2025-04-01 become dates.
+    let value = payload.value.trim();
+    let formula = if let Some(formula) = value.strip_prefix('=') {
+        formula.to_string()
+    } else if value.eq_ignore_ascii_case("TRUE") || value.eq_ignore_ascii_case("FALSE") {
+        value.to_ascii_uppercase()
+    } else if (value.parse::<f64>().is_ok()
+        && value.trim_start_matches('-').chars().all(|ch| ch.is_ascii_digit() || ch == '.'))
+        || parse_iso(value).is_some_and(|(_, len)| len == value.len())
+    {
+        value.to_string()
     } else {
-        // It's a string
-        app_state.cells[cell_index] = Cell::new_string(payload.value.clone());
-        app_state.sheet.data[row_index][col_index].value = CellValue::String(payload.value.clone());
+        format!("\"{}\"", value.replace('"', "\"\""))
+    };
+    let label = format!("{}{}", column_label(col_index as i32), row_index + 1);
+
+    let mut app_state = state.write().await;
+    let engine = &mut app_state.engine;
+    if let Err(e) = engine.set(&label, &formula) {
+        return failed(format!("Formula error: {}", e), e);
     }
 
     // Return success response
-    Json(UpdateResponse { success: true, message: "Cell updated successfully".to_string() })
+    (
+        StatusCode::OK,
+        Json(UpdateResponse {
+            success: true,
+            message: "Cell updated successfully".to_string(),
+            error: None,
+        }),
+    )
 }
 
-/// Parses and evaluates a cell formula.
+/// Processes query commands sent from the client.
 ///
-/// This function handles different types of formulas:
-/// - Simple values (e.g., =10)
-/// - Arithmetic expressions (e.g., =A1+B2)
-/// - Functions (e.g., =SUM(A1:B3))
+/// This handler parses and executes commands like formulas,
+/// functions, and other operations on the spreadsheet data. `trace <cell>` returns the
+/// cells of the loop the cell takes part in as the result, e.g. `A1 → B1 → A1`.
 ///
 /// # Arguments
 ///
-/// * `a` - The formula string
-/// * `c` - Number of columns in the sheet
-/// * `r` - Number of rows in the sheet
-/// * `arr` - Array of cells to update
-/// * `graph` - Dependency graph
-/// * `formula_array` - Array of formulas
-/// * `state` - Parser state
+/// * `state` - Application state containing the current sheet
+/// * `body` - Raw request body containing the query string
 ///
 /// # Returns
 ///
-/// Result indicating success or an error message
-pub fn cell_parser(
-    a: &str,
-    c: i32,
-    r: i32,
-    arr: &mut [Cell],
-    graph: &mut Graph,
-    formula_array: &mut [Formula],
-    state: &mut State1,
-) -> Result<(), &'static str> {
-    // Placeholder for the actual cell parser function
-    let pos_equalto = a.find('=').ok_or("No equals sign found")?;
-    let pos_end = a.len();
-
-    let mut value = false;
-    let mut arth_exp = false;
-    let mut func = false;
-    let mut found_digit = false;
-
-    for ch in a[pos_equalto + 1..pos_end].chars() {
-        if ch == '(' {
-            func = true;
-            break;
-        }
-        if is_digit(ch) {
-            found_digit = true;
-        }
-        if "+-*/".contains(ch) && found_digit {
-            arth_exp = true;
-            break;
+/// A JSON response with the result of the executed query
+pub async fn process_query(State(state): State<AppState>, body: Bytes) -> impl IntoResponse {
+    // Convert bytes to string
+    let query_string = match String::from_utf8(body.to_vec()) {
+        Ok(s) => s,
+        Err(_) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(QueryResponse {
+                    success: false,
+                    message: "Failed to parse query string".to_string(),
+                    result: None,
+                    error: None,
+                }),
+            )
         }
-    }
+    };
 
-    if !func && !arth_exp {
-        value = true;
+    let mut app_state = state.write().await;
+    let engine = &mut app_state.engine;
+
+    // `trace A1` lists the cells of the loop A1 takes part in, if any
+    if let Some(target) = query_string.trim().strip_prefix("trace ") {
+        return match engine.trace(target) {
+            Ok(path) => {
+                let message = match &path {
+                    Some(_) => format!("{} is part of a cycle", target.trim()),
+                    None => format!("{} is not part of a cycle", target.trim()),
+                };
+                let result = path.map(|path| path.join(" → "));
+                (
+                    StatusCode::OK,
+                    Json(QueryResponse { success: true, message, result, error: None }),
+                )
+            }
+            Err(e) => (
+                status_code(&e),
+                Json(QueryResponse {
+                    success: false,
+                    message: format!("Trace error: {}", e),
+                    result: None,
+                    error: Some(e),
+                }),
+            ),
+        };
     }
 
-    if func && arth_exp {
-        return Err("Invalid input: Cannot mix function and arithmetic");
+    // Process the query - for direct formula/command input. Assigning a cell past the
+    // last row or column grows the sheet first.
+    match engine.run(&query_string) {
+        Ok(_) => (
+            StatusCode::OK,
+            Json(QueryResponse {
+                success: true,
+                message: "Formula executed successfully".to_string(),
+                result: None,
+                error: None,
+            }),
+        ),
+        Err(e) => (
+            status_code(&e),
+            Json(QueryResponse {
+                success: false,
+                message: format!("Formula error: {}", e),
+                result: None,
+                error: Some(e),
+            }),
+        ),
     }
+}
 
-    if value {
-        print!(" [DEBUG] Value function: ");
-        value_func(a, c, r, pos_equalto, pos_end, arr, graph, formula_array, state)?;
-    } else if arth_exp {
-        arth_op(a, c, r, pos_equalto, pos_end, arr, graph, formula_array, state)?;
-    } else if func {
-        let func_name = &a[pos_equalto + 1..a[pos_equalto..].find('(').unwrap() + pos_equalto];
-        // println!("[DEBUG] Function name: {}", func_name);
-        match func_name {
-            "MIN" => {
-                range_func(a, c, r, pos_equalto, pos_end, arr, graph, formula_array, state, 9)?
-            }
-            "MAX" => {
-                range_func(a, c, r, pos_equalto, pos_end, arr, graph, formula_array, state, 10)?
-            }
-            "AVG" => {
-                range_func(a, c, r, pos_equalto, pos_end, arr, graph, formula_array, state, 11)?
-            }
-            "SUM" => {
-                range_func(a, c, r, pos_equalto, pos_end, arr, graph, formula_array, state, 12)?
-            }
-            "STDEV" => {
-                range_func(a, c, r, pos_equalto, pos_end, arr, graph, formula_array, state, 13)?
-            }
-            "SLEEP" => sleep_func(a, c, r, pos_equalto, pos_end, arr, graph, formula_array, state)?,
+/// Copies a range to the clipboard.
+///
+/// # Arguments
+///
+/// * `state` - Application state holding the clipboard
+/// * `payload` - The range to copy, e.g. `A1:B5`
+///
+/// # Returns
+///
+/// A JSON response indicating whether the range was valid
+pub async fn copy_range(
+    State(state): State<AppState>,
+    ExtractJson(payload): ExtractJson<ClipboardRequest>,
+) -> (StatusCode, Json<UpdateResponse>) {
+    clipboard_action(state, &payload.range, false).await
+}
 
-            _ => return Err("Unknown function"),
-        }
+/// Cuts a range to the clipboard, so that the next paste moves it.
+///
+/// # Arguments
+///
+/// * `state` - Application state holding the clipboard
+/// * `payload` - The range to cut, e.g. `A1:B5`
+///
+/// # Returns
+///
+/// A JSON response indicating whether the range was valid
+pub async fn cut_range(
+    State(state): State<AppState>,
+    ExtractJson(payload): ExtractJson<ClipboardRequest>,
+) -> (StatusCode, Json<UpdateResponse>) {
+    clipboard_action(state, &payload.range, true).await
+}
+
+async fn clipboard_action(
+    state: AppState,
+    range: &str,
+    cut: bool,
+) -> (StatusCode, Json<UpdateResponse>) {
+    let mut app_state = state.write().await;
+    match app_state.engine.copy(range, cut) {
+        Ok(()) => (
+            StatusCode::OK,
+            Json(UpdateResponse {
+                success: true,
+                message: format!("Copied {}", range),
+                error: None,
+            }),
+        ),
+        Err(e) => failed(format!("Range error: {}", e), e),
+    }
+}
+
+/// Pastes the clipboard with its top-left cell at the given target.
+///
+/// The paste is applied as a single operation: the previous state is pushed onto the undo
+/// stack, so one undo reverts the whole paste.
+///
+/// # Arguments
+///
+/// * `state` - Application state containing the sheet and the clipboard
+/// * `payload` - The top-left cell to paste to, e.g. `D1`
+///
+/// # Returns
+///
+/// A JSON response indicating success or failure of the paste
+pub async fn paste_range(
+    State(state): State<AppState>,
+    ExtractJson(payload): ExtractJson<PasteRequest>,
+) -> (StatusCode, Json<UpdateResponse>) {
+    let mut app_state = state.write().await;
+    let engine = &mut app_state.engine;
+    if let Err(e) = engine.paste(&payload.target) {
+        return failed(format!("Paste error: {}", e), e);
     }
 
-    Ok(())
+    (
+        StatusCode::OK,
+        Json(UpdateResponse {
+            success: true,
+            message: "Pasted successfully".to_string(),
+            error: None,
+        }),
+    )
 }
 
-/// Processes query commands sent from the client.
+/// Inserts a blank row before the given row, shifting the rows below it down.
 ///
-/// This handler parses and executes commands like formulas,
-/// functions, and other operations on the spreadsheet data.
+/// # Arguments
+///
+/// * `state` - Application state containing the current sheet
+/// * `payload` - Zero-based index of the row to insert before
+///
+/// # Returns
+///
+/// A JSON response indicating success or failure of the insertion
+pub async fn insert_row_action(
+    State(state): State<AppState>,
+    ExtractJson(payload): ExtractJson<LineRequest>,
+) -> (StatusCode, Json<UpdateResponse>) {
+    line_action(state, payload.index, insert_row, "Row inserted").await
+}
+
+/// Deletes the given row, shifting the rows below it up.
 ///
 /// # Arguments
 ///
 /// * `state` - Application state containing the current sheet
-/// * `body` - Raw request body containing the query string
+/// * `payload` - Zero-based index of the row to delete
 ///
 /// # Returns
 ///
-/// A JSON response with the result of the executed query
-pub async fn process_query(State(state): State<AppState>, body: Bytes) -> impl IntoResponse {
-    // Convert bytes to string
-    let query_string = match String::from_utf8(body.to_vec()) {
-        Ok(s) => s,
-        Err(_) => {
-            return Json(QueryResponse {
-                success: false,
-                message: "Failed to parse query string".to_string(),
-                result: None,
-            })
-        }
-    };
+/// A JSON response indicating success or failure of the deletion
+pub async fn delete_row_action(
+    State(state): State<AppState>,
+    ExtractJson(payload): ExtractJson<LineRequest>,
+) -> (StatusCode, Json<UpdateResponse>) {
+    line_action(state, payload.index, delete_row, "Row deleted").await
+}
 
+/// Inserts a blank column before the given column, shifting the columns to its right.
+///
+/// # Arguments
+///
+/// * `state` - Application state containing the current sheet
+/// * `payload` - Zero-based index of the column to insert before
+///
+/// # Returns
+///
+/// A JSON response indicating success or failure of the insertion
+pub async fn insert_col_action(
+    State(state): State<AppState>,
+    ExtractJson(payload): ExtractJson<LineRequest>,
+) -> (StatusCode, Json<UpdateResponse>) {
+    line_action(state, payload.index, insert_col, "Column inserted").await
+}
+
+/// Deletes the given column, shifting the columns to its right to the left.
+///
+/// # Arguments
+///
+/// * `state` - Application state containing the current sheet
+/// * `payload` - Zero-based index of the column to delete
+///
+/// # Returns
+///
+/// A JSON response indicating success or failure of the deletion
+pub async fn delete_col_action(
+    State(state): State<AppState>,
+    ExtractJson(payload): ExtractJson<LineRequest>,
+) -> (StatusCode, Json<UpdateResponse>) {
+    line_action(state, payload.index, delete_col, "Column deleted").await
+}
+
+/// Applies a row or column edit as a single undoable step.
+async fn line_action(
+    state: AppState,
+    index: usize,
+    edit: StructureEdit,
+    done: &str,
+) -> (StatusCode, Json<UpdateResponse>) {
     let mut app_state = state.write().await;
-    let cols = app_state.sheet.data[0].len();
-    let rows = app_state.sheet.data.len();
-
-    // Save current state for undo before modifying (for commands that modify state)
-    let query = query_string.trim();
-
-    let mut cells_clone = app_state.cells.clone();
-    let mut formula_array_clone = app_state.formula_array.clone();
-    let mut graph_clone = app_state.graph.clone();
-    let mut state_clone = app_state.state.clone();
-    // Process the query - for direct formula/command input
-    match parser(
-        query,
-        cols as i32,
-        rows as i32,
-        &mut cells_clone,
-        &mut graph_clone,
-        &mut formula_array_clone,
-        &mut state_clone,
-    ) {
-        Ok(_) => {
-            // Formula processed successfully
-            // Update the actual state with the modified clones
-            app_state.cells = cells_clone;
-            app_state.formula_array = formula_array_clone;
-            app_state.graph = graph_clone;
-            app_state.state = state_clone;
-
-            // Also update the display sheet
-            for r in 0..rows {
-                for c in 0..cols {
-                    let idx = r * cols + c;
-                    match &app_state.cells[idx].value {
-                        CellValue::Int(i) => app_state.sheet.data[r][c].value = CellValue::Int(*i),
-                        CellValue::Float(f) => {
-                            app_state.sheet.data[r][c].value = CellValue::Float(*f)
-                        }
-                        CellValue::String(s) => {
-                            app_state.sheet.data[r][c].value = CellValue::String(s.clone())
-                        }
-                    }
-                }
-            }
+    let index = i32::try_from(index).unwrap_or(i32::MAX);
 
-            // Formula processed successfully - continue with the existing code
-            Json(QueryResponse {
-                success: true,
-                message: "Formula executed successfully".to_string(),
-                result: None,
-            })
-        }
-        Err(e) => Json(QueryResponse {
-            success: false,
-            message: format!("Formula error: {}", e),
-            result: None,
-        }),
+    let engine = &mut app_state.engine;
+    if let Err(e) = engine.edit_line(edit, index) {
+        return failed(format!("Edit error: {}", e), e);
     }
+
+    (StatusCode::OK, Json(UpdateResponse { success: true, message: done.to_string(), error: None }))
+}
+
+/// Resizes the sheet to the given number of rows and columns.
+///
+/// Cells keep their row and column; cells outside the new size are dropped and references
+/// to them become `#REF!`. The resize is a single undoable step.
+///
+/// # Arguments
+///
+/// * `state` - Application state containing the current sheet
+/// * `payload` - The new number of rows and columns
+///
+/// # Returns
+///
+/// A JSON response indicating success or failure of the resize
+pub async fn resize_sheet(
+    State(state): State<AppState>,
+    ExtractJson(payload): ExtractJson<ResizeRequest>,
+) -> (StatusCode, Json<UpdateResponse>) {
+    let mut app_state = state.write().await;
+    let new_cols = i32::try_from(payload.cols).unwrap_or(i32::MAX);
+    let new_rows = i32::try_from(payload.rows).unwrap_or(i32::MAX);
+
+    let engine = &mut app_state.engine;
+    if let Err(e) = engine.resize(new_rows, new_cols) {
+        return failed(format!("Resize error: {}", e), e);
+    }
+
+    (
+        StatusCode::OK,
+        Json(UpdateResponse { success: true, message: "Sheet resized".to_string(), error: None }),
+    )
 }
@@ -11,39 +11,18 @@ use axum::{
     routing::{get, post},
     Router,
 };
-use sheet::function_ext::Cell;
-use sheet::graph_ext::StateSnapshot;
-use sheet::graph_ext::{Formula, Graph, State};
+use sheet::structure_ext::{MAX_COLS, MAX_ROWS};
 use std::{net::SocketAddr, sync::Arc};
 use tokio::sync::RwLock;
 use tower_http::cors::{Any, CorsLayer};
 
-use handlers::{get_sheet, process_query, redo_action, undo_action, update_cell};
-use server_models::Sheet;
+use handlers::{
+    copy_range, cut_range, delete_col_action, delete_row_action, get_sheet, insert_col_action,
+    insert_row_action, paste_range, process_query, redo_action, resize_sheet, undo_action,
+    update_cell,
+};
 use types::ExtendedState;
 
-/// Creates a snapshot of the current spreadsheet state for undo/redo functionality.
-///
-/// This function captures all data necessary to restore the sheet to a previous state,
-/// including cells, formulas, and dependency graph.
-///
-/// # Arguments
-///
-/// * `arr` - The array of cells representing the current sheet data
-/// * `formula_array` - The array of formulas associated with cells
-/// * `graph` - The dependency graph tracking relationships between cells
-///
-/// # Returns
-///
-/// A `StateSnapshot` containing clones of all state components
-fn create_snapshot(arr: &[Cell], formula_array: &[Formula], graph: &Graph) -> StateSnapshot {
-    StateSnapshot {
-        arr: arr.to_owned(),
-        formula_array: formula_array.to_owned(),
-        graph: graph.clone(),
-    }
-}
-
 /// Application entry point - initializes and runs the HTTP server.
 ///
 /// This function:
@@ -56,36 +35,20 @@ fn create_snapshot(arr: &[Cell], formula_array: &[Formula], graph: &Graph) -> St
 /// - Updating individual cells
 /// - Processing queries
 /// - Undo/redo operations
+/// - Copy, cut and paste of ranges
+/// - Inserting and deleting rows and columns
+/// - Resizing the sheet
+///
+/// The initial size can be given as `dummy_server [rows] [cols]`.
 #[tokio::main]
 async fn main() {
-    // Initialize the sheet with default values
-    let r = 10;
-    let c = 10;
-    let num_cells = r * c;
-
-    // Initialize extended state components
-    let cells = vec![Cell::new_int(0); num_cells];
-
-    // Initialize regular sheet model for API compatibility
-    let sheet = Sheet::new(10, 10);
-    let num_cells = r * c;
-
-    let formula_array = vec![Formula::default(); num_cells];
-    let graph = Graph::new(num_cells);
-    let state = State::new();
-    let undo_stack: Vec<StateSnapshot> = Vec::new();
-    let redo_stack: Vec<StateSnapshot> = Vec::new();
+    // Initialize the sheet with the size given on the command line (10x10 by default).
+    // The sheet grows when a cell past its last row or column is written.
+    let mut args = std::env::args().skip(1).map(|arg| arg.parse::<usize>().ok());
+    let r = args.next().flatten().unwrap_or(10).clamp(1, MAX_ROWS as usize);
+    let c = args.next().flatten().unwrap_or(10).clamp(1, MAX_COLS as usize);
 
-    // Create the extended state with all components
-    let extended_state = ExtendedState {
-        sheet: sheet.clone(),
-        cells: cells.clone(),
-        formula_array: formula_array.clone(),
-        graph: graph.clone(),
-        state: state.clone(),
-        undo_stack,
-        redo_stack,
-    };
+    let extended_state = ExtendedState { engine: sheet::Sheet::new(r as i32, c as i32) };
     let app_state = Arc::new(RwLock::new(extended_state));
 
     // Create a CORS layer that allows any origin
@@ -98,6 +61,14 @@ async fn main() {
         .route("/api/query", post(process_query))
         .route("/api/undo", post(undo_action)) // New endpoint for undo
         .route("/api/redo", post(redo_action)) // New endpoint for redo
+        .route("/api/copy", post(copy_range))
+        .route("/api/cut", post(cut_range))
+        .route("/api/paste", post(paste_range))
+        .route("/api/insert-row", post(insert_row_action))
+        .route("/api/delete-row", post(delete_row_action))
+        .route("/api/insert-col", post(insert_col_action))
+        .route("/api/delete-col", post(delete_col_action))
+        .route("/api/resize", post(resize_sheet))
         .with_state(app_state)
         .layer(cors);
 
@@ -107,6 +78,11 @@ async fn main() {
     println!("📝 Query endpoint available at http://{}/api/query", addr);
     println!("↩️ Undo endpoint available at http://{}/api/undo", addr);
     println!("↪️ Redo endpoint available at http://{}/api/redo", addr);
+    println!(
+        "📋 Copy, cut and paste endpoints available at http://{}/api/{{copy,cut,paste}}",
+        addr
+    );
+    println!("📐 Resize endpoint available at http://{}/api/resize", addr);
 
     axum::serve(tokio::net::TcpListener::bind(addr).await.unwrap(), app).await.unwrap();
 }
@@ -5,40 +5,62 @@
 
 use serde::{Deserialize, Serialize};
 use sheet::function_ext::Cell;
+use sheet::graph_ext::Formula;
+use std::collections::BTreeMap;
 
-/// Represents a spreadsheet with rows and columns of cells.
-///
-/// This structure is the primary model for maintaining and transmitting
-/// the grid of cells that make up the spreadsheet data.
+/// A cell as sent to the client: its computed value plus the formula behind it.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SheetCell {
+    /// The computed value of the cell.
+    #[serde(flatten)]
+    pub cell: Cell,
+    /// Canonical formula text (e.g. `=SUM(A1:A3)`), or `None` for plain values.
+    pub formula: Option<String>,
+}
+
+/// The spreadsheet as sent to the client: its size and the cells holding a value or a
+/// formula. Every other cell is empty, so the view stays small however large the sheet grows.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Sheet {
-    /// The two-dimensional grid of cells that stores the spreadsheet data.
-    pub data: Vec<Vec<Cell>>,
+    /// The number of rows.
+    pub rows: usize,
+    /// The number of columns.
+    pub cols: usize,
+    /// The cells that are not empty, keyed by their index `row * cols + col`.
+    pub cells: BTreeMap<usize, SheetCell>,
 }
 #[allow(dead_code)]
 impl Sheet {
-    /// Creates a new spreadsheet with the specified number of rows and columns.
-    ///
-    /// All cells are initialized with integer value 0.
-    ///
-    /// # Arguments
-    ///
-    /// * `rows` - The number of rows in the sheet
-    /// * `cols` - The number of columns in the sheet
-    ///
-    /// # Returns
-    ///
-    /// A new `Sheet` instance with the specified dimensions
+    /// Creates an empty view of a sheet with `rows` rows and `cols` columns.
     pub fn new(rows: usize, cols: usize) -> Self {
-        Self { data: vec![vec![Cell::new_int(0); cols]; rows] }
+        Self { rows, cols, cells: BTreeMap::new() }
+    }
+
+    /// Builds the view of `engine` from the cells its grids have allocated, without visiting
+    /// the rest of the sheet.
+    pub fn from_engine(engine: &sheet::Sheet) -> Self {
+        let mut view = Sheet::new(engine.rows() as usize, engine.cols() as usize);
+        let values = engine.cells();
+        for (idx, cell) in values.occupied() {
+            if *cell != Cell::empty() {
+                view.cells.insert(idx, SheetCell { cell: cell.clone(), formula: None });
+            }
+        }
+        for (idx, formula) in engine.formulas().occupied() {
+            if let Formula::Empty | Formula::Literal(_) = formula {
+                continue;
+            }
+            let entry = view
+                .cells
+                .entry(idx)
+                .or_insert_with(|| SheetCell { cell: values[idx].clone(), formula: None });
+            entry.formula = formula.to_formula_string(engine.cols());
+        }
+        view
     }
 
-    /// Returns a reference to the grid of cells.
-    ///
-    /// # Returns
-    ///
-    /// A reference to the two-dimensional vector of cells
-    pub fn get_data(&self) -> &Vec<Vec<Cell>> {
-        &self.data
+    /// Returns the cell at `row` and `col`, or `None` if it is empty.
+    pub fn get(&self, row: usize, col: usize) -> Option<&SheetCell> {
+        self.cells.get(&(row * self.cols + col))
     }
 }
@@ -8,33 +8,16 @@ use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
-use crate::server_models::Sheet;
-use sheet::function_ext::Cell;
-use sheet::graph_ext::{Formula, Graph, State, StateSnapshot};
+use sheet::error_ext::SheetError;
 
 /// Enhanced state container for the spreadsheet application.
 ///
-/// This structure maintains all aspects of the spreadsheet state, including:
-/// - The rendered sheet data
-/// - Cell values and formulas
-/// - Dependency tracking
-/// - History for undo/redo operations
-/// - Current cursor position
+/// This structure maintains all aspects of the spreadsheet state: the engine's sheet, with
+/// cell values, formulas, dependency tracking and history for undo/redo operations. The view
+/// sent to clients is built from it when they ask for it.
 pub struct ExtendedState {
-    /// Sheet model for API compatibility with existing endpoints
-    pub sheet: Sheet,
-    /// Typed cells (int, float, string)
-    pub cells: Vec<Cell>,
-    /// Cell formulas
-    pub formula_array: Vec<Formula>,
-    /// Dependency graph for formula evaluation
-    pub graph: Graph,
-    /// Global sheet state
-    pub state: State,
-    /// Stack of previous states for undo operations
-    pub undo_stack: Vec<StateSnapshot>,
-    /// Stack of undone states for redo operations
-    pub redo_stack: Vec<StateSnapshot>,
+    /// The sheet evaluated by the extended engine
+    pub engine: sheet::Sheet,
 }
 
 /// Thread-safe shared application state.
@@ -56,6 +39,36 @@ pub struct UpdateCellRequest {
     pub value: String,
 }
 
+/// Request payload for copying or cutting a range.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ClipboardRequest {
+    /// Range to copy or cut, e.g. `A1:B5`
+    pub range: String,
+}
+
+/// Request payload for pasting the clipboard.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PasteRequest {
+    /// Top-left cell of the pasted block, e.g. `D1`
+    pub target: String,
+}
+
+/// Request payload for inserting or deleting a row or column.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct LineRequest {
+    /// Zero-based index of the row or column
+    pub index: usize,
+}
+
+/// Request payload for resizing the sheet.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ResizeRequest {
+    /// New number of rows
+    pub rows: usize,
+    /// New number of columns
+    pub cols: usize,
+}
+
 /// Response format for cell update operations.
 ///
 /// Indicates whether the update was successful and provides a message.
@@ -65,6 +78,9 @@ pub struct UpdateResponse {
     pub success: bool,
     /// Message describing the result or error
     pub message: String,
+    /// The engine error behind a failure, e.g. the cells of a cycle
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<SheetError>,
 }
 
 /// Response format for query operations.
@@ -78,6 +94,9 @@ pub struct QueryResponse {
     pub message: String,
     /// Optional result value from the query
     pub result: Option<String>,
+    /// The engine error behind a failure, e.g. the cells of a cycle
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<SheetError>,
 }
 
 /// Response format for undo/redo operations.
@@ -89,4 +108,7 @@ pub struct UndoRedoResponse {
     pub success: bool,
     /// Message describing the result or error
     pub message: String,
+    /// The engine error behind a failure, e.g. the cells of a cycle
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<SheetError>,
 }
@@ -4,6 +4,7 @@
 //! It handles user interaction, cell value updates, and communication with the backend API.
 
 use crate::context::{AppAction, AppContext};
+use crate::models::EditResponse;
 use gloo::console::log;
 use gloo_net::http::Request;
 use wasm_bindgen::JsCast;
@@ -14,13 +15,22 @@ use yew::prelude::*;
 /// Properties for the cell component.
 ///
 /// Contains:
-/// - The current cell value
+/// - The current cell value and the formula behind it
 /// - Row and column identifiers
 /// - API endpoint for updates
 #[derive(Properties, PartialEq)]
 pub struct Props {
     /// Current value displayed in the cell
     pub value: String,
+    /// Formula text (e.g. `=SUM(A1:A3)`) shown instead of the value while editing
+    #[prop_or_default]
+    pub formula: Option<String>,
+    /// Whether the value is an error such as `#DIV/0!`, which is shown in red
+    #[prop_or_default]
+    pub is_error: bool,
+    /// Whether the cell is part of the cycle that made the last edit fail
+    #[prop_or_default]
+    pub in_cycle: bool,
     /// Row identifier (zero-based index)
     pub row_id: String,
     /// Column identifier (zero-based index)
@@ -29,10 +39,16 @@ pub struct Props {
     pub api_url: String,
 }
 
+/// Style of a cell.
+const CELL_STYLE: &str = "border: 1px solid #ccc; padding: 8px; position: relative;";
+/// Style of a cell in the cycle that made the last edit fail.
+const CYCLE_STYLE: &str =
+    "border: 2px solid #c00; padding: 8px; position: relative; background: #fee;";
+
 /// A component that represents an individual cell in the spreadsheet.
 ///
 /// Features:
-/// - Click to edit in-place
+/// - Click to edit in-place; formula cells are edited as their formula text
 /// - Auto-focus on edit mode
 /// - Submit on enter or blur
 /// - Communicates with backend when cell value changes
@@ -72,10 +88,16 @@ pub fn cell_component(props: &Props) -> Html {
             || ()
         });
     }
+    // The text the input starts with: the formula if there is one, otherwise the value.
+    let edit_value = props.formula.clone().unwrap_or_else(|| props.value.clone());
+
     let onclick = {
+        let value = value.clone();
         let is_editing = is_editing.clone();
+        let edit_value = edit_value.clone();
 
         Callback::from(move |_| {
+            value.set(edit_value.clone());
             is_editing.set(true);
         })
     };
@@ -92,7 +114,8 @@ pub fn cell_component(props: &Props) -> Html {
         let row_id = props.row_id.clone();
         let column_id = props.column_id.clone();
         let api_url = props.api_url.clone();
-        let original_value = props.value.clone(); // Clone the original value here
+        let original_value = edit_value.clone(); // Clone the original value here
+        let display_value = props.value.clone();
         let app_context = app_context.clone(); // Clone for the closure
 
         Callback::from(move |_: FocusEvent| {
@@ -102,6 +125,9 @@ pub fn cell_component(props: &Props) -> Html {
             let api_url = api_url.clone();
             let app_context = app_context.clone(); // Clone for the async closure
 
+            // Show the computed value again until the refreshed sheet arrives
+            value.set(display_value.clone());
+
             // Only send update if the value has changed
             if current_value != original_value {
                 spawn_local(async move {
@@ -127,10 +153,15 @@ pub fn cell_component(props: &Props) -> Html {
 
                     // Send the request
                     match request_with_body.send().await {
-                        Ok(_) => {
+                        Ok(response) => match response.json::<EditResponse>().await {
                             // Trigger a refresh after successful cell update
-                            app_context.dispatch(AppAction::Refresh);
-                        }
+                            Ok(reply) if reply.success => app_context.dispatch(AppAction::Refresh),
+                            // Show why the edit was rejected, e.g. the cells of a cycle
+                            Ok(reply) => {
+                                app_context.dispatch(AppAction::Failed(reply.message, reply.error))
+                            }
+                            Err(e) => log!("Error reading update response: {:?}", e.to_string()),
+                        },
                         Err(e) => {
                             log!("Error updating cell: {:?}", e.to_string());
                         }
@@ -178,7 +209,7 @@ pub fn cell_component(props: &Props) -> Html {
     };
 
     html! {
-        <td style="border: 1px solid #ccc; padding: 8px; position: relative;">
+        <td style={if props.in_cycle { CYCLE_STYLE } else { CELL_STYLE }}>
             {
                 if *is_editing {
                     html! {
@@ -193,8 +224,13 @@ pub fn cell_component(props: &Props) -> Html {
                         />
                     }
                 } else {
+                    let style = if props.is_error {
+                        "cursor: pointer; min-height: 20px; color: #c00; font-weight: bold;"
+                    } else {
+                        "cursor: pointer; min-height: 20px;"
+                    };
                     html! {
-                        <div onclick={onclick} style="cursor: pointer; min-height: 20px;">
+                        <div onclick={onclick} {style}>
                             { (*value).clone() }
                         </div>
                     }
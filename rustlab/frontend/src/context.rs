@@ -4,6 +4,7 @@
 //! It provides a central state mechanism using Yew's reducer pattern for components
 //! to interact with shared application data.
 
+use sheet::error_ext::SheetError;
 use std::rc::Rc;
 use yew::prelude::*;
 
@@ -16,6 +17,11 @@ pub struct AppState {
     /// Counter that increments each time the UI needs to be refreshed.
     /// Components can watch this value to detect when they should update.
     pub refresh_counter: u32,
+    /// Message of the last rejected edit, shown until the next refresh.
+    pub error: Option<String>,
+    /// Cells of the loop that made the last edit fail (e.g. `A1`, `B1`), which the table
+    /// highlights.
+    pub cycle: Vec<String>,
 }
 
 impl Default for AppState {
@@ -25,7 +31,7 @@ impl Default for AppState {
     ///
     /// A new `AppState` with initial values
     fn default() -> Self {
-        Self { refresh_counter: 0 }
+        Self { refresh_counter: 0, error: None, cycle: Vec::new() }
     }
 }
 
@@ -41,6 +47,9 @@ pub type AppContext = UseReducerHandle<AppState>;
 pub enum AppAction {
     /// Action to trigger a refresh of the UI.
     Refresh,
+    /// Action to report an edit rejected by the backend, with its message and the
+    /// engine's error, if any.
+    Failed(String, Option<SheetError>),
 }
 
 impl Reducible for AppState {
@@ -58,7 +67,21 @@ impl Reducible for AppState {
     /// A new version of the application state after applying the action
     fn reduce(self: Rc<Self>, action: Self::Action) -> Rc<Self> {
         match action {
-            AppAction::Refresh => AppState { refresh_counter: self.refresh_counter + 1 }.into(),
+            AppAction::Refresh => AppState {
+                refresh_counter: self.refresh_counter + 1,
+                error: None,
+                cycle: Vec::new(),
+            }
+            .into(),
+            AppAction::Failed(message, error) => AppState {
+                refresh_counter: self.refresh_counter,
+                error: Some(message),
+                cycle: match error {
+                    Some(SheetError::Cycle { path }) => path,
+                    _ => Vec::new(),
+                },
+            }
+            .into(),
         }
     }
 }
@@ -5,44 +5,54 @@
 //! and deserialized for communication with the backend.
 
 use serde::{Deserialize, Serialize};
+use sheet::error_ext::SheetError;
 use sheet::function_ext::Cell;
+use std::collections::BTreeMap;
 
-/// Represents a spreadsheet with rows and columns of cells.
-///
-/// This structure holds the entire grid of cells that make up the spreadsheet.
-/// It can be serialized for API communication and provides methods for creating
-/// and accessing the spreadsheet data.
+/// A cell as received from the backend: its computed value plus the formula behind it.
 #[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SheetCell {
+    /// The computed value of the cell.
+    #[serde(flatten)]
+    pub cell: Cell,
+    /// Canonical formula text (e.g. `=SUM(A1:A3)`), or `None` for plain values.
+    #[serde(default)]
+    pub formula: Option<String>,
+}
 
+/// The backend's reply to an edit or a query.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EditResponse {
+    /// Whether the edit was applied.
+    pub success: bool,
+    /// Message describing the result or error.
+    pub message: String,
+    /// The engine's error if the edit was rejected, e.g. with the cells of a cycle.
+    #[serde(default)]
+    pub error: Option<SheetError>,
+}
+
+/// The spreadsheet as received from the backend: its size and the cells holding a value or
+/// a formula. Every other cell is empty.
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Sheet {
-    /// The two-dimensional grid of cells that stores the spreadsheet data.
-    pub data: Vec<Vec<Cell>>,
+    /// The number of rows.
+    pub rows: usize,
+    /// The number of columns.
+    pub cols: usize,
+    /// The cells that are not empty, keyed by their index `row * cols + col`.
+    pub cells: BTreeMap<usize, SheetCell>,
 }
 
 #[allow(dead_code)]
 impl Sheet {
-    /// Creates a new spreadsheet with the specified number of rows and columns.
-    ///
-    /// All cells are initialized with integer value 0.
-    ///
-    /// # Arguments
-    ///
-    /// * `rows` - The number of rows in the sheet
-    /// * `cols` - The number of columns in the sheet
-    ///
-    /// # Returns
-    ///
-    /// A new `Sheet` instance with the specified dimensions
+    /// Creates an empty sheet with `rows` rows and `cols` columns.
     pub fn new(rows: usize, cols: usize) -> Self {
-        Self { data: vec![vec![Cell::new_int(0); cols]; rows] }
+        Self { rows, cols, cells: BTreeMap::new() }
     }
 
-    /// Returns a reference to the grid of cells.
-    ///
-    /// # Returns
-    ///
-    /// A reference to the two-dimensional vector of cells
-    pub fn get_data(&self) -> &Vec<Vec<Cell>> {
-        &self.data
+    /// Returns the cell at `row` and `col`, or `None` if it is empty.
+    pub fn get(&self, row: usize, col: usize) -> Option<&SheetCell> {
+        self.cells.get(&(row * self.cols + col))
     }
 }
@@ -9,6 +9,7 @@ use web_sys::HtmlInputElement;
 use yew::prelude::*;
 
 use crate::context::{AppAction, AppContext};
+use crate::models::EditResponse;
 
 /// Properties for the RequestForm component.
 ///
@@ -83,8 +84,16 @@ pub fn request_form(props: &RequestFormProps) -> Html {
                                             Ok(formatted) => {
                                                 response.set(formatted);
 
-                                                // Trigger a refresh after successful operation
-                                                app_context.dispatch(AppAction::Refresh);
+                                                match serde_json::from_value::<EditResponse>(json) {
+                                                    // Show why the command was rejected
+                                                    Ok(reply) if !reply.success => app_context
+                                                        .dispatch(AppAction::Failed(
+                                                            reply.message,
+                                                            reply.error,
+                                                        )),
+                                                    // Trigger a refresh after successful operation
+                                                    _ => app_context.dispatch(AppAction::Refresh),
+                                                }
                                             }
                                             Err(e) => response
                                                 .set(format!("Error formatting JSON: {:?}", e)),
@@ -12,7 +12,8 @@ use yew::prelude::*;
 use crate::cell_component::CellComponent;
 use crate::context::AppContext;
 use crate::models::*;
-use sheet::function_ext::CellValue;
+use sheet::date_ext::format_date;
+use sheet::function_ext::{Cell, CellValue};
 
 /// The main spreadsheet table component.
 ///
@@ -20,6 +21,7 @@ use sheet::function_ext::CellValue;
 /// - Fetches sheet data from the backend
 /// - Displays the grid of cells with row and column headers
 /// - Refreshes when the application state changes
+/// - Shows why the last edit was rejected, highlighting the cells of a cycle
 #[function_component(TableComponent)]
 pub fn table_component() -> Html {
     let sheet_state = use_state(|| None::<Sheet>);
@@ -67,6 +69,12 @@ pub fn table_component() -> Html {
 
     html! {
         <div>
+        {
+            match &app_context.error {
+                Some(error) => html! { <p style="color: #c00; font-weight: bold;">{ error }</p> },
+                None => html! {},
+            }
+        }
         {
             if let Some(error) = &*error_state {
                 html! { <p style="color: red;">{ error }</p> }
@@ -77,7 +85,7 @@ pub fn table_component() -> Html {
                             <tr>
                                 <th style="border: 1px solid #ccc; padding: 8px;">{ "↘" }</th>
                                 {
-                                    (0..sheet.data[0].len()).map(|c| html! {
+                                    (0..sheet.cols).map(|c| html! {
                                         <th style="border: 1px solid #ccc; padding: 8px; background:rgb(7, 188, 152);">
                                             { column_label(c) }
                                         </th>
@@ -87,21 +95,32 @@ pub fn table_component() -> Html {
                         </thead>
                         <tbody>
                             {
-                                sheet.data.iter().enumerate().map(|(r, row)| {
+                                (0..sheet.rows).map(|r| {
                                     html! {
                                         <tr>
                                             <td style="border: 1px solid #ccc; padding: 8px; background:rgb(7, 188, 152);">{ r + 1 }</td>
                                             {
-                                                row.iter().enumerate().map(|(c, cell)| {
+                                                (0..sheet.cols).map(|c| {
+                                                    let (cell, formula) = match sheet.get(r, c) {
+                                                        Some(cell) => (cell.cell.clone(), cell.formula.clone()),
+                                                        None => (Cell::empty(), None),
+                                                    };
                                                     let display_value = match &cell.value {
                                                         CellValue::Int(i) => i.to_string(),
                                                         CellValue::Float(f) => f.to_string(),
+                                                        CellValue::Date(serial) => format_date(*serial),
                                                         CellValue::String(s) => s.clone(),
+                                                        CellValue::Bool(b) => (if *b { "TRUE" } else { "FALSE" }).to_string(),
+                                                        CellValue::Error(kind) => kind.to_string(),
+                                                        CellValue::Empty => String::new(),
                                                     };
 
                                                     html! {
                                                         <CellComponent
                                                                 value={display_value}
+                                                                formula={formula}
+                                                                is_error={!cell.is_valid}
+                                                                in_cycle={app_context.cycle.contains(&format!("{}{}", column_label(c), r + 1))}
                                                                 row_id={r.to_string()}
                                                                 column_id={c.to_string()}
                                                                 api_url={"http://127.0.0.1:3001/update-cell".to_string()}
@@ -0,0 +1,86 @@
+//! Cost of editing and cloning the dependency graphs of a 1000 x 1000 sheet in which every
+//! cell reads the one before it.
+//!
+//! Run with `cargo bench -p sheet --bench graph_bench`.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use sheet::{graph, graph_ext};
+
+const COLS: usize = 1000;
+const NUM_CELLS: usize = COLS * 1000;
+
+fn standard_chain() -> graph::Graph {
+    let mut graph = graph::Graph::new(NUM_CELLS);
+    for cell in 1..NUM_CELLS {
+        graph.add_edge(cell - 1, cell);
+    }
+    graph
+}
+
+fn extended_chain() -> graph_ext::Graph {
+    let mut graph = graph_ext::Graph::new(NUM_CELLS);
+    for cell in 1..NUM_CELLS {
+        graph.add_edge(cell as i32, cell - 1);
+    }
+    graph
+}
+
+fn bench_standard(c: &mut Criterion) {
+    let mut graph = standard_chain();
+    // A cell in the middle of the sheet that some formula starts reading, then stops.
+    let (from, to) = (NUM_CELLS / 2, 7);
+    c.bench_function("graph/add_edge", |b| {
+        b.iter(|| {
+            graph.add_edge(black_box(from), black_box(to));
+            graph.delete_edge(from, to);
+        })
+    });
+    graph.add_edge(from, to);
+    c.bench_function("graph/delete_edge", |b| {
+        b.iter(|| {
+            graph.delete_edge(black_box(from), black_box(to));
+            graph.add_edge(from, to);
+        })
+    });
+    c.bench_function("graph/add_range", |b| {
+        b.iter(|| {
+            graph.add_range_to_graph(0, black_box(NUM_CELLS - 1), to, COLS);
+            graph.delete_range(to);
+        })
+    });
+    let mut group = c.benchmark_group("graph");
+    group.sample_size(10);
+    group.bench_function("clone", |b| b.iter(|| black_box(graph.clone())));
+    group.finish();
+}
+
+fn bench_extended(c: &mut Criterion) {
+    let mut graph = extended_chain();
+    let (cell, head_idx) = (7, NUM_CELLS / 2);
+    c.bench_function("graph_ext/add_edge", |b| {
+        b.iter(|| {
+            graph.add_edge(black_box(cell), black_box(head_idx));
+            graph.delete_node(cell, head_idx);
+        })
+    });
+    graph.add_edge(cell, head_idx);
+    c.bench_function("graph_ext/delete_node", |b| {
+        b.iter(|| {
+            graph.delete_node(black_box(cell), black_box(head_idx));
+            graph.add_edge(cell, head_idx);
+        })
+    });
+    c.bench_function("graph_ext/add_range", |b| {
+        b.iter(|| {
+            graph.add_range_to_graph(0, black_box(NUM_CELLS as i32 - 1), cell, COLS as i32);
+            graph.delete_range_from_graph(cell);
+        })
+    });
+    let mut group = c.benchmark_group("graph_ext");
+    group.sample_size(10);
+    group.bench_function("clone", |b| b.iter(|| black_box(graph.clone())));
+    group.finish();
+}
+
+criterion_group!(benches, bench_standard, bench_extended);
+criterion_main!(benches);
@@ -0,0 +1,60 @@
+//! Cost of finding the range formulas that read a cell, with the range index and with a scan
+//! of every range, on a 26 x 1000 sheet holding 10k `SUM` formulas.
+//!
+//! Run with `cargo bench -p sheet --bench range_index_bench`.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use sheet::Sheet;
+
+const COLS: i32 = 26;
+const ROWS: i32 = 1000;
+
+/// Every row has a value in A and ten formulas in K..T summing A..A, A..B, ..., A..J.
+fn range_sheet() -> Sheet {
+    let mut sheet = Sheet::new(ROWS, COLS);
+    for row in 1..=ROWS {
+        sheet.set(&format!("A{}", row), "1").unwrap();
+        for (i, col) in ('K'..='T').enumerate() {
+            let last = (b'A' + i as u8) as char;
+            sheet
+                .set(&format!("{}{}", col, row), &format!("SUM(A{}:{}{})", row, last, row))
+                .unwrap();
+        }
+    }
+    sheet
+}
+
+fn bench_covering(c: &mut Criterion) {
+    let mut sheet = range_sheet();
+    let queries: Vec<i32> = (0..ROWS).map(|row| row * COLS).collect();
+    let ranges = &sheet.graph().ranges;
+    c.bench_function("range_index/covering", |b| {
+        b.iter(|| {
+            queries.iter().map(|&cell| ranges.covering(black_box(cell), COLS).len()).sum::<usize>()
+        })
+    });
+    let mut group = c.benchmark_group("range_index");
+    group.sample_size(10);
+    group.bench_function("linear_scan", |b| {
+        b.iter(|| {
+            queries
+                .iter()
+                .map(|&cell| {
+                    ranges.iter().filter(|range| range.contains(black_box(cell), COLS)).count()
+                })
+                .sum::<usize>()
+        })
+    });
+    group.finish();
+
+    let mut value = 0;
+    c.bench_function("range_index/recalc_after_edit", |b| {
+        b.iter(|| {
+            value += 1;
+            sheet.run(&format!("A500={}", value)).unwrap();
+        })
+    });
+}
+
+criterion_group!(benches, bench_covering);
+criterion_main!(benches);
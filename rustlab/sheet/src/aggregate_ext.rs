@@ -0,0 +1,255 @@
+//! # Counting and Conditional Aggregates
+//!
+//! `COUNT`, `COUNTA` and `COUNTBLANK` count cells by type and skip everything else
+//! instead of failing on it. `COUNTIF`, `SUMIF`, `AVERAGEIF`, `COUNTIFS` and `SUMIFS`
+//! select cells with criteria such as `">10"`, `"<>apples"` or `"app*"`.
+//!
+//! Ranges and criteria are ordinary arguments, so every range is registered as a
+//! dependency and editing a cell in a criteria range or a value range recalculates
+//! the result.
+//!
+//! ## Criteria
+//! A criterion is either a value the cell must equal (`10`, `TRUE`, `"apples"`) or a
+//! string starting with a comparison operator (`=`, `<>`, `<`, `<=`, `>`, `>=`). Numeric
+//! criteria only match numbers; text comparisons ignore case, and with `=` and `<>`
+//! the wildcards `*` (any run of characters) and `?` (one character) may be used.
+//! `""` matches blank cells and `"<>"` non-blank ones.
+
+use crate::expr_ext::Expr;
+use crate::function_ext::{Cell, CellValue, ErrorKind};
+use crate::graph_ext::{cell_value, eval_expr, range_cells};
+use crate::grid_ext::Grid;
+use crate::lookup_ext::reference;
+use crate::util_ext::{compare_eval, number_cell};
+
+/// A parsed criterion: a comparison operator and the value cells are compared with.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Criteria {
+    /// Comparison operator: `=`, `<>`, `<`, `<=`, `>` or `>=`.
+    pub op: &'static str,
+    /// Right-hand side of the comparison.
+    pub value: CellValue,
+}
+
+impl Criteria {
+    /// Builds a criterion from the value of a criteria argument.
+    ///
+    /// Strings may start with an operator; the rest is read as a number or
+    /// `TRUE`/`FALSE` where possible and as text otherwise.
+    pub fn parse(value: &CellValue) -> Self {
+        let CellValue::String(text) = value else {
+            return Criteria { op: "=", value: value.clone() };
+        };
+        let op = ["<>", "<=", ">=", "<", ">", "="].into_iter().find(|op| text.starts_with(op));
+        let operand = &text[op.map_or(0, str::len)..];
+        let value = if let Ok(number) = operand.trim().parse::<f64>() {
+            number_cell(number).value
+        } else if operand.eq_ignore_ascii_case("TRUE") || operand.eq_ignore_ascii_case("FALSE") {
+            CellValue::Bool(operand.eq_ignore_ascii_case("TRUE"))
+        } else {
+            CellValue::String(operand.to_string())
+        };
+        Criteria { op: op.unwrap_or("="), value }
+    }
+
+    /// Returns `true` if a cell holding `cell` satisfies the criterion.
+    ///
+    /// Error cells never match.
+    pub fn matches(&self, cell: &CellValue) -> bool {
+        if let CellValue::Error(_) = cell {
+            return false;
+        }
+        if let (CellValue::String(pattern), "=" | "<>") = (&self.value, self.op) {
+            let equal = match cell {
+                CellValue::String(text) => {
+                    wildcard_match(&pattern.to_lowercase(), &text.to_lowercase())
+                }
+                CellValue::Empty => pattern.is_empty(),
+                _ => false,
+            };
+            return equal == (self.op == "=");
+        }
+        if !same_kind(&self.value, cell) {
+            return self.op == "<>";
+        }
+        let result = compare_eval(Cell::new(cell.clone()), Cell::new(self.value.clone()), self.op);
+        result.value == CellValue::Bool(true)
+    }
+}
+
+/// Returns `true` if both values are numbers, or both are of the same non-numeric type.
+pub(crate) fn same_kind(a: &CellValue, b: &CellValue) -> bool {
+    let numeric =
+        |v: &CellValue| matches!(v, CellValue::Int(_) | CellValue::Float(_) | CellValue::Date(_));
+    (numeric(a) && numeric(b)) || std::mem::discriminant(a) == std::mem::discriminant(b)
+}
+
+/// Matches `text` against `pattern`, where `*` stands for any run of characters and `?`
+/// for exactly one.
+fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Position of the last `*` seen and the text position it currently absorbs up to.
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&ch| ch == '*')
+}
+
+/// The cells of a range argument (a `start:end` range, a single cell or a reference built
+/// by `OFFSET`/`INDIRECT`) and its shape.
+pub(crate) struct RangeArg {
+    pub(crate) cells: Vec<i32>,
+    pub(crate) shape: (i32, i32),
+}
+
+impl RangeArg {
+    /// Resolves a range argument; any other kind of expression gives `#VALUE!`.
+    pub(crate) fn from_expr(expr: &Expr, arr: &Grid<Cell>, cols: i32) -> Result<Self, ErrorKind> {
+        let (start, end) = reference(expr, arr, cols).ok_or(ErrorKind::Value)??;
+        let rows = (start / cols - end / cols).abs() + 1;
+        let width = (start % cols - end % cols).abs() + 1;
+        Ok(RangeArg { cells: range_cells(start, end, cols).collect(), shape: (rows, width) })
+    }
+}
+
+/// Evaluates `COUNT`, `COUNTA` or `COUNTBLANK`.
+///
+/// - `COUNT` counts numbers, plus numbers and booleans given directly as arguments.
+/// - `COUNTA` counts every non-blank cell, including text and errors.
+/// - `COUNTBLANK` counts blank cells and empty strings in its single range argument.
+///
+/// Cells of other types are skipped, so these functions never fail on a cell's value.
+pub fn count_eval(name: &str, args: &[Expr], arr: &Grid<Cell>, cols: i32) -> Cell {
+    let mut count = 0;
+    for arg in args {
+        let Ok(range) = RangeArg::from_expr(arg, arr, cols) else {
+            // Direct values: only reachable for COUNT and COUNTA.
+            let value = eval_expr(arg, arr, cols).value;
+            count += match name {
+                "COUNT" => {
+                    matches!(
+                        value,
+                        CellValue::Int(_)
+                            | CellValue::Float(_)
+                            | CellValue::Date(_)
+                            | CellValue::Bool(_)
+                    )
+                }
+                "COUNTA" => value != CellValue::Empty,
+                _ => return Cell::error(ErrorKind::Value),
+            } as i32;
+            continue;
+        };
+        for idx in range.cells {
+            let value = cell_value(arr, idx).value;
+            count += match name {
+                "COUNT" => {
+                    matches!(value, CellValue::Int(_) | CellValue::Float(_) | CellValue::Date(_))
+                }
+                "COUNTA" => value != CellValue::Empty,
+                _ => {
+                    matches!(&value, CellValue::Empty) || value == CellValue::String(String::new())
+                }
+            } as i32;
+        }
+    }
+    Cell::new_int(count)
+}
+
+/// Evaluates `COUNTIF`, `SUMIF`, `AVERAGEIF`, `COUNTIFS` or `SUMIFS`.
+///
+/// Argument layouts:
+/// - `COUNTIF(range, criteria)`
+/// - `SUMIF(range, criteria[, sum_range])`, and likewise `AVERAGEIF`
+/// - `COUNTIFS(range1, criteria1, range2, criteria2, ...)`
+/// - `SUMIFS(sum_range, range1, criteria1, ...)`
+///
+/// A cell is selected when every criterion matches the cell at the same position of its
+/// range, so all ranges must have the same shape (`#VALUE!` otherwise). Sums skip
+/// non-numeric selected cells but propagate errors in them; `AVERAGEIF` gives `#DIV/0!`
+/// when nothing numeric is selected.
+pub fn conditional_aggregate(name: &str, args: &[Expr], arr: &Grid<Cell>, cols: i32) -> Cell {
+    match criteria_aggregate(name, args, arr, cols) {
+        Ok(cell) => cell,
+        Err(kind) => Cell::error(kind),
+    }
+}
+
+fn criteria_aggregate(
+    name: &str,
+    args: &[Expr],
+    arr: &Grid<Cell>,
+    cols: i32,
+) -> Result<Cell, ErrorKind> {
+    let (target, pairs) = match (name, args) {
+        ("COUNTIF" | "COUNTIFS", _) => (None, args),
+        ("SUMIF" | "AVERAGEIF", [range, _]) => (Some(range), &args[..2]),
+        ("SUMIF" | "AVERAGEIF", [_, _, target]) => (Some(target), &args[..2]),
+        ("SUMIFS", [target, pairs @ ..]) => (Some(target), pairs),
+        _ => return Err(ErrorKind::Value),
+    };
+    if pairs.is_empty() || pairs.len() % 2 != 0 {
+        return Err(ErrorKind::Value);
+    }
+
+    let mut shape = None;
+    let mut selected: Vec<bool> = Vec::new();
+    for pair in pairs.chunks(2) {
+        let range = RangeArg::from_expr(&pair[0], arr, cols)?;
+        let criteria = eval_expr(&pair[1], arr, cols);
+        if let Some(kind) = criteria.error_kind() {
+            return Err(kind);
+        }
+        let criteria = Criteria::parse(&criteria.value);
+        if *shape.get_or_insert(range.shape) != range.shape {
+            return Err(ErrorKind::Value);
+        }
+        let matched = range.cells.iter().map(|&idx| criteria.matches(&cell_value(arr, idx).value));
+        if selected.is_empty() {
+            selected = matched.collect();
+        } else {
+            selected.iter_mut().zip(matched).for_each(|(keep, m)| *keep &= m);
+        }
+    }
+
+    let Some(target) = target else {
+        return Ok(Cell::new_int(selected.iter().filter(|&&keep| keep).count() as i32));
+    };
+    let target = RangeArg::from_expr(target, arr, cols)?;
+    if Some(target.shape) != shape {
+        return Err(ErrorKind::Value);
+    }
+    let (mut sum, mut count) = (0.0, 0);
+    for (idx, _) in target.cells.iter().zip(&selected).filter(|(_, &keep)| keep) {
+        let cell = cell_value(arr, *idx);
+        if let Some(kind) = cell.error_kind() {
+            return Err(kind);
+        }
+        match cell.value {
+            CellValue::Int(i) => sum += i as f64,
+            CellValue::Float(f) | CellValue::Date(f) => sum += f,
+            _ => continue,
+        }
+        count += 1;
+    }
+    match name {
+        "AVERAGEIF" if count == 0 => Err(ErrorKind::Div0),
+        "AVERAGEIF" => Ok(number_cell(sum / count as f64)),
+        _ => Ok(number_cell(sum)),
+    }
+}
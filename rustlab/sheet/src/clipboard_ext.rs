@@ -0,0 +1,178 @@
+//! # Copy, Cut and Paste
+//!
+//! `copy A1:B5` and `cut A1:B5` remember a range in [`State::clipboard`]; `paste D1` writes it
+//! to the block whose top-left cell is `D1`. Like in Excel, the clipboard only holds the
+//! range, and the cells are read when they are pasted.
+//!
+//! - **Copy:** pasted formulas have their relative references shifted by the distance from
+//!   the source to the destination, while `$`-anchored columns and rows stay where they are.
+//!   A reference shifted off the sheet becomes `#REF!`.
+//! - **Cut:** the cells are moved rather than copied. Their formulas keep pointing at the
+//!   same cells, except for references into the moved block itself, which move with it. Every
+//!   other formula that referenced the moved cells is rewritten to follow them; ranges are
+//!   only rewritten if they lie entirely inside the moved block. The source cells that are
+//!   not overwritten are cleared, and the clipboard is emptied.
+//!
+//! A paste rebuilds the dependency edges of every changed cell and recalculates them in a
+//! single pass. If the result would contain a cycle, nothing is changed.
+
+use crate::error_ext::SheetError;
+use crate::function_ext::Cell;
+use crate::graph_ext::{range_cells, Formula, Graph, State};
+use crate::grid_ext::Grid;
+use crate::parser_ext::anchored_cell_parser;
+use crate::util_ext::{cell_label, column_label, Anchor};
+
+/// A range that was copied or cut.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Clipboard {
+    /// Top-left cell of the range.
+    pub start: i32,
+    /// Bottom-right cell of the range.
+    pub end: i32,
+    /// Whether the range was cut, so that pasting moves it.
+    pub cut: bool,
+}
+
+impl Clipboard {
+    /// Returns `true` if `cell` lies in the clipboard's range.
+    fn contains(&self, cell: i32, cols: i32) -> bool {
+        let (row, col) = (cell / cols, cell % cols);
+        (self.start / cols..=self.end / cols).contains(&row)
+            && (self.start % cols..=self.end % cols).contains(&col)
+    }
+}
+
+/// Parses a range such as `A1:B5` (or a single cell such as `A1`) into its top-left and
+/// bottom-right cells. The corners may be given in any order; anchors are ignored.
+///
+/// Returns a [`SheetError::InvalidRange`] if a corner is malformed or outside the sheet.
+pub fn parse_range(text: &str, c: i32, r: i32) -> Result<(i32, i32), SheetError> {
+    let invalid = |_| SheetError::InvalidRange { range: text.trim().to_string() };
+    let (first, second) = text.split_once(':').unwrap_or((text, text));
+    let (first, _) = anchored_cell_parser(first.trim(), c, r).map_err(invalid)?;
+    let (second, _) = anchored_cell_parser(second.trim(), c, r).map_err(invalid)?;
+    let (top, left) = ((first / c).min(second / c), (first % c).min(second % c));
+    let (bottom, right) = ((first / c).max(second / c), (first % c).max(second % c));
+    Ok((top * c + left, bottom * c + right))
+}
+
+/// Stores `range` in the clipboard, to be copied (or, if `cut`, moved) by [`paste`].
+pub fn copy(range: &str, cut: bool, c: i32, r: i32, state: &mut State) -> Result<(), SheetError> {
+    let (start, end) = parse_range(range, c, r)?;
+    state.clipboard = Some(Clipboard { start, end, cut });
+    Ok(())
+}
+
+/// Moves `cell` by `d_row` rows and `d_col` columns, leaving anchored parts in place.
+///
+/// Returns `None` if the result lies outside a sheet of `cols` columns and `rows` rows.
+pub fn shift_reference(
+    cell: i32,
+    anchor: Anchor,
+    d_row: i32,
+    d_col: i32,
+    cols: i32,
+    rows: i32,
+) -> Option<i32> {
+    let row = cell / cols + if anchor.row { 0 } else { d_row };
+    let col = cell % cols + if anchor.col { 0 } else { d_col };
+    ((0..rows).contains(&row) && (0..cols).contains(&col)).then_some(row * cols + col)
+}
+
+/// Pastes the clipboard with its top-left cell at `target`.
+///
+/// # Errors
+/// Returns an error if the clipboard is empty, a [`SheetError::InvalidRange`] if the pasted
+/// block would not fit on the sheet or a [`SheetError::Cycle`] if the paste would introduce
+/// a cycle.
+pub fn paste(
+    target: &str,
+    c: i32,
+    r: i32,
+    arr: &mut Grid<Cell>,
+    graph: &mut Graph,
+    formula_array: &mut Grid<Formula>,
+    state: &mut State,
+) -> Result<(), SheetError> {
+    let clipboard = state.clipboard.ok_or_else(|| SheetError::command("Nothing to paste"))?;
+    let (target, _) = anchored_cell_parser(target.trim(), c, r)?;
+    let (height, width) =
+        (clipboard.end / c - clipboard.start / c + 1, clipboard.end % c - clipboard.start % c + 1);
+    if target / c + height > r || target % c + width > c {
+        let end = format!("{}{}", column_label(target % c + width - 1), target / c + height);
+        return Err(SheetError::InvalidRange {
+            range: format!("{}:{}", cell_label(target, c), end),
+        });
+    }
+    let (d_row, d_col) = (target / c - clipboard.start / c, target % c - clipboard.start % c);
+    let shift = |cell: i32| cell + d_row * c + d_col;
+
+    // New formula and value of every changed cell; later entries win.
+    let mut changes = Vec::new();
+    if clipboard.cut {
+        let moved = |start, end, _| {
+            if clipboard.contains(start, c) && clipboard.contains(end, c) {
+                Some((shift(start), shift(end)))
+            } else {
+                Some((start, end))
+            }
+        };
+        for (cell, formula) in formula_array.occupied() {
+            let cell = cell as i32;
+            if !clipboard.contains(cell, c) {
+                let relocated = formula.relocated(&moved);
+                if relocated != *formula {
+                    changes.push((cell, relocated, arr[cell as usize].clone()));
+                }
+            }
+        }
+        for cell in range_cells(clipboard.start, clipboard.end, c) {
+            changes.push((cell, Formula::Empty, Cell::empty()));
+        }
+        for cell in range_cells(clipboard.start, clipboard.end, c) {
+            let formula = formula_array[cell as usize].relocated(&moved);
+            changes.push((shift(cell), formula, arr[cell as usize].clone()));
+        }
+    } else {
+        let shifted = |start, end, [a1, a2]: [Anchor; 2]| {
+            Some((
+                shift_reference(start, a1, d_row, d_col, c, r)?,
+                shift_reference(end, a2, d_row, d_col, c, r)?,
+            ))
+        };
+        for cell in range_cells(clipboard.start, clipboard.end, c) {
+            let formula = formula_array[cell as usize].relocated(&shifted);
+            changes.push((shift(cell), formula, arr[cell as usize].clone()));
+        }
+    }
+
+    let mut touched: Vec<i32> = changes.iter().map(|(cell, ..)| *cell).collect();
+    touched.sort_unstable();
+    touched.dedup();
+    let backup = (arr.clone(), formula_array.clone(), graph.clone());
+
+    for &cell in &touched {
+        graph.delete_edge(cell, formula_array);
+    }
+    for (cell, formula, value) in changes {
+        graph.add_formula(cell, formula, formula_array);
+        arr[cell as usize] = value;
+    }
+    for &cell in &touched {
+        graph.add_edge_formula(cell, c, formula_array);
+    }
+
+    state.has_cycle = false;
+    if let Err(e) = graph.recalc_cells(c, arr, &touched, formula_array, state) {
+        let (old_arr, old_formulas, old_graph) = backup;
+        *arr = old_arr;
+        *formula_array = old_formulas;
+        *graph = old_graph;
+        return Err(e);
+    }
+    if clipboard.cut {
+        state.clipboard = None;
+    }
+    Ok(())
+}
@@ -0,0 +1,418 @@
+//! # Dates and Times
+//!
+//! Dates are stored as [`CellValue::Date`] serial numbers, the convention spreadsheets use:
+//! the whole part counts days since 1899-12-30 and the fraction is the time of day, so
+//! `2025-04-01` is `45748` and `2025-04-01T18:00` is `45748.75`. Serials agree with Excel
+//! from 1900-03-01 on (Excel counts a 1900-02-29 that never existed).
+//!
+//! A date is written as an ISO literal, `A1=2025-04-01` or `A1=2025-04-01T09:30:00`, or built
+//! with `DATE(2025, 4, 1)`. Date functions also accept serial numbers and ISO text such as
+//! `"2025-04-01"`.
+//!
+//! Dates take part in arithmetic as their serial number: a date plus or minus a number of
+//! days is a date, and the difference of two dates is a number of days. Other operations
+//! on dates give plain numbers.
+//!
+//! `TODAY()` and `NOW()` read the time from the [`Clock`] in the sheet's
+//! [`State`](crate::graph_ext::State), which defaults to the system clock in UTC and can be
+//! replaced, e.g. by a [`FixedClock`] in tests. The clock is read once per recalculation, so
+//! every cell recalculated together sees the same time. Like every other formula they are
+//! evaluated when their cell is recalculated.
+
+use crate::aggregate_ext::RangeArg;
+use crate::expr_ext::{Expr, Signature};
+use crate::function_ext::{Cell, CellValue, ErrorKind};
+use crate::graph_ext::{cell_value, eval_expr};
+use crate::grid_ext::Grid;
+use crate::util_ext::{number_value, text_value};
+use std::cell::RefCell;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The date functions handled by [`date_eval`] and their numbers of arguments.
+pub const DATE_FUNCTIONS: &[Signature] = &[
+    ("DATE", 3, 3),
+    ("YEAR", 1, 1),
+    ("MONTH", 1, 1),
+    ("DAY", 1, 1),
+    ("WEEKDAY", 1, 2),
+    ("EDATE", 2, 2),
+    ("EOMONTH", 2, 2),
+    ("DATEDIF", 3, 3),
+    ("NETWORKDAYS", 2, 3),
+    ("TODAY", 0, 0),
+    ("NOW", 0, 0),
+];
+
+/// Serial number of 1970-01-01, the Unix epoch.
+const UNIX_EPOCH_SERIAL: f64 = 25569.0;
+
+/// Days from 0000-03-01 to 1899-12-30, the day of serial `0`.
+const SERIAL_EPOCH_DAYS: i64 = 693_899;
+
+/// A source for the current date and time.
+pub trait Clock: Send + Sync {
+    /// Returns the current date and time as a serial number.
+    fn now(&self) -> f64;
+}
+
+/// The system clock, in UTC.
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> f64 {
+        let secs = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0.0, |d| d.as_secs_f64());
+        UNIX_EPOCH_SERIAL + secs / 86400.0
+    }
+}
+
+/// A clock that always returns the same serial date-time.
+pub struct FixedClock(pub f64);
+
+impl Clock for FixedClock {
+    fn now(&self) -> f64 {
+        self.0
+    }
+}
+
+thread_local! {
+    /// The time of the recalculation running on this thread; see [`with_time`].
+    static NOW: RefCell<Option<f64>> = const { RefCell::new(None) };
+}
+
+/// Runs `f`, a recalculation, with `TODAY()` and `NOW()` giving the serial date-time `now`.
+/// The previous time is put back afterwards, so nothing carries over to other sheets.
+pub fn with_time<T>(now: f64, f: impl FnOnce() -> T) -> T {
+    struct Restore(Option<f64>);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            NOW.with(|cell| *cell.borrow_mut() = self.0);
+        }
+    }
+    let _restore = Restore(NOW.with(|cell| cell.borrow_mut().replace(now)));
+    f()
+}
+
+/// Returns the time of the running recalculation, or the system time outside of one.
+fn now() -> f64 {
+    NOW.with(|cell| *cell.borrow()).unwrap_or_else(|| SystemClock.now())
+}
+
+/// Days since 0000-03-01 of the given proleptic Gregorian date.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    // Count years from March so that the leap day is the last day of the year.
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = month as i64;
+    let day_of_year =
+        (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era
+}
+
+/// Inverse of [`days_from_civil`].
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+fn is_leap(year: i64) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+/// Number of days in `month` of `year`.
+pub fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if is_leap(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Serial number of a date. `month` may lie outside `1..=12` and rolls over into other years.
+pub fn serial_from_ymd(year: i64, month: i64, day: i64) -> f64 {
+    let months = year * 12 + month - 1;
+    let (year, month) = (months.div_euclid(12), (months.rem_euclid(12) + 1) as u32);
+    (days_from_civil(year, month, 1) + day - 1 - SERIAL_EPOCH_DAYS) as f64
+}
+
+/// Year, month and day of a serial number; the time of day is ignored.
+pub fn ymd_from_serial(serial: f64) -> (i64, u32, u32) {
+    civil_from_days(serial.floor() as i64 + SERIAL_EPOCH_DAYS)
+}
+
+/// Parses an ISO date at the start of `text`: `YYYY-MM-DD`, optionally followed by a time
+/// `THH:MM` or `THH:MM:SS`.
+///
+/// Returns the serial number and the number of bytes read, or `None` if `text` does not
+/// start with a valid date.
+pub fn parse_iso(text: &str) -> Option<(f64, usize)> {
+    let bytes = text.as_bytes();
+    let number = |from: usize, len: usize| -> Option<i64> {
+        let digits = bytes.get(from..from + len)?;
+        digits.iter().all(u8::is_ascii_digit).then(|| text[from..from + len].parse().ok())?
+    };
+    let (year, month, day) = (number(0, 4)?, number(5, 2)?, number(8, 2)?);
+    if bytes.get(4) != Some(&b'-') || bytes.get(7) != Some(&b'-') {
+        return None;
+    }
+    if !(1..=12).contains(&month) || day < 1 || day > days_in_month(year, month as u32) as i64 {
+        return None;
+    }
+    let mut serial = serial_from_ymd(year, month, day);
+    let mut len = 10;
+    if bytes.get(10) == Some(&b'T') && bytes.get(13) == Some(&b':') {
+        let (hours, minutes) = (number(11, 2)?, number(14, 2)?);
+        let seconds = if bytes.get(16) == Some(&b':') { number(17, 2)? } else { 0 };
+        if hours > 23 || minutes > 59 || seconds > 59 {
+            return None;
+        }
+        serial += (hours * 3600 + minutes * 60 + seconds) as f64 / 86400.0;
+        len = if bytes.get(16) == Some(&b':') { 19 } else { 16 };
+    }
+    Some((serial, len))
+}
+
+/// Formats a serial number as an ISO date, `2025-04-01`, adding the time of day
+/// (`2025-04-01T09:30:00`) if it is not midnight. The result reads back with [`parse_iso`].
+pub fn format_date(serial: f64) -> String {
+    let (year, month, day) = ymd_from_serial(serial);
+    let secs = ((serial - serial.floor()) * 86400.0).round() as i64;
+    // Rounding may carry a time just before midnight into the next day.
+    let (year, month, day, secs) = if secs >= 86400 {
+        let (y, m, d) = ymd_from_serial(serial.floor() + 1.0);
+        (y, m, d, 0)
+    } else {
+        (year, month, day, secs)
+    };
+    let date = format!("{:04}-{:02}-{:02}", year, month, day);
+    if secs == 0 {
+        date
+    } else {
+        format!("{}T{:02}:{:02}:{:02}", date, secs / 3600, secs / 60 % 60, secs % 60)
+    }
+}
+
+/// Evaluates one of the [`DATE_FUNCTIONS`].
+pub fn date_eval(name: &str, args: &[Expr], arr: &Grid<Cell>, cols: i32) -> Cell {
+    let result = match (name, args) {
+        ("TODAY", []) => Ok(Cell::new_date(now().floor())),
+        ("NOW", []) => Ok(Cell::new_date(now())),
+        ("DATE", [year, month, day]) => date(year, month, day, arr, cols),
+        ("YEAR" | "MONTH" | "DAY", [date]) => date_arg(date, arr, cols).map(|serial| {
+            let (year, month, day) = ymd_from_serial(serial);
+            let part = match name {
+                "YEAR" => year,
+                "MONTH" => month as i64,
+                _ => day as i64,
+            };
+            Cell::new_int(part as i32)
+        }),
+        ("WEEKDAY", [date, kind @ ..]) => weekday(date, kind.first(), arr, cols),
+        ("EDATE" | "EOMONTH", [start, months]) => {
+            shift_months(name == "EOMONTH", start, months, arr, cols)
+        }
+        ("DATEDIF", [start, end, unit]) => datedif(start, end, unit, arr, cols),
+        ("NETWORKDAYS", [start, end, holidays @ ..]) => {
+            networkdays(start, end, holidays.first(), arr, cols)
+        }
+        _ => Err(ErrorKind::Value),
+    };
+    result.unwrap_or_else(Cell::error)
+}
+
+/// Converts a value to a serial number: dates and numbers as they are, ISO text parsed.
+///
+/// # Errors
+/// Returns the value's error, `#VALUE!` for other text and booleans, and `#NUM!` for a
+/// negative serial.
+pub fn date_value(cell: &Cell) -> Result<f64, ErrorKind> {
+    if let Some(kind) = cell.error_kind() {
+        return Err(kind);
+    }
+    let serial = match &cell.value {
+        CellValue::Date(serial) => *serial,
+        CellValue::String(text) => match parse_iso(text.trim()) {
+            Some((serial, len)) if len == text.trim().len() => serial,
+            _ => return Err(ErrorKind::Value),
+        },
+        CellValue::Bool(_) => return Err(ErrorKind::Value),
+        _ => number_value(cell)?,
+    };
+    if serial < 0.0 {
+        return Err(ErrorKind::Num);
+    }
+    Ok(serial)
+}
+
+fn date_arg(expr: &Expr, arr: &Grid<Cell>, cols: i32) -> Result<f64, ErrorKind> {
+    date_value(&eval_expr(expr, arr, cols))
+}
+
+fn int_arg(expr: &Expr, arr: &Grid<Cell>, cols: i32) -> Result<i64, ErrorKind> {
+    Ok(number_value(&eval_expr(expr, arr, cols))?.trunc() as i64)
+}
+
+/// `DATE(year, month, day)`. Years below 1900 are taken as offsets from 1900, and months and
+/// days outside their usual range roll over, e.g. `DATE(2025, 14, 1)` is 2026-02-01.
+fn date(
+    year: &Expr,
+    month: &Expr,
+    day: &Expr,
+    arr: &Grid<Cell>,
+    cols: i32,
+) -> Result<Cell, ErrorKind> {
+    let (year, month, day) =
+        (int_arg(year, arr, cols)?, int_arg(month, arr, cols)?, int_arg(day, arr, cols)?);
+    let year = if (0..1900).contains(&year) { year + 1900 } else { year };
+    // Keep far-out months and days from overflowing; anything this large is past 9999 anyway.
+    if !(0..=9999).contains(&year) || month.abs() > 120_000 || day.abs() > 4_000_000 {
+        return Err(ErrorKind::Num);
+    }
+    let serial = serial_from_ymd(year, month, day);
+    if serial < 0.0 {
+        return Err(ErrorKind::Num);
+    }
+    Ok(Cell::new_date(serial))
+}
+
+/// `WEEKDAY(date[, type])`: `1` (default) counts Sunday = 1 to Saturday = 7, `2` Monday = 1
+/// to Sunday = 7 and `3` Monday = 0 to Sunday = 6.
+fn weekday(
+    date: &Expr,
+    kind: Option<&Expr>,
+    arr: &Grid<Cell>,
+    cols: i32,
+) -> Result<Cell, ErrorKind> {
+    let serial = date_arg(date, arr, cols)?;
+    let kind = match kind {
+        Some(kind) => int_arg(kind, arr, cols)?,
+        None => 1,
+    };
+    // Serial 0, 1899-12-30, was a Saturday.
+    let from_sunday = (serial.floor() as i64 + 6).rem_euclid(7);
+    let from_monday = (from_sunday + 6) % 7;
+    let day = match kind {
+        1 => from_sunday + 1,
+        2 => from_monday + 1,
+        3 => from_monday,
+        _ => return Err(ErrorKind::Num),
+    };
+    Ok(Cell::new_int(day as i32))
+}
+
+/// `EDATE(start, months)`: the same day `months` months later, clamped to the end of a
+/// shorter month. `EOMONTH(start, months)` gives the last day of that month instead.
+fn shift_months(
+    end_of_month: bool,
+    start: &Expr,
+    months: &Expr,
+    arr: &Grid<Cell>,
+    cols: i32,
+) -> Result<Cell, ErrorKind> {
+    let (year, month, day) = ymd_from_serial(date_arg(start, arr, cols)?);
+    let months = int_arg(months, arr, cols)?;
+    if months.abs() > 120_000 {
+        return Err(ErrorKind::Num);
+    }
+    let total = year * 12 + month as i64 - 1 + months;
+    let (year, month) = (total.div_euclid(12), (total.rem_euclid(12) + 1) as u32);
+    let last = days_in_month(year, month);
+    let day = if end_of_month { last } else { day.min(last) };
+    let serial = serial_from_ymd(year, month as i64, day as i64);
+    if serial < 0.0 {
+        return Err(ErrorKind::Num);
+    }
+    Ok(Cell::new_date(serial))
+}
+
+/// `DATEDIF(start, end, unit)`: the time between two dates in complete years (`"Y"`),
+/// months (`"M"`) or days (`"D"`), or the days (`"MD"`), months (`"YM"`) or days (`"YD"`)
+/// left over after whole months or years. `start` after `end` gives `#NUM!`.
+fn datedif(
+    start: &Expr,
+    end: &Expr,
+    unit: &Expr,
+    arr: &Grid<Cell>,
+    cols: i32,
+) -> Result<Cell, ErrorKind> {
+    let (start, end) = (date_arg(start, arr, cols)?.floor(), date_arg(end, arr, cols)?.floor());
+    let unit = text_value(&eval_expr(unit, arr, cols))?.to_ascii_uppercase();
+    if start > end {
+        return Err(ErrorKind::Num);
+    }
+    let (y1, m1, d1) = ymd_from_serial(start);
+    let (y2, m2, d2) = ymd_from_serial(end);
+    let months = (y2 - y1) * 12 + m2 as i64 - m1 as i64 - if d2 < d1 { 1 } else { 0 };
+    let result = match unit.as_str() {
+        "Y" => months / 12,
+        "M" => months,
+        "D" => (end - start) as i64,
+        "YM" => months % 12,
+        "MD" if d2 >= d1 => (d2 - d1) as i64,
+        "MD" => {
+            let (year, month) = if m2 == 1 { (y2 - 1, 12) } else { (y2, m2 - 1) };
+            (days_in_month(year, month) + d2) as i64 - d1 as i64
+        }
+        "YD" => {
+            // Move the start into the last year that keeps it on or before the end.
+            let mut anniversary = shifted_year(y2, m1, d1);
+            if anniversary > end {
+                anniversary = shifted_year(y2 - 1, m1, d1);
+            }
+            (end - anniversary) as i64
+        }
+        _ => return Err(ErrorKind::Num),
+    };
+    Ok(Cell::new_int(result as i32))
+}
+
+/// The serial of `month`/`day` in `year`, with 29 February becoming the 28th if needed.
+fn shifted_year(year: i64, month: u32, day: u32) -> f64 {
+    serial_from_ymd(year, month as i64, day.min(days_in_month(year, month)) as i64)
+}
+
+/// `NETWORKDAYS(start, end[, holidays])`: the number of weekdays from `start` to `end`,
+/// both included, that are not among the dates in the `holidays` range. Negative if `start`
+/// is after `end`.
+fn networkdays(
+    start: &Expr,
+    end: &Expr,
+    holidays: Option<&Expr>,
+    arr: &Grid<Cell>,
+    cols: i32,
+) -> Result<Cell, ErrorKind> {
+    let (start, end) = (date_arg(start, arr, cols)?.floor(), date_arg(end, arr, cols)?.floor());
+    let (first, last) = (start.min(end) as i64, start.max(end) as i64);
+    let is_weekday = |day: i64| (day + 6).rem_euclid(7) % 6 != 0;
+
+    let days = last - first + 1;
+    let mut count = days / 7 * 5;
+    count += (first + days / 7 * 7..=last).filter(|&day| is_weekday(day)).count() as i64;
+
+    if let Some(holidays) = holidays {
+        let mut seen = Vec::new();
+        for idx in RangeArg::from_expr(holidays, arr, cols)?.cells {
+            let cell = cell_value(arr, idx);
+            if cell.value == CellValue::Empty {
+                continue;
+            }
+            let day = date_value(&cell)?.floor() as i64;
+            if (first..=last).contains(&day) && is_weekday(day) && !seen.contains(&day) {
+                seen.push(day);
+                count -= 1;
+            }
+        }
+    }
+    let count = if start > end { -count } else { count };
+    Ok(Cell::new_int(count as i32))
+}
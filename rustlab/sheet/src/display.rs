@@ -1,4 +1,5 @@
 use crate::graph::Graph;
+use crate::grid_ext::Grid;
 use std::cmp::min;
 // use std::i32;
 
@@ -18,7 +19,7 @@ use std::cmp::min;
 /// - Column headers in Excel-style format (`A`, `B`, ..., `Z`, `AA`, `AB`, etc.).
 /// - Row numbers along the left edge.
 /// - Values from the spreadsheet grid, with `"ERR"` shown for invalid entries.
-pub fn printer(currx: usize, curry: usize, arr: &[i32], c: usize, r: usize) {
+pub fn printer(currx: usize, curry: usize, arr: &Grid<i32>, c: usize, r: usize) {
     print!("      ");
 
     let visible_cols = min(10, c.saturating_sub(currx));
@@ -77,7 +78,7 @@ pub fn printer(currx: usize, curry: usize, arr: &[i32], c: usize, r: usize) {
 /// * `-1` if the command is unrecognized.
 pub fn scroller(
     a: &str,
-    _arr: &mut [i32],
+    _arr: &mut Grid<i32>,
     currx: &mut usize,
     curry: &mut usize,
     c: usize,
@@ -1,5 +1,8 @@
+use crate::date_ext::format_date;
+use crate::error_ext::SheetError;
 use crate::function_ext::{Cell, CellValue};
 use crate::graph_ext::Graph;
+use crate::grid_ext::Grid;
 use crate::parser_ext::cell_parser;
 use std::cmp::min;
 
@@ -13,10 +16,11 @@ use std::cmp::min;
 /// * `r` - Total number of rows in the spreadsheet.
 ///
 /// The function prints a tabular view of up to 10 rows and columns. Column headers are displayed
-/// in Excel-style format (A, B, ..., Z, AA, AB, ...), and invalid cells show `ERR`.
+/// in Excel-style format (A, B, ..., Z, AA, AB, ...), and error cells show their kind,
+/// e.g. `#DIV/0!`. Blank cells are shown as `0`.
 ///
 /// This function is used to simulate viewport-like scrolling in a terminal-based spreadsheet UI.
-pub fn printer(currx: i32, curry: i32, arr: &[Cell], c: i32, r: i32) {
+pub fn printer(currx: i32, curry: i32, arr: &Grid<Cell>, c: i32, r: i32) {
     print!("      ");
 
     let display_cols = min(10, c - currx);
@@ -38,24 +42,30 @@ pub fn printer(currx: i32, curry: i32, arr: &[Cell], c: i32, r: i32) {
         print!("{:<3}   ", curry + j + 1);
         for i in 0..display_cols {
             let value = &arr[((currx + i) + c * (curry + j)) as usize];
-            if !value.is_valid {
-                print!("{:<10}", "ERR");
-            } else {
-                match &value.value {
-                    CellValue::Int(i) => print!("{:<10}", i),
-                    CellValue::Float(f) => print!("{:<10.2}", f),
-                    CellValue::String(s) => {
-                        let truncated =
-                            if s.len() > 10 { format!("{:.10}", &s[..10]) } else { s.clone() };
-                        print!("{:<10}", truncated);
-                    }
+            match &value.value {
+                CellValue::Int(i) => print!("{:<10}", i),
+                CellValue::Float(f) => print!("{:<10.2}", f),
+                // The column shows the date; a time of day does not fit.
+                CellValue::Date(serial) => print!("{:<10.10}", format_date(*serial)),
+                CellValue::String(s) => {
+                    // Truncate by characters: slicing by bytes panics inside multi-byte text.
+                    let truncated: String = s.chars().take(10).collect();
+                    print!("{:<10}", truncated);
                 }
+                CellValue::Empty => print!("{:<10}", 0),
+                CellValue::Bool(b) => print!("{:<10}", if *b { "TRUE" } else { "FALSE" }),
+                CellValue::Error(kind) => print!("{:<10}", kind.to_string()),
             }
         }
         println!();
     }
 }
 
+/// Returns `true` if `a` is a command for [`scroller`], i.e. it only moves the viewport.
+pub fn is_scroll(a: &str) -> bool {
+    matches!(a, "w" | "a" | "s" | "d") || a.starts_with("scroll_to ")
+}
+
 /// Handles scrolling logic for the spreadsheet viewport using directional commands or `scroll_to`.
 ///
 /// # Arguments
@@ -79,13 +89,13 @@ pub fn printer(currx: i32, curry: i32, arr: &[Cell], c: i32, r: i32) {
 /// This function updates `currx` and `curry` based on navigation input, ensuring they remain in bounds.
 pub fn scroller(
     a: &str,
-    _arr: &[Cell],
+    _arr: &Grid<Cell>,
     currx: &mut i32,
     curry: &mut i32,
     c: i32,
     r: i32,
     _graph: &Graph,
-) -> Result<(), &'static str> {
+) -> Result<(), SheetError> {
     // let mut _flag = false;
 
     match a {
@@ -134,7 +144,7 @@ pub fn scroller(
         s if s.starts_with("scroll_to ") => {
             let parts: Vec<&str> = s[9..].split_whitespace().collect();
             if parts.len() != 1 {
-                return Err("Invalid scroll_to format");
+                return Err(SheetError::command("Invalid scroll_to format"));
             }
             let cell = cell_parser(parts[0], c, r, 0, parts[0].len() - 1)?;
             if cell < 0 || cell >= (c * r) {
@@ -150,7 +160,7 @@ pub fn scroller(
                 }
             }
         }
-        _ => return Err("Unknown scroll command"),
+        _ => return Err(SheetError::command("Unknown scroll command")),
     }
 
     Ok(())
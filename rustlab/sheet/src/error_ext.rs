@@ -0,0 +1,87 @@
+//! # Errors
+//!
+//! [`SheetError`] is the error type of the extended engine. Each variant carries what a
+//! front end needs to report the problem: where a formula stopped parsing, which reference
+//! or function was wrong, or which cells form a cycle. It serializes to JSON as an object
+//! tagged with its `kind`, e.g. `{"kind":"cycle","path":["A1","B1","A1"]}`.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// An error reported by the extended engine.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SheetError {
+    /// The formula is malformed: `expected` was expected at byte `position` of the formula.
+    Parse { position: usize, expected: String },
+    /// A call to a function that doesn't exist.
+    UnknownFunction { name: String },
+    /// A cell, row or column reference that is malformed or lies outside the sheet.
+    BadReference { reference: String },
+    /// The change would make cells depend on themselves. `path` lists the cells of the loop,
+    /// each reading the next, starting and ending with the same cell.
+    Cycle { path: Vec<String> },
+    /// A range whose corners are malformed or that doesn't fit in the sheet.
+    InvalidRange { range: String },
+    /// A value of the wrong type, e.g. text where a number is needed.
+    TypeMismatch { expected: String },
+    /// A command that can't be carried out, e.g. an undo with nothing to undo or a
+    /// command with the wrong arguments.
+    Command { message: String },
+}
+
+impl SheetError {
+    /// Shorthand for a [`SheetError::Parse`] error.
+    pub fn parse(position: usize, expected: impl Into<String>) -> Self {
+        SheetError::Parse { position, expected: expected.into() }
+    }
+
+    /// Shorthand for a [`SheetError::BadReference`] error.
+    pub fn bad_reference(reference: impl Into<String>) -> Self {
+        SheetError::BadReference { reference: reference.into() }
+    }
+
+    /// Shorthand for a [`SheetError::Command`] error.
+    pub fn command(message: impl Into<String>) -> Self {
+        SheetError::Command { message: message.into() }
+    }
+
+    /// Returns `true` for a [`SheetError::Cycle`].
+    pub fn is_cycle(&self) -> bool {
+        matches!(self, SheetError::Cycle { .. })
+    }
+
+    /// Moves the position of a parse error by `offset` bytes, e.g. from the start of the
+    /// formula to the start of the whole input line.
+    pub fn offset(self, offset: usize) -> Self {
+        match self {
+            SheetError::Parse { position, expected } => {
+                SheetError::Parse { position: position + offset, expected }
+            }
+            other => other,
+        }
+    }
+}
+
+impl fmt::Display for SheetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SheetError::Parse { position, expected } => {
+                write!(f, "Parse error at column {}: expected {}", position + 1, expected)
+            }
+            SheetError::UnknownFunction { name } => write!(f, "Unknown function {}", name),
+            SheetError::BadReference { reference } => write!(f, "Invalid reference {}", reference),
+            SheetError::Cycle { path } if path.is_empty() => {
+                write!(f, "Circular dependency detected")
+            }
+            SheetError::Cycle { path } => write!(f, "Circular dependency: {}", path.join(" → ")),
+            SheetError::InvalidRange { range } => write!(f, "Invalid range {}", range),
+            SheetError::TypeMismatch { expected } => {
+                write!(f, "Type mismatch: expected {}", expected)
+            }
+            SheetError::Command { message } => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for SheetError {}
@@ -0,0 +1,708 @@
+//! # Formula Expressions
+//!
+//! Tokenizer, abstract syntax tree and precedence-climbing parser for formulas in the
+//! extended spreadsheet. A formula such as `=(A1+B1)*2-SUM(C1:C3)` is turned into an
+//! [`Expr`] tree, which is stored for the cell and evaluated by
+//! [`Graph::recalc`](crate::graph_ext::Graph::recalc) whenever one of its inputs changes.
+//!
+//! ## Grammar
+//! ```text
+//! expr    := unary (binop unary)*          (precedence: ^, * /, + -, &, comparisons)
+//! unary   := ('+' | '-') unary | primary
+//! primary := number | date | string | error | TRUE | FALSE | cell | NAME '(' args ')'
+//!          | '(' expr ')'
+//! args    := arg (',' arg)*
+//! arg     := cell ':' cell | expr           (ranges are only valid as arguments)
+//! binop   := '+' | '-' | '*' | '/' | '^' | '&' | '=' | '<>' | '<' | '<=' | '>' | '>='
+//! date    := YYYY '-' MM '-' DD ('T' hh ':' mm (':' ss)?)?
+//! error   := '#DIV/0!' | '#VALUE!' | '#REF!' | '#NAME?' | '#NUM!' | '#CYCLE!' | '#N/A'
+//! ```
+//!
+//! A date literal must be written in full ISO form, e.g. `2025-04-01`; `2025-4-1` is still
+//! read as a subtraction.
+
+use crate::date_ext::{format_date, parse_iso, DATE_FUNCTIONS};
+use crate::error_ext::SheetError;
+use crate::finance_ext::FINANCE_FUNCTIONS;
+use crate::function_ext::Cell;
+use crate::function_ext::{CellValue, ErrorKind};
+use crate::lookup_ext::LOOKUP_FUNCTIONS;
+use crate::math_ext::MATH_FUNCTIONS;
+use crate::parser_ext::anchored_cell_parser;
+use crate::stats_ext::STAT_FUNCTIONS;
+use crate::text_ext::TEXT_FUNCTIONS;
+use crate::util_ext::{
+    anchored_label, arithmetic_eval, compare_eval, concat_eval, split_anchors, Anchor,
+};
+
+/// Names of the aggregate functions. Each argument may be a range, a cell, a literal
+/// or a nested expression, e.g. `SUM(A1:A3, C5, 10, B1:B2)`.
+pub const RANGE_FUNCTIONS: [&str; 5] = ["MIN", "MAX", "AVG", "SUM", "STDEV"];
+
+/// Name of a function with its minimum and maximum number of arguments.
+pub type Signature = (&'static str, usize, usize);
+
+/// The functions evaluated by [`graph_ext`](crate::graph_ext) itself: the aggregates, `SLEEP`,
+/// and the logical and counting functions.
+pub const CORE_FUNCTIONS: &[Signature] = &[
+    ("MIN", 1, usize::MAX),
+    ("MAX", 1, usize::MAX),
+    ("AVG", 1, usize::MAX),
+    ("SUM", 1, usize::MAX),
+    ("STDEV", 1, usize::MAX),
+    ("SLEEP", 1, 1),
+    ("IF", 2, 3),
+    ("AND", 1, usize::MAX),
+    ("OR", 1, usize::MAX),
+    ("NOT", 1, 1),
+    ("IFERROR", 2, 2),
+    ("COUNT", 1, usize::MAX),
+    ("COUNTA", 1, usize::MAX),
+    ("COUNTBLANK", 1, 1),
+    ("COUNTIF", 2, 2),
+    ("SUMIF", 2, 3),
+    ("AVERAGEIF", 2, 3),
+    ("COUNTIFS", 2, usize::MAX),
+    ("SUMIFS", 3, usize::MAX),
+];
+
+/// The function tables of every module that evaluates functions.
+const FUNCTION_TABLES: &[&[Signature]] = &[
+    CORE_FUNCTIONS,
+    STAT_FUNCTIONS,
+    MATH_FUNCTIONS,
+    TEXT_FUNCTIONS,
+    DATE_FUNCTIONS,
+    FINANCE_FUNCTIONS,
+    LOOKUP_FUNCTIONS,
+];
+
+/// Returns `true` if `table` lists the function `name`.
+pub fn has_function(table: &[Signature], name: &str) -> bool {
+    table.iter().any(|&(f, ..)| f == name)
+}
+
+/// Returns the minimum and maximum number of arguments of the function `name`, or `None` if
+/// no module evaluates it.
+pub fn arity(name: &str) -> Option<(usize, usize)> {
+    FUNCTION_TABLES
+        .iter()
+        .flat_map(|table| table.iter())
+        .find(|&&(f, ..)| f == name)
+        .map(|&(_, min_args, max_args)| (min_args, max_args))
+}
+
+/// A lexical token of a formula, tagged with its byte offset in the source.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Token {
+    /// The kind of token.
+    pub kind: TokenKind,
+    /// Byte offset of the first character of the token.
+    pub pos: usize,
+}
+
+/// The different kinds of tokens produced by [`tokenize`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum TokenKind {
+    /// Integer literal, e.g. `42`
+    Int(i32),
+    /// Floating-point literal, e.g. `3.14`
+    Float(f64),
+    /// ISO date literal as a serial number, e.g. `2025-04-01`
+    Date(f64),
+    /// Quoted string literal with the quotes removed
+    Str(String),
+    /// Error literal, e.g. the `#REF!` left behind by a reference pasted off the sheet
+    Error(ErrorKind),
+    /// Cell reference, e.g. `B12`
+    Ref(String),
+    /// Bare identifier, e.g. a function name
+    Ident(String),
+    /// Arithmetic or text operator: `+`, `-`, `*`, `/`, `^`, `&`
+    Op(char),
+    /// Comparison operator: `=`, `<>`, `<`, `<=`, `>`, `>=`
+    Cmp(BinaryOp),
+    /// `(`
+    LParen,
+    /// `)`
+    RParen,
+    /// `,`
+    Comma,
+    /// `:`
+    Colon,
+}
+
+/// Unary operators.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UnaryOp {
+    /// `+x`
+    Plus,
+    /// `-x`
+    Neg,
+}
+
+/// Binary operators.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BinaryOp {
+    /// `+`
+    Add,
+    /// `-`
+    Sub,
+    /// `*`
+    Mul,
+    /// `/`
+    Div,
+    /// `^` (exponentiation)
+    Pow,
+    /// `&` (text concatenation)
+    Concat,
+    /// `=`
+    Eq,
+    /// `<>`
+    Ne,
+    /// `<`
+    Lt,
+    /// `<=`
+    Le,
+    /// `>`
+    Gt,
+    /// `>=`
+    Ge,
+}
+
+impl BinaryOp {
+    /// Maps an operator character to its `BinaryOp`.
+    pub fn from_char(op: char) -> Option<Self> {
+        match op {
+            '+' => Some(BinaryOp::Add),
+            '-' => Some(BinaryOp::Sub),
+            '*' => Some(BinaryOp::Mul),
+            '/' => Some(BinaryOp::Div),
+            '^' => Some(BinaryOp::Pow),
+            '&' => Some(BinaryOp::Concat),
+            _ => None,
+        }
+    }
+
+    /// Returns the operator as written in formulas.
+    pub fn symbol(self) -> &'static str {
+        match self {
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mul => "*",
+            BinaryOp::Div => "/",
+            BinaryOp::Pow => "^",
+            BinaryOp::Concat => "&",
+            BinaryOp::Eq => "=",
+            BinaryOp::Ne => "<>",
+            BinaryOp::Lt => "<",
+            BinaryOp::Le => "<=",
+            BinaryOp::Gt => ">",
+            BinaryOp::Ge => ">=",
+        }
+    }
+
+    /// Returns `true` for the comparison operators, which evaluate to a boolean.
+    pub fn is_comparison(self) -> bool {
+        self.precedence() == 1
+    }
+
+    /// Binding strength of the operator; higher binds tighter.
+    pub fn precedence(self) -> u8 {
+        match self {
+            BinaryOp::Eq
+            | BinaryOp::Ne
+            | BinaryOp::Lt
+            | BinaryOp::Le
+            | BinaryOp::Gt
+            | BinaryOp::Ge => 1,
+            BinaryOp::Concat => 2,
+            BinaryOp::Add | BinaryOp::Sub => 3,
+            BinaryOp::Mul | BinaryOp::Div => 4,
+            BinaryOp::Pow => 5,
+        }
+    }
+
+    /// Applies the operator to two values, using [`arithmetic_eval`], [`concat_eval`] or
+    /// [`compare_eval`].
+    pub fn apply(self, lhs: Cell, rhs: Cell) -> Cell {
+        match self {
+            BinaryOp::Add => arithmetic_eval(lhs, rhs, '+'),
+            BinaryOp::Sub => arithmetic_eval(lhs, rhs, '-'),
+            BinaryOp::Mul => arithmetic_eval(lhs, rhs, '*'),
+            BinaryOp::Div => arithmetic_eval(lhs, rhs, '/'),
+            BinaryOp::Pow => arithmetic_eval(lhs, rhs, '^'),
+            BinaryOp::Concat => concat_eval(lhs, rhs),
+            cmp => compare_eval(lhs, rhs, cmp.symbol()),
+        }
+    }
+}
+
+/// A parsed formula.
+///
+/// Cell references and ranges hold flat cell indices (`row * cols + col`).
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expr {
+    /// A constant value.
+    Literal(CellValue),
+    /// A reference to a single cell, with the `$` anchors it was written with.
+    Cell(i32, Anchor),
+    /// A rectangular range given by its two corner cells and their anchors.
+    Range(i32, i32, [Anchor; 2]),
+    /// A unary operation.
+    Unary { op: UnaryOp, operand: Box<Expr> },
+    /// A binary operation.
+    Binary { op: BinaryOp, lhs: Box<Expr>, rhs: Box<Expr> },
+    /// A function call such as `SUM(A1:A3)`.
+    Call { name: String, args: Vec<Expr> },
+    /// A parenthesised sub-expression.
+    Group(Box<Expr>),
+}
+
+impl Expr {
+    /// Collects every single-cell reference and every range used by the expression.
+    ///
+    /// These are the static dependencies registered in the dependency graph.
+    pub fn collect_refs(&self, cells: &mut Vec<i32>, ranges: &mut Vec<(i32, i32)>) {
+        match self {
+            Expr::Literal(_) => {}
+            Expr::Cell(idx, _) => cells.push(*idx),
+            Expr::Range(start, end, _) => ranges.push((*start, *end)),
+            Expr::Unary { operand, .. } => operand.collect_refs(cells, ranges),
+            Expr::Binary { lhs, rhs, .. } => {
+                lhs.collect_refs(cells, ranges);
+                rhs.collect_refs(cells, ranges);
+            }
+            Expr::Call { args, .. } => {
+                for arg in args {
+                    arg.collect_refs(cells, ranges);
+                }
+            }
+            Expr::Group(inner) => inner.collect_refs(cells, ranges),
+        }
+    }
+
+    /// Returns a copy of the expression with every reference passed through `relocate`.
+    ///
+    /// `relocate` gets the two corners of a range and their anchors (both corners are the
+    /// same cell for a single reference) and returns the new corners, or `None` if the
+    /// reference no longer points into the sheet, in which case it is replaced by `#REF!`.
+    pub fn relocated(
+        &self,
+        relocate: &impl Fn(i32, i32, [Anchor; 2]) -> Option<(i32, i32)>,
+    ) -> Expr {
+        let off_sheet = Expr::Literal(CellValue::Error(ErrorKind::Ref));
+        match self {
+            Expr::Literal(_) => self.clone(),
+            Expr::Cell(idx, anchor) => relocate(*idx, *idx, [*anchor; 2])
+                .map_or(off_sheet, |(idx, _)| Expr::Cell(idx, *anchor)),
+            Expr::Range(start, end, anchors) => relocate(*start, *end, *anchors)
+                .map_or(off_sheet, |(start, end)| Expr::Range(start, end, *anchors)),
+            Expr::Unary { op, operand } => {
+                Expr::Unary { op: *op, operand: Box::new(operand.relocated(relocate)) }
+            }
+            Expr::Binary { op, lhs, rhs } => Expr::Binary {
+                op: *op,
+                lhs: Box::new(lhs.relocated(relocate)),
+                rhs: Box::new(rhs.relocated(relocate)),
+            },
+            Expr::Call { name, args } => Expr::Call {
+                name: name.clone(),
+                args: args.iter().map(|arg| arg.relocated(relocate)).collect(),
+            },
+            Expr::Group(inner) => Expr::Group(Box::new(inner.relocated(relocate))),
+        }
+    }
+
+    /// Renders the expression back to canonical formula text (without the leading `=`).
+    ///
+    /// Cell indices are turned back into labels using `cols`; the result parses back
+    /// into an equivalent expression.
+    pub fn to_formula_string(&self, cols: i32) -> String {
+        match self {
+            Expr::Literal(CellValue::Int(i)) => i.to_string(),
+            Expr::Literal(CellValue::Float(f)) if f.fract() == 0.0 => format!("{:.1}", f),
+            Expr::Literal(CellValue::Float(f)) => f.to_string(),
+            Expr::Literal(CellValue::Date(serial)) => format_date(*serial),
+            Expr::Literal(CellValue::String(s)) => format!("\"{}\"", s.replace('"', "\"\"")),
+            Expr::Literal(CellValue::Bool(b)) => (if *b { "TRUE" } else { "FALSE" }).to_string(),
+            Expr::Literal(CellValue::Error(kind)) => kind.to_string(),
+            Expr::Literal(CellValue::Empty) => "\"\"".to_string(),
+            Expr::Cell(idx, anchor) => anchored_label(*idx, cols, *anchor),
+            Expr::Range(start, end, [a1, a2]) => {
+                format!("{}:{}", anchored_label(*start, cols, *a1), anchored_label(*end, cols, *a2))
+            }
+            Expr::Unary { op, operand } => {
+                let sign = if *op == UnaryOp::Neg { '-' } else { '+' };
+                match **operand {
+                    Expr::Binary { .. } => format!("{}({})", sign, operand.to_formula_string(cols)),
+                    _ => format!("{}{}", sign, operand.to_formula_string(cols)),
+                }
+            }
+            Expr::Binary { op, lhs, rhs } => {
+                let mut lhs_text = lhs.to_formula_string(cols);
+                let mut rhs_text = rhs.to_formula_string(cols);
+                if matches!(**lhs, Expr::Binary { op: l, .. } if l.precedence() < op.precedence()) {
+                    lhs_text = format!("({})", lhs_text);
+                }
+                if matches!(**rhs, Expr::Binary { op: r, .. } if r.precedence() <= op.precedence())
+                {
+                    rhs_text = format!("({})", rhs_text);
+                }
+                format!("{}{}{}", lhs_text, op.symbol(), rhs_text)
+            }
+            Expr::Call { name, args } => {
+                let args: Vec<String> = args.iter().map(|a| a.to_formula_string(cols)).collect();
+                format!("{}({})", name, args.join(","))
+            }
+            Expr::Group(inner) => format!("({})", inner.to_formula_string(cols)),
+        }
+    }
+}
+
+/// Splits formula text into tokens.
+///
+/// # Errors
+/// Returns a [`SheetError::Parse`] on an unterminated string, a malformed number or an
+/// unexpected character, and a [`SheetError::BadReference`] on a misplaced `$`.
+pub fn tokenize(src: &str) -> Result<Vec<Token>, SheetError> {
+    let bytes = src.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let ch = bytes[i] as char;
+        let start = i;
+        let kind = match ch {
+            ' ' | '\t' => {
+                i += 1;
+                continue;
+            }
+            '+' | '-' | '*' | '/' | '^' | '&' => {
+                i += 1;
+                TokenKind::Op(ch)
+            }
+            '(' => {
+                i += 1;
+                TokenKind::LParen
+            }
+            ')' => {
+                i += 1;
+                TokenKind::RParen
+            }
+            '=' => {
+                i += 1;
+                TokenKind::Cmp(BinaryOp::Eq)
+            }
+            '<' | '>' => {
+                let op = match (ch, bytes.get(i + 1)) {
+                    ('<', Some(b'>')) => BinaryOp::Ne,
+                    ('<', Some(b'=')) => BinaryOp::Le,
+                    ('>', Some(b'=')) => BinaryOp::Ge,
+                    ('<', _) => BinaryOp::Lt,
+                    _ => BinaryOp::Gt,
+                };
+                i += op.symbol().len();
+                TokenKind::Cmp(op)
+            }
+            ',' => {
+                i += 1;
+                TokenKind::Comma
+            }
+            ':' => {
+                i += 1;
+                TokenKind::Colon
+            }
+            '"' => {
+                // Strings run to the next quote; a doubled quote stands for a literal one.
+                let mut value = String::new();
+                i += 1;
+                loop {
+                    match src[i..].find('"') {
+                        Some(off) => {
+                            value.push_str(&src[i..i + off]);
+                            i += off + 1;
+                            if i < bytes.len() && bytes[i] == b'"' {
+                                value.push('"');
+                                i += 1;
+                            } else {
+                                break;
+                            }
+                        }
+                        None => return Err(SheetError::parse(src.len(), "a closing '\"'")),
+                    }
+                }
+                TokenKind::Str(value)
+            }
+            '#' => {
+                let kind = ERROR_KINDS
+                    .into_iter()
+                    .find(|kind| src[start..].starts_with(&kind.to_string()))
+                    .ok_or_else(|| SheetError::parse(start, "an error value such as #N/A"))?;
+                i += kind.to_string().len();
+                TokenKind::Error(kind)
+            }
+            c if c.is_ascii_digit() && parse_iso(&src[start..]).is_some() => {
+                let (serial, len) = parse_iso(&src[start..]).unwrap_or_default();
+                i += len;
+                TokenKind::Date(serial)
+            }
+            c if c.is_ascii_digit() || c == '.' => {
+                while i < bytes.len() && (bytes[i].is_ascii_digit() || bytes[i] == b'.') {
+                    i += 1;
+                }
+                let text = &src[start..i];
+                if text.contains('.') {
+                    TokenKind::Float(text.parse::<f64>().map_err(|_| number(start))?)
+                } else {
+                    match text.parse::<i32>() {
+                        Ok(v) => TokenKind::Int(v),
+                        Err(_) => TokenKind::Float(text.parse::<f64>().map_err(|_| number(start))?),
+                    }
+                }
+            }
+            c if c.is_ascii_alphabetic() || c == '$' => {
+                while i < bytes.len()
+                    && (bytes[i].is_ascii_alphanumeric() || matches!(bytes[i], b'_' | b'.' | b'$'))
+                {
+                    i += 1;
+                }
+                let text = &src[start..i];
+                // A name followed by '(' is a function call even when it looks like a cell
+                // reference, e.g. `LOG10(x)` or `ATAN2(x, y)`.
+                let is_call = src[i..].trim_start().starts_with('(');
+                if is_cell_name(text) && !is_call {
+                    TokenKind::Ref(text.to_string())
+                } else if text.contains('$') {
+                    return Err(SheetError::bad_reference(text));
+                } else {
+                    TokenKind::Ident(text.to_string())
+                }
+            }
+            _ => return Err(SheetError::parse(start, "a value, reference or operator")),
+        };
+        tokens.push(Token { kind, pos: start });
+    }
+
+    Ok(tokens)
+}
+
+/// The error for a malformed number starting at `position`.
+fn number(position: usize) -> SheetError {
+    SheetError::parse(position, "a number")
+}
+
+/// Error values that can be written as literals.
+const ERROR_KINDS: [ErrorKind; 7] = [
+    ErrorKind::Div0,
+    ErrorKind::Value,
+    ErrorKind::Ref,
+    ErrorKind::Name,
+    ErrorKind::Num,
+    ErrorKind::Cycle,
+    ErrorKind::NotAvailable,
+];
+
+/// Returns true for names shaped like a cell reference: uppercase letters followed by digits,
+/// each optionally anchored with `$`.
+fn is_cell_name(text: &str) -> bool {
+    let Some((text, _)) = split_anchors(text) else {
+        return false;
+    };
+    let letters = text.chars().take_while(|c| c.is_ascii_uppercase()).count();
+    letters > 0 && letters < text.len() && text[letters..].chars().all(|c| c.is_ascii_digit())
+}
+
+/// Precedence-climbing parser over a token stream.
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    /// Length of the formula text, where a missing token is expected.
+    len: usize,
+    cols: i32,
+    rows: i32,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&TokenKind> {
+        self.tokens.get(self.pos).map(|t| &t.kind)
+    }
+
+    fn next(&mut self) -> Option<TokenKind> {
+        let tok = self.tokens.get(self.pos).map(|t| t.kind.clone());
+        self.pos += 1;
+        tok
+    }
+
+    /// Returns the position in the formula text of the current token.
+    fn here(&self) -> usize {
+        self.tokens.get(self.pos).map_or(self.len, |t| t.pos)
+    }
+
+    /// The error for an unexpected token, or a missing one, at the current position.
+    fn expected(&self, expected: &str) -> SheetError {
+        SheetError::parse(self.here(), expected)
+    }
+
+    fn expect_rparen(&mut self) -> Result<(), SheetError> {
+        if self.peek() != Some(&TokenKind::RParen) {
+            return Err(self.expected("')'"));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn cell(&self, name: &str) -> Result<(i32, Anchor), SheetError> {
+        anchored_cell_parser(name, self.cols, self.rows)
+    }
+
+    /// Parses binary operators whose precedence is at least `min_prec`.
+    fn parse_expr(&mut self, min_prec: u8) -> Result<Expr, SheetError> {
+        let mut lhs = self.parse_unary()?;
+        loop {
+            let op = match self.peek() {
+                Some(TokenKind::Op(ch)) => BinaryOp::from_char(*ch),
+                Some(TokenKind::Cmp(op)) => Some(*op),
+                _ => None,
+            };
+            let op = match op {
+                Some(op) if op.precedence() >= min_prec => op,
+                _ => break,
+            };
+            self.pos += 1;
+            let rhs = self.parse_expr(op.precedence() + 1)?;
+            lhs = Expr::Binary { op, lhs: Box::new(lhs), rhs: Box::new(rhs) };
+        }
+        Ok(lhs)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, SheetError> {
+        let op = match self.peek() {
+            Some(TokenKind::Op('-')) => UnaryOp::Neg,
+            Some(TokenKind::Op('+')) => UnaryOp::Plus,
+            _ => return self.parse_primary(),
+        };
+        self.pos += 1;
+        let operand = self.parse_unary()?;
+        // Fold signs on numeric literals so that `-5` stays a plain constant.
+        Ok(match (op, operand) {
+            (UnaryOp::Plus, lit @ Expr::Literal(CellValue::Int(_) | CellValue::Float(_))) => lit,
+            (UnaryOp::Neg, Expr::Literal(CellValue::Int(v))) => Expr::Literal(CellValue::Int(-v)),
+            (UnaryOp::Neg, Expr::Literal(CellValue::Float(v))) => {
+                Expr::Literal(CellValue::Float(-v))
+            }
+            (op, operand) => Expr::Unary { op, operand: Box::new(operand) },
+        })
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, SheetError> {
+        let start = self.here();
+        match self.next() {
+            Some(TokenKind::Int(v)) => Ok(Expr::Literal(CellValue::Int(v))),
+            Some(TokenKind::Float(v)) => Ok(Expr::Literal(CellValue::Float(v))),
+            Some(TokenKind::Date(v)) => Ok(Expr::Literal(CellValue::Date(v))),
+            Some(TokenKind::Str(s)) => Ok(Expr::Literal(CellValue::String(s))),
+            Some(TokenKind::Error(kind)) => Ok(Expr::Literal(CellValue::Error(kind))),
+            Some(TokenKind::Ref(name)) => {
+                let (start, a1) = self.cell(&name)?;
+                if self.peek() == Some(&TokenKind::Colon) {
+                    self.pos += 1;
+                    match self.next() {
+                        Some(TokenKind::Ref(end)) => {
+                            let (end, a2) = self.cell(&end)?;
+                            Ok(Expr::Range(start, end, [a1, a2]))
+                        }
+                        _ => Err(SheetError::InvalidRange { range: format!("{}:", name) }),
+                    }
+                } else {
+                    Ok(Expr::Cell(start, a1))
+                }
+            }
+            Some(TokenKind::Ident(name)) => {
+                if self.peek() != Some(&TokenKind::LParen) {
+                    return match name.to_ascii_uppercase().as_str() {
+                        "TRUE" => Ok(Expr::Literal(CellValue::Bool(true))),
+                        "FALSE" => Ok(Expr::Literal(CellValue::Bool(false))),
+                        _ => Err(SheetError::bad_reference(name)),
+                    };
+                }
+                self.pos += 1;
+                self.parse_call(name.to_ascii_uppercase())
+            }
+            Some(TokenKind::LParen) => {
+                let inner = self.parse_expr(0)?;
+                self.expect_rparen()?;
+                Ok(Expr::Group(Box::new(inner)))
+            }
+            _ => Err(SheetError::parse(start, "an expression")),
+        }
+    }
+
+    /// Parses the argument list of a call whose `NAME(` has already been consumed.
+    fn parse_call(&mut self, name: String) -> Result<Expr, SheetError> {
+        let Some((min_args, max_args)) = arity(&name) else {
+            return Err(SheetError::UnknownFunction { name });
+        };
+        let mut args = Vec::new();
+        if self.peek() != Some(&TokenKind::RParen) {
+            loop {
+                let arg = self.parse_expr(0)?;
+                match self.peek() {
+                    Some(TokenKind::Comma) => self.pos += 1,
+                    // `MIN(B1 C1)`: two adjacent cells are most likely a range without its colon.
+                    Some(TokenKind::Ref(_)) if matches!(arg, Expr::Cell(..)) => {
+                        return Err(self.expected("':'"))
+                    }
+                    _ => {
+                        args.push(arg);
+                        break;
+                    }
+                }
+                args.push(arg);
+            }
+        }
+        let end = self.here();
+        self.expect_rparen()?;
+        // The multi-criteria functions take (range, criteria) pairs.
+        let unpaired = match name.as_str() {
+            "COUNTIFS" => args.len() % 2 != 0,
+            "SUMIFS" => args.len() % 2 == 0,
+            _ => false,
+        };
+        if args.len() < min_args || args.len() > max_args || unpaired {
+            let count = |n: usize| format!("{} argument{}", n, if n == 1 { "" } else { "s" });
+            let expected = if unpaired {
+                "range and criteria pairs".to_string()
+            } else if min_args == max_args {
+                format!("{} to {}", count(min_args), name)
+            } else if max_args == usize::MAX {
+                format!("at least {} to {}", count(min_args), name)
+            } else {
+                format!("{} to {} arguments to {}", min_args, max_args, name)
+            };
+            return Err(SheetError::parse(end, expected));
+        }
+        Ok(Expr::Call { name, args })
+    }
+}
+
+/// Parses the right-hand side of a formula (the text after `=`) into an [`Expr`].
+///
+/// # Arguments
+/// - `src`: Formula text, e.g. `"(A1+B1)/2"`.
+/// - `c`: Number of columns in the spreadsheet.
+/// - `r`: Number of rows in the spreadsheet.
+///
+/// # Errors
+/// Returns a [`SheetError`] if the text is not a well-formed formula, references a cell
+/// outside the sheet or calls an unknown function. Parse error positions are byte offsets
+/// into `src`.
+pub fn parse_formula(src: &str, c: i32, r: i32) -> Result<Expr, SheetError> {
+    let tokens = tokenize(src)?;
+    let mut parser = Parser { tokens, pos: 0, len: src.len(), cols: c, rows: r };
+    let expr = parser.parse_expr(0)?;
+    if parser.pos < parser.tokens.len() {
+        return Err(parser.expected("an operator"));
+    }
+    Ok(expr)
+}
@@ -0,0 +1,243 @@
+//! # Financial Functions
+//!
+//! Time-value-of-money functions for the extended engine: `PMT`, `PV`, `FV` and `NPER` for
+//! loans and annuities, `RATE` for the interest rate of one, and `NPV`, `XNPV` and `IRR` for
+//! series of cash flows.
+//!
+//! Signs follow Excel: money paid out is negative and money received positive, so the
+//! payment on a loan taken out (`pv > 0`) is negative. `type` is `0` (default) for payments
+//! at the end of each period and any other number for payments at the start.
+//!
+//! Cash flows are read like the range functions: every argument may be a range, a cell or
+//! an expression, blanks are skipped and text gives `#VALUE!`. `XNPV` pairs its values and
+//! dates cell by cell instead, and needs two ranges of the same size.
+//!
+//! `IRR` and `RATE` have no closed form and are solved with Newton's method from `guess`
+//! (default 10%). If that does not converge within [`MAX_ITERATIONS`] steps the result is
+//! `#NUM!`; so is every other result that is not a finite number.
+
+use crate::aggregate_ext::RangeArg;
+use crate::date_ext::date_value;
+use crate::expr_ext::{Expr, Signature};
+use crate::function_ext::{Cell, CellValue, ErrorKind};
+use crate::graph_ext::{cell_value, collect_numbers, eval_expr};
+use crate::grid_ext::Grid;
+use crate::util_ext::{float_cell, number_value};
+
+/// The financial functions handled by [`finance_eval`] and their numbers of arguments.
+pub const FINANCE_FUNCTIONS: &[Signature] = &[
+    ("PMT", 3, 5),
+    ("PV", 3, 5),
+    ("FV", 3, 5),
+    ("NPV", 2, usize::MAX),
+    ("IRR", 1, 2),
+    ("XNPV", 3, 3),
+    ("RATE", 3, 6),
+    ("NPER", 3, 5),
+];
+
+/// Most Newton steps `IRR` and `RATE` take before giving up with `#NUM!`.
+pub const MAX_ITERATIONS: usize = 100;
+
+/// `IRR` and `RATE` stop once a step changes the rate by less than this.
+const TOLERANCE: f64 = 1e-10;
+
+/// Rate used by `IRR` and `RATE` when no guess is given.
+const DEFAULT_GUESS: f64 = 0.1;
+
+/// Evaluates one of the [`FINANCE_FUNCTIONS`].
+pub fn finance_eval(name: &str, args: &[Expr], arr: &Grid<Cell>, cols: i32) -> Cell {
+    let result = match (name, args) {
+        ("NPV", [rate, values @ ..]) => npv(rate, values, arr, cols),
+        ("IRR", [values, guess @ ..]) => irr(values, guess.first(), arr, cols),
+        ("XNPV", [rate, values, dates]) => xnpv(rate, values, dates, arr, cols),
+        _ => annuity(name, args, arr, cols),
+    };
+    result.and_then(|value| Ok(float_cell(finite(value)?))).unwrap_or_else(Cell::error)
+}
+
+fn finite(value: f64) -> Result<f64, ErrorKind> {
+    if value.is_finite() {
+        Ok(value)
+    } else {
+        Err(ErrorKind::Num)
+    }
+}
+
+/// Evaluates the arguments of `PMT`, `PV`, `FV`, `NPER` or `RATE`: the required ones, then
+/// the optional ones, which default to `0` except for `RATE`'s guess.
+fn annuity(name: &str, args: &[Expr], arr: &Grid<Cell>, cols: i32) -> Result<f64, ErrorKind> {
+    let mut values = Vec::with_capacity(6);
+    for arg in args {
+        values.push(number_value(&eval_expr(arg, arr, cols))?);
+    }
+    let arg = |i: usize| values.get(i).copied().unwrap_or(0.0);
+    let at_start = arg(4) != 0.0;
+    match name {
+        "PMT" => pmt(arg(0), arg(1), arg(2), arg(3), at_start),
+        "PV" => pv(arg(0), arg(1), arg(2), arg(3), at_start),
+        "FV" => fv(arg(0), arg(1), arg(2), arg(3), at_start),
+        "NPER" => nper(arg(0), arg(1), arg(2), arg(3), at_start),
+        "RATE" => {
+            let guess = values.get(5).copied().unwrap_or(DEFAULT_GUESS);
+            rate(arg(0), arg(1), arg(2), arg(3), at_start, guess)
+        }
+        _ => Err(ErrorKind::Value),
+    }
+}
+
+/// The annuity factors shared by the closed-form functions: `(1 + rate)^nper` and the
+/// value at the end of the last period of paying `1` every period.
+fn growth(rate: f64, nper: f64, at_start: bool) -> (f64, f64) {
+    if rate == 0.0 {
+        return (1.0, nper);
+    }
+    let compound = (1.0 + rate).powf(nper);
+    let start_factor = if at_start { 1.0 + rate } else { 1.0 };
+    (compound, start_factor * (compound - 1.0) / rate)
+}
+
+/// `PMT(rate, nper, pv[, fv[, type]])`: the payment per period that pays off `pv` and leaves
+/// `fv`.
+fn pmt(rate: f64, nper: f64, pv: f64, fv: f64, at_start: bool) -> Result<f64, ErrorKind> {
+    let (compound, annuity) = growth(rate, nper, at_start);
+    if annuity == 0.0 {
+        return Err(ErrorKind::Num);
+    }
+    Ok(-(pv * compound + fv) / annuity)
+}
+
+/// `PV(rate, nper, pmt[, fv[, type]])`: the present value of `nper` payments of `pmt`.
+fn pv(rate: f64, nper: f64, pmt: f64, fv: f64, at_start: bool) -> Result<f64, ErrorKind> {
+    let (compound, annuity) = growth(rate, nper, at_start);
+    Ok(-(fv + pmt * annuity) / compound)
+}
+
+/// `FV(rate, nper, pmt[, pv[, type]])`: the value of an investment after `nper` periods.
+fn fv(rate: f64, nper: f64, pmt: f64, pv: f64, at_start: bool) -> Result<f64, ErrorKind> {
+    let (compound, annuity) = growth(rate, nper, at_start);
+    Ok(-(pv * compound + pmt * annuity))
+}
+
+/// `NPER(rate, pmt, pv[, fv[, type]])`: the number of payments needed to get from `pv` to
+/// `fv`. Gives `#NUM!` if `fv` can never be reached.
+fn nper(rate: f64, pmt: f64, pv: f64, fv: f64, at_start: bool) -> Result<f64, ErrorKind> {
+    if rate == 0.0 {
+        if pmt == 0.0 {
+            return Err(ErrorKind::Num);
+        }
+        return Ok(-(pv + fv) / pmt);
+    }
+    let payment = if at_start { pmt * (1.0 + rate) } else { pmt };
+    let ratio = (payment - fv * rate) / (payment + pv * rate);
+    if ratio <= 0.0 || rate <= -1.0 {
+        return Err(ErrorKind::Num);
+    }
+    Ok(ratio.ln() / (1.0 + rate).ln())
+}
+
+/// `RATE(nper, pmt, pv[, fv[, type[, guess]]])`: the interest rate per period for which
+/// `nper` payments of `pmt` get from `pv` to `fv`.
+fn rate(
+    nper: f64,
+    pmt: f64,
+    pv: f64,
+    fv: f64,
+    at_start: bool,
+    guess: f64,
+) -> Result<f64, ErrorKind> {
+    if nper <= 0.0 {
+        return Err(ErrorKind::Num);
+    }
+    // The future value left over at `rate`, which is zero at the answer.
+    solve(guess, |rate| {
+        let (compound, annuity) = growth(rate, nper, at_start);
+        pv * compound + pmt * annuity + fv
+    })
+}
+
+/// `NPV(rate, values...)`: the value one period before the first cash flow of cash flows at
+/// the end of each period.
+fn npv(rate: &Expr, values: &[Expr], arr: &Grid<Cell>, cols: i32) -> Result<f64, ErrorKind> {
+    let rate = number_value(&eval_expr(rate, arr, cols))?;
+    if rate == -1.0 {
+        return Err(ErrorKind::Div0);
+    }
+    let values = collect_numbers(values, arr, cols)?;
+    Ok(discounted(rate, &values) / (1.0 + rate))
+}
+
+/// Value at the first cash flow of `values`, one period apart, discounted at `rate`.
+fn discounted(rate: f64, values: &[f64]) -> f64 {
+    values.iter().rev().fold(0.0, |total, value| total / (1.0 + rate) + value)
+}
+
+/// `IRR(values[, guess])`: the rate at which the cash flows, one period apart, have a net
+/// present value of zero. Needs at least one positive and one negative cash flow.
+fn irr(values: &Expr, guess: Option<&Expr>, arr: &Grid<Cell>, cols: i32) -> Result<f64, ErrorKind> {
+    let values = collect_numbers(std::slice::from_ref(values), arr, cols)?;
+    let guess = match guess {
+        Some(guess) => number_value(&eval_expr(guess, arr, cols))?,
+        None => DEFAULT_GUESS,
+    };
+    if !values.iter().any(|&v| v > 0.0) || !values.iter().any(|&v| v < 0.0) {
+        return Err(ErrorKind::Num);
+    }
+    solve(guess, |rate| discounted(rate, &values))
+}
+
+/// `XNPV(rate, values, dates)`: the net present value of cash flows paid on the given
+/// dates, discounted to the first date over 365-day years.
+fn xnpv(
+    rate: &Expr,
+    values: &Expr,
+    dates: &Expr,
+    arr: &Grid<Cell>,
+    cols: i32,
+) -> Result<f64, ErrorKind> {
+    let rate = number_value(&eval_expr(rate, arr, cols))?;
+    let values = RangeArg::from_expr(values, arr, cols)?.cells;
+    let dates = RangeArg::from_expr(dates, arr, cols)?.cells;
+    if values.len() != dates.len() || rate <= -1.0 {
+        return Err(ErrorKind::Num);
+    }
+    let mut flows = Vec::with_capacity(values.len());
+    for (&value, &date) in values.iter().zip(&dates) {
+        let (value, date) = (cell_value(arr, value), cell_value(arr, date));
+        if value.value == CellValue::Empty {
+            continue;
+        }
+        // A blank date would otherwise count as serial 0.
+        if date.value == CellValue::Empty {
+            return Err(ErrorKind::Value);
+        }
+        flows.push((number_value(&value)?, date_value(&date)?.floor()));
+    }
+    let Some(&(_, first)) = flows.first() else {
+        return Err(ErrorKind::Num);
+    };
+    Ok(flows.iter().map(|(value, date)| value / (1.0 + rate).powf((date - first) / 365.0)).sum())
+}
+
+/// Finds a root of `f` with Newton's method from `guess`, using a numerical derivative.
+/// Rates at or below `-100%` are not meaningful, so a step that leaves them gives `#NUM!`.
+fn solve(guess: f64, f: impl Fn(f64) -> f64) -> Result<f64, ErrorKind> {
+    let mut rate = guess;
+    for _ in 0..MAX_ITERATIONS {
+        let value = f(rate);
+        let h = 1e-6 * rate.abs().max(1e-3);
+        let slope = (f(rate + h) - f(rate - h)) / (2.0 * h);
+        if !value.is_finite() || !slope.is_finite() || slope == 0.0 {
+            return Err(ErrorKind::Num);
+        }
+        let next = rate - value / slope;
+        if next <= -1.0 {
+            return Err(ErrorKind::Num);
+        }
+        if (next - rate).abs() < TOLERANCE {
+            return Ok(next);
+        }
+        rate = next;
+    }
+    Err(ErrorKind::Num)
+}
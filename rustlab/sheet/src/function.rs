@@ -13,10 +13,10 @@
 //! - `sleep_func`: Introduces a delay based on a cell value or a literal
 //!
 //! ## Architecture
-//! - Spreadsheet is stored in a flat array (`Vec<i32>`) indexed as `row * cols + col`
+//! - Spreadsheet is stored in a sparse [`Grid<i32>`](crate::grid_ext::Grid) indexed as `row * cols + col`
 //! - Dependency tracking is handled by a custom `Graph` structure
 //! - Formulas are stored in a separate array and referenced by index
-//! - Errors are tracked using a global `INVALID_RANGE` flag and sentinel value `i32::MIN`
+//! - Invalid input is reported as a [`ParseError`]; cells holding an error have the sentinel value `i32::MIN`
 //!
 //! ## Example Usage
 //! ```text
@@ -25,34 +25,12 @@
 //! ```
 
 use crate::graph::{Formula, Graph};
-use crate::parser::cell_parser;
+use crate::grid_ext::Grid;
+use crate::parser::{cell_parser, ParseError};
 use std::cmp::{max, min};
 // use std::i32;
 // use std::thread::sleep;
 // use std::time::Duration;
-/// Global flag to indicate if a function encountered an invalid range.
-pub static mut INVALID_RANGE: bool = false;
-
-fn error_usize() -> usize {
-    unsafe {
-        INVALID_RANGE = true;
-    }
-    0
-}
-
-fn error_range() -> (usize, usize) {
-    unsafe {
-        INVALID_RANGE = true;
-    }
-    (0, 0)
-}
-
-fn error_return() -> i32 {
-    unsafe {
-        INVALID_RANGE = true;
-    }
-    -1
-}
 /// Validates that `start` to `end` defines a proper left-to-right, top-to-bottom rectangular range.
 fn validate_range(start: usize, end: usize, cols: usize) -> bool {
     let (sr, sc) = (start / cols, start % cols);
@@ -99,7 +77,7 @@ fn extract_range_cells(
 
 /// Computes the minimum value within a specified range and stores it in the target cell.
 /// Adds the formula and dependency to the graph for recalculation tracking.
-/// Returns 1 on success.
+/// Returns an error if the target cell or the range is invalid.
 #[allow(clippy::too_many_arguments)]
 pub fn min_func(
     a: &str,
@@ -107,20 +85,20 @@ pub fn min_func(
     r: usize,
     eq_idx: usize,
     _: usize,
-    arr: &mut [i32],
+    arr: &mut Grid<i32>,
     graph: &mut Graph,
-    formula_array: &mut [Formula],
-) -> i32 {
+    formula_array: &mut Grid<Formula>,
+) -> Result<(), ParseError> {
     // Get the first cell (target) for the formula
-    let first_cell = cell_parser(a, c, r, 0, eq_idx - 1, graph).unwrap_or_else(error_usize);
+    let first_cell = cell_parser(a, c, r, 0, eq_idx - 1, graph).ok_or(ParseError::Unrecognized)?;
 
     // Extract the range (start and end for both rows and columns)
     let (range_start, range_end) =
-        extract_range_cells(a, eq_idx, c, r, graph).unwrap_or_else(error_range);
+        extract_range_cells(a, eq_idx, c, r, graph).ok_or(ParseError::InvalidRange)?;
 
     // Add formula to the graph
     Graph::add_formula(graph, first_cell, range_start, range_end, 9, formula_array);
-    graph.add_range_to_graph(range_start, range_end, first_cell);
+    graph.add_range_to_graph(range_start, range_end, first_cell, c);
 
     // Get the starting and ending row/column indices
     let (start_row, start_col) = (range_start / c, range_start % c);
@@ -146,11 +124,11 @@ pub fn min_func(
     }
 
     arr[first_cell] = min_val;
-    1
+    Ok(())
 }
 /// Computes the maximum value within a specified range and stores it in the target cell.
 /// Adds the formula and dependency to the graph for recalculation tracking.
-/// Returns 1 on success.
+/// Returns an error if the target cell or the range is invalid.
 #[allow(clippy::too_many_arguments)]
 pub fn max_func(
     a: &str,
@@ -158,20 +136,20 @@ pub fn max_func(
     r: usize,
     eq_idx: usize,
     _: usize,
-    arr: &mut [i32],
+    arr: &mut Grid<i32>,
     graph: &mut Graph,
-    formula_array: &mut [Formula],
-) -> i32 {
+    formula_array: &mut Grid<Formula>,
+) -> Result<(), ParseError> {
     // Get the first cell (target) for the formula
-    let first_cell = cell_parser(a, c, r, 0, eq_idx - 1, graph).unwrap_or_else(error_usize);
+    let first_cell = cell_parser(a, c, r, 0, eq_idx - 1, graph).ok_or(ParseError::Unrecognized)?;
 
     // Extract the 2D range (start and end for both rows and columns)
     let (range_start, range_end) =
-        extract_range_cells(a, eq_idx, c, r, graph).unwrap_or_else(error_range);
+        extract_range_cells(a, eq_idx, c, r, graph).ok_or(ParseError::InvalidRange)?;
 
     // Add formula to the graph
     Graph::add_formula(graph, first_cell, range_start, range_end, 10, formula_array);
-    graph.add_range_to_graph(range_start, range_end, first_cell);
+    graph.add_range_to_graph(range_start, range_end, first_cell, c);
 
     // Determine 2D range bounds
     let (start_row, start_col) = (range_start / c, range_start % c);
@@ -192,12 +170,12 @@ pub fn max_func(
     arr[first_cell] = max_value;
     // println!("max_value: {}", max_value);
     // println!("arr[first_cell]: {}", arr[first_cell]);
-    1
+    Ok(())
 }
 
 /// Computes the total sum of values within a specified range and stores it in the target cell.
 /// Adds the formula and dependency to the graph for recalculation tracking.
-/// Returns 1 on success.
+/// Returns an error if the target cell or the range is invalid.
 #[allow(clippy::too_many_arguments)]
 pub fn sum_func(
     a: &str,
@@ -205,22 +183,22 @@ pub fn sum_func(
     r: usize,
     eq_idx: usize,
     _: usize,
-    arr: &mut [i32],
+    arr: &mut Grid<i32>,
     graph: &mut Graph,
-    formula_array: &mut [Formula],
-) -> i32 {
-    let first_cell = cell_parser(a, c, r, 0, eq_idx - 1, graph).unwrap_or_else(error_usize);
+    formula_array: &mut Grid<Formula>,
+) -> Result<(), ParseError> {
+    let first_cell = cell_parser(a, c, r, 0, eq_idx - 1, graph).ok_or(ParseError::Unrecognized)?;
     let (range_start, range_end) =
-        extract_range_cells(a, eq_idx, c, r, graph).unwrap_or_else(error_range);
+        extract_range_cells(a, eq_idx, c, r, graph).ok_or(ParseError::InvalidRange)?;
     Graph::add_formula(graph, first_cell, range_start, range_end, 12, formula_array);
-    graph.add_range_to_graph(range_start, range_end, first_cell);
+    graph.add_range_to_graph(range_start, range_end, first_cell, c);
     let sum: i32 = (range_start..=range_end).map(|idx| arr[idx]).sum();
     arr[first_cell] = sum;
-    1
+    Ok(())
 }
 /// Computes the average value within a specified range and stores it in the target cell.
 /// Adds the formula and dependency to the graph for recalculation tracking.
-/// Returns 1 on success.
+/// Returns an error if the target cell or the range is invalid.
 #[allow(clippy::too_many_arguments)]
 pub fn avg_func(
     a: &str,
@@ -228,16 +206,16 @@ pub fn avg_func(
     r: usize,
     eq_idx: usize,
     _: usize,
-    arr: &mut [i32],
+    arr: &mut Grid<i32>,
     graph: &mut Graph,
-    formula_array: &mut [Formula],
-) -> i32 {
-    let first_cell = cell_parser(a, c, r, 0, eq_idx - 1, graph).unwrap_or_else(error_usize);
+    formula_array: &mut Grid<Formula>,
+) -> Result<(), ParseError> {
+    let first_cell = cell_parser(a, c, r, 0, eq_idx - 1, graph).ok_or(ParseError::Unrecognized)?;
     let (range_start, range_end) =
-        extract_range_cells(a, eq_idx, c, r, graph).unwrap_or_else(error_range);
+        extract_range_cells(a, eq_idx, c, r, graph).ok_or(ParseError::InvalidRange)?;
 
     Graph::add_formula(graph, first_cell, range_start, range_end, 11, formula_array);
-    graph.add_range_to_graph(range_start, range_end, first_cell);
+    graph.add_range_to_graph(range_start, range_end, first_cell, c);
 
     let (start_row, start_col) = (range_start / c, range_start % c);
     let (end_row, end_col) = (range_end / c, range_end % c);
@@ -253,12 +231,12 @@ pub fn avg_func(
     let sum: i32 = values.iter().sum();
     let count = values.len();
     arr[first_cell] = if count > 0 { sum / count as i32 } else { 0 };
-    1
+    Ok(())
 }
 
 /// Computes the standard deviation of values within a specified range and stores it in the target cell.
 /// Adds the formula and dependency to the graph for recalculation tracking.
-/// Returns 1 on success.
+/// Returns an error if the target cell or the range is invalid.
 #[allow(clippy::too_many_arguments)]
 pub fn stdev_func(
     a: &str,
@@ -266,16 +244,16 @@ pub fn stdev_func(
     r: usize,
     eq_idx: usize,
     _: usize,
-    arr: &mut [i32],
+    arr: &mut Grid<i32>,
     graph: &mut Graph,
-    formula_array: &mut [Formula],
-) -> i32 {
-    let first_cell = cell_parser(a, c, r, 0, eq_idx - 1, graph).unwrap_or_else(error_usize);
+    formula_array: &mut Grid<Formula>,
+) -> Result<(), ParseError> {
+    let first_cell = cell_parser(a, c, r, 0, eq_idx - 1, graph).ok_or(ParseError::Unrecognized)?;
     let (range_start, range_end) =
-        extract_range_cells(a, eq_idx, c, r, graph).unwrap_or_else(error_range);
+        extract_range_cells(a, eq_idx, c, r, graph).ok_or(ParseError::InvalidRange)?;
 
     Graph::add_formula(graph, first_cell, range_start, range_end, 13, formula_array);
-    graph.add_range_to_graph(range_start, range_end, first_cell);
+    graph.add_range_to_graph(range_start, range_end, first_cell, c);
 
     let (start_row, start_col) = (range_start / c, range_start % c);
     let (end_row, end_col) = (range_end / c, range_end % c);
@@ -289,11 +267,11 @@ pub fn stdev_func(
     }
 
     arr[first_cell] = std(&values);
-    1
+    Ok(())
 }
 /// Delays execution for a number of seconds specified either directly or from a referenced cell.
 /// Also stores the sleep duration in the target cell and logs the dependency.
-/// Returns 1 on success.
+/// Returns an error if the target cell or the argument is invalid.
 #[allow(clippy::too_many_arguments)]
 pub fn sleep_func(
     a: &str,
@@ -301,16 +279,16 @@ pub fn sleep_func(
     r: usize,
     eq_idx: usize,
     _: usize,
-    arr: &mut [i32],
+    arr: &mut Grid<i32>,
     graph: &mut Graph,
-    formula_array: &mut [Formula],
-) -> i32 {
-    let target_cell = cell_parser(a, c, r, 0, eq_idx - 1, graph).unwrap_or_else(error_usize);
+    formula_array: &mut Grid<Formula>,
+) -> Result<(), ParseError> {
+    let target_cell = cell_parser(a, c, r, 0, eq_idx - 1, graph).ok_or(ParseError::Unrecognized)?;
     let open_paren = a[eq_idx..].find('(').map(|i| i + eq_idx).unwrap_or(0);
     let close_paren = a[eq_idx..].find(')').map(|i| i + eq_idx).unwrap_or(a.len() - 1);
     if close_paren <= open_paren + 1 {
         // println!("Invalid sleep function syntax");
-        return error_return();
+        return Err(ParseError::Unrecognized);
     }
     // println!("1");
     if let Some(ref_cell) = cell_parser(a, c, r, open_paren + 1, close_paren - 1, graph) {
@@ -318,7 +296,7 @@ pub fn sleep_func(
         // println!("Sleep value: {}", sleep_value);
         if sleep_value == i32::MIN {
             arr[target_cell] = i32::MIN;
-            return 1;
+            return Ok(());
         }
         graph.add_edge(ref_cell, target_cell);
         Graph::add_formula(graph, target_cell, ref_cell, sleep_value as usize, 14, formula_array);
@@ -341,5 +319,5 @@ pub fn sleep_func(
             // println!("Sleep value is less than 0, status = OK");
         }
     }
-    1
+    Ok(())
 }
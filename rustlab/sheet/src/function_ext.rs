@@ -1,28 +1,72 @@
 use serde::{Deserialize, Serialize};
 use std::f64;
+use std::fmt;
 /// Represents a single cell in a spreadsheet.
 ///
-/// Each cell holds a typed value (`Int`, `Float`, or `String`) and a flag indicating whether it's valid.
-/// Invalid cells are used to represent errors (e.g., formula issues, range errors).
+/// Each cell holds a typed value (`Int`, `Float`, `Date`, `String`, `Bool`, `Error` or `Empty`) and a flag indicating whether
+/// it's valid. A cell is invalid exactly when its value is a [`CellValue::Error`].
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct Cell {
     /// The actual value of the cell.
     pub value: CellValue,
-    /// Indicates whether the cell's value is valid.
+    /// Indicates whether the cell's value is valid (i.e. not an error).
     pub is_valid: bool,
 }
 
 /// Represents the value stored in a `Cell`.
 ///
-/// A cell can contain either an integer, a floating-point number, or a string.
+/// A cell can contain an integer, a floating-point number, a date, a string, a boolean or an
+/// error, or be blank.
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub enum CellValue {
     /// Integer value
     Int(i32),
     /// Floating-point value
     Float(f64),
+    /// Date and time as a serial number of days since 1899-12-30; see [`crate::date_ext`]
+    Date(f64),
     /// String value
     String(String),
+    /// Boolean value, e.g. the result of a comparison
+    Bool(bool),
+    /// Error value, e.g. the result of a division by zero
+    Error(ErrorKind),
+    /// A blank cell that was never assigned; counts as `0` in arithmetic
+    Empty,
+}
+
+/// The kinds of errors a cell can hold, shown Excel-style (e.g. `#DIV/0!`).
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// `#DIV/0!`: division by zero
+    Div0,
+    /// `#VALUE!`: an operand has the wrong type, e.g. a string in `SUM`
+    Value,
+    /// `#REF!`: a reference points outside the sheet
+    Ref,
+    /// `#NAME?`: an unknown function name
+    Name,
+    /// `#NUM!`: a numeric result that is not a finite number
+    Num,
+    /// `#CYCLE!`: the cell is part of a circular dependency
+    Cycle,
+    /// `#N/A`: no value is available, e.g. ranges of different sizes
+    NotAvailable,
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            ErrorKind::Div0 => "#DIV/0!",
+            ErrorKind::Value => "#VALUE!",
+            ErrorKind::Ref => "#REF!",
+            ErrorKind::Name => "#NAME?",
+            ErrorKind::Num => "#NUM!",
+            ErrorKind::Cycle => "#CYCLE!",
+            ErrorKind::NotAvailable => "#N/A",
+        };
+        f.write_str(text)
+    }
 }
 
 impl Cell {
@@ -42,6 +86,14 @@ impl Cell {
         Cell { value: CellValue::Float(value), is_valid: true }
     }
 
+    /// Creates a new `Cell` containing a date.
+    ///
+    /// # Arguments
+    /// * `serial` - The date as a serial number of days since 1899-12-30.
+    pub fn new_date(serial: f64) -> Self {
+        Cell { value: CellValue::Date(serial), is_valid: true }
+    }
+
     /// Creates a new `Cell` containing a string value.
     ///
     /// # Arguments
@@ -50,11 +102,46 @@ impl Cell {
         Cell { value: CellValue::String(value), is_valid: true }
     }
 
-    /// Returns an invalid `Cell` instance.
+    /// Creates a new `Cell` containing a boolean value.
+    ///
+    /// # Arguments
+    /// * `value` - The boolean to store in the cell.
+    pub fn new_bool(value: bool) -> Self {
+        Cell { value: CellValue::Bool(value), is_valid: true }
+    }
+
+    /// Creates a blank `Cell`, the initial content of every cell in a new sheet.
+    pub fn empty() -> Self {
+        Cell { value: CellValue::Empty, is_valid: true }
+    }
+
+    /// Creates a new `Cell` holding `value`; error values make the cell invalid.
+    pub fn new(value: CellValue) -> Self {
+        let is_valid = !matches!(value, CellValue::Error(_));
+        Cell { value, is_valid }
+    }
+
+    /// Creates a new `Cell` holding the error `kind`.
+    pub fn error(kind: ErrorKind) -> Self {
+        Cell { value: CellValue::Error(kind), is_valid: false }
+    }
+
+    /// Returns an invalid `Cell` instance holding a generic `#VALUE!` error.
     ///
     /// This is typically used when parsing fails or a computation is invalid.
     pub fn invalid() -> Self {
-        Cell { value: CellValue::Int(0), is_valid: false }
+        Cell::error(ErrorKind::Value)
+    }
+
+    /// Returns the error held by the cell, if any.
+    ///
+    /// A cell flagged invalid without a specific error counts as `#VALUE!`.
+    pub fn error_kind(&self) -> Option<ErrorKind> {
+        match self.value {
+            CellValue::Error(kind) => Some(kind),
+            _ if !self.is_valid => Some(ErrorKind::Value),
+            _ => None,
+        }
     }
 }
 
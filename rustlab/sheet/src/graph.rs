@@ -1,45 +1,56 @@
+use crate::grid_ext::Grid;
+use crate::range_index_ext::{Range, RangeIndex};
+use crate::util_ext::{anchored_label, Anchor};
 // use std::collections::VecDeque;
+use std::collections::HashSet;
 // use std::i32;
 /// Represents a formula associated with a spreadsheet cell.
 ///
 /// - `op_type`: Indicates the operation type (e.g. 0: assign, 1-4: unary op, 5-8: binary op, 9-13: range functions).
 /// - `op_info1`: First operand (may be a cell index or constant).
 /// - `op_info2`: Second operand or auxiliary value.
+/// - `anchors`: The `$` anchors of `op_info1` and `op_info2` where they are cell references.
 #[derive(Clone, Copy, Debug, Default)]
 pub struct Formula {
     pub op_type: i32,
     pub op_info1: i32,
     pub op_info2: i32,
+    pub anchors: [Anchor; 2],
 }
 
-/// A node in an adjacency list used to represent dependencies between cells.
-#[derive(Debug)]
-pub struct Cell {
-    /// Index of the dependent cell.
-    pub cell: usize,
-    /// Pointer to the next dependent cell.
-    pub next: Option<Box<Cell>>,
-}
-
-/// Represents a rectangular cell range with a dependent output cell.
-#[derive(Debug)]
-pub struct Range {
-    /// Start cell of the range.
-    pub start_cell: usize,
-    /// End cell of the range.
-    pub end_cell: usize,
-    /// Cell that depends on the result of the range.
-    pub dependent_cell: usize,
-    /// Pointer to the next range node.
-    pub next: Option<Box<Range>>,
+impl Formula {
+    /// Returns the canonical formula text of `cell`, e.g. `=B1+2` or `=SUM(A1:A3)`.
+    ///
+    /// The text is regenerated from the operation type and operands; `cols` is needed
+    /// to turn cell indices back into labels.
+    pub fn to_formula_string(&self, cell: usize, cols: usize) -> String {
+        let first = anchored_label(self.op_info1, cols as i32, self.anchors[0]);
+        let second = anchored_label(self.op_info2, cols as i32, self.anchors[1]);
+        let op = |base: i32| ['+', '-', '*', '/'][(self.op_type - base) as usize];
+        match self.op_type {
+            1 if self.op_info2 == 0 => format!("={}", first),
+            1..=4 => format!("={}{}{}", first, op(1), self.op_info2),
+            5..=8 => format!("={}{}{}", first, op(5), second),
+            9..=13 => {
+                let name = ["MIN", "MAX", "AVG", "SUM", "STDEV"][(self.op_type - 9) as usize];
+                format!("={}({}:{})", name, first, second)
+            }
+            14 if self.op_info1 as usize == cell => format!("=SLEEP({})", self.op_info2),
+            14 => format!("=SLEEP({})", first),
+            15 => format!("={}/{}", self.op_info1, second),
+            _ => format!("={}", self.op_info1),
+        }
+    }
 }
 
 /// The core graph structure used for tracking dependencies and formula evaluation.
+#[derive(Clone)]
 pub struct Graph {
-    /// Adjacency list where each index points to a list of dependents.
-    pub adj_lists: Vec<Option<Box<Cell>>>,
-    /// Linked list of rectangular ranges with dependent cells.
-    pub ranges: Option<Box<Range>>,
+    /// Adjacency list holding the dependents of each cell. Only the chunks of cells that have
+    /// dependents are allocated.
+    pub adj_lists: Grid<Vec<usize>>,
+    /// Rectangular ranges with dependent cells, indexed by the part of the sheet they cover.
+    pub ranges: RangeIndex,
     /// Total number of cells in the spreadsheet.
     pub num_cells: usize,
 }
@@ -47,78 +58,37 @@ pub struct Graph {
 impl Graph {
     /// Creates a new `Graph` with the given number of cells.
     pub fn new(num_cells: usize) -> Self {
-        Self { adj_lists: vec![None; num_cells], ranges: None, num_cells }
-    }
-    /// Returns a new boxed `Cell` node for the given index.
-    pub fn add_cell(cell: usize) -> Option<Box<Cell>> {
-        Some(Box::new(Cell { cell, next: None }))
-    }
-    /// Returns a new boxed `Range` from start to end affecting the dependent cell.
-    pub fn add_range(start: usize, end: usize, dependent: usize) -> Option<Box<Range>> {
-        Some(Box::new(Range {
-            start_cell: start,
-            end_cell: end,
-            dependent_cell: dependent,
-            next: None,
-        }))
+        Self { adj_lists: Grid::new(num_cells, Vec::new()), ranges: RangeIndex::new(), num_cells }
     }
     /// Adds a directed edge from one cell to another.
     pub fn add_edge(&mut self, from: usize, to: usize) {
-        if self.has_edge(from, to) {
-            return;
+        if !self.has_edge(from, to) {
+            self.adj_lists[from].push(to);
         }
-        let new_cell = Box::new(Cell { cell: to, next: self.adj_lists[from].take() });
-        self.adj_lists[from] = Some(new_cell);
     }
     /// Checks whether a directed edge already exists from one cell to another.
     pub fn has_edge(&self, from: usize, to: usize) -> bool {
-        let mut current = &self.adj_lists[from];
-        while let Some(cell) = current {
-            if cell.cell == to {
-                return true;
-            }
-            current = &cell.next;
-        }
-        false
+        self.adj_lists[from].contains(&to)
     }
     /// Deletes a dependency edge from the graph.
     pub fn delete_edge(&mut self, from: usize, to: usize) {
-        let head = self.adj_lists[from].take();
-        let mut dummy = Box::new(Cell { cell: 0, next: head });
-        let mut prev = &mut dummy;
-
-        while let Some(mut node) = prev.next.take() {
-            if node.cell == to {
-                prev.next = node.next.take();
-                break;
-            } else {
-                prev.next = Some(node);
-                prev = prev.next.as_mut().unwrap();
-            }
+        if let Some(pos) = self.adj_lists[from].iter().position(|&cell| cell == to) {
+            self.adj_lists[from].remove(pos);
         }
-
-        self.adj_lists[from] = dummy.next;
     }
-    /// Inserts a new range-based dependency into the graph.
-    pub fn add_range_to_graph(&mut self, start: usize, end: usize, dependent: usize) {
-        let mut new_range = Self::add_range(start, end, dependent);
-        if let Some(ref mut r) = new_range {
-            r.next = self.ranges.take();
-        }
-        self.ranges = new_range;
+    /// Inserts a new range-based dependency into a sheet of `cols` columns.
+    pub fn add_range_to_graph(&mut self, start: usize, end: usize, dependent: usize, cols: usize) {
+        let range = Range {
+            start_cell: start as i32,
+            end_cell: end as i32,
+            dependent_cell: dependent as i32,
+            dynamic: false,
+        };
+        self.ranges.insert(range, cols as i32);
     }
-    /// Removes a range from the graph if it targets the specified dependent cell.
+    /// Removes every range that targets the specified dependent cell.
     pub fn delete_range(&mut self, dependent: usize) {
-        let mut prev: *mut Option<Box<Range>> = &mut self.ranges;
-        unsafe {
-            while let Some(ref mut current) = *prev {
-                if current.dependent_cell == dependent {
-                    *prev = current.next.take();
-                } else {
-                    prev = &mut current.next;
-                }
-            }
-        }
+        self.ranges.remove_dependent(dependent as i32);
     }
     /// Adds a formula to the formula array for a specific cell.
     pub fn add_formula(
@@ -127,9 +97,10 @@ impl Graph {
         c1: usize,
         c2: usize,
         op_type: i32,
-        formula_array: &mut [Formula],
+        formula_array: &mut Grid<Formula>,
     ) {
-        formula_array[cell] = Formula { op_type, op_info1: c1 as i32, op_info2: c2 as i32 };
+        formula_array[cell] =
+            Formula { op_type, op_info1: c1 as i32, op_info2: c2 as i32, ..Formula::default() };
     }
     /// Evaluates two integers with the specified arithmetic operation.
     pub fn arithmetic_eval2(v1: i32, v2: i32, op: char) -> i32 {
@@ -141,83 +112,54 @@ impl Graph {
             _ => i32::MIN,
         }
     }
+    /// Returns the cells that read `cell`, directly or through a range.
+    pub fn dependents(&self, cell: usize, cols: usize) -> Vec<usize> {
+        let mut dependents = self.adj_lists[cell].clone();
+        let ranges = self.ranges.covering(cell as i32, cols as i32);
+        dependents.extend(ranges.iter().map(|range| range.dependent_cell as usize));
+        dependents
+    }
     /// Performs a depth-first topological sort starting from a cell, detecting cycles.
     ///
-    /// Updates `stack` with a valid evaluation order if no cycles are found.
-    #[allow(clippy::only_used_in_recursion)]
+    /// Updates `stack` with a valid evaluation order if no cycles are found. The DFS path is
+    /// kept on an explicit stack, so that long dependency chains cannot overflow the call
+    /// stack.
     #[allow(clippy::too_many_arguments)]
     pub fn topo_sort_from_cell(
         &self,
         start: usize,
         cols: usize,
-        visited: &mut Vec<bool>,
-        on_stack: &mut Vec<bool>,
+        visited: &mut HashSet<usize>,
+        on_stack: &mut HashSet<usize>,
         stack: &mut Vec<usize>,
-        formula_array: &[Formula],
+        _formula_array: &Grid<Formula>,
         has_cycle: &mut bool,
     ) {
         if *has_cycle {
             return;
         }
 
-        visited[start] = true;
-        on_stack[start] = true;
+        visited.insert(start);
+        on_stack.insert(start);
 
-        let mut current = &self.adj_lists[start];
-        while let Some(node) = current {
-            let dep = node.cell;
-            if !visited[dep] {
-                self.topo_sort_from_cell(
-                    dep,
-                    cols,
-                    visited,
-                    on_stack,
-                    stack,
-                    formula_array,
-                    has_cycle,
-                );
-            } else if on_stack[dep] {
+        // Each frame holds a cell on the path, its dependents and how many were visited.
+        let mut path = vec![(start, self.dependents(start, cols), 0)];
+        while let Some((cell, dependents, next)) = path.last_mut() {
+            let Some(&dep) = dependents.get(*next) else {
+                on_stack.remove(cell);
+                stack.push(*cell);
+                path.pop();
+                continue;
+            };
+            *next += 1;
+            if visited.insert(dep) {
+                on_stack.insert(dep);
+                path.push((dep, self.dependents(dep, cols), 0));
+            } else if on_stack.contains(&dep) {
                 *has_cycle = true;
-                // HAS_CYCLE.store(true, Ordering::Relaxed);  // or Ordering::SeqCst if needed
-
                 return;
             }
-            current = &node.next;
-        }
-
-        let mut range = &self.ranges;
-        while let Some(r) = range {
-            let sr = r.start_cell / cols;
-            let sc = r.start_cell % cols;
-            let er = r.end_cell / cols;
-            let ec = r.end_cell % cols;
-            let row = start / cols;
-            let col = start % cols;
-
-            if row >= sr && row <= er && col >= sc && col <= ec {
-                if !visited[r.dependent_cell] {
-                    self.topo_sort_from_cell(
-                        r.dependent_cell,
-                        cols,
-                        visited,
-                        on_stack,
-                        stack,
-                        formula_array,
-                        has_cycle,
-                    );
-                } else if on_stack[r.dependent_cell] {
-                    *has_cycle = true;
-                    // HAS_CYCLE.store(true, Ordering::Relaxed);  // or Ordering::SeqCst if needed
-
-                    return;
-                }
-            }
-
-            range = &r.next;
         }
-
-        on_stack[start] = false;
-        stack.push(start);
     }
     /// Evaluates and updates all dependent cells starting from `start_cell`.
     ///
@@ -225,13 +167,13 @@ impl Graph {
     pub fn recalc(
         &mut self,
         cols: usize,
-        arr: &mut [i32],
+        arr: &mut Grid<i32>,
         start_cell: usize,
-        formula_array: &mut [Formula],
+        formula_array: &mut Grid<Formula>,
         has_cycle: &mut bool,
     ) {
-        let mut visited = vec![false; self.num_cells];
-        let mut on_stack = vec![false; self.num_cells];
+        let mut visited = HashSet::new();
+        let mut on_stack = HashSet::new();
         let mut stack = Vec::new();
 
         self.topo_sort_from_cell(
@@ -400,20 +342,3 @@ impl Graph {
         }
     }
 }
-/// Clones a dependency list node recursively.
-impl Clone for Cell {
-    fn clone(&self) -> Self {
-        Self { cell: self.cell, next: self.next.clone() }
-    }
-}
-/// Clones a range list node recursively.
-impl Clone for Range {
-    fn clone(&self) -> Self {
-        Self {
-            start_cell: self.start_cell,
-            end_cell: self.end_cell,
-            dependent_cell: self.dependent_cell,
-            next: self.next.clone(),
-        }
-    }
-}
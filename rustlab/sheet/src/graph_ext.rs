@@ -1,348 +1,462 @@
-use crate::function_ext::Cell;
-use crate::util_ext::arithmetic_eval;
+use crate::aggregate_ext::{conditional_aggregate, count_eval};
+use crate::clipboard_ext::Clipboard;
+use crate::date_ext::{date_eval, with_time, Clock, SystemClock, DATE_FUNCTIONS};
+use crate::error_ext::SheetError;
+use crate::expr_ext::{has_function, BinaryOp, Expr, UnaryOp};
+use crate::finance_ext::{finance_eval, FINANCE_FUNCTIONS};
+use crate::function_ext::{Cell, ErrorKind};
+use crate::grid_ext::Grid;
+use crate::lookup_ext::{
+    dynamic_refs, has_dynamic_refs, lookup_eval, range_reference, LOOKUP_FUNCTIONS,
+};
+use crate::math_ext::{math_eval, MATH_FUNCTIONS};
+use crate::range_index_ext::{Range, RangeIndex};
+use crate::stats_ext::{stat_eval, STAT_FUNCTIONS};
+use crate::text_ext::{text_eval, TEXT_FUNCTIONS};
+use crate::util_ext::{cell_label, number_cell, truth_value, Anchor};
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
 use std::thread::sleep;
 use std::time::Duration;
 
 use crate::function_ext::CellValue;
-/// A compact formula representation for spreadsheet cells.
-///
-/// - `op_type`: The operation type (e.g., literal assignment, arithmetic, sum, avg, etc.).
-/// - `op_info1`: First operand (could be a cell index or constant).
-/// - `op_info2`: Second operand (used for binary ops or range end).
-#[derive(Clone, Copy, Default)]
-pub struct Formula {
-    pub op_type: i32,
-    pub op_info1: i32,
-    pub op_info2: i32,
+/// An operand of a simple formula: either a constant or a reference to another cell.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Operand {
+    /// A constant value, e.g. the `5` in `=B1+5`.
+    Const(CellValue),
+    /// A cell index, e.g. the `B1` in `=B1+5`.
+    Cell(i32),
 }
-/// A node in the adjacency list representing a dependency from one cell to another.
 
-#[derive(Clone)]
-pub struct GraphNode {
-    /// The dependent cell.
-    pub cell: i32,
-    /// Pointer to the next dependency in the list.
-    pub next: Option<Box<GraphNode>>,
+impl Operand {
+    /// Returns the current value of the operand.
+    pub fn value(&self, arr: &Grid<Cell>) -> Cell {
+        match self {
+            Operand::Const(value) => Cell::new(value.clone()),
+            Operand::Cell(idx) => cell_value(arr, *idx),
+        }
+    }
+
+    fn from_expr(expr: &Expr) -> Option<Self> {
+        match expr {
+            Expr::Literal(value) => Some(Operand::Const(value.clone())),
+            Expr::Cell(idx, anchor) if anchor.is_relative() => Some(Operand::Cell(*idx)),
+            _ => None,
+        }
+    }
+
+    fn to_expr(&self) -> Expr {
+        match self {
+            Operand::Const(value) => Expr::Literal(value.clone()),
+            Operand::Cell(idx) => Expr::Cell(*idx, Anchor::RELATIVE),
+        }
+    }
 }
-/// A rectangular range in the spreadsheet and the cell it affects.
-#[derive(Clone)]
-pub struct Range {
-    /// Starting cell index of the range.
-    pub start_cell: i32,
-    /// Ending cell index of the range.
-    pub end_cell: i32,
-    /// Cell that depends on the values in this range.
-    pub dependent_cell: i32,
-    /// Next range in the linked list.
-    pub next: Option<Box<Range>>,
+
+/// Functions that aggregate a rectangular range of cells.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RangeFunc {
+    /// `MIN(range)`
+    Min,
+    /// `MAX(range)`
+    Max,
+    /// `AVG(range)`
+    Avg,
+    /// `SUM(range)`
+    Sum,
+    /// `STDEV(range)` (population standard deviation)
+    Stdev,
+}
+
+impl RangeFunc {
+    /// Looks up a range function by its (upper-case) name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "MIN" => Some(RangeFunc::Min),
+            "MAX" => Some(RangeFunc::Max),
+            "AVG" => Some(RangeFunc::Avg),
+            "SUM" => Some(RangeFunc::Sum),
+            "STDEV" => Some(RangeFunc::Stdev),
+            _ => None,
+        }
+    }
+
+    /// Returns the function name as written in formulas.
+    pub fn name(self) -> &'static str {
+        match self {
+            RangeFunc::Min => "MIN",
+            RangeFunc::Max => "MAX",
+            RangeFunc::Avg => "AVG",
+            RangeFunc::Sum => "SUM",
+            RangeFunc::Stdev => "STDEV",
+        }
+    }
+}
+
+/// The formula assigned to a spreadsheet cell.
+///
+/// Common shapes get their own variant so that their operands are directly visible;
+/// anything more complex is kept as a parsed [`Expr`] tree.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum Formula {
+    /// No formula; the cell only holds whatever value was written into it.
+    #[default]
+    Empty,
+    /// A constant: `=5`, `=2.5`, `="text"`.
+    Literal(CellValue),
+    /// A plain reference to another cell: `=B1`.
+    Ref(i32),
+    /// A single arithmetic operation on two operands: `=B1+5`, `=B1*C1`, `=20/B1`.
+    /// Comparisons such as `=B1>5` are kept as [`Formula::Expr`].
+    Arith { lhs: Operand, op: BinaryOp, rhs: Operand },
+    /// A range function over the rectangle spanned by `start` and `end`: `=SUM(B1:C3)`.
+    Range { func: RangeFunc, start: i32, end: i32 },
+    /// `=SLEEP(x)`: waits `x` seconds and evaluates to `x`.
+    Sleep(Operand),
+    /// Any other expression, e.g. `=(A1+B1)*2-SUM(C1:C3)`.
+    Expr(Box<Expr>),
+}
+
+impl Formula {
+    /// Builds the formula for a parsed expression, picking the most specific variant.
+    pub fn from_expr(expr: Expr) -> Self {
+        match &expr {
+            Expr::Literal(value) => return Formula::Literal(value.clone()),
+            Expr::Cell(idx, anchor) if anchor.is_relative() => return Formula::Ref(*idx),
+            Expr::Binary { op, lhs, rhs } if !op.is_comparison() => {
+                if let (Some(lhs), Some(rhs)) = (Operand::from_expr(lhs), Operand::from_expr(rhs)) {
+                    return Formula::Arith { lhs, op: *op, rhs };
+                }
+            }
+            Expr::Call { name, args } => match (RangeFunc::from_name(name), args.as_slice()) {
+                (Some(func), [Expr::Range(start, end, anchors)])
+                    if anchors.iter().all(|anchor| anchor.is_relative()) =>
+                {
+                    return Formula::Range { func, start: *start, end: *end };
+                }
+                (None, [arg]) if name == "SLEEP" => {
+                    if let Some(operand) = Operand::from_expr(arg) {
+                        return Formula::Sleep(operand);
+                    }
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+        Formula::Expr(Box::new(expr))
+    }
+
+    /// Converts the formula back into an expression tree, or `None` for [`Formula::Empty`].
+    pub fn to_expr(&self) -> Option<Expr> {
+        Some(match self {
+            Formula::Empty => return None,
+            Formula::Literal(value) => Expr::Literal(value.clone()),
+            Formula::Ref(idx) => Expr::Cell(*idx, Anchor::RELATIVE),
+            Formula::Arith { lhs, op, rhs } => {
+                Expr::Binary { op: *op, lhs: Box::new(lhs.to_expr()), rhs: Box::new(rhs.to_expr()) }
+            }
+            Formula::Range { func, start, end } => Expr::Call {
+                name: func.name().to_string(),
+                args: vec![Expr::Range(*start, *end, [Anchor::RELATIVE; 2])],
+            },
+            Formula::Sleep(arg) => {
+                Expr::Call { name: "SLEEP".to_string(), args: vec![arg.to_expr()] }
+            }
+            Formula::Expr(expr) => (**expr).clone(),
+        })
+    }
+
+    /// Returns the canonical formula text, e.g. `=SUM(A1:A3)`, or `None` for [`Formula::Empty`].
+    ///
+    /// The text is regenerated from the parsed form, so it always matches what the cell computes.
+    pub fn to_formula_string(&self, cols: i32) -> Option<String> {
+        self.to_expr().map(|expr| format!("={}", expr.to_formula_string(cols)))
+    }
+
+    /// Returns the formula with every reference passed through `relocate`; see
+    /// [`Expr::relocated`]. Formulas without references are returned unchanged.
+    pub fn relocated(
+        &self,
+        relocate: &impl Fn(i32, i32, [Anchor; 2]) -> Option<(i32, i32)>,
+    ) -> Self {
+        match self.to_expr() {
+            Some(expr) if !matches!(self, Formula::Literal(_)) => {
+                Formula::from_expr(expr.relocated(relocate))
+            }
+            _ => self.clone(),
+        }
+    }
+
+    /// Returns `true` if no formula is assigned.
+    pub fn is_empty(&self) -> bool {
+        matches!(self, Formula::Empty)
+    }
+
+    /// Collects the cells and ranges this formula reads from.
+    pub fn dependencies(&self) -> (Vec<i32>, Vec<(i32, i32)>) {
+        let (mut cells, mut ranges) = (Vec::new(), Vec::new());
+        match self {
+            Formula::Empty | Formula::Literal(_) => {}
+            Formula::Ref(idx) => cells.push(*idx),
+            Formula::Arith { lhs, rhs, .. } => {
+                for operand in [lhs, rhs] {
+                    if let Operand::Cell(idx) = operand {
+                        cells.push(*idx);
+                    }
+                }
+            }
+            Formula::Range { start, end, .. } => ranges.push((*start, *end)),
+            Formula::Sleep(arg) => {
+                if let Operand::Cell(idx) = arg {
+                    cells.push(*idx);
+                }
+            }
+            Formula::Expr(expr) => expr.collect_refs(&mut cells, &mut ranges),
+        }
+        (cells, ranges)
+    }
+
+    /// Returns `true` if the formula calls `OFFSET` or `INDIRECT`, whose references are only
+    /// known at evaluation time.
+    pub fn is_dynamic(&self) -> bool {
+        matches!(self, Formula::Expr(expr) if has_dynamic_refs(expr))
+    }
+
+    /// Resolves the ranges read through `OFFSET` and `INDIRECT` against the current values.
+    ///
+    /// These are not part of [`Formula::dependencies`]; [`Graph::recalc`] registers them as
+    /// dynamic ranges whenever the cell is recalculated.
+    pub fn dynamic_dependencies(&self, arr: &Grid<Cell>, cols: i32) -> Vec<(i32, i32)> {
+        let mut refs = Vec::new();
+        if let Formula::Expr(expr) = self {
+            dynamic_refs(expr, arr, cols, &mut refs);
+        }
+        refs
+    }
+
+    /// Evaluates the formula against the current cell values.
+    ///
+    /// Returns `None` for [`Formula::Empty`], leaving the cell's value untouched.
+    /// A formula that evaluates to a blank cell (e.g. `=B1` with `B1` blank) gives `0`.
+    pub fn eval(&self, arr: &Grid<Cell>, cols: i32) -> Option<Cell> {
+        let value = match self {
+            Formula::Empty => return None,
+            Formula::Literal(value) => Cell::new(value.clone()),
+            Formula::Ref(idx) => cell_value(arr, *idx),
+            Formula::Arith { lhs, op, rhs } => op.apply(lhs.value(arr), rhs.value(arr)),
+            Formula::Range { func, start, end } => range_aggregate(*func, *start, *end, arr, cols),
+            Formula::Sleep(arg) => sleep_eval(arg.value(arr)),
+            Formula::Expr(expr) => eval_expr(expr, arr, cols),
+        };
+        Some(if value.value == CellValue::Empty { Cell::new_int(0) } else { value })
+    }
 }
 /// The core data structure representing dependencies between cells.
 ///
 /// - Uses adjacency lists to represent single-cell dependencies.
-/// - Also maintains a linked list of range-based dependencies.
+/// - Also maintains a spatial index of range-based dependencies.
+#[derive(Clone)]
 pub struct Graph {
-    /// Heads of adjacency lists where each index corresponds to a cell.
-    pub adj_lists_head: Vec<Option<Box<GraphNode>>>,
-    /// Head of a linked list representing all cell ranges with dependencies.
-    pub ranges_head: Option<Box<Range>>,
-}
-impl Clone for Graph {
-    fn clone(&self) -> Self {
-        Graph { adj_lists_head: self.adj_lists_head.clone(), ranges_head: self.ranges_head.clone() }
-    }
+    /// The cells that read each cell, in the order the edges were added. Only the chunks of
+    /// cells that have dependents are allocated.
+    pub adj_lists: Grid<Vec<i32>>,
+    /// All cell ranges with dependencies, indexed by the part of the sheet they cover.
+    pub ranges: RangeIndex,
 }
 
 impl Graph {
     /// Creates a new dependency graph for a spreadsheet with `num_cells` cells.
     ///
-    /// Initializes an adjacency list and empty range list.
+    /// Initializes an adjacency list and empty range index.
     pub fn new(num_cells: usize) -> Self {
-        let mut adj_lists_head = Vec::with_capacity(num_cells);
-        for _ in 0..num_cells {
-            adj_lists_head.push(None);
-        }
-        Graph { adj_lists_head, ranges_head: None }
+        Graph { adj_lists: Grid::new(num_cells, Vec::new()), ranges: RangeIndex::new() }
     }
 
-    /// Adds a formula for a specific cell, recording the operation type and operands.
+    /// Assigns `formula` to `cell` in the formula array.
     ///
-    /// - `cell`: The index of the cell being assigned a formula.
-    /// - `c1`, `c2`: Operands (cell references or constants).
-    /// - `op_type`: Type of operation (e.g., addition, SUM, AVG, etc.).
-    /// - `formula_array`: Mutable reference to the formula array.
-    pub fn add_formula(
-        &mut self,
-        cell: i32,
-        c1: i32,
-        c2: i32,
-        op_type: i32,
-        formula_array: &mut [Formula],
-    ) {
-        let mut new_formula = Formula { op_type, op_info1: -1, op_info2: -1 };
-        if op_type == 0 {
-            new_formula.op_info1 = c1;
-        } else {
-            new_formula.op_info1 = c1;
-            new_formula.op_info2 = c2;
-        }
-        formula_array[cell as usize] = new_formula;
+    /// Dependency edges are not touched; call [`Graph::add_edge_formula`] afterwards.
+    pub fn add_formula(&mut self, cell: i32, formula: Formula, formula_array: &mut Grid<Formula>) {
+        formula_array[cell as usize] = formula;
     }
 
-    fn add_node(cell: i32) -> Box<GraphNode> {
-        Box::new(GraphNode { cell, next: None })
+    /// Adds a single dependency edge to the graph from `cell1` to `head_idx`.
+    ///
+    /// Ensures no duplicate edges.
+    pub fn add_edge(&mut self, cell1: i32, head_idx: usize) {
+        if !self.adj_lists[head_idx].contains(&cell1) {
+            self.adj_lists[head_idx].push(cell1);
+        }
     }
-
-    fn add_range(
+    /// Adds a rectangular dependency range to the graph with the specified dependent cell, in
+    /// a sheet of `cols` columns.
+    pub fn add_range_to_graph(
         &mut self,
         start_cell: i32,
         end_cell: i32,
         dependent_cell: i32,
-    ) -> Option<Box<Range>> {
-        Some(Box::new(Range {
-            start_cell,
-            end_cell,
-            dependent_cell,
-            next: self.ranges_head.take(),
-        }))
+        cols: i32,
+    ) {
+        let range = Range { start_cell, end_cell, dependent_cell, dynamic: false };
+        self.ranges.insert(range, cols);
     }
-    /// Adds a single dependency edge to the graph from `cell1` to `head_idx`.
-    ///
-    /// Ensures no duplicate edges.
-    pub fn add_edge(&mut self, cell1: i32, head_idx: usize) {
-        let head = &mut self.adj_lists_head[head_idx];
-        if head.is_none() {
-            self.adj_lists_head[head_idx] = Some(Self::add_node(cell1));
-            return;
-        }
-        let mut current = head.as_mut().unwrap();
-        if current.cell == cell1 {
-            return;
-        }
-        while let Some(ref mut next) = current.next {
-            if next.cell == cell1 {
-                return;
-            }
-            current = next;
-        }
-        current.next = Some(Self::add_node(cell1));
+
+    /// Returns the dynamic ranges currently registered for `dependent_cell`, in the order they
+    /// were set.
+    pub fn dynamic_ranges(&self, dependent_cell: i32) -> Vec<(i32, i32)> {
+        self.ranges
+            .of_dependent(dependent_cell)
+            .filter(|range| range.dynamic)
+            .map(|range| (range.start_cell, range.end_cell))
+            .collect()
     }
-    /// Adds a rectangular dependency range to the graph with the specified dependent cell.
-    pub fn add_range_to_graph(&mut self, start_cell: i32, end_cell: i32, dependent_cell: i32) {
-        if let Some(new_range) = self.add_range(start_cell, end_cell, dependent_cell) {
-            self.ranges_head = Some(new_range);
+
+    /// Replaces the dynamic ranges of `dependent_cell` with `ranges`, in a sheet of `cols`
+    /// columns.
+    pub fn set_dynamic_ranges(&mut self, dependent_cell: i32, ranges: &[(i32, i32)], cols: i32) {
+        self.ranges.remove_where(dependent_cell, |range| range.dynamic);
+        for &(start_cell, end_cell) in ranges {
+            let range = Range { start_cell, end_cell, dependent_cell, dynamic: true };
+            self.ranges.insert(range, cols);
         }
     }
     /// Deletes a single dependency node (edge) pointing from `cell1` in the list at `head_idx`.
     pub fn delete_node(&mut self, cell1: i32, head_idx: usize) {
-        let head = &mut self.adj_lists_head[head_idx];
-        if head.is_none() {
-            return;
-        }
-        if head.as_ref().unwrap().cell == cell1 {
-            self.adj_lists_head[head_idx] = head.as_mut().unwrap().next.take();
-            return;
+        // Look before writing, so that a missing edge doesn't allocate the chunk.
+        if let Some(pos) = self.adj_lists[head_idx].iter().position(|&cell| cell == cell1) {
+            self.adj_lists[head_idx].remove(pos);
         }
-        let mut current = head.as_mut().unwrap();
-        while let Some(ref mut next) = current.next {
-            if next.cell == cell1 {
-                current.next = next.next.take();
-                return;
-            }
-            if next.next.is_none() {
-                break;
-            }
-            current = current.next.as_mut().unwrap();
-        }
-    }
-
-    //     void DeleteRangeFromGraph(Graph *graph, int dependentCell)
-    // {
-    //     Range *current = graph->ranges_head;
-    //     Range *prev = NULL;
-
-    //     while (current != NULL)
-    //     {
-    //         if (current->dependentCell == dependentCell)
-    //         {
-    //             // Remove this range
-    //             if (prev == NULL)
-    //             {
-    //                 // It's the head node
-    //                 graph->ranges_head = current->next;
-    //                 free(current);
-    //                 current = graph->ranges_head;
-    //             }
-    //             else
-    //             {
-    //                 // Middle or end node
-    //                 prev->next = current->next;
-    //                 free(current);
-    //                 current = prev->next;
-    //             }
-    //         }
-    //         else
-    //         {
-    //             prev = current;
-    //             current = current->next;
-    //         }
-    //     }
-    // }
-    /// Deletes any range that affects `dependent_cell`.
+    }
+
+    /// Deletes every range that affects `dependent_cell`; a formula registers one range per
+    /// range argument, e.g. two for `=SUM(A1:A3, B1:B2)`.
     ///
     /// Used for cleaning up graph dependencies on formula deletion.
     pub fn delete_range_from_graph(&mut self, dependent_cell: i32) {
-        let mut current = &mut self.ranges_head;
-
-        while current.is_some() {
-            let should_remove = current.as_ref().unwrap().dependent_cell == dependent_cell;
-
-            if should_remove {
-                let next = current.as_mut().unwrap().next.take();
-                *current = next;
-                break;
-            } else {
-                current = &mut current.as_mut().unwrap().next;
-            }
-        }
+        self.ranges.remove_dependent(dependent_cell);
     }
 
-    /// Removes all dependency edges associated with a given formula.
-    pub fn delete_edge(&mut self, cell: i32, _cols: i32, formula_array: &[Formula]) {
-        let x = formula_array[cell as usize];
-        match x.op_type {
-            -1 => self.delete_node(cell, x.op_info1 as usize),
-            1..=4 => self.delete_node(cell, x.op_info1 as usize),
-            5..=8 => {
-                self.delete_node(cell, x.op_info1 as usize);
-                self.delete_node(cell, x.op_info2 as usize);
-            }
-            9..=13 => self.delete_range_from_graph(cell),
-            14 => self.delete_node(cell, x.op_info1 as usize),
-            15 => self.delete_node(cell, x.op_info2 as usize),
-            _ => {}
+    /// Removes all dependency edges associated with a given formula, including the dynamic
+    /// ranges registered for it.
+    pub fn delete_edge(&mut self, cell: i32, formula_array: &Grid<Formula>) {
+        let formula = &formula_array[cell as usize];
+        let (cells, ranges) = formula.dependencies();
+        for dep in cells {
+            self.delete_node(cell, dep as usize);
+        }
+        if !ranges.is_empty() || formula.is_dynamic() {
+            self.delete_range_from_graph(cell);
         }
     }
-    /// Rebuilds all dependency edges for the given formula.
+    /// Rebuilds all static dependency edges for the given formula.
     ///
-    /// Useful after modifying a formula or loading a snapshot.
-    pub fn add_edge_formula(&mut self, cell: i32, _cols: i32, formula_array: &[Formula]) {
-        let x = formula_array[cell as usize];
-        match x.op_type {
-            -1 => self.add_edge(cell, x.op_info1 as usize),
-            1..=4 => self.add_edge(cell, x.op_info1 as usize),
-            5..=8 => {
-                self.add_edge(cell, x.op_info1 as usize);
-                self.add_edge(cell, x.op_info2 as usize);
-            }
-            9..=13 => {
-                let start_cell = x.op_info1;
-                let end_cell = x.op_info2;
-                self.add_range_to_graph(start_cell, end_cell, cell);
-            }
-            14 => {
-                if x.op_info1 != cell {
-                    self.add_edge(cell, x.op_info1 as usize);
-                }
-            }
-            15 => self.add_edge(cell, x.op_info2 as usize),
-            _ => {}
+    /// Useful after modifying a formula or loading a snapshot. Dynamic ranges are registered
+    /// by [`Graph::recalc`], or directly with [`Graph::set_dynamic_ranges`].
+    pub fn add_edge_formula(&mut self, cell: i32, cols: i32, formula_array: &Grid<Formula>) {
+        let (cells, ranges) = formula_array[cell as usize].dependencies();
+        for dep in cells {
+            self.add_edge(cell, dep as usize);
+        }
+        for (start_cell, end_cell) in ranges {
+            self.add_range_to_graph(start_cell, end_cell, cell, cols);
         }
     }
+    /// Returns the cells that read `cell`, directly or through a range, in a sheet of `cols`
+    /// columns.
+    pub fn dependents(&self, cell: i32, cols: i32) -> Vec<i32> {
+        let mut dependents = self.adj_lists[cell as usize].clone();
+        dependents.extend(self.ranges.covering(cell, cols).iter().map(|r| r.dependent_cell));
+        dependents
+    }
+
     /// Internal DFS used to perform topological sort and detect cycles.
     ///
-    /// Traverses standard dependencies and range-based dependencies.
-    /// - `cell`: Current DFS node
-    /// - `visited`: Whether cell has been visited
-    /// - `on_stack`: DFS recursion stack flag
+    /// Traverses standard dependencies and range-based dependencies. The path from `start`
+    /// is kept on an explicit stack rather than the call stack, so that long dependency
+    /// chains cannot overflow it.
+    /// - `start`: Cell the DFS starts from
+    /// - `visited`: Cells visited so far
+    /// - `on_stack`: Cells on the current DFS path
     /// - `result`: Output topologically sorted result
-    /// - `has_cycle`: Set to true if a cycle is detected
+    /// - `cycle`: Set to the cells of a cycle, each reading the next, if one is detected
     /// - `cols`: Number of spreadsheet columns
     fn dfs(
         &self,
-        cell: i32,
-        visited: &mut [bool],
-        on_stack: &mut [bool],
+        start: i32,
+        visited: &mut HashSet<i32>,
+        on_stack: &mut HashSet<i32>,
         result: &mut Vec<i32>,
-        has_cycle: &mut bool,
+        cycle: &mut Option<Vec<i32>>,
         cols: i32,
     ) {
-        if *has_cycle {
+        if cycle.is_some() {
             return;
         }
-        visited[cell as usize] = true;
-        on_stack[cell as usize] = true;
-        let mut current = &self.adj_lists_head[cell as usize];
-        while let Some(node) = current.as_ref() {
-            let dependent = node.cell;
-            if !visited[dependent as usize] {
-                self.dfs(dependent, visited, on_stack, result, has_cycle, cols);
-            } else if on_stack[dependent as usize] {
-                *has_cycle = true;
-                // HAS_CYCLE.store(true, Ordering::Relaxed);  // or Ordering::SeqCst if needed
-
-                return;
-            }
-            if *has_cycle {
+        visited.insert(start);
+        on_stack.insert(start);
+        // Each frame holds a cell on the path, its dependents and how many were visited.
+        let mut path = vec![(start, self.dependents(start, cols), 0)];
+        while let Some((cell, dependents, next)) = path.last_mut() {
+            let Some(&dependent) = dependents.get(*next) else {
+                on_stack.remove(cell);
+                result.push(*cell);
+                path.pop();
+                continue;
+            };
+            *next += 1;
+            if visited.insert(dependent) {
+                on_stack.insert(dependent);
+                path.push((dependent, self.dependents(dependent, cols), 0));
+            } else if on_stack.contains(&dependent) {
+                // Each cell on the path is read by the next, so the loop runs backwards
+                // from `dependent`, which reads the cell at the top of the path.
+                let first = path.iter().position(|(cell, ..)| *cell == dependent).unwrap_or(0);
+                let mut cells = vec![dependent];
+                cells.extend(path[first..].iter().rev().map(|(cell, ..)| *cell));
+                *cycle = Some(cells);
                 return;
             }
-            current = &node.next;
-        }
-        let mut range = &self.ranges_head;
-        while let Some(r) = range.as_ref() {
-            let start_cell = r.start_cell;
-            let end_cell = r.end_cell;
-            let dependent = r.dependent_cell;
-            let start_row = start_cell / cols;
-            let start_col = start_cell % cols;
-            let end_row = end_cell / cols;
-            let end_col = end_cell % cols;
-            let (start_row, end_row) =
-                if start_row > end_row { (end_row, start_row) } else { (start_row, end_row) };
-            let (start_col, end_col) =
-                if start_col > end_col { (end_col, start_col) } else { (start_col, end_col) };
-            let cell_row = cell / cols;
-            let cell_col = cell % cols;
-            if cell_row >= start_row
-                && cell_row <= end_row
-                && cell_col >= start_col
-                && cell_col <= end_col
-            {
-                if !visited[dependent as usize] {
-                    self.dfs(dependent, visited, on_stack, result, has_cycle, cols);
-                } else if on_stack[dependent as usize] {
-                    *has_cycle = true;
-                    // HAS_CYCLE.store(true, Ordering::Relaxed);  // or Ordering::SeqCst if needed
-
-                    return;
-                }
-                if *has_cycle {
-                    return;
-                }
-            }
-            range = &r.next;
         }
-        on_stack[cell as usize] = false;
-        result.push(cell);
     }
     /// Topologically sorts all cells reachable from `start_cell`.
     ///
     /// Used before recalculation to ensure a valid execution order.
     ///
-    /// Returns an error if a circular dependency is detected.
+    /// Returns a [`SheetError::Cycle`] if a circular dependency is detected.
     pub fn topo_sort_from_cell(
         &self,
         start_cell: i32,
         cols: i32,
         state: &mut State,
-    ) -> Result<Vec<i32>, &'static str> {
-        let mut visited = vec![false; state.num_cells];
-        let mut on_stack = vec![false; state.num_cells];
+    ) -> Result<Vec<i32>, SheetError> {
+        self.topo_sort_from_cells(&[start_cell], cols, state)
+    }
+
+    /// Topologically sorts all cells reachable from any of `start_cells`, so that a cell
+    /// always comes after every cell it depends on.
+    ///
+    /// Returns a [`SheetError::Cycle`] with the cells of the loop if a circular dependency
+    /// is detected.
+    pub fn topo_sort_from_cells(
+        &self,
+        start_cells: &[i32],
+        cols: i32,
+        state: &mut State,
+    ) -> Result<Vec<i32>, SheetError> {
+        let mut visited = HashSet::new();
+        let mut on_stack = HashSet::new();
         let mut result = Vec::new();
-        let mut has_cycle = false;
-        self.dfs(start_cell, &mut visited, &mut on_stack, &mut result, &mut has_cycle, cols);
-        if has_cycle {
+        let mut cycle = None;
+        for &cell in start_cells {
+            if !visited.contains(&cell) {
+                self.dfs(cell, &mut visited, &mut on_stack, &mut result, &mut cycle, cols);
+            }
+        }
+        if let Some(cells) = cycle {
             state.has_cycle = true;
-            return Err("Circular dependency detected");
+            let path = cells.into_iter().map(|cell| cell_label(cell, cols)).collect();
+            return Err(SheetError::Cycle { path });
         }
         result.reverse();
         Ok(result)
@@ -350,221 +464,406 @@ impl Graph {
     /// Recalculates all formulas reachable from `start_cell`
     /// in topological order based on the dependency graph.
     ///
-    /// Supports direct assignment, binary operations, range-based functions,
-    /// and sleep-based side-effects.
+    /// Cells without a formula keep their current value.
+    ///
+    /// Before a dynamic formula (one using `OFFSET` or `INDIRECT`) is evaluated, its
+    /// references are resolved again. If they changed, the new ranges replace the old ones and
+    /// the remaining cells are re-sorted, so that a newly referenced cell is computed first.
+    /// A reference that would close a cycle is dropped and the cell is set to `#CYCLE!`.
     pub fn recalc(
-        &self,
+        &mut self,
         cols: i32,
-        arr: &mut [Cell],
+        arr: &mut Grid<Cell>,
         start_cell: i32,
-        formula_array: &[Formula],
+        formula_array: &Grid<Formula>,
+        state: &mut State,
+    ) -> Result<(), SheetError> {
+        self.recalc_cells(cols, arr, &[start_cell], formula_array, state)
+    }
+
+    /// Like [`Graph::recalc`], but recalculates everything reachable from any of
+    /// `start_cells` in a single pass, e.g. after pasting a block of cells.
+    pub fn recalc_cells(
+        &mut self,
+        cols: i32,
+        arr: &mut Grid<Cell>,
+        start_cells: &[i32],
+        formula_array: &Grid<Formula>,
         state: &mut State,
-    ) -> Result<(), &'static str> {
-        let sorted_cells = self.topo_sort_from_cell(start_cell, cols, state)?;
-        for &cell in &sorted_cells {
-            let f = formula_array[cell as usize];
-            match f.op_type {
-                -1 => {
-                    // for cell = cell
-                    let v1 = arr[f.op_info1 as usize].clone();
-
-                    if !v1.is_valid {
-                        // println!("Invalid value for cell {}: {:?}", f.op_info1, v1);
-                        arr[cell as usize] = Cell::invalid();
+    ) -> Result<(), SheetError> {
+        let now = state.clock.as_ref().map_or_else(|| SystemClock.now(), |clock| clock.now());
+        with_time(now, || self.recalc_sorted(cols, arr, start_cells, formula_array, state))
+    }
+
+    /// The body of [`Graph::recalc_cells`], run while `TODAY()` and `NOW()` read the clock.
+    fn recalc_sorted(
+        &mut self,
+        cols: i32,
+        arr: &mut Grid<Cell>,
+        start_cells: &[i32],
+        formula_array: &Grid<Formula>,
+        state: &mut State,
+    ) -> Result<(), SheetError> {
+        let mut sorted_cells = self.topo_sort_from_cells(start_cells, cols, state)?;
+        let mut i = 0;
+        while let Some(&cell) = sorted_cells.get(i) {
+            let formula = &formula_array[cell as usize];
+            if formula.is_dynamic() {
+                let refs = formula.dynamic_dependencies(arr, cols);
+                if refs != self.dynamic_ranges(cell) {
+                    if self.reaches_any(cell, &refs, cols, state)? {
+                        self.set_dynamic_ranges(cell, &[], cols);
+                        arr[cell as usize] = Cell::error(ErrorKind::Cycle);
+                        i += 1;
                         continue;
                     }
-                    // let op = match f.op_type {
-                    //     1 => '+',
-                    //     2 => '-',
-                    //     3 => '*',
-                    //     4 => '/',
-                    //     _ => unreachable!(),
-                    // };
-                    arr[cell as usize] = v1;
+                    self.set_dynamic_ranges(cell, &refs, cols);
+                    // The cells before `cell` are computed and can't read it, so only the
+                    // rest needs sorting again.
+                    let rest = self.topo_sort_from_cells(&sorted_cells[i..], cols, state)?;
+                    sorted_cells.truncate(i);
+                    sorted_cells.extend(rest);
+                    continue;
                 }
-                0 => arr[cell as usize] = Cell::new_int(f.op_info1),
-                1..=4 => {
-                    let v1 = arr[f.op_info1 as usize].clone();
-                    let v2 = Cell::new_int(f.op_info2);
-
-                    if !v1.is_valid {
-                        // println!("Invalid value for cell {}: {:?}", f.op_info1, v1);
-                        arr[cell as usize] = Cell::invalid();
-                        continue;
-                    }
-                    let op = match f.op_type {
-                        1 => '+',
-                        2 => '-',
-                        3 => '*',
-                        4 => '/',
-                        _ => unreachable!(),
-                    };
-                    arr[cell as usize] = arithmetic_eval(v1, v2, op);
+            }
+            if let Some(value) = formula.eval(arr, cols) {
+                arr[cell as usize] = value;
+            }
+            i += 1;
+        }
+        Ok(())
+    }
+
+    /// Returns `true` if `cell` itself or any cell depending on it lies in one of `ranges`,
+    /// i.e. if making `cell` read those ranges would create a cycle.
+    fn reaches_any(
+        &self,
+        cell: i32,
+        ranges: &[(i32, i32)],
+        cols: i32,
+        state: &mut State,
+    ) -> Result<bool, SheetError> {
+        let reachable = self.topo_sort_from_cell(cell, cols, state)?;
+        Ok(reachable.iter().any(|&c| {
+            ranges.iter().any(|&(start_cell, end_cell)| {
+                let range = Range { start_cell, end_cell, dependent_cell: cell, dynamic: true };
+                range.contains(c, cols)
+            })
+        }))
+    }
+}
+
+/// Returns the shortest loop through `cell` as a list of cells, each reading the next, that
+/// starts and ends with `cell`, or `None` if `cell` is not part of a loop.
+///
+/// The loop is found by following the references of the formulas as they resolve right now,
+/// rather than the edges of a graph, so it includes the references given by `OFFSET` and
+/// `INDIRECT` that were dropped because they would close a loop (the cell shows `#CYCLE!`).
+pub fn cycle_through(
+    cell: i32,
+    arr: &Grid<Cell>,
+    formula_array: &Grid<Formula>,
+    cols: i32,
+) -> Option<Vec<i32>> {
+    let has_formula = |other: i32| !formula_array[other as usize].is_empty();
+    // The cells with a formula that `reader` reads; the others can't lead back to `cell`.
+    let read_by = |reader: i32| {
+        let formula = &formula_array[reader as usize];
+        let (mut cells, mut ranges) = formula.dependencies();
+        cells.retain(|&other| has_formula(other));
+        ranges.extend(formula.dynamic_dependencies(arr, cols));
+        for (start_cell, end_cell) in ranges {
+            let range = Range { start_cell, end_cell, dependent_cell: reader, dynamic: false };
+            cells.extend(
+                formula_array
+                    .occupied()
+                    .map(|(idx, _)| idx as i32)
+                    .filter(|&other| has_formula(other) && range.contains(other, cols)),
+            );
+        }
+        cells
+    };
+
+    // Search breadth-first through the cells `cell` reads, directly or indirectly, for one
+    // that reads `cell`. `read_from` maps each cell found to the cell that reads it.
+    let mut read_from = HashMap::new();
+    let mut queue = VecDeque::from([cell]);
+    while let Some(current) = queue.pop_front() {
+        for next in read_by(current) {
+            if next == cell {
+                let mut path = vec![cell];
+                let mut at = current;
+                while at != cell {
+                    path.push(at);
+                    at = read_from[&at];
                 }
-                5..=8 => {
-                    let v1 = arr[f.op_info1 as usize].clone();
-                    let v2 = arr[f.op_info2 as usize].clone();
-                    if !v1.is_valid || !v2.is_valid {
-                        // println!("Invalid value for cell {}: {:?}", f.op_info1, v1);
-                        // println!("Invalid value for cell {}: {:?}", f.op_info2, v2);
-                        arr[cell as usize] = Cell::invalid();
-                        continue;
-                    }
-                    let op = match f.op_type {
-                        5 => '+',
-                        6 => '-',
-                        7 => '*',
-                        8 => '/',
-                        _ => unreachable!(),
-                    };
-                    arr[cell as usize] = arithmetic_eval(v1, v2, op);
+                path[1..].reverse();
+                path.push(cell);
+                return Some(path);
+            }
+            if let Entry::Vacant(entry) = read_from.entry(next) {
+                entry.insert(current);
+                queue.push_back(next);
+            }
+        }
+    }
+    None
+}
+
+/// Returns the indices of the cells in the rectangle spanned by `start_cell` and `end_cell`,
+/// row by row. The corners may be given in any order.
+pub fn range_cells(start_cell: i32, end_cell: i32, cols: i32) -> impl Iterator<Item = i32> {
+    let (start_row, end_row) = (start_cell / cols, end_cell / cols);
+    let (start_col, end_col) = (start_cell % cols, end_cell % cols);
+    let (start_row, end_row) = (start_row.min(end_row), start_row.max(end_row));
+    let (start_col, end_col) = (start_col.min(end_col), start_col.max(end_col));
+    (start_row..=end_row)
+        .flat_map(move |row| (start_col..=end_col).map(move |col| row * cols + col))
+}
+
+/// Computes a range function over the rectangle spanned by `start_cell` and `end_cell`.
+///
+/// Shorthand for [`aggregate`] with a single range argument.
+pub fn range_aggregate(
+    func: RangeFunc,
+    start_cell: i32,
+    end_cell: i32,
+    arr: &Grid<Cell>,
+    cols: i32,
+) -> Cell {
+    aggregate(func, &[Expr::Range(start_cell, end_cell, [Anchor::RELATIVE; 2])], arr, cols)
+}
+
+/// Computes a range function over a list of arguments, e.g. `SUM(A1:A3, C5, 10, B1:B2)`.
+///
+/// Every cell of a range argument counts, as does the value of any other argument;
+/// booleans passed directly count as `1` or `0` and blank cells are skipped. The first
+/// error found is propagated, and a string (or a boolean inside a range) gives `#VALUE!`.
+/// With no values at all, `SUM`, `MIN` and `MAX` give `0` and `AVG` and `STDEV` `#DIV/0!`.
+pub fn aggregate(func: RangeFunc, args: &[Expr], arr: &Grid<Cell>, cols: i32) -> Cell {
+    let values = match collect_numbers(args, arr, cols) {
+        Ok(values) => values,
+        Err(kind) => return Cell::error(kind),
+    };
+    if values.is_empty() {
+        return match func {
+            RangeFunc::Avg | RangeFunc::Stdev => Cell::error(ErrorKind::Div0),
+            _ => Cell::new_int(0),
+        };
+    }
+    let count = values.len() as f64;
+    let sum: f64 = values.iter().sum();
+    let result = match func {
+        RangeFunc::Min => values.iter().copied().fold(f64::MAX, f64::min),
+        RangeFunc::Max => values.iter().copied().fold(f64::MIN, f64::max),
+        RangeFunc::Avg => sum / count,
+        RangeFunc::Sum => sum,
+        RangeFunc::Stdev => {
+            let mean = sum / count;
+            let variance = values.iter().map(|&x| (x - mean) * (x - mean)).sum::<f64>() / count;
+            variance.sqrt()
+        }
+    };
+    number_cell(result)
+}
+
+/// Collects the numbers of a range function's arguments, following the rules of [`aggregate`].
+pub(crate) fn collect_numbers(
+    args: &[Expr],
+    arr: &Grid<Cell>,
+    cols: i32,
+) -> Result<Vec<f64>, ErrorKind> {
+    let mut values = Vec::new();
+    for arg in args {
+        match range_reference(arg, arr, cols) {
+            Some(range) => {
+                let (start, end) = range?;
+                range_cells(start, end, cols)
+                    .try_for_each(|idx| push_number(cell_value(arr, idx), false, &mut values))?
+            }
+            None => push_number(eval_expr(arg, arr, cols), true, &mut values)?,
+        }
+    }
+    Ok(values)
+}
+
+/// Appends the numeric value of `cell` to `values`, accepting booleans only if `allow_bool`.
+fn push_number(cell: Cell, allow_bool: bool, values: &mut Vec<f64>) -> Result<(), ErrorKind> {
+    if let Some(kind) = cell.error_kind() {
+        return Err(kind);
+    }
+    match cell.value {
+        CellValue::Int(i) => values.push(i as f64),
+        CellValue::Float(f) | CellValue::Date(f) => values.push(f),
+        CellValue::Bool(b) if allow_bool => values.push(b as i32 as f64),
+        CellValue::Empty => {}
+        _ => return Err(ErrorKind::Value),
+    }
+    Ok(())
+}
+
+/// Evaluates an expression tree against the current cell values.
+///
+/// Errors in any operand propagate to the result; type mismatches give `#VALUE!`,
+/// references outside the sheet `#REF!` and unknown functions `#NAME?`.
+///
+/// `IF` and `IFERROR` are evaluated lazily: only the branch selected by the condition
+/// (or the fallback, when the value is an error) is computed. All branches stay registered
+/// as dependencies, so changing the condition re-evaluates the cell and picks up the
+/// other branch, while an error or a slow `SLEEP` in the unused branch has no effect.
+pub fn eval_expr(expr: &Expr, arr: &Grid<Cell>, cols: i32) -> Cell {
+    match expr {
+        Expr::Literal(value) => Cell::new(value.clone()),
+        Expr::Cell(idx, _) => cell_value(arr, *idx),
+        // A bare range has no single value; ranges are only meaningful as function arguments.
+        Expr::Range(..) => Cell::error(ErrorKind::Value),
+        Expr::Group(inner) => eval_expr(inner, arr, cols),
+        Expr::Unary { op, operand } => {
+            let v = eval_expr(operand, arr, cols);
+            if let Some(kind) = v.error_kind() {
+                return Cell::error(kind);
+            }
+            match (op, v.value) {
+                (
+                    UnaryOp::Plus,
+                    value @ (CellValue::Int(_) | CellValue::Float(_) | CellValue::Date(_)),
+                ) => Cell::new(value),
+                (UnaryOp::Neg, CellValue::Int(i)) => {
+                    i.checked_neg().map_or_else(|| Cell::new_float(-(i as f64)), Cell::new_int)
                 }
-                9..=13 => {
-                    let start_cell = f.op_info1;
-                    let end_cell = f.op_info2;
-                    let start_row = start_cell / cols;
-                    let start_col = start_cell % cols;
-                    let end_row = end_cell / cols;
-                    let end_col = end_cell % cols;
-                    let (start_row, end_row) = if start_row > end_row {
-                        (end_row, start_row)
-                    } else {
-                        (start_row, end_row)
-                    };
-                    let (start_col, end_col) = if start_col > end_col {
-                        (end_col, start_col)
+                (UnaryOp::Neg, CellValue::Float(f) | CellValue::Date(f)) => Cell::new_float(-f),
+                (_, CellValue::Empty) => Cell::new_int(0),
+                _ => Cell::error(ErrorKind::Value),
+            }
+        }
+        Expr::Binary { op, lhs, rhs } => {
+            op.apply(eval_expr(lhs, arr, cols), eval_expr(rhs, arr, cols))
+        }
+        Expr::Call { name, args } => {
+            if let Some(func) = RangeFunc::from_name(name) {
+                return aggregate(func, args, arr, cols);
+            }
+            if has_function(STAT_FUNCTIONS, name) {
+                return stat_eval(name, args, arr, cols);
+            }
+            if has_function(MATH_FUNCTIONS, name) {
+                return math_eval(name, args, arr, cols);
+            }
+            if has_function(TEXT_FUNCTIONS, name) {
+                return text_eval(name, args, arr, cols);
+            }
+            if has_function(DATE_FUNCTIONS, name) {
+                return date_eval(name, args, arr, cols);
+            }
+            if has_function(FINANCE_FUNCTIONS, name) {
+                return finance_eval(name, args, arr, cols);
+            }
+            if has_function(LOOKUP_FUNCTIONS, name) {
+                return lookup_eval(name, args, arr, cols);
+            }
+            match (name.as_str(), args.as_slice()) {
+                ("SLEEP", [arg]) => sleep_eval(eval_expr(arg, arr, cols)),
+                ("IF", [cond, then]) => if_eval(cond, then, None, arr, cols),
+                ("IF", [cond, then, otherwise]) => if_eval(cond, then, Some(otherwise), arr, cols),
+                ("IFERROR", [value, fallback]) => {
+                    let value = eval_expr(value, arr, cols);
+                    if value.error_kind().is_some() {
+                        eval_expr(fallback, arr, cols)
                     } else {
-                        (start_col, end_col)
-                    };
-                    let mut sum = 0.0;
-                    let mut count = 0;
-                    let mut min_val = f64::MAX;
-                    let mut max_val = f64::MIN;
-                    let mut has_error = false;
-                    let mut values = Vec::new();
-                    'outer: for row in start_row..=end_row {
-                        for col in start_col..=end_col {
-                            let idx = row * cols + col;
-                            let val = &arr[idx as usize];
-                            if !val.is_valid {
-                                has_error = true;
-                                break 'outer;
-                            }
-                            match &val.value {
-                                CellValue::Int(i) => {
-                                    let v = *i as f64;
-                                    sum += v;
-                                    count += 1;
-                                    min_val = min_val.min(v);
-                                    max_val = max_val.max(v);
-                                    values.push(v);
-                                }
-                                CellValue::Float(f) => {
-                                    sum += *f;
-                                    count += 1;
-                                    min_val = min_val.min(*f);
-                                    max_val = max_val.max(*f);
-                                    values.push(*f);
-                                }
-                                CellValue::String(_) => {
-                                    has_error = true;
-                                    break 'outer;
-                                }
-                            }
-                        }
-                    }
-                    if has_error || count == 0 {
-                        // println!("Invalid range for cell {}: {:?}", cell, arr[cell as usize]);
-                        arr[cell as usize] = Cell::invalid();
-                        continue;
+                        value
                     }
-                    arr[cell as usize] = match f.op_type {
-                        9 => {
-                            if min_val.fract() == 0.0 {
-                                Cell::new_int(min_val as i32)
-                            } else {
-                                Cell::new_float(min_val)
-                            }
-                        }
-                        10 => {
-                            if max_val.fract() == 0.0 {
-                                Cell::new_int(max_val as i32)
-                            } else {
-                                Cell::new_float(max_val)
-                            }
-                        }
-                        11 => {
-                            let avg = sum / count as f64;
-                            if avg.fract() == 0.0 {
-                                Cell::new_int(avg as i32)
-                            } else {
-                                Cell::new_float(avg)
-                            }
-                        }
-                        12 => {
-                            if sum.fract() == 0.0 {
-                                Cell::new_int(sum as i32)
-                            } else {
-                                Cell::new_float(sum)
-                            }
-                        }
-                        13 => {
-                            let mean = sum / count as f64;
-                            let variance =
-                                values.iter().map(|&x| (x - mean) * (x - mean)).sum::<f64>()
-                                    / count as f64;
-                            let stdev = variance.sqrt();
-                            if stdev.fract() == 0.0 {
-                                Cell::new_int(stdev as i32)
-                            } else {
-                                Cell::new_float(stdev)
-                            }
-                        }
-                        _ => unreachable!(),
-                    };
                 }
-                14 => {
-                    let mut sleep_value = arr[f.op_info1 as usize].clone();
-                    if f.op_info1 == cell {
-                        sleep_value = Cell::new_int(f.op_info2);
-                    }
-                    if !sleep_value.is_valid {
-                        // println!("Invalid value for cell {}: {:?}", f.op_info1, sleep_value);
-                        arr[cell as usize] = Cell::invalid();
-                        continue;
-                    }
-                    if let CellValue::Int(val) = sleep_value.value {
-                        if val > 0 {
-                            sleep(Duration::from_secs(val as u64));
-                        }
-                    }
-                    arr[cell as usize] = sleep_value;
-                }
-                15 => {
-                    let v1 = Cell::new_int(f.op_info1);
-                    let v2 = arr[f.op_info2 as usize].clone();
-                    if !v2.is_valid {
-                        // println!("Invalid value for cell {}: {:?}", f.op_info2, v2);
-                        arr[cell as usize] = Cell::invalid();
-                        continue;
-                    }
-                    arr[cell as usize] = arithmetic_eval(v1, v2, '/');
-                }
-                16 => {
-                    // Do nothing — string is already assigned in arr, skip overwriting
-                }
-                17 => {
-                    //Do nothing - float is already assigned
+                ("NOT", [arg]) => match truth_value(&eval_expr(arg, arr, cols)) {
+                    Ok(b) => Cell::new_bool(!b),
+                    Err(kind) => Cell::error(kind),
+                },
+                ("COUNT" | "COUNTA" | "COUNTBLANK", [_, ..]) => count_eval(name, args, arr, cols),
+                ("COUNTIF" | "SUMIF" | "AVERAGEIF" | "COUNTIFS" | "SUMIFS", _) => {
+                    conditional_aggregate(name, args, arr, cols)
                 }
+                ("AND", [_, ..]) => logical_eval(true, args, arr, cols),
+                ("OR", [_, ..]) => logical_eval(false, args, arr, cols),
+                (
+                    "SLEEP" | "IF" | "IFERROR" | "NOT" | "AND" | "OR" | "COUNT" | "COUNTA"
+                    | "COUNTBLANK",
+                    _,
+                ) => Cell::error(ErrorKind::Value),
+                _ => Cell::error(ErrorKind::Name),
+            }
+        }
+    }
+}
 
-                _ => {
-                    // println!("Invalid formula type for cell {}: {:?}", cell, f.op_type); ;
-                    arr[cell as usize] = Cell::invalid()
+/// Evaluates `IF(cond, then, otherwise)`, computing only the selected branch.
+///
+/// A missing `otherwise` branch evaluates to `FALSE`.
+fn if_eval(
+    cond: &Expr,
+    then: &Expr,
+    otherwise: Option<&Expr>,
+    arr: &Grid<Cell>,
+    cols: i32,
+) -> Cell {
+    match truth_value(&eval_expr(cond, arr, cols)) {
+        Ok(true) => eval_expr(then, arr, cols),
+        Ok(false) => otherwise.map_or(Cell::new_bool(false), |e| eval_expr(e, arr, cols)),
+        Err(kind) => Cell::error(kind),
+    }
+}
+
+/// Evaluates `AND(...)` (`all == true`) or `OR(...)` (`all == false`).
+///
+/// Range arguments contribute their booleans and numbers and skip strings and blanks; any error
+/// propagates, and `#VALUE!` is returned if no argument yields a truth value.
+fn logical_eval(all: bool, args: &[Expr], arr: &Grid<Cell>, cols: i32) -> Cell {
+    let mut values = Vec::new();
+    for arg in args {
+        if let Some(range) = range_reference(arg, arr, cols) {
+            let (start, end) = match range {
+                Ok(range) => range,
+                Err(kind) => return Cell::error(kind),
+            };
+            for idx in range_cells(start, end, cols) {
+                let cell = cell_value(arr, idx);
+                if !matches!(cell.value, CellValue::String(_) | CellValue::Empty) {
+                    values.push(truth_value(&cell));
                 }
             }
+        } else {
+            values.push(truth_value(&eval_expr(arg, arr, cols)));
+        }
+    }
+    if values.is_empty() {
+        return Cell::error(ErrorKind::Value);
+    }
+    let mut result = all;
+    for value in values {
+        match value {
+            Ok(b) if all => result &= b,
+            Ok(b) => result |= b,
+            Err(kind) => return Cell::error(kind),
+        }
+    }
+    Cell::new_bool(result)
+}
+
+/// Returns the value of the cell at `idx`, or `#REF!` if it lies outside the sheet.
+pub(crate) fn cell_value(arr: &Grid<Cell>, idx: i32) -> Cell {
+    usize::try_from(idx)
+        .ok()
+        .and_then(|idx| arr.get(idx))
+        .cloned()
+        .unwrap_or_else(|| Cell::error(ErrorKind::Ref))
+}
+
+/// Sleeps for `value` seconds if it is a positive integer and returns it unchanged.
+fn sleep_eval(value: Cell) -> Cell {
+    if let (true, CellValue::Int(secs)) = (value.is_valid, &value.value) {
+        if *secs > 0 {
+            sleep(Duration::from_secs(*secs as u64));
         }
-        Ok(())
     }
+    value
 }
 
 impl Drop for Graph {
@@ -576,13 +875,16 @@ impl Drop for Graph {
 pub struct State {
     /// Previous value of the cell before recalculation.
     pub old_value: Cell,
-    pub old_op_type: i32,
-    pub old_op_info1: i32,
-    pub old_op_info2: i32,
+    /// Previous formula of the cell, restored if the new one introduces a cycle.
+    pub old_formula: Formula,
     /// Whether a cycle was detected during traversal.
     pub has_cycle: bool,
     /// Number of cells in the spreadsheet.
     pub num_cells: usize,
+    /// The range last copied or cut, if any.
+    pub clipboard: Option<Clipboard>,
+    /// The clock read by `TODAY()` and `NOW()`, or `None` for the [`SystemClock`].
+    pub clock: Option<Arc<dyn Clock>>,
 }
 
 impl State {
@@ -590,11 +892,11 @@ impl State {
     pub fn new() -> Self {
         State {
             old_value: Cell::invalid(),
-            old_op_type: 0,
-            old_op_info1: 0,
-            old_op_info2: 0,
+            old_formula: Formula::default(),
             has_cycle: false,
             num_cells: 0,
+            clipboard: None,
+            clock: None,
         }
     }
 }
@@ -605,9 +907,13 @@ impl State {
 #[derive(Clone)]
 pub struct StateSnapshot {
     /// Current values in the spreadsheet.
-    pub arr: Vec<Cell>,
+    pub arr: Grid<Cell>,
     /// Formulas assigned to each cell.
-    pub formula_array: Vec<Formula>,
+    pub formula_array: Grid<Formula>,
     /// Dependency graph linking formulas and affected cells.
     pub graph: Graph,
+    /// Number of columns, which a resize may have changed.
+    pub cols: i32,
+    /// Number of rows, which a resize may have changed.
+    pub rows: i32,
 }
@@ -0,0 +1,125 @@
+//! # Sparse Cell Storage
+//!
+//! A sheet of 999 rows and 18278 columns has over 18 million cells, of which usually only a
+//! few hundred are in use. [`Grid`] stores one value per cell in fixed-size chunks that are
+//! only allocated once a cell in them is written; reading a cell in a missing chunk returns
+//! the grid's fill value (e.g. an empty cell) without allocating.
+//!
+//! Both engines use it for cell values, formulas and the dependency lists, so memory and
+//! the cost of cloning a sheet for undo grow with the used part of the sheet rather than
+//! with its size.
+
+use std::fmt;
+use std::ops::{Index, IndexMut};
+
+/// Number of cells in one chunk.
+const CHUNK_SIZE: usize = 1024;
+
+/// A fixed-length sequence of values, indexed like a `Vec`, that only allocates memory for
+/// the chunks that have been written to.
+#[derive(Clone)]
+pub struct Grid<T> {
+    len: usize,
+    fill: T,
+    chunks: Vec<Option<Box<[T]>>>,
+}
+
+impl<T: Clone> Grid<T> {
+    /// Creates a grid of `len` cells that all hold `fill`.
+    pub fn new(len: usize, fill: T) -> Self {
+        Grid { len, fill, chunks: vec![None; len.div_ceil(CHUNK_SIZE)] }
+    }
+
+    /// Returns the number of cells.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the grid has no cells.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the value every cell holds until it is written.
+    pub fn fill(&self) -> &T {
+        &self.fill
+    }
+
+    /// Returns the cell at `idx`, or `None` if it lies past the end.
+    pub fn get(&self, idx: usize) -> Option<&T> {
+        (idx < self.len).then(|| match &self.chunks[idx / CHUNK_SIZE] {
+            Some(chunk) => &chunk[idx % CHUNK_SIZE],
+            None => &self.fill,
+        })
+    }
+
+    /// Iterates over every cell in order, including the ones never written.
+    pub fn iter(&self) -> impl Iterator<Item = &T> + '_ {
+        (0..self.len).map(move |idx| &self[idx])
+    }
+
+    /// Iterates over the cells of the allocated chunks with their indices. Every cell not
+    /// visited holds the fill value.
+    pub fn occupied(&self) -> impl Iterator<Item = (usize, &T)> + '_ {
+        self.chunks.iter().enumerate().flat_map(move |(number, chunk)| {
+            let start = number * CHUNK_SIZE;
+            chunk
+                .iter()
+                .flat_map(|chunk| chunk.iter().enumerate())
+                .map(move |(offset, value)| (start + offset, value))
+                .take_while(move |(idx, _)| *idx < self.len)
+        })
+    }
+}
+
+impl<T: Clone> Index<usize> for Grid<T> {
+    type Output = T;
+
+    fn index(&self, idx: usize) -> &T {
+        match self.get(idx) {
+            Some(value) => value,
+            None => panic!("index out of bounds: the len is {} but the index is {}", self.len, idx),
+        }
+    }
+}
+
+impl<T: Clone> IndexMut<usize> for Grid<T> {
+    /// Returns the cell at `idx` for writing, allocating its chunk if needed.
+    fn index_mut(&mut self, idx: usize) -> &mut T {
+        assert!(
+            idx < self.len,
+            "index out of bounds: the len is {} but the index is {}",
+            self.len,
+            idx
+        );
+        let fill = &self.fill;
+        let chunk = self.chunks[idx / CHUNK_SIZE]
+            .get_or_insert_with(|| vec![fill.clone(); CHUNK_SIZE].into_boxed_slice());
+        &mut chunk[idx % CHUNK_SIZE]
+    }
+}
+
+impl<T: Clone + PartialEq> PartialEq for Grid<T> {
+    /// Two grids are equal if they hold the same values, however they are allocated.
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && self.iter().eq(other.iter())
+    }
+}
+
+impl<T: Clone + PartialEq + fmt::Debug> fmt::Debug for Grid<T> {
+    /// Lists the cells that differ from the fill value.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.occupied().filter(|(_, value)| **value != self.fill)).finish()
+    }
+}
+
+impl<T: Clone + Default> From<Vec<T>> for Grid<T> {
+    /// Builds a grid holding `values`, with the type's default as fill value.
+    fn from(values: Vec<T>) -> Self {
+        let mut grid = Grid::new(values.len(), T::default());
+        for (idx, value) in values.into_iter().enumerate() {
+            grid[idx] = value;
+        }
+        grid
+    }
+}
@@ -4,13 +4,16 @@
 //! dependency tracking, and interactive features. This project supports both standard and extended spreadsheet modes.
 //!
 //! ## Features
-//! - Arithmetic expression parsing and evaluation
+//! - Arithmetic expression parsing and evaluation, with arbitrarily nested formulas in extended mode
 //! - Cell dependency management using graph structures
 //! - Cycle detection and topological sorting for recalculation
 //! - Built-in functions: `SUM`, `AVG`, `MIN`, `MAX`, `STDEV`, etc.
 //! - Extended support for `String`, `Float`, and typed cell operations
 //! - Support for undo/redo functionality (in extended mode)
 //! - Autofill feature with pattern detection (AP, GP, etc.)
+//! - Copy, cut and paste of ranges that adjust relative references (in extended mode)
+//! - Inserting and deleting rows and columns (in extended mode)
+//! - Sparse storage, so that large sheets only use memory for the cells in use
 //!
 //! ## Modules
 //! - [`display`](display): Handles rendering of spreadsheet UI (standard mode).
@@ -18,14 +21,29 @@
 //! - [`graph`](graph): Tracks dependencies and handles formula recalculations (standard mode).
 //! - [`parser`](parser): Parses and evaluates expressions with support for integer cells (standard mode).
 //! - [`util_ext`](util_ext): Shared utilities used in extended evaluation logic.
+//! - [`aggregate_ext`](aggregate_ext): Counting functions and criteria-based aggregates (`COUNTIF`, `SUMIFS`, ...).
+//! - [`clipboard_ext`](clipboard_ext): Copy, cut and paste of ranges with reference adjustment.
+//! - [`date_ext`](date_ext): Date values, ISO date literals and date functions (`DATE`, `EDATE`, `DATEDIF`, `TODAY`, ...).
 //! - [`display_ext`](display_ext): Enhanced rendering logic with extended features.
+//! - [`error_ext`](error_ext): The [`SheetError`](error_ext::SheetError) type reported by the extended engine.
+//! - [`expr_ext`](expr_ext): Tokenizer, expression tree and parser for extended formulas.
+//! - [`finance_ext`](finance_ext): Financial functions (`PMT`, `PV`, `FV`, `NPV`, `IRR`, `XNPV`, `RATE`, `NPER`).
 //! - [`function_ext`](function_ext): Extended functions for float and string cell values.
 //! - [`graph_ext`](graph_ext): Extended dependency tracking with range support and advanced recalculation.
+//! - [`grid_ext`](grid_ext): Sparse, chunked storage for cell values, formulas and dependents.
+//! - [`lookup_ext`](lookup_ext): Lookup and reference functions (`VLOOKUP`, `INDEX`, `MATCH`, `OFFSET`, `INDIRECT`, ...).
+//! - [`math_ext`](math_ext): Scalar math and rounding functions (`ROUND`, `ABS`, `SQRT`, trigonometry, ...).
+//! - [`range_index_ext`](range_index_ext): Spatial index of the ranges formulas depend on.
 //! - [`parser_ext`](parser_ext): Extended parser for multiple cell types and string operations.
+//! - [`sheet_ext`](sheet_ext): The [`Sheet`] type bundling a sheet's cells, formulas, graph and history.
+//! - [`stats_ext`](stats_ext): Statistical functions (`VAR.S`, `MEDIAN`, `CORREL`, `LINEST`, ...).
+//! - [`structure_ext`](structure_ext): Inserting and deleting rows and columns with reference rewriting.
+//! - [`text_ext`](text_ext): Unicode-aware text functions (`LEN`, `MID`, `SUBSTITUTE`, `TEXTJOIN`, `TEXT`, ...).
 //!
 //! ## Getting Started
 //! To use the spreadsheet engine, select either the standard or extended mode through your binary's entry point.
 //! This selection allows dynamic inclusion of features depending on user needs.
+//! Front ends of the extended mode drive a [`Sheet`], which keeps all state of one spreadsheet.
 //!
 //! ### Example
 //! ```bash
@@ -38,12 +56,28 @@
 //! **Author**: Built by Vani Gupta, 2025  
 //! *Modular design encourages future contributions and easy feature expansion.*
 
+pub mod aggregate_ext;
+pub mod clipboard_ext;
+pub mod date_ext;
 pub mod display;
 pub mod display_ext;
+pub mod error_ext;
+pub mod expr_ext;
+pub mod finance_ext;
 pub mod function;
 pub mod function_ext;
 pub mod graph;
 pub mod graph_ext;
+pub mod grid_ext;
+pub mod lookup_ext;
+pub mod math_ext;
 pub mod parser;
 pub mod parser_ext;
+pub mod range_index_ext;
+pub mod sheet_ext;
+pub mod stats_ext;
+pub mod structure_ext;
+pub mod text_ext;
 pub mod util_ext;
+
+pub use sheet_ext::Sheet;
@@ -0,0 +1,460 @@
+//! # Lookup and Reference Functions
+//!
+//! `VLOOKUP`, `HLOOKUP`, `INDEX`, `MATCH`, `XLOOKUP` and `CHOOSE` pick a value out of a
+//! table, while `OFFSET` and `INDIRECT` build a reference from data:
+//! `=OFFSET(A1, B1, 0)` points `B1` rows below `A1`, and `=INDIRECT("C" & D1)` at the cell
+//! named by the text. A reference built this way can be used wherever a range is accepted,
+//! e.g. `=SUM(OFFSET(A1, 0, 0, 3, 1))` or `=SUM(INDIRECT("A1:A3"))`.
+//!
+//! ## Dynamic dependencies
+//! The cells an `OFFSET` or `INDIRECT` reads are only known once their arguments have been
+//! evaluated, so they cannot be registered when the formula is entered. Instead,
+//! [`Graph::recalc`](crate::graph_ext::Graph::recalc) resolves them with [`dynamic_refs`]
+//! every time the cell is recalculated and registers them as dynamic ranges, replacing the
+//! ones found the previous time. A dynamic reference that would close a cycle is not
+//! registered and the cell evaluates to `#CYCLE!` instead.
+//!
+//! ## Matching
+//! Exact matches ignore case and, except in `XLOOKUP`, accept the wildcards `*` and `?`.
+//! Approximate matches (`VLOOKUP`/`HLOOKUP` by default, `MATCH` with type `1` or `-1`)
+//! expect sorted data and return the last value not past the lookup value. Values of a
+//! different type than the lookup value are skipped. No match gives `#N/A`.
+
+use crate::aggregate_ext::{same_kind, Criteria, RangeArg};
+use crate::expr_ext::{Expr, Signature};
+use crate::function_ext::{Cell, CellValue, ErrorKind};
+use crate::graph_ext::{cell_value, eval_expr};
+use crate::grid_ext::Grid;
+use crate::parser_ext::cell_parser;
+use crate::util_ext::{compare_values, number_value, text_value, truth_value};
+use std::cmp::Ordering;
+
+/// The lookup and reference functions handled by [`lookup_eval`] and their numbers of arguments.
+pub const LOOKUP_FUNCTIONS: &[Signature] = &[
+    ("VLOOKUP", 3, 4),
+    ("HLOOKUP", 3, 4),
+    ("INDEX", 2, 3),
+    ("MATCH", 2, 3),
+    ("XLOOKUP", 3, 6),
+    ("CHOOSE", 2, usize::MAX),
+    ("OFFSET", 3, 5),
+    ("INDIRECT", 1, 2),
+];
+
+/// Functions whose result refers to cells that are only known at evaluation time.
+pub const DYNAMIC_FUNCTIONS: [&str; 2] = ["OFFSET", "INDIRECT"];
+
+/// Evaluates one of the [`LOOKUP_FUNCTIONS`].
+///
+/// `OFFSET` and `INDIRECT` used as a value must refer to a single cell (`#VALUE!`
+/// otherwise); a reference outside the sheet gives `#REF!`.
+pub fn lookup_eval(name: &str, args: &[Expr], arr: &Grid<Cell>, cols: i32) -> Cell {
+    let result = match (name, args) {
+        ("VLOOKUP" | "HLOOKUP", [x, table, index, approx @ ..]) => {
+            table_lookup(name == "VLOOKUP", x, table, index, approx.first(), arr, cols)
+        }
+        ("INDEX", [range, row, col @ ..]) => index(range, row, col.first(), arr, cols),
+        ("MATCH", [x, range, kind @ ..]) => match_position(x, range, kind.first(), arr, cols),
+        ("XLOOKUP", [x, lookup, ret, rest @ ..]) => xlookup(x, lookup, ret, rest, arr, cols),
+        ("CHOOSE", [index, options @ ..]) => {
+            choose_index(index, options.len(), arr, cols).map(|i| eval_expr(&options[i], arr, cols))
+        }
+        ("OFFSET" | "INDIRECT", _) => match call_reference(name, args, arr, cols) {
+            Some(Ok((start, end))) if start == end => Ok(cell_value(arr, start)),
+            Some(Ok(_)) => Err(ErrorKind::Value),
+            Some(Err(kind)) => Err(kind),
+            None => Err(ErrorKind::Value),
+        },
+        _ => Err(ErrorKind::Value),
+    };
+    result.unwrap_or_else(Cell::error)
+}
+
+/// Resolves an argument that stands for a range: a `start:end` range, or an `OFFSET`,
+/// `INDIRECT` or `CHOOSE` call that yields a reference.
+///
+/// Returns `None` for any other expression, including single cells, so callers can treat
+/// it as a plain value.
+pub(crate) fn range_reference(
+    expr: &Expr,
+    arr: &Grid<Cell>,
+    cols: i32,
+) -> Option<Result<(i32, i32), ErrorKind>> {
+    match expr {
+        Expr::Range(start, end, _) => Some(Ok((*start, *end))),
+        Expr::Call { name, args } => call_reference(name, args, arr, cols),
+        _ => None,
+    }
+}
+
+/// The reference a call to `name` yields, if it is a reference function.
+fn call_reference(
+    name: &str,
+    args: &[Expr],
+    arr: &Grid<Cell>,
+    cols: i32,
+) -> Option<Result<(i32, i32), ErrorKind>> {
+    match (name, args) {
+        ("OFFSET", [base, rows, columns, size @ ..]) if size.len() <= 2 => {
+            Some(offset(base, rows, columns, size, arr, cols))
+        }
+        ("INDIRECT", [text, style @ ..]) if style.len() <= 1 => {
+            Some(indirect(text, style.first(), arr, cols))
+        }
+        ("CHOOSE", [index, options @ ..]) => {
+            let i = choose_index(index, options.len(), arr, cols).ok()?;
+            range_reference(&options[i], arr, cols)
+        }
+        _ => None,
+    }
+}
+
+/// Like [`range_reference`], but also accepts a single cell.
+pub(crate) fn reference(
+    expr: &Expr,
+    arr: &Grid<Cell>,
+    cols: i32,
+) -> Option<Result<(i32, i32), ErrorKind>> {
+    match expr {
+        Expr::Cell(idx, _) => Some(Ok((*idx, *idx))),
+        _ => range_reference(expr, arr, cols),
+    }
+}
+
+/// Appends the ranges read by the `OFFSET` and `INDIRECT` calls in `expr`, resolved
+/// against the current cell values. References that do not resolve are skipped.
+pub(crate) fn dynamic_refs(expr: &Expr, arr: &Grid<Cell>, cols: i32, refs: &mut Vec<(i32, i32)>) {
+    match expr {
+        Expr::Call { name, args } => {
+            if DYNAMIC_FUNCTIONS.contains(&name.as_str()) {
+                if let Some(Ok(range)) = range_reference(expr, arr, cols) {
+                    refs.push(range);
+                }
+            }
+            for arg in args {
+                dynamic_refs(arg, arr, cols, refs);
+            }
+        }
+        Expr::Unary { operand, .. } => dynamic_refs(operand, arr, cols, refs),
+        Expr::Binary { lhs, rhs, .. } => {
+            dynamic_refs(lhs, arr, cols, refs);
+            dynamic_refs(rhs, arr, cols, refs);
+        }
+        Expr::Group(inner) => dynamic_refs(inner, arr, cols, refs),
+        Expr::Literal(_) | Expr::Cell(..) | Expr::Range(..) => {}
+    }
+}
+
+/// Returns `true` if `expr` calls `OFFSET` or `INDIRECT`.
+pub(crate) fn has_dynamic_refs(expr: &Expr) -> bool {
+    match expr {
+        Expr::Call { name, args } => {
+            DYNAMIC_FUNCTIONS.contains(&name.as_str()) || args.iter().any(has_dynamic_refs)
+        }
+        Expr::Unary { operand, .. } => has_dynamic_refs(operand),
+        Expr::Binary { lhs, rhs, .. } => has_dynamic_refs(lhs) || has_dynamic_refs(rhs),
+        Expr::Group(inner) => has_dynamic_refs(inner),
+        Expr::Literal(_) | Expr::Cell(..) | Expr::Range(..) => false,
+    }
+}
+
+/// Evaluates an argument that must be a whole number.
+fn int_arg(expr: &Expr, arr: &Grid<Cell>, cols: i32) -> Result<i32, ErrorKind> {
+    Ok(number_value(&eval_expr(expr, arr, cols))?.trunc() as i32)
+}
+
+/// Evaluates the lookup value; errors propagate.
+fn lookup_value(expr: &Expr, arr: &Grid<Cell>, cols: i32) -> Result<CellValue, ErrorKind> {
+    let cell = eval_expr(expr, arr, cols);
+    match cell.error_kind() {
+        Some(kind) => Err(kind),
+        None => Ok(cell.value),
+    }
+}
+
+/// `OFFSET(reference, rows, cols[, height[, width]])`: the range of `height` × `width`
+/// cells (by default the size of `reference`) whose top-left corner is `rows` rows below
+/// and `cols` columns right of the top-left corner of `reference`.
+fn offset(
+    base: &Expr,
+    rows: &Expr,
+    columns: &Expr,
+    size: &[Expr],
+    arr: &Grid<Cell>,
+    cols: i32,
+) -> Result<(i32, i32), ErrorKind> {
+    let (start, end) = reference(base, arr, cols).ok_or(ErrorKind::Value)??;
+    let (top, left) = ((start / cols).min(end / cols), (start % cols).min(end % cols));
+    let mut height = (start / cols - end / cols).abs() + 1;
+    let mut width = (start % cols - end % cols).abs() + 1;
+    let (top, left) = (
+        top.saturating_add(int_arg(rows, arr, cols)?),
+        left.saturating_add(int_arg(columns, arr, cols)?),
+    );
+    if let Some(h) = size.first() {
+        height = int_arg(h, arr, cols)?;
+    }
+    if let Some(w) = size.get(1) {
+        width = int_arg(w, arr, cols)?;
+    }
+    let sheet_rows = arr.len() as i32 / cols;
+    if height < 1
+        || width < 1
+        || top < 0
+        || left < 0
+        || top > sheet_rows - height
+        || left > cols - width
+    {
+        return Err(ErrorKind::Ref);
+    }
+    Ok((top * cols + left, (top + height - 1) * cols + left + width - 1))
+}
+
+/// `INDIRECT(text[, a1])`: the cell or range named by `text`, e.g. `"B3"` or `"A1:C2"`.
+///
+/// Only A1-style references are supported; `a1 = FALSE` (R1C1 style) gives `#REF!`.
+fn indirect(
+    text: &Expr,
+    style: Option<&Expr>,
+    arr: &Grid<Cell>,
+    cols: i32,
+) -> Result<(i32, i32), ErrorKind> {
+    let text = text_value(&eval_expr(text, arr, cols))?;
+    if let Some(style) = style {
+        if !truth_value(&eval_expr(style, arr, cols))? {
+            return Err(ErrorKind::Ref);
+        }
+    }
+    let text = text.trim().to_ascii_uppercase();
+    if !text.is_ascii() {
+        return Err(ErrorKind::Ref);
+    }
+    let rows = arr.len() as i32 / cols;
+    let parse = |label: &str| {
+        cell_parser(label, cols, rows, 0, label.len().saturating_sub(1)).map_err(|_| ErrorKind::Ref)
+    };
+    match text.split_once(':') {
+        Some((start, end)) => Ok((parse(start)?, parse(end)?)),
+        None => parse(&text).map(|cell| (cell, cell)),
+    }
+}
+
+/// Evaluates the index of `CHOOSE`, which must pick one of the `count` options.
+fn choose_index(
+    index: &Expr,
+    count: usize,
+    arr: &Grid<Cell>,
+    cols: i32,
+) -> Result<usize, ErrorKind> {
+    match int_arg(index, arr, cols)? {
+        i if i >= 1 && i as usize <= count => Ok(i as usize - 1),
+        _ => Err(ErrorKind::Value),
+    }
+}
+
+/// Position of the first value equal to `x`; with `wildcards`, text may use `*` and `?`.
+fn exact_position(values: &[CellValue], x: &CellValue, wildcards: bool) -> Option<usize> {
+    if wildcards {
+        let criteria = Criteria { op: "=", value: x.clone() };
+        return values.iter().position(|value| criteria.matches(value));
+    }
+    values.iter().position(|value| order(value, x) == Some(Ordering::Equal))
+}
+
+/// Approximate match in sorted data: the last value not past `x`, i.e. the largest value
+/// `<= x` in ascending data, or the smallest value `>= x` in descending data.
+fn sorted_position(values: &[CellValue], x: &CellValue, descending: bool) -> Option<usize> {
+    let past = if descending { Ordering::Less } else { Ordering::Greater };
+    let mut found = None;
+    for (i, value) in values.iter().enumerate() {
+        match order(value, x) {
+            Some(ordering) if ordering == past => break,
+            Some(_) => found = Some(i),
+            None => {}
+        }
+    }
+    found
+}
+
+/// `XLOOKUP`'s next smaller/larger match: an exact match if there is one, otherwise the
+/// closest value below (`larger == false`) or above `x`.
+fn nearest_position(values: &[CellValue], x: &CellValue, larger: bool) -> Option<usize> {
+    let wanted = if larger { Ordering::Greater } else { Ordering::Less };
+    let mut best: Option<usize> = None;
+    for (i, value) in values.iter().enumerate() {
+        match order(value, x) {
+            Some(Ordering::Equal) => return Some(i),
+            Some(ordering)
+                if ordering == wanted
+                    && best.is_none_or(|b| order(value, &values[b]) == Some(wanted.reverse())) =>
+            {
+                best = Some(i);
+            }
+            _ => {}
+        }
+    }
+    best
+}
+
+/// Orders `value` against `x`, or `None` if they are not of the same kind.
+fn order(value: &CellValue, x: &CellValue) -> Option<Ordering> {
+    same_kind(value, x).then(|| compare_values(value, x))
+}
+
+/// `VLOOKUP(x, table, col[, approx])` (`vertical`) or `HLOOKUP(x, table, row[, approx])`:
+/// finds `x` in the first column (row) of `table` and returns the cell in column (row)
+/// `col` of the matching row (column). `approx` defaults to `TRUE`.
+fn table_lookup(
+    vertical: bool,
+    x: &Expr,
+    table: &Expr,
+    index: &Expr,
+    approx: Option<&Expr>,
+    arr: &Grid<Cell>,
+    cols: i32,
+) -> Result<Cell, ErrorKind> {
+    let x = lookup_value(x, arr, cols)?;
+    let table = RangeArg::from_expr(table, arr, cols)?;
+    let index = int_arg(index, arr, cols)?;
+    let approx = match approx {
+        Some(approx) => truth_value(&eval_expr(approx, arr, cols))?,
+        None => true,
+    };
+    let (rows, width) = table.shape;
+    let at = |row: i32, col: i32| table.cells[(row * width + col) as usize];
+    let (lines, depth) = if vertical { (rows, width) } else { (width, rows) };
+    if index < 1 {
+        return Err(ErrorKind::Value);
+    }
+    if index > depth {
+        return Err(ErrorKind::Ref);
+    }
+    let keys: Vec<CellValue> = (0..lines)
+        .map(|i| cell_value(arr, if vertical { at(i, 0) } else { at(0, i) }).value)
+        .collect();
+    let found =
+        if approx { sorted_position(&keys, &x, false) } else { exact_position(&keys, &x, true) };
+    let i = found.ok_or(ErrorKind::NotAvailable)? as i32;
+    Ok(cell_value(arr, if vertical { at(i, index - 1) } else { at(index - 1, i) }))
+}
+
+/// `INDEX(range, row[, col])`: the cell at 1-based `row` and `col` of `range`. For a single
+/// row or column, one index is enough.
+fn index(
+    range: &Expr,
+    row: &Expr,
+    col: Option<&Expr>,
+    arr: &Grid<Cell>,
+    cols: i32,
+) -> Result<Cell, ErrorKind> {
+    let range = RangeArg::from_expr(range, arr, cols)?;
+    let (rows, width) = range.shape;
+    let first = int_arg(row, arr, cols)?;
+    let (mut row, mut col) = match col {
+        Some(col) => (first, int_arg(col, arr, cols)?),
+        None if rows == 1 => (1, first),
+        None if width == 1 => (first, 1),
+        None => return Err(ErrorKind::Value),
+    };
+    // An index of 0 selects the whole row or column, which is a single cell only if the
+    // range is one cell wide in that direction.
+    if row == 0 && rows == 1 {
+        row = 1;
+    }
+    if col == 0 && width == 1 {
+        col = 1;
+    }
+    if row < 1 || col < 1 {
+        return Err(ErrorKind::Value);
+    }
+    if row > rows || col > width {
+        return Err(ErrorKind::Ref);
+    }
+    Ok(cell_value(arr, range.cells[((row - 1) * width + col - 1) as usize]))
+}
+
+/// Reads a single row or column; a two-dimensional range gives `#N/A`.
+fn vector(expr: &Expr, arr: &Grid<Cell>, cols: i32) -> Result<Vec<i32>, ErrorKind> {
+    let range = RangeArg::from_expr(expr, arr, cols)?;
+    if range.shape.0 != 1 && range.shape.1 != 1 {
+        return Err(ErrorKind::NotAvailable);
+    }
+    Ok(range.cells)
+}
+
+/// `MATCH(x, range[, type])`: 1-based position of `x` in a single row or column.
+/// `type` is `1` (default, ascending data), `0` (exact) or `-1` (descending data).
+fn match_position(
+    x: &Expr,
+    range: &Expr,
+    kind: Option<&Expr>,
+    arr: &Grid<Cell>,
+    cols: i32,
+) -> Result<Cell, ErrorKind> {
+    let x = lookup_value(x, arr, cols)?;
+    let values: Vec<CellValue> =
+        vector(range, arr, cols)?.into_iter().map(|idx| cell_value(arr, idx).value).collect();
+    let kind = match kind {
+        Some(kind) => int_arg(kind, arr, cols)?,
+        None => 1,
+    };
+    let found = match kind.signum() {
+        0 => exact_position(&values, &x, true),
+        1 => sorted_position(&values, &x, false),
+        _ => sorted_position(&values, &x, true),
+    };
+    Ok(Cell::new_int(found.ok_or(ErrorKind::NotAvailable)? as i32 + 1))
+}
+
+/// `XLOOKUP(x, lookup, return[, if_not_found[, match_mode[, search_mode]]])`.
+///
+/// - `match_mode`: `0` exact (default), `-1` exact or next smaller, `1` exact or next
+///   larger, `2` wildcard.
+/// - `search_mode`: `1` first to last (default), `-1` last to first; the binary search
+///   modes `2` and `-2` are accepted and behave the same way.
+///
+/// `if_not_found` is only evaluated when nothing matches; without it the result is `#N/A`.
+fn xlookup(
+    x: &Expr,
+    lookup: &Expr,
+    ret: &Expr,
+    rest: &[Expr],
+    arr: &Grid<Cell>,
+    cols: i32,
+) -> Result<Cell, ErrorKind> {
+    let x = lookup_value(x, arr, cols)?;
+    let lookup = vector(lookup, arr, cols)?;
+    let ret = RangeArg::from_expr(ret, arr, cols)?.cells;
+    if ret.len() != lookup.len() {
+        return Err(ErrorKind::Value);
+    }
+    let mode = match rest.get(1) {
+        Some(mode) => int_arg(mode, arr, cols)?,
+        None => 0,
+    };
+    let reverse = match rest.get(2) {
+        Some(search) => match int_arg(search, arr, cols)? {
+            1 | 2 => false,
+            -1 | -2 => true,
+            _ => return Err(ErrorKind::Value),
+        },
+        None => false,
+    };
+    let mut order: Vec<usize> = (0..lookup.len()).collect();
+    if reverse {
+        order.reverse();
+    }
+    let values: Vec<CellValue> = order.iter().map(|&i| cell_value(arr, lookup[i]).value).collect();
+    let found = match mode {
+        0 => exact_position(&values, &x, false),
+        2 => exact_position(&values, &x, true),
+        -1 => nearest_position(&values, &x, false),
+        1 => nearest_position(&values, &x, true),
+        _ => return Err(ErrorKind::Value),
+    };
+    match (found, rest.first()) {
+        (Some(i), _) => Ok(cell_value(arr, ret[order[i]])),
+        (None, Some(fallback)) => Ok(eval_expr(fallback, arr, cols)),
+        (None, None) => Err(ErrorKind::NotAvailable),
+    }
+}
@@ -0,0 +1,193 @@
+//! # Math Functions
+//!
+//! Scalar math and rounding functions for the extended engine, e.g. `=ROUND(A1/3, 2)`,
+//! `=ABS(B2)` or `=SQRT(POWER(A1, 2) + POWER(B1, 2))`. Every argument is an expression.
+//!
+//! Results follow the promotion rules of [`arithmetic_eval`]: functions that keep the
+//! magnitude of their input (`ABS`, `MOD`, `ROUND`, `TRUNC`, `FLOOR`, `CEILING`) return an
+//! `Int` for `Int` inputs and a `Float` otherwise, and fall back to `Float` on overflow.
+//! `INT` and `SIGN` return integers, while `SQRT`, `LOG`, `EXP`, `PI` and the trigonometric
+//! functions always return a `Float`.
+//!
+//! Booleans count as `1`/`0` and blank cells as `0`; text gives `#VALUE!`. Domain violations
+//! such as `SQRT(-1)` or `LOG(0)` give `#NUM!`, and a zero divisor gives `#DIV/0!`.
+
+use crate::expr_ext::{Expr, Signature};
+use crate::function_ext::{Cell, CellValue, ErrorKind};
+use crate::graph_ext::eval_expr;
+use crate::grid_ext::Grid;
+use crate::util_ext::{arithmetic_eval, float_cell, number_cell};
+use std::f64::consts::PI;
+
+/// The math functions handled by [`math_eval`] and their numbers of arguments.
+pub const MATH_FUNCTIONS: &[Signature] = &[
+    ("ABS", 1, 1),
+    ("SIGN", 1, 1),
+    ("INT", 1, 1),
+    ("TRUNC", 1, 2),
+    ("ROUND", 2, 2),
+    ("FLOOR", 1, 2),
+    ("CEILING", 1, 2),
+    ("MOD", 2, 2),
+    ("POWER", 2, 2),
+    ("SQRT", 1, 1),
+    ("EXP", 1, 1),
+    ("LN", 1, 1),
+    ("LOG", 1, 2),
+    ("PI", 0, 0),
+    ("SIN", 1, 1),
+    ("COS", 1, 1),
+    ("TAN", 1, 1),
+    ("ASIN", 1, 1),
+    ("ACOS", 1, 1),
+    ("ATAN", 1, 1),
+    ("ATAN2", 2, 2),
+    ("DEGREES", 1, 1),
+    ("RADIANS", 1, 1),
+    ("LOG10", 1, 1),
+];
+
+/// A numeric argument, keeping track of whether it is an integer.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Num {
+    Int(i32),
+    Float(f64),
+}
+
+impl Num {
+    fn from_cell(cell: Cell) -> Result<Self, ErrorKind> {
+        if let Some(kind) = cell.error_kind() {
+            return Err(kind);
+        }
+        match cell.value {
+            CellValue::Int(i) => Ok(Num::Int(i)),
+            CellValue::Float(f) | CellValue::Date(f) => Ok(Num::Float(f)),
+            CellValue::Bool(b) => Ok(Num::Int(b as i32)),
+            CellValue::Empty => Ok(Num::Int(0)),
+            CellValue::String(_) | CellValue::Error(_) => Err(ErrorKind::Value),
+        }
+    }
+
+    fn value(self) -> f64 {
+        match self {
+            Num::Int(i) => i as f64,
+            Num::Float(f) => f,
+        }
+    }
+
+    fn cell(self) -> Cell {
+        match self {
+            Num::Int(i) => Cell::new_int(i),
+            Num::Float(f) => Cell::new_float(f),
+        }
+    }
+}
+
+/// Returns `value` as an `Int` if `int` is set and it fits, and as a `Float` otherwise.
+fn typed_cell(value: f64, int: bool) -> Cell {
+    if int {
+        number_cell(value)
+    } else {
+        float_cell(value)
+    }
+}
+
+/// Evaluates one of the [`MATH_FUNCTIONS`] on already-parsed arguments.
+///
+/// All arguments are evaluated first; the first error among them is propagated.
+pub fn math_eval(name: &str, args: &[Expr], arr: &Grid<Cell>, cols: i32) -> Cell {
+    let mut nums = Vec::with_capacity(args.len());
+    for arg in args {
+        match Num::from_cell(eval_expr(arg, arr, cols)) {
+            Ok(num) => nums.push(num),
+            Err(kind) => return Cell::error(kind),
+        }
+    }
+    apply(name, &nums).unwrap_or_else(Cell::error)
+}
+
+fn apply(name: &str, nums: &[Num]) -> Result<Cell, ErrorKind> {
+    let float = |x: Num| x.value();
+    Ok(match (name, nums) {
+        ("PI", []) => Cell::new_float(PI),
+        ("ABS", [Num::Int(i)]) => {
+            i.checked_abs().map_or_else(|| float_cell(-(*i as f64)), Cell::new_int)
+        }
+        ("ABS", [x]) => float_cell(float(*x).abs()),
+        ("SIGN", [x]) => Cell::new_int(match float(*x) {
+            v if v > 0.0 => 1,
+            v if v < 0.0 => -1,
+            _ => 0,
+        }),
+        ("INT", [x]) => number_cell(float(*x).floor()),
+        ("TRUNC", [x]) => round_to(*x, Num::Int(0), f64::trunc),
+        ("TRUNC", [x, digits]) => round_to(*x, *digits, f64::trunc),
+        ("ROUND", [x, digits]) => round_to(*x, *digits, f64::round),
+        ("FLOOR" | "CEILING", [x]) => to_multiple(name, *x, Num::Int(1))?,
+        ("FLOOR" | "CEILING", [x, significance]) => to_multiple(name, *x, *significance)?,
+        ("MOD", [_, d]) if float(*d) == 0.0 => return Err(ErrorKind::Div0),
+        ("MOD", [Num::Int(n), Num::Int(d)]) => {
+            // The result takes the sign of the divisor, as in Excel: MOD(-3, 2) = 1.
+            let r = n.checked_rem(*d).unwrap_or(0);
+            Cell::new_int(if r != 0 && (r < 0) != (*d < 0) { r + d } else { r })
+        }
+        ("MOD", [n, d]) => {
+            let (n, d) = (float(*n), float(*d));
+            float_cell(n - d * (n / d).floor())
+        }
+        ("POWER", [base, exp]) => arithmetic_eval(base.cell(), exp.cell(), '^'),
+        ("SQRT", [x]) if float(*x) < 0.0 => return Err(ErrorKind::Num),
+        ("SQRT", [x]) => float_cell(float(*x).sqrt()),
+        ("EXP", [x]) => float_cell(float(*x).exp()),
+        ("LN" | "LOG" | "LOG10", [x, ..]) if float(*x) <= 0.0 => return Err(ErrorKind::Num),
+        ("LN", [x]) => float_cell(float(*x).ln()),
+        ("LOG10" | "LOG", [x]) => float_cell(float(*x).log10()),
+        ("LOG", [_, base]) if float(*base) <= 0.0 => return Err(ErrorKind::Num),
+        ("LOG", [_, base]) if float(*base) == 1.0 => return Err(ErrorKind::Div0),
+        ("LOG", [x, base]) => float_cell(float(*x).ln() / float(*base).ln()),
+        ("SIN", [x]) => float_cell(float(*x).sin()),
+        ("COS", [x]) => float_cell(float(*x).cos()),
+        ("TAN", [x]) => float_cell(float(*x).tan()),
+        ("ASIN" | "ACOS", [x]) if float(*x).abs() > 1.0 => return Err(ErrorKind::Num),
+        ("ASIN", [x]) => float_cell(float(*x).asin()),
+        ("ACOS", [x]) => float_cell(float(*x).acos()),
+        ("ATAN", [x]) => float_cell(float(*x).atan()),
+        // Excel's argument order: ATAN2(x, y).
+        ("ATAN2", [x, y]) if float(*x) == 0.0 && float(*y) == 0.0 => return Err(ErrorKind::Div0),
+        ("ATAN2", [x, y]) => float_cell(float(*y).atan2(float(*x))),
+        ("DEGREES", [x]) => float_cell(float(*x).to_degrees()),
+        ("RADIANS", [x]) => float_cell(float(*x).to_radians()),
+        _ => return Err(ErrorKind::Value),
+    })
+}
+
+/// Rounds `x` to `digits` decimal places (to tens, hundreds, ... if negative) with `mode`.
+fn round_to(x: Num, digits: Num, mode: fn(f64) -> f64) -> Cell {
+    let digits = digits.value().trunc().clamp(-308.0, 308.0) as i32;
+    if let (Num::Int(_), true) = (x, digits >= 0) {
+        return x.cell();
+    }
+    let factor = 10f64.powi(digits.abs());
+    let value = x.value();
+    let rounded =
+        if digits >= 0 { mode(value * factor) / factor } else { mode(value / factor) * factor };
+    typed_cell(rounded, matches!(x, Num::Int(_)))
+}
+
+/// `FLOOR`/`CEILING`: rounds `x` down/up to a multiple of `significance`.
+///
+/// A positive `x` with a negative `significance` gives `#NUM!`. `FLOOR` with a zero
+/// significance gives `#DIV/0!`, while `CEILING` returns `0`.
+fn to_multiple(name: &str, x: Num, significance: Num) -> Result<Cell, ErrorKind> {
+    let (value, sig) = (x.value(), significance.value());
+    if value > 0.0 && sig < 0.0 {
+        return Err(ErrorKind::Num);
+    }
+    if sig == 0.0 {
+        return if name == "FLOOR" { Err(ErrorKind::Div0) } else { Ok(Cell::new_int(0)) };
+    }
+    let quotient = value / sig;
+    let steps = if name == "FLOOR" { quotient.floor() } else { quotient.ceil() };
+    let both_int = matches!((x, significance), (Num::Int(_), Num::Int(_)));
+    Ok(typed_cell(steps * sig, both_int))
+}
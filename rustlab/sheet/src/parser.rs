@@ -1,28 +1,38 @@
 use crate::function::*;
 use crate::graph::{Formula, Graph};
+use crate::grid_ext::Grid;
+use crate::util_ext::{split_anchors, Anchor};
+use std::fmt;
 
-/// Global flag indicating whether a circular dependency (cycle)
-/// was detected during graph traversal or formula evaluation.
-///
-/// This is used by the `parser` and `recalc` logic to rollback
-/// changes if a cycle is found, ensuring spreadsheet integrity.
-///
-/// # Safety
-/// Since this is a mutable static variable, it must be accessed
-/// within `unsafe` blocks. Use with caution and ensure proper synchronization.
-pub static mut HAS_CYCLE: bool = false;
+/// Why [`parser`] rejected an input.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParseError {
+    /// The input is not an assignment the standard engine understands.
+    Unrecognized,
+    /// A range function was given a malformed range, or one that runs backwards.
+    InvalidRange,
+    /// The assignment would make a cell depend on itself; it has been rolled back.
+    CircularDependency,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ParseError::Unrecognized => "unrecognized command",
+            ParseError::InvalidRange => "Invalid range",
+            ParseError::CircularDependency => "Circular dependency detected",
+        })
+    }
+}
+
+impl std::error::Error for ParseError {}
 
-/// Global flag indicating whether an invalid range was encountered
-/// during a range function (e.g., `MIN`, `MAX`, `SUM`, etc.) evaluation.
-///
-/// # Safety
-/// This mutable static should be used inside `unsafe` blocks only.
-/// Be careful of concurrent access if used in multithreaded context.
-pub static mut INVALID_RANGE: bool = false;
 /// Parses a spreadsheet cell label (e.g., "A1", "B12") into a linear cell index.
 /// Converts column letters to number and parses row digits.
 /// Returns `Some(index)` if valid, else `None`.
 ///
+/// Anchored labels (`$A$1`, `A$1`, `$A1`) are accepted and give the same index as `A1`.
+///
 /// # Parameters
 /// - `a`: Full input string
 /// - `cols`: Number of columns in the sheet
@@ -41,7 +51,7 @@ pub fn cell_parser(
     if end >= a.len() || start > end {
         return None;
     }
-    let slice = &a[start..=end];
+    let (slice, _) = split_anchors(&a[start..=end])?;
 
     let mut col = 0;
     let mut row = 0;
@@ -74,11 +84,69 @@ pub fn cell_parser(
 
     Some(row_idx * cols + col_idx)
 }
+/// The anchors of the cell references in a formula's right-hand side, in order of appearance.
+fn reference_anchors(right: &str) -> Vec<Anchor> {
+    right
+        .split(|ch: char| !(ch.is_ascii_alphanumeric() || ch == '$'))
+        .filter_map(split_anchors)
+        .filter(|(label, _)| {
+            let letters = label.chars().take_while(char::is_ascii_alphabetic).count();
+            letters > 0 && letters < label.len()
+        })
+        .map(|(_, anchor)| anchor)
+        .collect()
+}
+
+/// Cells whose values the formula of `cell` reads directly, i.e. the sources of its edges.
+fn precedents(cell: usize, formula: &Formula) -> Vec<usize> {
+    match formula.op_type {
+        1..=4 => vec![formula.op_info1 as usize],
+        5..=8 => vec![formula.op_info1 as usize, formula.op_info2 as usize],
+        14 if formula.op_info1 as usize != cell => vec![formula.op_info1 as usize],
+        15 => vec![formula.op_info2 as usize],
+        _ => Vec::new(),
+    }
+}
+
+/// Removes the edges and range that the formula of `cell` added to the graph.
+fn remove_edges(graph: &mut Graph, cell: usize, formula: &Formula) {
+    for precedent in precedents(cell, formula) {
+        graph.delete_edge(precedent, cell);
+    }
+    if (9..=13).contains(&formula.op_type) {
+        graph.delete_range(cell);
+    }
+}
+
+/// Adds the edges and range that the formula of `cell` needs to the graph.
+fn add_edges(graph: &mut Graph, cell: usize, formula: &Formula, cols: usize) {
+    for precedent in precedents(cell, formula) {
+        graph.add_edge(precedent, cell);
+    }
+    if (9..=13).contains(&formula.op_type) {
+        let (start, end) = (formula.op_info1 as usize, formula.op_info2 as usize);
+        graph.add_range_to_graph(start, end, cell, cols);
+    }
+}
+
 /// Detects whether the expression contains an arithmetic operator.
 fn is_arithmetic(expr: &str) -> bool {
     expr.contains('+') || expr.contains('-') || expr.contains('*') || expr.contains('/')
 }
 
+/// Splits `expr` at its first operator into the trimmed left operand, the operator and the
+/// trimmed right operand, or `None` if there is no operator or an operand is empty.
+fn split_operation(expr: &str) -> Option<(&str, char, &str)> {
+    let ops = ['+', '-', '*', '/'];
+    let (op_index, op_char) = expr.char_indices().find(|(_, ch)| ops.contains(ch))?;
+    let left_expr = expr[..op_index].trim();
+    let right_expr = expr[op_index + 1..].trim();
+    if left_expr.is_empty() || right_expr.is_empty() {
+        return None;
+    }
+    Some((left_expr, op_char, right_expr))
+}
+
 /// Main parser function for spreadsheet cell assignment or function application.
 /// Handles constants, arithmetic operations, cell references, and supported functions
 /// like MIN, MAX, SUM, AVG, STDEV, and SLEEP. Also handles cycle detection and rollback.
@@ -91,59 +159,40 @@ fn is_arithmetic(expr: &str) -> bool {
 /// - `graph`: Dependency graph for formula evaluation
 /// - `formula_array`: Stores formulas for each cell
 ///
-/// # Returns
-/// - `1` if parsing and evaluation succeed
-/// - `-1` if parsing fails or cycle is detected
+/// # Errors
+/// Returns a [`ParseError`] if the input is not understood, a range is invalid or the
+/// assignment would create a cycle; the cell then keeps its old formula, value and edges. All
+/// state lives in the arguments, so several sheets can be evaluated at the same time.
 pub fn parser(
     input: &str,
     cols: usize,
     rows: usize,
-    arr: &mut [i32],
+    arr: &mut Grid<i32>,
     graph: &mut Graph,
-    formula_array: &mut [Formula],
-) -> i32 {
-    unsafe {
-        HAS_CYCLE = false;
-        INVALID_RANGE = false;
-    }
-
+    formula_array: &mut Grid<Formula>,
+) -> Result<(), ParseError> {
     let trimmed = input.trim();
     // Check for '=' sign indicating a formula assignment
-    let eq_pos = trimmed.find('=');
-    if eq_pos.is_none() {
-        return -1;
-    }
-
-    let eq_index = eq_pos.unwrap();
+    let eq_index = trimmed.find('=').ok_or(ParseError::Unrecognized)?;
     let _left = &trimmed[..eq_index];
     // let right = &trimmed[eq_index + 1..];
     let right = &trimmed[eq_index + 1..].trim();
 
     if eq_index == 0 || eq_index > trimmed.len() {
-        return -1;
+        return Err(ParseError::Unrecognized);
     }
     // Parse left-hand side cell index
-    let left_cell = match cell_parser(trimmed, cols, rows, 0, eq_index - 1, graph) {
-        Some(idx) => idx,
-        None => return -1,
-    };
+    let left_cell =
+        cell_parser(trimmed, cols, rows, 0, eq_index - 1, graph).ok_or(ParseError::Unrecognized)?;
 
     // Save old state for rollback if needed
     let old_value = arr[left_cell];
     let old_formula = formula_array[left_cell];
 
-    // Clear old edges if any
-    if old_formula.op_type != 0 {
-        graph.delete_edge(left_cell, old_formula.op_info1 as usize);
-        if (1..=8).contains(&old_formula.op_type) {
-            graph.delete_edge(left_cell, old_formula.op_info2 as usize);
-        }
-        if (9..=13).contains(&old_formula.op_type) {
-            graph.delete_range(left_cell);
-        }
-    }
+    // Clear old edges; they are put back below if the new formula is rejected
+    remove_edges(graph, left_cell, &old_formula);
 
-    let mut status = -1;
+    let mut status = Err(ParseError::Unrecognized);
     // Supported: MIN(), MAX(), AVG(), SUM(), STDEV()
     if right.starts_with("MIN(") {
         status = min_func(trimmed, cols, rows, eq_index, trimmed.len(), arr, graph, formula_array);
@@ -174,13 +223,14 @@ pub fn parser(
                 );
                 // SLEEP value is parsed as expected
             } else if let Some(idx) =
-                cell_parser(value_str, cols, rows, 0, value_str.len() - 1, graph)
+                cell_parser(value_str, cols, rows, 0, value_str.len().saturating_sub(1), graph)
             {
                 graph.add_edge(idx, left_cell);
                 formula_array[left_cell] = Formula {
                     op_type: 14, // Assuming 14 represents SLEEP with cell reference
                     op_info1: idx as i32,
                     op_info2: 0,
+                    ..Formula::default()
                 };
                 status = sleep_func(
                     trimmed,
@@ -194,33 +244,29 @@ pub fn parser(
                 );
             } else {
                 // Invalid number or cell reference inside SLEEP()
-                return -1;
+                status = Err(ParseError::Unrecognized);
             }
         } else {
             // Invalid SLEEP syntax, missing closing parenthesis
-            return -1;
+            status = Err(ParseError::Unrecognized);
         }
     } else if right.starts_with('-') && right[1..].chars().all(|c| c.is_ascii_digit()) {
         let const_val = right.parse::<i32>().unwrap_or(i32::MIN);
         // println!("const_val: {}", const_val);
-        formula_array[left_cell] = Formula { op_type: 0, op_info1: const_val, op_info2: 0 };
+        formula_array[left_cell] =
+            Formula { op_type: 0, op_info1: const_val, op_info2: 0, ..Formula::default() };
 
         arr[left_cell] = const_val;
 
         // println!("************8{}", arr[left_cell]);
-        status = 1;
-        // return 1;
+        status = Ok(());
+        // return Ok(());
         // Extract operands and operators like A1 + 5, 3 * B2, etc.
-    } else if is_arithmetic(right) {
+    } else if is_arithmetic(right) && split_operation(right).is_none() {
+        // An operand is missing, as in `A1+` or `+5`
+        status = Err(ParseError::Unrecognized);
+    } else if let Some((left_expr, op_char, right_expr)) = split_operation(right) {
         // println!("Arithmetic expression detected: {}", right);
-        // Parse left and right of operator
-        let ops = ['+', '-', '*', '/'];
-        let (op_index, op_char) =
-            right.char_indices().find(|(_, ch)| ops.contains(ch)).unwrap_or((0, '+'));
-
-        let left_expr = right[..op_index].trim();
-        let right_expr = right[op_index + 1..].trim();
-
         let is_left_cell =
             cell_parser(left_expr, cols, rows, 0, left_expr.len() - 1, graph).is_some();
         let is_right_cell =
@@ -241,6 +287,7 @@ pub fn parser(
                 },
                 op_info1: idx1 as i32,
                 op_info2: idx2 as i32,
+                ..Formula::default()
             };
             (arr[idx1], arr[idx2], formula_array[left_cell].op_type)
         } else if is_left_cell {
@@ -257,14 +304,19 @@ pub fn parser(
                 },
                 op_info1: idx1 as i32,
                 op_info2: val2,
+                ..Formula::default()
             };
             (arr[idx1], val2, formula_array[left_cell].op_type)
         } else if is_right_cell {
             let idx2 = cell_parser(right_expr, cols, rows, 0, right_expr.len() - 1, graph).unwrap();
             let val1 = left_expr.parse::<i32>().unwrap_or(i32::MIN);
             graph.add_edge(idx2, left_cell);
-            formula_array[left_cell] =
-                Formula { op_type: 15, op_info1: val1, op_info2: idx2 as i32 };
+            formula_array[left_cell] = Formula {
+                op_type: 15,
+                op_info1: val1,
+                op_info2: idx2 as i32,
+                ..Formula::default()
+            };
             (val1, arr[idx2], 15)
         } else {
             let val1 = left_expr.parse::<i32>().unwrap_or(i32::MIN);
@@ -275,16 +327,11 @@ pub fn parser(
                     '+' => val1 + val2,
                     '-' => val1 - val2,
                     '*' => val1 * val2,
-                    '/' => {
-                        if val2 != 0 {
-                            val1 / val2
-                        } else {
-                            i32::MIN
-                        }
-                    }
+                    '/' if val2 != 0 => val1 / val2,
                     _ => i32::MIN,
                 },
                 op_info2: 0,
+                ..Formula::default()
             };
             (val1, val2, 0)
         };
@@ -302,90 +349,68 @@ pub fn parser(
                 '+' => left_val + right_val,
                 '-' => left_val - right_val,
                 '*' => left_val * right_val,
-                '/' => {
-                    if right_val != 0 {
-                        left_val / right_val
-                    } else {
-                        i32::MIN
-                    }
-                }
+                '/' if right_val != 0 => left_val / right_val,
                 _ => i32::MIN,
             }
         };
 
-        status = 1;
+        status = Ok(());
     } else {
         // Plain value or reference
         if right.starts_with('-') && right[1..].chars().all(|c| c.is_ascii_digit()) {
             let const_val = right.parse::<i32>().unwrap_or(i32::MIN);
             // println!("const_val: {}", const_val);
-            formula_array[left_cell] = Formula { op_type: 0, op_info1: const_val, op_info2: 0 };
+            formula_array[left_cell] =
+                Formula { op_type: 0, op_info1: const_val, op_info2: 0, ..Formula::default() };
             arr[left_cell] = const_val;
-            return 1;
+            return Ok(());
         }
 
         if let Ok(const_val) = right.parse::<i32>() {
-            formula_array[left_cell] = Formula { op_type: 0, op_info1: const_val, op_info2: 0 };
+            formula_array[left_cell] =
+                Formula { op_type: 0, op_info1: const_val, op_info2: 0, ..Formula::default() };
             arr[left_cell] = const_val;
-            status = 1;
-        } else if let Some(idx) = cell_parser(right, cols, rows, 0, right.len() - 1, graph) {
+            status = Ok(());
+        } else if let Some(idx) =
+            cell_parser(right, cols, rows, 0, right.len().saturating_sub(1), graph)
+        {
             graph.add_edge(idx, left_cell);
-            formula_array[left_cell] = Formula { op_type: 1, op_info1: idx as i32, op_info2: 0 };
+            formula_array[left_cell] =
+                Formula { op_type: 1, op_info1: idx as i32, op_info2: 0, ..Formula::default() };
             arr[left_cell] = arr[idx];
-            status = 1;
+            status = Ok(());
         }
     }
 
-    if status == 1 {
-        // Perform recalculation
-        // println!("{}", arr[left_cell]);
-        unsafe {
-            HAS_CYCLE = false;
+    if status.is_ok() {
+        // Remember the `$` anchors of the references; `=5/A1` keeps its reference second.
+        let anchors = reference_anchors(right);
+        let formula = &mut formula_array[left_cell];
+        let (first, second) = if formula.op_type == 15 { (1, 0) } else { (0, 1) };
+        formula.anchors = Default::default();
+        if let Some(&anchor) = anchors.first() {
+            formula.anchors[first] = anchor;
+        }
+        if let Some(&anchor) = anchors.get(1) {
+            formula.anchors[second] = anchor;
         }
-        // status = 0;
-        #[allow(static_mut_refs)]
-        graph.recalc(cols, arr, left_cell, formula_array, unsafe { &mut HAS_CYCLE });
-        // println!("Graph recalculation complete");
-        // println!("{}", arr[left_cell]);
-        unsafe {
-            if HAS_CYCLE {
-                // Rollback to old value and reinsert old edges
-
-                // println!("Cycle detected, reverting to old value");
-
-                // Step 1: Remove the edges added by the bad formula
-                let new_formula = formula_array[left_cell];
-                if (1..=8).contains(&new_formula.op_type) {
-                    graph.delete_edge(new_formula.op_info1 as usize, left_cell);
-                    graph.delete_edge(new_formula.op_info2 as usize, left_cell);
-                } else if (9..=13).contains(&new_formula.op_type) {
-                    graph.delete_range(left_cell);
-                } else if [1, 15].contains(&new_formula.op_type) {
-                    graph.delete_edge(new_formula.op_info1 as usize, left_cell);
-                }
-
-                // Step 2: Restore the old formula and value
-                arr[left_cell] = old_value;
-                formula_array[left_cell] = old_formula;
-
-                // Step 3: Re-establish old edges
-                if (1..=8).contains(&old_formula.op_type) {
-                    graph.add_edge(old_formula.op_info1 as usize, left_cell);
-                    graph.add_edge(old_formula.op_info2 as usize, left_cell);
-                } else if (9..=13).contains(&old_formula.op_type) {
-                    graph.add_range_to_graph(
-                        old_formula.op_info1 as usize,
-                        old_formula.op_info2 as usize,
-                        left_cell,
-                    );
-                } else if [1, 15].contains(&old_formula.op_type) {
-                    graph.add_edge(old_formula.op_info1 as usize, left_cell);
-                }
 
-                return -1;
-            }
+        // Perform recalculation
+        let mut has_cycle = false;
+        graph.recalc(cols, arr, left_cell, formula_array, &mut has_cycle);
+        if has_cycle {
+            status = Err(ParseError::CircularDependency);
         }
     }
 
+    if status.is_err() {
+        // Rollback: remove the edges added by the bad formula, then restore the old formula,
+        // its value and its edges
+        remove_edges(graph, left_cell, &formula_array[left_cell]);
+        arr[left_cell] = old_value;
+        formula_array[left_cell] = old_formula;
+        add_edges(graph, left_cell, &old_formula, cols);
+    }
+
     status
 }
@@ -0,0 +1,185 @@
+//! # Spatial Index of Range Dependencies
+//!
+//! A formula such as `=SUM(A1:B10)` depends on every cell of its range. Instead of keeping
+//! such ranges in a list that has to be scanned for every visited cell during recalculation,
+//! [`RangeIndex`] sorts them into square tiles of the sheet: a range is registered in every
+//! tile it overlaps, and the ranges covering a cell are found by looking at the cell's tile
+//! only. The cost of a lookup thus depends on how many ranges overlap that part of the
+//! sheet, not on how many ranges there are.
+//!
+//! Both engines use it for their range dependencies.
+
+use std::collections::HashMap;
+
+/// Rows and columns covered by one tile.
+const TILE_SIZE: i32 = 32;
+
+/// A rectangular range in the spreadsheet and the cell it affects.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Range {
+    /// Starting cell index of the range.
+    pub start_cell: i32,
+    /// Ending cell index of the range.
+    pub end_cell: i32,
+    /// Cell that depends on the values in this range.
+    pub dependent_cell: i32,
+    /// Whether the range was resolved from an `OFFSET` or `INDIRECT` at evaluation time
+    /// rather than written in the formula.
+    pub dynamic: bool,
+}
+
+impl Range {
+    /// Returns the top row, left column, bottom row and right column of the range in a sheet
+    /// of `cols` columns. The corners may have been given in any order.
+    fn bounds(&self, cols: i32) -> (i32, i32, i32, i32) {
+        let (start_row, end_row) = (self.start_cell / cols, self.end_cell / cols);
+        let (start_col, end_col) = (self.start_cell % cols, self.end_cell % cols);
+        (
+            start_row.min(end_row),
+            start_col.min(end_col),
+            start_row.max(end_row),
+            start_col.max(end_col),
+        )
+    }
+
+    /// Returns `true` if `cell` lies in the range.
+    pub fn contains(&self, cell: i32, cols: i32) -> bool {
+        let (top, left, bottom, right) = self.bounds(cols);
+        (top..=bottom).contains(&(cell / cols)) && (left..=right).contains(&(cell % cols))
+    }
+
+    /// Returns the tiles the range overlaps.
+    fn tiles(&self, cols: i32) -> impl Iterator<Item = (i32, i32)> {
+        let (top, left, bottom, right) = self.bounds(cols);
+        (top / TILE_SIZE..=bottom / TILE_SIZE).flat_map(move |tile_row| {
+            (left / TILE_SIZE..=right / TILE_SIZE).map(move |tile_col| (tile_row, tile_col))
+        })
+    }
+}
+
+/// The range dependencies of a sheet, indexed by the tiles they overlap and by their
+/// dependent cell.
+#[derive(Clone, Debug, Default)]
+pub struct RangeIndex {
+    /// Number of columns the tiles were computed for; `0` while the index is empty.
+    cols: i32,
+    /// Every range ever inserted, with `None` for removed ones.
+    ranges: Vec<Option<Range>>,
+    /// Slots of removed ranges, reused by [`RangeIndex::insert`].
+    free: Vec<usize>,
+    /// Slots of the ranges overlapping each tile.
+    tiles: HashMap<(i32, i32), Vec<usize>>,
+    /// Slots of the ranges of each dependent cell, in insertion order.
+    by_dependent: HashMap<i32, Vec<usize>>,
+}
+
+impl RangeIndex {
+    /// Creates an empty index.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of ranges.
+    pub fn len(&self) -> usize {
+        self.ranges.len() - self.free.len()
+    }
+
+    /// Returns `true` if there are no ranges.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Iterates over all ranges.
+    pub fn iter(&self) -> impl Iterator<Item = &Range> {
+        self.ranges.iter().flatten()
+    }
+
+    /// Adds `range` for a sheet of `cols` columns. Duplicates are kept, like for a formula
+    /// that names the same range twice.
+    pub fn insert(&mut self, range: Range, cols: i32) {
+        if cols != self.cols {
+            self.retile(cols);
+        }
+        let slot = match self.free.pop() {
+            Some(slot) => slot,
+            None => {
+                self.ranges.push(None);
+                self.ranges.len() - 1
+            }
+        };
+        for tile in range.tiles(cols) {
+            self.tiles.entry(tile).or_default().push(slot);
+        }
+        self.by_dependent.entry(range.dependent_cell).or_default().push(slot);
+        self.ranges[slot] = Some(range);
+    }
+
+    /// Returns the ranges of `dependent_cell`, in the order they were inserted.
+    pub fn of_dependent(&self, dependent_cell: i32) -> impl Iterator<Item = &Range> {
+        let slots = self.by_dependent.get(&dependent_cell).map_or(&[][..], Vec::as_slice);
+        slots.iter().filter_map(|&slot| self.ranges[slot].as_ref())
+    }
+
+    /// Removes the ranges of `dependent_cell` for which `remove` returns `true`.
+    pub fn remove_where(&mut self, dependent_cell: i32, remove: impl Fn(&Range) -> bool) {
+        let Some(slots) = self.by_dependent.get_mut(&dependent_cell) else {
+            return;
+        };
+        let mut removed = Vec::new();
+        slots.retain(|&slot| match &self.ranges[slot] {
+            Some(range) if remove(range) => {
+                removed.push(slot);
+                false
+            }
+            _ => true,
+        });
+        if slots.is_empty() {
+            self.by_dependent.remove(&dependent_cell);
+        }
+        for slot in removed {
+            let range = self.ranges[slot].take().expect("indexed range exists");
+            for tile in range.tiles(self.cols) {
+                if let Some(bucket) = self.tiles.get_mut(&tile) {
+                    bucket.retain(|&other| other != slot);
+                    if bucket.is_empty() {
+                        self.tiles.remove(&tile);
+                    }
+                }
+            }
+            self.free.push(slot);
+        }
+    }
+
+    /// Removes every range of `dependent_cell`.
+    pub fn remove_dependent(&mut self, dependent_cell: i32) {
+        self.remove_where(dependent_cell, |_| true);
+    }
+
+    /// Returns the ranges that contain `cell` in a sheet of `cols` columns.
+    pub fn covering(&self, cell: i32, cols: i32) -> Vec<&Range> {
+        if cols != self.cols {
+            // The tiles were computed for another width; fall back to checking every range.
+            return self.iter().filter(|range| range.contains(cell, cols)).collect();
+        }
+        let tile = (cell / cols / TILE_SIZE, cell % cols / TILE_SIZE);
+        let slots = self.tiles.get(&tile).map_or(&[][..], Vec::as_slice);
+        slots
+            .iter()
+            .filter_map(|&slot| self.ranges[slot].as_ref())
+            .filter(|range| range.contains(cell, cols))
+            .collect()
+    }
+
+    /// Recomputes the tiles of every range for a sheet of `cols` columns.
+    fn retile(&mut self, cols: i32) {
+        self.cols = cols;
+        self.tiles.clear();
+        for (slot, range) in self.ranges.iter().enumerate() {
+            if let Some(range) = range {
+                for tile in range.tiles(cols) {
+                    self.tiles.entry(tile).or_default().push(slot);
+                }
+            }
+        }
+    }
+}
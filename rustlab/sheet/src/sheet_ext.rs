@@ -0,0 +1,406 @@
+//! # Sheet
+//!
+//! [`Sheet`] bundles everything the extended engine needs to evaluate a spreadsheet: the
+//! cell values, the formulas, the dependency graph, the evaluation state, the size of the
+//! sheet and the undo and redo history. Front ends create one sheet and call its methods
+//! instead of passing these pieces to the parser, the structural edits and the clipboard
+//! functions themselves.
+//!
+//! Every change is applied as a whole or not at all: if a step fails (e.g. because the new
+//! formula would create a cycle), the sheet is left as it was, nothing is added to the
+//! history and the step returns a [`SheetError`] describing the problem.
+//!
+//! ```
+//! use sheet::function_ext::Cell;
+//! use sheet::Sheet;
+//!
+//! let mut sheet = Sheet::new(10, 10);
+//! sheet.set("A1", "2").unwrap();
+//! sheet.set("B1", "A1*3").unwrap();
+//! assert_eq!(sheet.get("B1"), Ok(&Cell::new_int(6)));
+//! assert_eq!(sheet.formula("B1"), Ok(Some("=A1*3".to_string())));
+//!
+//! sheet.undo().unwrap();
+//! assert_eq!(sheet.get("B1"), Ok(&Cell::empty()));
+//! ```
+
+use crate::clipboard_ext::{copy, paste};
+use crate::date_ext::Clock;
+use crate::display_ext::is_scroll;
+use crate::error_ext::SheetError;
+use crate::function_ext::Cell;
+use crate::graph_ext::{cycle_through, range_cells, Formula, Graph, State, StateSnapshot};
+use crate::grid_ext::Grid;
+use crate::parser_ext::{anchored_cell_parser, expr_func, parser};
+use crate::structure_ext::{grow_to_fit, resize, StructureEdit};
+use crate::util_ext::cell_label;
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+/// One step of the undo or redo history.
+enum Step {
+    /// The formula and value that each cell replaced by an assignment or paste had before.
+    Cells(Vec<(i32, Formula, Cell)>),
+    /// The whole sheet, for edits that may move any cell, such as a resize.
+    Sheet(Box<StateSnapshot>),
+}
+
+/// A spreadsheet evaluated by the extended engine, with its undo and redo history.
+pub struct Sheet {
+    cols: i32,
+    rows: i32,
+    arr: Grid<Cell>,
+    formula_array: Grid<Formula>,
+    graph: Graph,
+    state: State,
+    undo_stack: VecDeque<Step>,
+    redo_stack: Vec<Step>,
+    undo_limit: usize,
+}
+
+impl Sheet {
+    /// Creates an empty sheet of `rows` rows and `cols` columns with an unlimited history.
+    pub fn new(rows: i32, cols: i32) -> Self {
+        let num_cells = rows as usize * cols as usize;
+        Sheet {
+            cols,
+            rows,
+            arr: Grid::new(num_cells, Cell::empty()),
+            formula_array: Grid::new(num_cells, Formula::default()),
+            graph: Graph::new(num_cells),
+            state: State::new(),
+            undo_stack: VecDeque::new(),
+            redo_stack: Vec::new(),
+            undo_limit: usize::MAX,
+        }
+    }
+
+    /// Keeps only the last `limit` steps in the undo history.
+    pub fn with_undo_limit(mut self, limit: usize) -> Self {
+        self.undo_limit = limit;
+        self
+    }
+
+    /// Returns the number of rows.
+    pub fn rows(&self) -> i32 {
+        self.rows
+    }
+
+    /// Returns the number of columns.
+    pub fn cols(&self) -> i32 {
+        self.cols
+    }
+
+    /// Returns the values of all cells, row by row.
+    pub fn cells(&self) -> &Grid<Cell> {
+        &self.arr
+    }
+
+    /// Returns the formulas of all cells, row by row.
+    pub fn formulas(&self) -> &Grid<Formula> {
+        &self.formula_array
+    }
+
+    /// Returns the dependency graph.
+    pub fn graph(&self) -> &Graph {
+        &self.graph
+    }
+
+    /// Returns the index of the cell labelled `cell` (e.g. `B2` or `$B$2`).
+    pub fn index(&self, cell: &str) -> Result<usize, SheetError> {
+        anchored_cell_parser(cell.trim(), self.cols, self.rows).map(|(idx, _)| idx as usize)
+    }
+
+    /// Returns the value of the cell labelled `cell`.
+    pub fn get(&self, cell: &str) -> Result<&Cell, SheetError> {
+        Ok(&self.arr[self.index(cell)?])
+    }
+
+    /// Returns the formula text of the cell labelled `cell`, e.g. `=SUM(A1:A3)`, or `None`
+    /// if the cell has no formula.
+    pub fn formula(&self, cell: &str) -> Result<Option<String>, SheetError> {
+        Ok(self.formula_array[self.index(cell)?].to_formula_string(self.cols))
+    }
+
+    /// Returns the loop of cells that `cell` (e.g. `A1`) takes part in, each reading the
+    /// next and starting and ending with `cell`, e.g. `["A1", "B3", "C7", "A1"]`, or `None`
+    /// if it is not part of one.
+    ///
+    /// Assignments that would close a loop are rejected, so the loops found are the ones
+    /// behind the `#CYCLE!` values of `OFFSET` and `INDIRECT`; see
+    /// [`cycle_through`](crate::graph_ext::cycle_through).
+    pub fn trace(&self, cell: &str) -> Result<Option<Vec<String>>, SheetError> {
+        let idx = self.index(cell)? as i32;
+        let path = cycle_through(idx, &self.arr, &self.formula_array, self.cols);
+        Ok(path.map(|path| path.into_iter().map(|cell| cell_label(cell, self.cols)).collect()))
+    }
+
+    /// Assigns `formula`, written as after the `=` of an assignment (e.g. `5`, `A1+1` or
+    /// `"text"`), to the cell labelled `cell`, growing the sheet if the cell lies past its
+    /// last row or column. The assignment is one undoable step.
+    ///
+    /// The position of a [`SheetError::Parse`] error is counted from the start of `formula`.
+    pub fn set(&mut self, cell: &str, formula: &str) -> Result<(), SheetError> {
+        let input = format!("{}={}", cell.trim(), formula);
+        let pos_equalto = cell.trim().len();
+        let cells = self.assigned_cells(&input);
+        let result = self.edit(cells, |sheet| {
+            sheet.grow_to_fit(&input)?;
+            let Sheet { cols, rows, arr, formula_array, graph, state, .. } = sheet;
+            expr_func(
+                &input,
+                *cols,
+                *rows,
+                pos_equalto,
+                input.len(),
+                arr,
+                graph,
+                formula_array,
+                state,
+            )
+        });
+        result.map_err(|e| match e {
+            SheetError::Parse { position, expected } => {
+                SheetError::Parse { position: position.saturating_sub(pos_equalto + 1), expected }
+            }
+            e => e,
+        })
+    }
+
+    /// Runs one line of input as typed in the command line, such as `A1=B1+1`, `copy A1:B2`,
+    /// `paste D1`, `insert_row 3`, `resize 20 30`, `undo` or `redo`.
+    ///
+    /// Everything but scrolling, copying, cutting, undo and redo is one undoable step. Assigning a cell
+    /// past the last row or column grows the sheet first.
+    pub fn run(&mut self, input: &str) -> Result<(), SheetError> {
+        let input = input.trim();
+        match input {
+            "undo" => return self.undo(),
+            "redo" => return self.redo(),
+            _ => {}
+        }
+        if let Some(size) = input.strip_prefix("resize ") {
+            let size: Result<Vec<i32>, _> = size.split_whitespace().map(str::parse).collect();
+            return match size.as_deref() {
+                Ok(&[rows, cols]) => self.resize(rows, cols),
+                _ => Err(SheetError::command("Usage: resize <rows> <columns>")),
+            };
+        }
+        // Scrolling only moves the view and copying only fills the clipboard, so neither is an
+        // undoable step; pasting is.
+        if is_scroll(input) {
+            return Ok(());
+        }
+        if let Some(range) = input.strip_prefix("copy ") {
+            return self.copy(range, false);
+        }
+        if let Some(range) = input.strip_prefix("cut ") {
+            return self.copy(range, true);
+        }
+        if let Some(target) = input.strip_prefix("paste ") {
+            return self.paste(target);
+        }
+        let cells = self.assigned_cells(input);
+        self.edit(cells, |sheet| {
+            sheet.grow_to_fit(input)?;
+            let Sheet { cols, rows, arr, formula_array, graph, state, .. } = sheet;
+            parser(input, *cols, *rows, arr, graph, formula_array, state)
+        })
+    }
+
+    /// Copies the range `range` (e.g. `A1:B5`) to the clipboard; if `cut` is set, the next
+    /// paste moves it instead.
+    pub fn copy(&mut self, range: &str, cut: bool) -> Result<(), SheetError> {
+        copy(range, cut, self.cols, self.rows, &mut self.state)
+    }
+
+    /// Pastes the clipboard with its top-left cell at `target` (e.g. `D1`) as one undoable
+    /// step.
+    pub fn paste(&mut self, target: &str) -> Result<(), SheetError> {
+        // A cut also rewrites the formulas reading the moved block, wherever they are.
+        let cells = match self.state.clipboard {
+            Some(clipboard) if !clipboard.cut => {
+                let (c, r) = (self.cols, self.rows);
+                let (height, width) = (
+                    clipboard.end / c - clipboard.start / c,
+                    clipboard.end % c - clipboard.start % c,
+                );
+                match anchored_cell_parser(target.trim(), c, r) {
+                    Ok((start, _)) if start / c + height < r && start % c + width < c => {
+                        Some(range_cells(start, start + height * c + width, c).collect())
+                    }
+                    _ => Some(Vec::new()),
+                }
+            }
+            _ => None,
+        };
+        self.edit(cells, |sheet| {
+            let Sheet { cols, rows, arr, formula_array, graph, state, .. } = sheet;
+            paste(target, *cols, *rows, arr, graph, formula_array, state)
+        })
+    }
+
+    /// Applies a row or column edit such as
+    /// [`insert_row`](crate::structure_ext::insert_row) at the zero-based `index` as one
+    /// undoable step.
+    pub fn edit_line(&mut self, edit: StructureEdit, index: i32) -> Result<(), SheetError> {
+        self.edit(None, |sheet| {
+            let Sheet { cols, rows, arr, formula_array, graph, state, .. } = sheet;
+            edit(index, *cols, *rows, arr, graph, formula_array, state)
+        })
+    }
+
+    /// Resizes the sheet to `rows` rows and `cols` columns as one undoable step; see
+    /// [`resize`](crate::structure_ext::resize).
+    pub fn resize(&mut self, rows: i32, cols: i32) -> Result<(), SheetError> {
+        self.edit(None, |sheet| {
+            let Sheet { cols: old_cols, arr, formula_array, graph, state, .. } = sheet;
+            resize(cols, rows, *old_cols, arr, graph, formula_array, state)?;
+            (sheet.cols, sheet.rows) = (cols, rows);
+            Ok(())
+        })
+    }
+
+    /// Reverts the last step.
+    pub fn undo(&mut self) -> Result<(), SheetError> {
+        let prev =
+            self.undo_stack.pop_back().ok_or_else(|| SheetError::command("Nothing to undo"))?;
+        let next = self.apply(prev)?;
+        self.redo_stack.push(next);
+        Ok(())
+    }
+
+    /// Applies the last reverted step again.
+    pub fn redo(&mut self) -> Result<(), SheetError> {
+        let next = self.redo_stack.pop().ok_or_else(|| SheetError::command("Nothing to redo"))?;
+        let prev = self.apply(next)?;
+        self.undo_stack.push_back(prev);
+        Ok(())
+    }
+
+    /// Replaces the clock read by `TODAY()` and `NOW()`, e.g. with a
+    /// [`FixedClock`](crate::date_ext::FixedClock) in tests. Formulas see the new time the
+    /// next time they are recalculated.
+    pub fn set_clock(&mut self, clock: impl Clock + 'static) {
+        self.state.clock = Some(Arc::new(clock));
+    }
+
+    /// Recalculates every formula of the sheet, e.g. to refresh `TODAY()`.
+    pub fn recalc_all(&mut self) -> Result<(), SheetError> {
+        let formula_cells: Vec<i32> = self
+            .formula_array
+            .occupied()
+            .filter(|(_, formula)| !formula.is_empty())
+            .map(|(idx, _)| idx as i32)
+            .collect();
+        let Sheet { cols, arr, formula_array, graph, state, .. } = self;
+        state.has_cycle = false;
+        graph.recalc_cells(*cols, arr, &formula_cells, formula_array, state)
+    }
+
+    /// Grows the sheet so that the cell assigned by `input` lies on it.
+    fn grow_to_fit(&mut self, input: &str) -> Result<(), SheetError> {
+        let Sheet { cols, rows, arr, formula_array, graph, state, .. } = self;
+        (self.cols, self.rows) =
+            grow_to_fit(input, *cols, *rows, arr, graph, formula_array, state)?;
+        Ok(())
+    }
+
+    /// Returns the cells whose formulas the assignment `input` replaces, or `None` if it may
+    /// change any cell: structural edits, autofill and assignments that grow the sheet.
+    fn assigned_cells(&self, input: &str) -> Option<Vec<i32>> {
+        let commands = ["insert_row ", "delete_row ", "insert_col ", "delete_col ", "=autofill"];
+        if commands.iter().any(|command| input.starts_with(command)) {
+            return None;
+        }
+        let Some((target, _)) = input.split_once('=') else {
+            return Some(Vec::new());
+        };
+        match self.index(target) {
+            Ok(idx) => Some(vec![idx as i32]),
+            Err(_) => None,
+        }
+    }
+
+    /// Applies `change` as one undoable step, restoring the sheet if it fails. If `cells` is
+    /// given, `change` replaces the formulas of no other cells, so only theirs are recorded.
+    fn edit(
+        &mut self,
+        cells: Option<Vec<i32>>,
+        change: impl FnOnce(&mut Self) -> Result<(), SheetError>,
+    ) -> Result<(), SheetError> {
+        let step = match cells {
+            Some(cells) => Step::Cells(
+                cells
+                    .into_iter()
+                    .map(|cell| {
+                        let idx = cell as usize;
+                        (cell, self.formula_array[idx].clone(), self.arr[idx].clone())
+                    })
+                    .collect(),
+            ),
+            None => Step::Sheet(Box::new(self.snapshot())),
+        };
+        if let Err(e) = change(self) {
+            self.apply(step)?;
+            return Err(e);
+        }
+        self.undo_stack.push_back(step);
+        if self.undo_stack.len() > self.undo_limit {
+            self.undo_stack.pop_front();
+        }
+        self.redo_stack.clear();
+        Ok(())
+    }
+
+    /// Puts the sheet back into the state recorded by `step` and returns the step that
+    /// reverts this again.
+    fn apply(&mut self, step: Step) -> Result<Step, SheetError> {
+        match step {
+            Step::Cells(cells) => {
+                let Sheet { cols, arr, formula_array, graph, state, .. } = self;
+                let mut touched = Vec::with_capacity(cells.len());
+                let mut current = Vec::with_capacity(cells.len());
+                for (cell, ..) in &cells {
+                    let idx = *cell as usize;
+                    current.push((*cell, formula_array[idx].clone(), arr[idx].clone()));
+                    graph.delete_edge(*cell, formula_array);
+                    touched.push(*cell);
+                }
+                for (cell, formula, value) in cells {
+                    graph.add_formula(cell, formula, formula_array);
+                    arr[cell as usize] = value;
+                }
+                for &cell in &touched {
+                    graph.add_edge_formula(cell, *cols, formula_array);
+                }
+                state.has_cycle = false;
+                graph.recalc_cells(*cols, arr, &touched, formula_array, state)?;
+                Ok(Step::Cells(current))
+            }
+            Step::Sheet(snapshot) => {
+                let current = self.snapshot();
+                self.restore(*snapshot);
+                Ok(Step::Sheet(Box::new(current)))
+            }
+        }
+    }
+
+    fn snapshot(&self) -> StateSnapshot {
+        StateSnapshot {
+            arr: self.arr.clone(),
+            formula_array: self.formula_array.clone(),
+            graph: self.graph.clone(),
+            cols: self.cols,
+            rows: self.rows,
+        }
+    }
+
+    fn restore(&mut self, snapshot: StateSnapshot) {
+        self.arr = snapshot.arr;
+        self.formula_array = snapshot.formula_array;
+        self.graph = snapshot.graph;
+        (self.cols, self.rows) = (snapshot.cols, snapshot.rows);
+        self.state.num_cells = self.arr.len();
+    }
+}
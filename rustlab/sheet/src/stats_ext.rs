@@ -0,0 +1,222 @@
+//! # Statistical Functions
+//!
+//! Statistics over one range (`VAR.S`, `MEDIAN`, `PERCENTILE`, ...) or over two ranges of
+//! paired values (`CORREL`, `SLOPE`, ...) for the extended engine.
+//!
+//! Single-range functions read their values like the range functions: every argument may
+//! be a range, a cell, a literal or an expression, blanks are skipped and text gives
+//! `#VALUE!`. Paired functions take two ranges with the same number of cells and skip
+//! every pair where either cell is not a number; ranges of different sizes give `#N/A`.
+//!
+//! Derived statistics are returned as `CellValue::Float`; functions that return one of the
+//! input values (`MEDIAN`, `MODE`) keep integers as `Int`. Too few values give `#DIV/0!`
+//! (`#NUM!` for `MEDIAN`, `#N/A` for `MODE`), and an out-of-range parameter gives `#NUM!`.
+
+use crate::aggregate_ext::RangeArg;
+use crate::expr_ext::{Expr, Signature};
+use crate::function_ext::{Cell, CellValue, ErrorKind};
+use crate::graph_ext::{cell_value, collect_numbers, eval_expr};
+use crate::grid_ext::Grid;
+use crate::util_ext::{float_cell, number_cell, number_value, truth_value};
+
+/// The statistical functions handled by [`stat_eval`] and their numbers of arguments.
+pub const STAT_FUNCTIONS: &[Signature] = &[
+    ("STDEV.S", 1, usize::MAX),
+    ("STDEV.P", 1, usize::MAX),
+    ("VAR.S", 1, usize::MAX),
+    ("VAR.P", 1, usize::MAX),
+    ("MEDIAN", 1, usize::MAX),
+    ("MODE", 1, usize::MAX),
+    ("PERCENTILE", 2, 2),
+    ("QUARTILE", 2, 2),
+    ("RANK", 2, 3),
+    ("CORREL", 2, 2),
+    ("COVAR", 2, 2),
+    ("SLOPE", 2, 2),
+    ("INTERCEPT", 2, 2),
+    ("LINEST", 1, 2),
+];
+
+/// Evaluates one of the [`STAT_FUNCTIONS`].
+///
+/// `LINEST` returns the slope of the least-squares line, which is what Excel shows when the
+/// function is entered into a single cell; `INTERCEPT` gives the other coefficient.
+pub fn stat_eval(name: &str, args: &[Expr], arr: &Grid<Cell>, cols: i32) -> Cell {
+    let result = match (name, args) {
+        ("STDEV.S" | "STDEV.P" | "VAR.S" | "VAR.P" | "MEDIAN" | "MODE", [_, ..]) => {
+            collect_numbers(args, arr, cols).and_then(|values| single_range(name, &values))
+        }
+        ("PERCENTILE" | "QUARTILE", [range, k]) => {
+            let values = collect_numbers(std::slice::from_ref(range), arr, cols);
+            values.and_then(|values| {
+                let k = number_arg(k, arr, cols)?;
+                let k = if name == "QUARTILE" { quartile_fraction(k)? } else { k };
+                percentile(values, k)
+            })
+        }
+        ("RANK", [x, range, order @ ..]) => rank(x, range, order.first(), arr, cols),
+        ("CORREL" | "COVAR" | "SLOPE" | "INTERCEPT", [ys, xs]) => {
+            paired_values(ys, xs, arr, cols).and_then(|(ys, xs)| paired(name, &ys, &xs))
+        }
+        ("LINEST", [ys]) => collect_numbers(std::slice::from_ref(ys), arr, cols).and_then(|ys| {
+            let xs: Vec<f64> = (1..=ys.len()).map(|x| x as f64).collect();
+            paired("SLOPE", &ys, &xs)
+        }),
+        ("LINEST", [ys, xs]) => {
+            paired_values(ys, xs, arr, cols).and_then(|(ys, xs)| paired("SLOPE", &ys, &xs))
+        }
+        _ => Err(ErrorKind::Value),
+    };
+    result.unwrap_or_else(Cell::error)
+}
+
+fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+/// Sum of squared deviations from the mean.
+fn sum_squares(values: &[f64]) -> f64 {
+    let mean = mean(values);
+    values.iter().map(|&x| (x - mean) * (x - mean)).sum()
+}
+
+fn single_range(name: &str, values: &[f64]) -> Result<Cell, ErrorKind> {
+    let n = values.len() as f64;
+    let sample = name.ends_with(".S");
+    if values.is_empty() || (sample && values.len() < 2) {
+        return Err(match name {
+            "MEDIAN" => ErrorKind::Num,
+            "MODE" => ErrorKind::NotAvailable,
+            _ => ErrorKind::Div0,
+        });
+    }
+    let variance = || sum_squares(values) / if sample { n - 1.0 } else { n };
+    Ok(match name {
+        "VAR.S" | "VAR.P" => float_cell(variance()),
+        "STDEV.S" | "STDEV.P" => float_cell(variance().sqrt()),
+        "MEDIAN" => {
+            let sorted = sorted(values.to_vec());
+            let mid = sorted.len() / 2;
+            if sorted.len() % 2 == 1 {
+                number_cell(sorted[mid])
+            } else {
+                number_cell((sorted[mid - 1] + sorted[mid]) / 2.0)
+            }
+        }
+        // MODE: the most frequent value, the earliest one on ties; #N/A if nothing repeats.
+        _ => {
+            let mut best: Option<(f64, usize)> = None;
+            for &x in values {
+                let count = values.iter().filter(|&&y| y == x).count();
+                if count > 1 && best.is_none_or(|(_, c)| count > c) {
+                    best = Some((x, count));
+                }
+            }
+            number_cell(best.ok_or(ErrorKind::NotAvailable)?.0)
+        }
+    })
+}
+
+fn sorted(mut values: Vec<f64>) -> Vec<f64> {
+    values.sort_by(f64::total_cmp);
+    values
+}
+
+/// Evaluates an argument that must be a single number.
+fn number_arg(expr: &Expr, arr: &Grid<Cell>, cols: i32) -> Result<f64, ErrorKind> {
+    number_value(&eval_expr(expr, arr, cols))
+}
+
+/// Maps a `QUARTILE` index (0 to 4) to the matching percentile fraction.
+fn quartile_fraction(q: f64) -> Result<f64, ErrorKind> {
+    let q = q.trunc();
+    if (0.0..=4.0).contains(&q) {
+        Ok(q / 4.0)
+    } else {
+        Err(ErrorKind::Num)
+    }
+}
+
+/// `PERCENTILE`: the `k`-th percentile (0 ≤ k ≤ 1), interpolating linearly between values.
+fn percentile(values: Vec<f64>, k: f64) -> Result<Cell, ErrorKind> {
+    if values.is_empty() || !(0.0..=1.0).contains(&k) {
+        return Err(ErrorKind::Num);
+    }
+    let sorted = sorted(values);
+    let pos = k * (sorted.len() - 1) as f64;
+    let (lower, frac) = (pos.floor() as usize, pos.fract());
+    let upper = (lower + 1).min(sorted.len() - 1);
+    Ok(float_cell(sorted[lower] + frac * (sorted[upper] - sorted[lower])))
+}
+
+/// `RANK(x, range[, order])`: position of `x` in `range`, largest first unless `order` is
+/// non-zero. Gives `#N/A` if `x` is not in the range.
+fn rank(
+    x: &Expr,
+    range: &Expr,
+    order: Option<&Expr>,
+    arr: &Grid<Cell>,
+    cols: i32,
+) -> Result<Cell, ErrorKind> {
+    let x = number_arg(x, arr, cols)?;
+    let values = collect_numbers(std::slice::from_ref(range), arr, cols)?;
+    let ascending = match order {
+        Some(order) => truth_value(&eval_expr(order, arr, cols))?,
+        None => false,
+    };
+    if !values.contains(&x) {
+        return Err(ErrorKind::NotAvailable);
+    }
+    let ahead = values.iter().filter(|&&v| if ascending { v < x } else { v > x }).count();
+    Ok(Cell::new_int(ahead as i32 + 1))
+}
+
+/// Reads two equally sized ranges as `(y, x)` pairs, skipping pairs that are not both numbers.
+fn paired_values(
+    ys: &Expr,
+    xs: &Expr,
+    arr: &Grid<Cell>,
+    cols: i32,
+) -> Result<(Vec<f64>, Vec<f64>), ErrorKind> {
+    let (ys, xs) = (RangeArg::from_expr(ys, arr, cols)?, RangeArg::from_expr(xs, arr, cols)?);
+    if ys.cells.len() != xs.cells.len() {
+        return Err(ErrorKind::NotAvailable);
+    }
+    let number = |idx: i32| -> Result<Option<f64>, ErrorKind> {
+        let cell = cell_value(arr, idx);
+        match cell.value {
+            CellValue::Error(kind) => Err(kind),
+            CellValue::Int(i) => Ok(Some(i as f64)),
+            CellValue::Float(f) | CellValue::Date(f) => Ok(Some(f)),
+            _ => Ok(None),
+        }
+    };
+    let (mut y_values, mut x_values) = (Vec::new(), Vec::new());
+    for (&y, &x) in ys.cells.iter().zip(&xs.cells) {
+        if let (Some(y), Some(x)) = (number(y)?, number(x)?) {
+            y_values.push(y);
+            x_values.push(x);
+        }
+    }
+    Ok((y_values, x_values))
+}
+
+/// `CORREL`, `COVAR` (population covariance), `SLOPE` and `INTERCEPT` of the least-squares
+/// line through the `(x, y)` pairs.
+fn paired(name: &str, ys: &[f64], xs: &[f64]) -> Result<Cell, ErrorKind> {
+    if ys.is_empty() || (name != "COVAR" && ys.len() < 2) {
+        return Err(ErrorKind::Div0);
+    }
+    let (mean_y, mean_x) = (mean(ys), mean(xs));
+    let cross: f64 = ys.iter().zip(xs).map(|(y, x)| (y - mean_y) * (x - mean_x)).sum();
+    let (ss_x, ss_y) = (sum_squares(xs), sum_squares(ys));
+    let result = match name {
+        "COVAR" => cross / ys.len() as f64,
+        "CORREL" if ss_x == 0.0 || ss_y == 0.0 => return Err(ErrorKind::Div0),
+        "CORREL" => cross / (ss_x * ss_y).sqrt(),
+        _ if ss_x == 0.0 => return Err(ErrorKind::Div0),
+        "SLOPE" => cross / ss_x,
+        _ => mean_y - cross / ss_x * mean_x,
+    };
+    Ok(float_cell(result))
+}
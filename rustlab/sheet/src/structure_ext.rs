@@ -0,0 +1,407 @@
+//! # Inserting and Deleting Rows and Columns, and Resizing
+//!
+//! `insert_row 3` pushes row 3 and everything below it down by one and leaves a blank row 3;
+//! `delete_row 3` removes row 3 and pulls the rows below it up. `insert_col C` and
+//! `delete_col C` do the same for columns. The sheet keeps its size, so an insert is refused
+//! while the last row (or column) holds a value or formula, and a delete adds a blank one at
+//! the end.
+//!
+//! Every reference is rewritten to keep pointing at the same cell, whether it is anchored
+//! with `$` or not:
+//! - a reference to a deleted cell, or to one pushed off the sheet, becomes `#REF!`;
+//! - a range grows when a line is inserted inside it and shrinks when one of its lines is
+//!   deleted. A range whose lines are all deleted becomes `#REF!`, and one pushed partly off
+//!   the sheet is cut off at the edge.
+//!
+//! The dependency graph is rebuilt from the rewritten formulas and every formula is
+//! recalculated once, since `OFFSET` and `INDIRECT` may now read different cells.
+//!
+//! The sheet itself can be resized with [`resize`] (`resize <rows> <cols>` in the CLI), and
+//! [`grow_to_fit`] extends it when a cell past the last row or column is assigned. Cells keep
+//! their row and column, so every flat index (`row * cols + col`) is remapped.
+
+use crate::error_ext::SheetError;
+use crate::expr_ext::parse_formula;
+use crate::function_ext::Cell;
+use crate::graph_ext::{Formula, Graph, State};
+use crate::grid_ext::Grid;
+use crate::util_ext::{column_label, split_anchors, Anchor};
+
+/// Most rows a sheet can grow to (the classic spreadsheet limit).
+pub const MAX_ROWS: i32 = 999;
+
+/// Most columns a sheet can grow to, up to column `ZZZ`.
+pub const MAX_COLS: i32 = 18278;
+
+/// Whether an edit applies to rows or to columns.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Axis {
+    /// Rows, numbered from `1` in commands.
+    Row,
+    /// Columns, labelled `A`, `B`, ... in commands.
+    Col,
+}
+
+/// An inserted or deleted row or column.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct LineEdit {
+    axis: Axis,
+    /// Zero-based index of the inserted or deleted line.
+    at: i32,
+    insert: bool,
+}
+
+impl LineEdit {
+    /// Splits `cell` into its position along the edited axis and across it.
+    fn split(&self, cell: i32, cols: i32) -> (i32, i32) {
+        match self.axis {
+            Axis::Row => (cell / cols, cell % cols),
+            Axis::Col => (cell % cols, cell / cols),
+        }
+    }
+
+    fn join(&self, along: i32, across: i32, cols: i32) -> i32 {
+        match self.axis {
+            Axis::Row => along * cols + across,
+            Axis::Col => across * cols + along,
+        }
+    }
+
+    /// Number of lines along the edited axis.
+    fn lines(&self, cols: i32, rows: i32) -> i32 {
+        match self.axis {
+            Axis::Row => rows,
+            Axis::Col => cols,
+        }
+    }
+
+    /// Name of `line` as typed in commands, e.g. `3` for a row or `C` for a column.
+    fn label(&self, line: i32) -> String {
+        match self.axis {
+            Axis::Row => (line as i64 + 1).to_string(),
+            Axis::Col => column_label(line),
+        }
+    }
+
+    /// New position of the cell at `line`, or `None` if it is deleted or pushed off the end.
+    fn move_line(&self, line: i32, lines: i32) -> Option<i32> {
+        if line < self.at {
+            Some(line)
+        } else if self.insert {
+            Some(line + 1).filter(|&line| line < lines)
+        } else if line == self.at {
+            None
+        } else {
+            Some(line - 1)
+        }
+    }
+
+    /// New bounds of the lines `first..=last` of a range, or `None` if none of them is left.
+    fn move_span(&self, first: i32, last: i32, lines: i32) -> Option<(i32, i32)> {
+        if self.insert {
+            let first = self.move_line(first, lines)?;
+            Some((first, (if last >= self.at { last + 1 } else { last }).min(lines - 1)))
+        } else if first == last {
+            self.move_line(first, lines).map(|line| (line, line))
+        } else {
+            let first = if first > self.at { first - 1 } else { first };
+            let last = if last >= self.at { last - 1 } else { last };
+            Some((first, last))
+        }
+    }
+
+    /// New corners of the range spanned by `start` and `end` (the same cell for a single
+    /// reference), keeping the corners in the order they were written.
+    fn move_range(&self, start: i32, end: i32, cols: i32, rows: i32) -> Option<(i32, i32)> {
+        let ((a, a_across), (b, b_across)) = (self.split(start, cols), self.split(end, cols));
+        let (first, last) = self.move_span(a.min(b), a.max(b), self.lines(cols, rows))?;
+        let (a, b) = if a <= b { (first, last) } else { (last, first) };
+        Some((self.join(a, a_across, cols), self.join(b, b_across, cols)))
+    }
+}
+
+/// Signature shared by [`insert_row`], [`delete_row`], [`insert_col`] and [`delete_col`].
+pub type StructureEdit = fn(
+    i32,
+    i32,
+    i32,
+    &mut Grid<Cell>,
+    &mut Graph,
+    &mut Grid<Formula>,
+    &mut State,
+) -> Result<(), SheetError>;
+
+/// Parses the row number (`3`) or column label (`C`) of a command into a zero-based index.
+///
+/// Returns a [`SheetError::BadReference`] if it is malformed or outside the sheet.
+pub fn parse_line(text: &str, axis: Axis, c: i32, r: i32) -> Result<i32, SheetError> {
+    let text = text.trim();
+    let bad_reference = || SheetError::bad_reference(text);
+    let (index, lines) = match axis {
+        Axis::Row => (text.parse::<i32>().map_err(|_| bad_reference())? - 1, r),
+        Axis::Col => {
+            if text.is_empty() || !text.chars().all(|ch| ch.is_ascii_uppercase()) {
+                return Err(bad_reference());
+            }
+            let col = text.chars().fold(0i32, |acc, ch| {
+                acc.saturating_mul(26).saturating_add(ch as i32 - 'A' as i32 + 1)
+            });
+            (col - 1, c)
+        }
+    };
+    if index < 0 || index >= lines {
+        return Err(bad_reference());
+    }
+    Ok(index)
+}
+
+/// Inserts a blank row before the zero-based row `row`.
+///
+/// Returns a [`SheetError::Command`] if the last row holds a value or formula.
+pub fn insert_row(
+    row: i32,
+    c: i32,
+    r: i32,
+    arr: &mut Grid<Cell>,
+    graph: &mut Graph,
+    formula_array: &mut Grid<Formula>,
+    state: &mut State,
+) -> Result<(), SheetError> {
+    let edit = LineEdit { axis: Axis::Row, at: row, insert: true };
+    apply(edit, c, r, arr, graph, formula_array, state)
+}
+
+/// Deletes the zero-based row `row`.
+pub fn delete_row(
+    row: i32,
+    c: i32,
+    r: i32,
+    arr: &mut Grid<Cell>,
+    graph: &mut Graph,
+    formula_array: &mut Grid<Formula>,
+    state: &mut State,
+) -> Result<(), SheetError> {
+    let edit = LineEdit { axis: Axis::Row, at: row, insert: false };
+    apply(edit, c, r, arr, graph, formula_array, state)
+}
+
+/// Inserts a blank column before the zero-based column `col`.
+///
+/// Returns a [`SheetError::Command`] if the last column holds a value or formula.
+pub fn insert_col(
+    col: i32,
+    c: i32,
+    r: i32,
+    arr: &mut Grid<Cell>,
+    graph: &mut Graph,
+    formula_array: &mut Grid<Formula>,
+    state: &mut State,
+) -> Result<(), SheetError> {
+    let edit = LineEdit { axis: Axis::Col, at: col, insert: true };
+    apply(edit, c, r, arr, graph, formula_array, state)
+}
+
+/// Deletes the zero-based column `col`.
+pub fn delete_col(
+    col: i32,
+    c: i32,
+    r: i32,
+    arr: &mut Grid<Cell>,
+    graph: &mut Graph,
+    formula_array: &mut Grid<Formula>,
+    state: &mut State,
+) -> Result<(), SheetError> {
+    let edit = LineEdit { axis: Axis::Col, at: col, insert: false };
+    apply(edit, c, r, arr, graph, formula_array, state)
+}
+
+/// Moves every cell to its new position, rewrites all references, rebuilds the graph and
+/// recalculates every formula.
+fn apply(
+    edit: LineEdit,
+    c: i32,
+    r: i32,
+    arr: &mut Grid<Cell>,
+    graph: &mut Graph,
+    formula_array: &mut Grid<Formula>,
+    state: &mut State,
+) -> Result<(), SheetError> {
+    let lines = edit.lines(c, r);
+    if edit.at < 0 || edit.at >= lines {
+        return Err(SheetError::bad_reference(edit.label(edit.at)));
+    }
+    if edit.insert {
+        let across = c * r / lines;
+        let occupied = (0..across)
+            .map(|across| edit.join(lines - 1, across, c) as usize)
+            .any(|cell| arr[cell] != *arr.fill() || !formula_array[cell].is_empty());
+        if occupied {
+            let line = match edit.axis {
+                Axis::Row => "Row",
+                Axis::Col => "Column",
+            };
+            return Err(SheetError::command(format!(
+                "{} {} is not empty; resize the sheet before inserting",
+                line,
+                edit.label(lines - 1)
+            )));
+        }
+    }
+    let (new_arr, new_formulas, new_graph) = rebuild(
+        c,
+        arr.len(),
+        |cell| edit.move_range(cell, cell, c, r).map(|(cell, _)| cell),
+        |start, end, _| edit.move_range(start, end, c, r),
+        arr,
+        formula_array,
+        state,
+    )?;
+    *arr = new_arr;
+    *formula_array = new_formulas;
+    *graph = new_graph;
+    Ok(())
+}
+
+/// Resizes a sheet of `c` columns to `new_c` columns and `new_r` rows.
+///
+/// Every cell keeps its row and column. Cells outside the new size are dropped and references
+/// to them become `#REF!`; a range that only partly leaves the sheet is cut off at the edge.
+///
+/// # Errors
+/// Returns a [`SheetError::Command`] if either new dimension is below `1` or above
+/// [`MAX_ROWS`] or [`MAX_COLS`], or the [`SheetError::Cycle`] found while recalculating.
+pub fn resize(
+    new_c: i32,
+    new_r: i32,
+    c: i32,
+    arr: &mut Grid<Cell>,
+    graph: &mut Graph,
+    formula_array: &mut Grid<Formula>,
+    state: &mut State,
+) -> Result<(), SheetError> {
+    if !(1..=MAX_COLS).contains(&new_c) || !(1..=MAX_ROWS).contains(&new_r) {
+        return Err(SheetError::command("Invalid sheet size"));
+    }
+    let position = |cell: i32| (cell / c, cell % c);
+    let index = |row: i32, col: i32| row * new_c + col;
+    let (new_arr, new_formulas, new_graph) = rebuild(
+        new_c,
+        (new_c * new_r) as usize,
+        |cell| {
+            let (row, col) = position(cell);
+            (row < new_r && col < new_c).then(|| index(row, col))
+        },
+        |start, end, _| {
+            let ((start_row, start_col), (end_row, end_col)) = (position(start), position(end));
+            if start_row.min(end_row) >= new_r || start_col.min(end_col) >= new_c {
+                return None;
+            }
+            let clamp = |row: i32, col: i32| index(row.min(new_r - 1), col.min(new_c - 1));
+            Some((clamp(start_row, start_col), clamp(end_row, end_col)))
+        },
+        arr,
+        formula_array,
+        state,
+    )?;
+    *arr = new_arr;
+    *formula_array = new_formulas;
+    *graph = new_graph;
+    Ok(())
+}
+
+/// Grows the sheet so that the cell assigned by `a` (e.g. `L20=5`) lies on it, the way
+/// writing past the last row or column of a real spreadsheet does.
+///
+/// Returns the new number of columns and rows, which are unchanged if the cell already lies
+/// on the sheet or `a` is not a valid assignment. A target beyond [`MAX_COLS`] or [`MAX_ROWS`] is
+/// left for the parser to reject.
+pub fn grow_to_fit(
+    a: &str,
+    c: i32,
+    r: i32,
+    arr: &mut Grid<Cell>,
+    graph: &mut Graph,
+    formula_array: &mut Grid<Formula>,
+    state: &mut State,
+) -> Result<(i32, i32), SheetError> {
+    let Some((target, formula)) = a.split_once('=') else {
+        return Ok((c, r));
+    };
+    let Some((col, row)) = label_position(target) else {
+        return Ok((c, r));
+    };
+    let (new_c, new_r) = (c.max(col + 1), r.max(row + 1));
+    if (new_c, new_r) == (c, r) || new_c > MAX_COLS || new_r > MAX_ROWS {
+        return Ok((c, r));
+    }
+    // Don't grow the sheet for an assignment that will be rejected anyway.
+    if parse_formula(formula, new_c, new_r).is_err() {
+        return Ok((c, r));
+    }
+    resize(new_c, new_r, c, arr, graph, formula_array, state)?;
+    Ok((new_c, new_r))
+}
+
+/// Zero-based column and row of a cell label such as `L20` or `$L$20`, whatever the size
+/// of the sheet.
+fn label_position(label: &str) -> Option<(i32, i32)> {
+    let (label, _) = split_anchors(label.trim())?;
+    let letters = label.chars().take_while(|ch| ch.is_ascii_uppercase()).count();
+    let (col, row) = label.split_at(letters);
+    if col.is_empty() || col.len() > 3 || row.is_empty() || row.len() > 9 {
+        return None;
+    }
+    let col = col.chars().fold(0, |acc, ch| acc * 26 + (ch as i32 - 'A' as i32 + 1));
+    let row = row.parse::<i32>().ok().filter(|&row| row > 0)?;
+    Some((col - 1, row - 1))
+}
+
+/// Builds the cells and formulas of a sheet of `num_cells` cells with `cols` columns:
+/// the cell at every old index goes to `move_cell(index)` (or is dropped if that is `None`),
+/// and its references are passed through `relocate`. Then the dependency graph is rebuilt and
+/// every formula recalculated once, since `OFFSET` and `INDIRECT` may now read other cells.
+fn rebuild(
+    cols: i32,
+    num_cells: usize,
+    move_cell: impl Fn(i32) -> Option<i32>,
+    relocate: impl Fn(i32, i32, [Anchor; 2]) -> Option<(i32, i32)>,
+    arr: &Grid<Cell>,
+    formula_array: &Grid<Formula>,
+    state: &mut State,
+) -> Result<(Grid<Cell>, Grid<Formula>, Graph), SheetError> {
+    let mut new_arr = Grid::new(num_cells, Cell::empty());
+    let mut new_formulas = Grid::new(num_cells, Formula::Empty);
+    let mut formula_cells = Vec::new();
+    for cell in used_cells(arr, formula_array) {
+        if let Some(target) = move_cell(cell as i32) {
+            new_arr[target as usize] = arr[cell].clone();
+            let formula = formula_array[cell].relocated(&relocate);
+            if !formula.is_empty() {
+                new_formulas[target as usize] = formula;
+                formula_cells.push(target);
+            }
+        }
+    }
+
+    let mut new_graph = Graph::new(num_cells);
+    for &cell in &formula_cells {
+        new_graph.add_edge_formula(cell, cols, &new_formulas);
+    }
+    state.num_cells = num_cells;
+    state.has_cycle = false;
+    new_graph.recalc_cells(cols, &mut new_arr, &formula_cells, &new_formulas, state)?;
+    // A copied range no longer covers the same cells.
+    state.clipboard = None;
+    Ok((new_arr, new_formulas, new_graph))
+}
+
+/// Returns the indices of the cells that hold a value or a formula, in ascending order.
+fn used_cells(arr: &Grid<Cell>, formula_array: &Grid<Formula>) -> Vec<usize> {
+    let values = arr.occupied().filter(|(_, cell)| *cell != arr.fill()).map(|(idx, _)| idx);
+    let formulas =
+        formula_array.occupied().filter(|(_, formula)| !formula.is_empty()).map(|(idx, _)| idx);
+    let mut cells: Vec<usize> = values.chain(formulas).collect();
+    cells.sort_unstable();
+    cells.dedup();
+    cells
+}
@@ -0,0 +1,304 @@
+//! # Text Functions
+//!
+//! String functions for the extended engine: `LEN`, `UPPER`, `LOWER`, `TRIM`, `LEFT`,
+//! `RIGHT`, `MID`, `FIND`, `SUBSTITUTE`, `REPLACE`, `CONCAT`, `TEXTJOIN`, `VALUE`, `TEXT`
+//! and `REPT`. The `&` operator joins two values like a two-argument `CONCAT`.
+//!
+//! Positions and lengths count characters, not bytes, so `LEFT("héllo", 2)` is `"hé"`.
+//! Positions are 1-based as in Excel. Numbers and booleans given where text is expected are
+//! converted with [`text_value`]; text given where a number is expected gives `#VALUE!`, as
+//! does a position before the start of the text or a negative length.
+
+use crate::aggregate_ext::RangeArg;
+use crate::expr_ext::{Expr, Signature};
+use crate::function_ext::{Cell, CellValue, ErrorKind};
+use crate::graph_ext::{cell_value, eval_expr};
+use crate::grid_ext::Grid;
+use crate::lookup_ext::range_reference;
+use crate::util_ext::{number_cell, number_value, text_value, truth_value};
+
+/// The text functions handled by [`text_eval`] and their numbers of arguments.
+pub const TEXT_FUNCTIONS: &[Signature] = &[
+    ("LEN", 1, 1),
+    ("UPPER", 1, 1),
+    ("LOWER", 1, 1),
+    ("TRIM", 1, 1),
+    ("LEFT", 1, 2),
+    ("RIGHT", 1, 2),
+    ("MID", 3, 3),
+    ("FIND", 2, 3),
+    ("SUBSTITUTE", 3, 4),
+    ("REPLACE", 4, 4),
+    ("CONCAT", 1, usize::MAX),
+    ("TEXTJOIN", 3, usize::MAX),
+    ("VALUE", 1, 1),
+    ("TEXT", 2, 2),
+    ("REPT", 2, 2),
+];
+
+/// Longest string `REPT` may build, the length limit of an Excel cell.
+const MAX_TEXT_LEN: usize = 32767;
+
+/// Evaluates one of the [`TEXT_FUNCTIONS`].
+pub fn text_eval(name: &str, args: &[Expr], arr: &Grid<Cell>, cols: i32) -> Cell {
+    let result = match (name, args) {
+        ("CONCAT", _) => joined_values(args, arr, cols).map(|parts| parts.concat()),
+        ("TEXTJOIN", [delimiter, ignore_empty, values @ ..]) => {
+            textjoin(delimiter, ignore_empty, values, arr, cols)
+        }
+        ("VALUE", [text]) => return value(eval_expr(text, arr, cols)),
+        ("LEN", [text]) => {
+            let len = text_arg(text, arr, cols).map(|s| s.chars().count() as i32);
+            return len.map_or_else(Cell::error, Cell::new_int);
+        }
+        ("FIND", [needle, haystack, start @ ..]) => {
+            let pos = find(needle, haystack, start.first(), arr, cols);
+            return pos.map_or_else(Cell::error, Cell::new_int);
+        }
+        _ => string_function(name, args, arr, cols),
+    };
+    result.map_or_else(Cell::error, Cell::new_string)
+}
+
+/// The functions that take plain arguments and return a string.
+fn string_function(
+    name: &str,
+    args: &[Expr],
+    arr: &Grid<Cell>,
+    cols: i32,
+) -> Result<String, ErrorKind> {
+    let text = |i: usize| text_arg(&args[i], arr, cols);
+    let count = |i: usize| count_arg(&args[i], arr, cols);
+    Ok(match (name, args.len()) {
+        ("UPPER", 1) => text(0)?.to_uppercase(),
+        ("LOWER", 1) => text(0)?.to_lowercase(),
+        ("TRIM", 1) => text(0)?.split_whitespace().collect::<Vec<_>>().join(" "),
+        ("LEFT", 1 | 2) => {
+            let n = if args.len() == 2 { count(1)? } else { 1 };
+            text(0)?.chars().take(n).collect()
+        }
+        ("RIGHT", 1 | 2) => {
+            let n = if args.len() == 2 { count(1)? } else { 1 };
+            let chars: Vec<char> = text(0)?.chars().collect();
+            chars[chars.len().saturating_sub(n)..].iter().collect()
+        }
+        ("MID", 3) => {
+            let (s, start, n) = (text(0)?, position(&args[1], arr, cols)?, count(2)?);
+            s.chars().skip(start - 1).take(n).collect()
+        }
+        ("SUBSTITUTE", 3 | 4) => {
+            let (s, old, new) = (text(0)?, text(1)?, text(2)?);
+            let instance =
+                if args.len() == 4 { Some(position(&args[3], arr, cols)?) } else { None };
+            substitute(&s, &old, &new, instance)
+        }
+        ("REPLACE", 4) => {
+            let (s, start, n, new) =
+                (text(0)?, position(&args[1], arr, cols)?, count(2)?, text(3)?);
+            let chars: Vec<char> = s.chars().collect();
+            let start = (start - 1).min(chars.len());
+            let end = start.saturating_add(n).min(chars.len());
+            let (head, tail): (String, String) =
+                (chars[..start].iter().collect(), chars[end..].iter().collect());
+            head + &new + &tail
+        }
+        ("REPT", 2) => {
+            let (s, n) = (text(0)?, count(1)?);
+            if s.chars().count().saturating_mul(n) > MAX_TEXT_LEN {
+                return Err(ErrorKind::Value);
+            }
+            s.repeat(n)
+        }
+        ("TEXT", 2) => {
+            let value = eval_expr(&args[0], arr, cols);
+            match value.value {
+                CellValue::Int(_) | CellValue::Float(_) | CellValue::Date(_) | CellValue::Empty => {
+                    format_number(number_value(&value)?, &text(1)?)
+                }
+                // Text and booleans are returned unchanged.
+                _ => text_value(&value)?,
+            }
+        }
+        _ => return Err(ErrorKind::Value),
+    })
+}
+
+/// Evaluates an argument as text.
+fn text_arg(expr: &Expr, arr: &Grid<Cell>, cols: i32) -> Result<String, ErrorKind> {
+    text_value(&eval_expr(expr, arr, cols))
+}
+
+/// Evaluates a character count, which must not be negative.
+fn count_arg(expr: &Expr, arr: &Grid<Cell>, cols: i32) -> Result<usize, ErrorKind> {
+    let n = number_value(&eval_expr(expr, arr, cols))?.trunc();
+    if n < 0.0 {
+        return Err(ErrorKind::Value);
+    }
+    Ok(n as usize)
+}
+
+/// Evaluates a 1-based character position, which must be at least 1.
+fn position(expr: &Expr, arr: &Grid<Cell>, cols: i32) -> Result<usize, ErrorKind> {
+    match count_arg(expr, arr, cols)? {
+        0 => Err(ErrorKind::Value),
+        n => Ok(n),
+    }
+}
+
+/// Text of every value in `args`, reading ranges cell by cell in row-major order.
+fn joined_values(args: &[Expr], arr: &Grid<Cell>, cols: i32) -> Result<Vec<String>, ErrorKind> {
+    let mut parts = Vec::new();
+    for arg in args {
+        if range_reference(arg, arr, cols).is_some() {
+            for idx in RangeArg::from_expr(arg, arr, cols)?.cells {
+                parts.push(text_value(&cell_value(arr, idx))?);
+            }
+        } else {
+            parts.push(text_arg(arg, arr, cols)?);
+        }
+    }
+    Ok(parts)
+}
+
+/// `TEXTJOIN(delimiter, ignore_empty, values...)`.
+fn textjoin(
+    delimiter: &Expr,
+    ignore_empty: &Expr,
+    values: &[Expr],
+    arr: &Grid<Cell>,
+    cols: i32,
+) -> Result<String, ErrorKind> {
+    let delimiter = text_arg(delimiter, arr, cols)?;
+    let ignore_empty = truth_value(&eval_expr(ignore_empty, arr, cols))?;
+    let mut parts = joined_values(values, arr, cols)?;
+    if ignore_empty {
+        parts.retain(|part| !part.is_empty());
+    }
+    Ok(parts.join(&delimiter))
+}
+
+/// `VALUE(text)`: reads a number, optionally written as a percentage (`"50%"`).
+fn value(cell: Cell) -> Cell {
+    if let Some(kind) = cell.error_kind() {
+        return Cell::error(kind);
+    }
+    match &cell.value {
+        CellValue::Int(_) | CellValue::Float(_) => cell,
+        CellValue::Date(serial) => number_cell(*serial),
+        CellValue::Empty => Cell::new_int(0),
+        CellValue::String(s) => {
+            let s = s.trim();
+            let (digits, scale) = match s.strip_suffix('%') {
+                Some(digits) => (digits.trim_end(), 0.01),
+                None => (s, 1.0),
+            };
+            match digits.parse::<f64>() {
+                Ok(n) if n.is_finite() => number_cell(n * scale),
+                _ => Cell::error(ErrorKind::Value),
+            }
+        }
+        _ => Cell::error(ErrorKind::Value),
+    }
+}
+
+/// `FIND(needle, haystack[, start])`: case-sensitive 1-based position of `needle`,
+/// searching from character `start`. Gives `#VALUE!` if it is not found.
+fn find(
+    needle: &Expr,
+    haystack: &Expr,
+    start: Option<&Expr>,
+    arr: &Grid<Cell>,
+    cols: i32,
+) -> Result<i32, ErrorKind> {
+    let (needle, haystack) = (text_arg(needle, arr, cols)?, text_arg(haystack, arr, cols)?);
+    let start = match start {
+        Some(start) => position(start, arr, cols)?,
+        None => 1,
+    };
+    let len = haystack.chars().count();
+    if start > len + 1 {
+        return Err(ErrorKind::Value);
+    }
+    let offset = haystack.char_indices().nth(start - 1).map_or(haystack.len(), |(i, _)| i);
+    let found = haystack[offset..].find(&needle).ok_or(ErrorKind::Value)?;
+    Ok((start + haystack[offset..offset + found].chars().count()) as i32)
+}
+
+/// Replaces every occurrence of `old` in `text`, or only the `instance`-th one.
+fn substitute(text: &str, old: &str, new: &str, instance: Option<usize>) -> String {
+    if old.is_empty() {
+        return text.to_string();
+    }
+    match instance {
+        None => text.replace(old, new),
+        Some(n) => match text.match_indices(old).nth(n - 1) {
+            Some((i, _)) => format!("{}{}{}", &text[..i], new, &text[i + old.len()..]),
+            None => text.to_string(),
+        },
+    }
+}
+
+/// Formats a number with an Excel-style format such as `"0.00"`, `"#,##0"`, `"0%"` or
+/// `"$#,##0.00"`.
+///
+/// Supported codes: `0` (digit, padded with zeros), `#` (optional digit), `.` (decimal
+/// point), `,` (thousands separator) and `%` (multiply by 100). Text before and after the
+/// digit codes is copied as-is. `"General"` formats the number as it is displayed in cells.
+fn format_number(value: f64, format: &str) -> String {
+    if format.eq_ignore_ascii_case("general") {
+        return text_value(&number_cell(value)).unwrap_or_default();
+    }
+    let is_code = |c: char| matches!(c, '0' | '#' | '.' | ',');
+    let Some(first) = format.find(['0', '#']) else {
+        return format.to_string();
+    };
+    let last = format[first..].find(|c| !is_code(c)).map_or(format.len(), |i| first + i);
+    let (prefix, code, suffix) = (&format[..first], &format[first..last], &format[last..]);
+    let value = if format.contains('%') { value * 100.0 } else { value };
+
+    let (int_code, dec_code) = code.split_once('.').unwrap_or((code, ""));
+    let min_decimals = dec_code.chars().filter(|&c| c == '0').count();
+    let max_decimals = dec_code.chars().filter(|&c| c == '0' || c == '#').count();
+    let min_digits = int_code.chars().filter(|&c| c == '0').count();
+
+    let rounded = format!("{:.*}", max_decimals, value.abs());
+    let (int_part, dec_part) = rounded.split_once('.').unwrap_or((&rounded, ""));
+    let mut decimals = dec_part.to_string();
+    while decimals.len() > min_decimals && decimals.ends_with('0') {
+        decimals.pop();
+    }
+    let mut digits =
+        if int_part == "0" && min_digits == 0 { String::new() } else { int_part.to_string() };
+    while digits.len() < min_digits {
+        digits.insert(0, '0');
+    }
+    if int_code.contains(',') {
+        digits = group_thousands(&digits);
+    }
+
+    let negative = value < 0.0 && rounded.chars().any(|c| c.is_ascii_digit() && c != '0');
+    let mut out = String::new();
+    if negative {
+        out.push('-');
+    }
+    out.push_str(prefix);
+    out.push_str(&digits);
+    if !decimals.is_empty() {
+        out.push('.');
+        out.push_str(&decimals);
+    }
+    out.push_str(suffix);
+    out
+}
+
+/// Inserts a `,` between every group of three digits, e.g. `1234567` to `1,234,567`.
+fn group_thousands(digits: &str) -> String {
+    let mut out = String::new();
+    for (i, ch) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(ch);
+    }
+    out
+}
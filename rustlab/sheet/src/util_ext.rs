@@ -1,108 +1,278 @@
+use crate::date_ext::format_date;
 use crate::function_ext::Cell;
-use crate::function_ext::CellValue;
+use crate::function_ext::{CellValue, ErrorKind};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
 /// Evaluates an arithmetic operation between two `Cell` values.
 ///
 /// Supports operations on combinations of integers, floats, and strings:
-/// - Integer + Integer = Integer
+/// - Integer + Integer = Integer (Float if the result overflows `i32`)
 /// - Integer / Integer = Integer if divisible, otherwise Float
+/// - Integer ^ Integer = Integer for a non-negative exponent, otherwise Float
 /// - Float + Float = Float
 /// - Mixed Int/Float = Float
+/// - Date ± number = Date, number + Date = Date and Date - Date = number of days; any other
+///   operation on a date uses its serial number and gives a plain number
 /// - String + String = String concatenation
+/// - Booleans count as `1` (`TRUE`) and `0` (`FALSE`), blank cells as `0`
 ///
-/// Returns an invalid `Cell` if:
-/// - Any operand is invalid
-/// - Division by zero occurs
-/// - Invalid operation is attempted (e.g., subtracting strings)
+/// Returns an error `Cell` if:
+/// - Any operand is an error (the first operand's error wins)
+/// - Division by zero occurs, including `0` raised to a negative power (`#DIV/0!`)
+/// - Invalid operation is attempted, e.g. subtracting strings (`#VALUE!`)
+/// - The result is not a finite number, e.g. `(-8)^0.5` or `0^0` (`#NUM!`)
 ///
 /// # Parameters
 /// - `v1`: First operand as a `Cell`
 /// - `v2`: Second operand as a `Cell`
-/// - `op`: Operator character (`+`, `-`, `*`, `/`)
+/// - `op`: Operator character (`+`, `-`, `*`, `/`, `^`)
 ///
 /// # Returns
-/// A new `Cell` with the result of the operation or marked invalid.
+/// A new `Cell` with the result of the operation or an error.
 pub fn arithmetic_eval(v1: Cell, v2: Cell, op: char) -> Cell {
-    if !v1.is_valid || !v2.is_valid {
-        return Cell::invalid();
+    if let Some(kind) = v1.error_kind().or(v2.error_kind()) {
+        return Cell::error(kind);
     }
+    if let Some(cell) = date_arithmetic(&v1, &v2, op) {
+        return cell;
+    }
+    let (v1, v2) = (numeric_operand(v1), numeric_operand(v2));
 
     match (&v1.value, &v2.value) {
-        (CellValue::Int(i1), CellValue::Int(i2)) => match op {
-            '+' => Cell::new_int(i1 + i2),
-            '-' => Cell::new_int(i1 - i2),
-            '*' => Cell::new_int(i1 * i2),
-            '/' => {
-                if *i2 == 0 {
-                    Cell::invalid()
-                } else if *i1 % *i2 == 0 {
-                    Cell::new_int(i1 / i2)
-                } else {
-                    Cell::new_float((*i1 as f64) / (*i2 as f64))
-                }
-            }
-            _ => Cell::invalid(),
-        },
-        (CellValue::Float(f1), CellValue::Float(f2)) => match op {
-            '+' => Cell::new_float(f1 + f2),
-            '-' => Cell::new_float(f1 - f2),
-            '*' => Cell::new_float(f1 * f2),
-            '/' => {
-                if *f2 == 0.0 {
-                    Cell::invalid()
-                } else {
-                    Cell::new_float(f1 / f2)
-                }
-            }
-            _ => Cell::invalid(),
-        },
-        (CellValue::Int(i1), CellValue::Float(f2)) => {
-            let f1 = *i1 as f64;
-            match op {
-                '+' => Cell::new_float(f1 + f2),
-                '-' => Cell::new_float(f1 - f2),
-                '*' => Cell::new_float(f1 * f2),
-                '/' => {
-                    if *f2 == 0.0 {
-                        Cell::invalid()
-                    } else {
-                        Cell::new_float(f1 / f2)
-                    }
-                }
-                _ => Cell::invalid(),
-            }
-        }
-        (CellValue::Float(f1), CellValue::Int(i2)) => {
-            let f2 = *i2 as f64;
-            match op {
-                '+' => Cell::new_float(f1 + f2),
-                '-' => Cell::new_float(f1 - f2),
-                '*' => Cell::new_float(f1 * f2),
-                '/' => {
-                    if f2 == 0.0 {
-                        Cell::invalid()
-                    } else {
-                        Cell::new_float(f1 / f2)
-                    }
-                }
-                _ => Cell::invalid(),
+        (CellValue::Int(i1), CellValue::Int(i2)) => {
+            let checked = match op {
+                '+' => i1.checked_add(*i2),
+                '-' => i1.checked_sub(*i2),
+                '*' => i1.checked_mul(*i2),
+                '/' if *i2 == 0 => return Cell::error(ErrorKind::Div0),
+                '/' if i1.checked_rem(*i2) == Some(0) => i1.checked_div(*i2),
+                '/' => None,
+                '^' if *i1 == 0 && *i2 <= 0 => None,
+                '^' => u32::try_from(*i2).ok().and_then(|exp| i1.checked_pow(exp)),
+                _ => return Cell::error(ErrorKind::Value),
+            };
+            match checked {
+                Some(v) => Cell::new_int(v),
+                None => float_eval(*i1 as f64, *i2 as f64, op),
             }
         }
+        (CellValue::Float(f1), CellValue::Float(f2)) => float_eval(*f1, *f2, op),
+        (CellValue::Int(i1), CellValue::Float(f2)) => float_eval(*i1 as f64, *f2, op),
+        (CellValue::Float(f1), CellValue::Int(i2)) => float_eval(*f1, *i2 as f64, op),
         (CellValue::String(s1), CellValue::String(s2)) => {
             // println!("String operation: {} {} {}", s1, op, s2);
             match op {
                 '+' => Cell::new_string(format!("{}{}", s1, s2)),
-                '-' => Cell::invalid(),
-                '*' => Cell::invalid(),
-                '/' => Cell::invalid(),
-                _ => Cell::invalid(),
+                _ => Cell::error(ErrorKind::Value),
             }
         }
         _ => {
             println!("Invalid operation: {:?} {} {:?}", v1.value, op, v2.value);
-            Cell::invalid()
+            Cell::error(ErrorKind::Value)
+        }
+    }
+}
+
+/// Arithmetic with at least one date operand, or `None` if neither operand is a date.
+fn date_arithmetic(v1: &Cell, v2: &Cell, op: char) -> Option<Cell> {
+    let is_date = |cell: &Cell| matches!(cell.value, CellValue::Date(_));
+    let (d1, d2) = (is_date(v1), is_date(v2));
+    if !d1 && !d2 {
+        return None;
+    }
+    let (f1, f2) = match (number_value(v1), number_value(v2)) {
+        (Ok(f1), Ok(f2)) => (f1, f2),
+        (Err(kind), _) | (_, Err(kind)) => return Some(Cell::error(kind)),
+    };
+    let result = float_eval(f1, f2, op);
+    Some(match (&result.value, op) {
+        (CellValue::Float(serial), '+') if !(d1 && d2) => Cell::new_date(*serial),
+        (CellValue::Float(serial), '-') if !d2 => Cell::new_date(*serial),
+        (CellValue::Float(value), _) => number_cell(*value),
+        (_, _) => result,
+    })
+}
+
+/// Replaces a boolean by `1` or `0` and a blank by `0` so they can take part in arithmetic.
+fn numeric_operand(cell: Cell) -> Cell {
+    match cell.value {
+        CellValue::Bool(b) => Cell::new_int(b as i32),
+        CellValue::Empty => Cell::new_int(0),
+        _ => cell,
+    }
+}
+
+/// Evaluates a comparison between two `Cell` values, giving a boolean `Cell`.
+///
+/// Numbers compare numerically (`Int` and `Float` mix freely), strings compare
+/// case-insensitively and `FALSE < TRUE`. Values of different types are ordered
+/// numbers < strings < booleans, so e.g. `1 < "a"` is `TRUE` and `1 = "1"` is `FALSE`.
+/// A blank cell equals `0`, `""` and `FALSE`.
+///
+/// # Parameters
+/// - `v1`: Left operand as a `Cell`
+/// - `v2`: Right operand as a `Cell`
+/// - `op`: Comparison operator (`=`, `<>`, `<`, `<=`, `>`, `>=`)
+///
+/// # Returns
+/// A boolean `Cell`, the first operand error if either side is an error, or
+/// `#VALUE!` for an unknown operator.
+pub fn compare_eval(v1: Cell, v2: Cell, op: &str) -> Cell {
+    if let Some(kind) = v1.error_kind().or(v2.error_kind()) {
+        return Cell::error(kind);
+    }
+    let ordering = compare_values(&v1.value, &v2.value);
+    let result = match op {
+        "=" => ordering == Ordering::Equal,
+        "<>" => ordering != Ordering::Equal,
+        "<" => ordering == Ordering::Less,
+        "<=" => ordering != Ordering::Greater,
+        ">" => ordering == Ordering::Greater,
+        ">=" => ordering != Ordering::Less,
+        _ => return Cell::error(ErrorKind::Value),
+    };
+    Cell::new_bool(result)
+}
+
+/// Total order used by [`compare_eval`] for two non-error values.
+pub(crate) fn compare_values(v1: &CellValue, v2: &CellValue) -> Ordering {
+    fn rank(value: &CellValue) -> u8 {
+        match value {
+            CellValue::Int(_) | CellValue::Float(_) | CellValue::Date(_) | CellValue::Empty => 0,
+            CellValue::String(_) => 1,
+            CellValue::Bool(_) => 2,
+            CellValue::Error(_) => 3,
+        }
+    }
+    // A blank takes the "zero" of whatever it is compared with.
+    fn blank_like(other: &CellValue) -> CellValue {
+        match other {
+            CellValue::String(_) => CellValue::String(String::new()),
+            CellValue::Bool(_) => CellValue::Bool(false),
+            _ => CellValue::Int(0),
         }
     }
+    match (v1, v2) {
+        (CellValue::Empty, CellValue::Empty) => Ordering::Equal,
+        (CellValue::Empty, other) => compare_values(&blank_like(other), other),
+        (other, CellValue::Empty) => compare_values(other, &blank_like(other)),
+        (CellValue::Int(i1), CellValue::Int(i2)) => i1.cmp(i2),
+        (
+            CellValue::Int(_) | CellValue::Float(_) | CellValue::Date(_),
+            CellValue::Int(_) | CellValue::Float(_) | CellValue::Date(_),
+        ) => {
+            let as_f64 = |v: &CellValue| match v {
+                CellValue::Int(i) => *i as f64,
+                CellValue::Float(f) | CellValue::Date(f) => *f,
+                _ => 0.0,
+            };
+            as_f64(v1).partial_cmp(&as_f64(v2)).unwrap_or(Ordering::Equal)
+        }
+        (CellValue::String(s1), CellValue::String(s2)) => s1.to_lowercase().cmp(&s2.to_lowercase()),
+        (CellValue::Bool(b1), CellValue::Bool(b2)) => b1.cmp(b2),
+        _ => rank(v1).cmp(&rank(v2)),
+    }
 }
+
+/// Interprets a `Cell` as a condition: booleans as-is, numbers are `TRUE` unless zero,
+/// and a blank cell is `FALSE`.
+///
+/// # Errors
+/// Returns the cell's error if it holds one, and `#VALUE!` for strings.
+pub fn truth_value(cell: &Cell) -> Result<bool, ErrorKind> {
+    if let Some(kind) = cell.error_kind() {
+        return Err(kind);
+    }
+    match &cell.value {
+        CellValue::Bool(b) => Ok(*b),
+        CellValue::Int(i) => Ok(*i != 0),
+        CellValue::Float(f) | CellValue::Date(f) => Ok(*f != 0.0),
+        CellValue::Empty => Ok(false),
+        CellValue::String(_) | CellValue::Error(_) => Err(ErrorKind::Value),
+    }
+}
+
+/// Interprets a `Cell` as a number: dates as their serial number, booleans as `1`/`0` and a
+/// blank cell as `0`.
+///
+/// # Errors
+/// Returns the cell's error if it holds one, and `#VALUE!` for strings.
+pub fn number_value(cell: &Cell) -> Result<f64, ErrorKind> {
+    if let Some(kind) = cell.error_kind() {
+        return Err(kind);
+    }
+    match &cell.value {
+        CellValue::Int(i) => Ok(*i as f64),
+        CellValue::Float(f) | CellValue::Date(f) => Ok(*f),
+        CellValue::Bool(b) => Ok(*b as i32 as f64),
+        CellValue::Empty => Ok(0.0),
+        CellValue::String(_) | CellValue::Error(_) => Err(ErrorKind::Value),
+    }
+}
+
+/// Converts a `Cell` to the text it stands for in string functions and `&`: numbers as
+/// written (`2.5`, `3`), dates in ISO form (`2025-04-01`), booleans as `TRUE`/`FALSE` and a
+/// blank cell as `""`.
+///
+/// # Errors
+/// Returns the cell's error if it holds one.
+pub fn text_value(cell: &Cell) -> Result<String, ErrorKind> {
+    if let Some(kind) = cell.error_kind() {
+        return Err(kind);
+    }
+    Ok(match &cell.value {
+        CellValue::String(s) => s.clone(),
+        CellValue::Int(i) => i.to_string(),
+        CellValue::Float(f) => f.to_string(),
+        CellValue::Date(serial) => format_date(*serial),
+        CellValue::Bool(b) => if *b { "TRUE" } else { "FALSE" }.to_string(),
+        CellValue::Empty | CellValue::Error(_) => String::new(),
+    })
+}
+
+/// Joins two values as text, the `&` operator. Errors propagate, the first operand's first.
+pub fn concat_eval(v1: Cell, v2: Cell) -> Cell {
+    match (text_value(&v1), text_value(&v2)) {
+        (Ok(s1), Ok(s2)) => Cell::new_string(s1 + &s2),
+        (Err(kind), _) | (_, Err(kind)) => Cell::error(kind),
+    }
+}
+
+/// Applies `op` to two floats, mapping division by zero to `#DIV/0!`
+/// and non-finite results to `#NUM!`.
+fn float_eval(f1: f64, f2: f64, op: char) -> Cell {
+    let result = match op {
+        '+' => f1 + f2,
+        '-' => f1 - f2,
+        '*' => f1 * f2,
+        '/' if f2 == 0.0 => return Cell::error(ErrorKind::Div0),
+        '/' => f1 / f2,
+        '^' if f1 == 0.0 && f2 < 0.0 => return Cell::error(ErrorKind::Div0),
+        '^' if f1 == 0.0 && f2 == 0.0 => return Cell::error(ErrorKind::Num),
+        '^' => f1.powf(f2),
+        _ => return Cell::error(ErrorKind::Value),
+    };
+    float_cell(result)
+}
+
+/// Wraps a float result in a `Cell`, turning infinities and NaN into `#NUM!`.
+pub fn float_cell(value: f64) -> Cell {
+    if value.is_finite() {
+        Cell::new_float(value)
+    } else {
+        Cell::error(ErrorKind::Num)
+    }
+}
+/// Wraps a numeric result in a `Cell`: an `Int` if it is integral and fits in `i32`,
+/// a `Float` otherwise, and `#NUM!` if it is not finite.
+pub fn number_cell(value: f64) -> Cell {
+    if value.fract() == 0.0 && value >= i32::MIN as f64 && value <= i32::MAX as f64 {
+        Cell::new_int(value as i32)
+    } else {
+        float_cell(value)
+    }
+}
+
 /// Returns the internal operation code used to represent an arithmetic operator in formulas.
 ///
 /// # Mapping
@@ -126,3 +296,75 @@ pub fn return_optype(op: char) -> i32 {
         _ => -1,
     }
 }
+
+/// Converts a zero-based column index to its spreadsheet label (0 → `A`, 25 → `Z`, 26 → `AA`).
+pub fn column_label(col: i32) -> String {
+    let mut label = String::new();
+    let mut index = col + 1;
+    while index > 0 {
+        index -= 1;
+        label.insert(0, (b'A' + (index % 26) as u8) as char);
+        index /= 26;
+    }
+    label
+}
+
+/// Converts a flat cell index (`row * cols + col`) to its label, e.g. `12` → `C2` with 10 columns.
+pub fn cell_label(cell: i32, cols: i32) -> String {
+    format!("{}{}", column_label(cell % cols), cell / cols + 1)
+}
+
+/// The `$` anchors of a cell reference. An anchored column or row (`$A1`, `A$1`, `$A$1`)
+/// stays fixed when a formula is copied or filled, while a relative one shifts with it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Anchor {
+    /// The column is written with a `$`.
+    pub col: bool,
+    /// The row is written with a `$`.
+    pub row: bool,
+}
+
+impl Anchor {
+    /// A plain reference such as `A1`.
+    pub const RELATIVE: Anchor = Anchor { col: false, row: false };
+    /// A fully anchored reference such as `$A$1`.
+    pub const ABSOLUTE: Anchor = Anchor { col: true, row: true };
+
+    /// Returns `true` if neither the column nor the row is anchored.
+    pub fn is_relative(self) -> bool {
+        self == Anchor::RELATIVE
+    }
+}
+
+/// Converts a flat cell index to its label with the given anchors, e.g. `12` → `$C2`.
+pub fn anchored_label(cell: i32, cols: i32, anchor: Anchor) -> String {
+    let dollar = |anchored: bool| if anchored { "$" } else { "" };
+    format!(
+        "{}{}{}{}",
+        dollar(anchor.col),
+        column_label(cell % cols),
+        dollar(anchor.row),
+        cell / cols + 1
+    )
+}
+
+/// Splits the anchors off a reference such as `$A$1`, giving the plain label (`A1`) and its
+/// [`Anchor`]. A `$` may only stand before the column letters and before the row digits.
+///
+/// Returns `None` if a `$` is anywhere else. The label itself is not validated.
+pub fn split_anchors(reference: &str) -> Option<(String, Anchor)> {
+    let (col_anchored, rest) = match reference.strip_prefix('$') {
+        Some(rest) => (true, rest),
+        None => (false, reference),
+    };
+    let digits = rest.find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(rest.len());
+    let (letters, rest) = rest.split_at(digits);
+    let (row_anchored, digits) = match rest.strip_prefix('$') {
+        Some(digits) => (true, digits),
+        None => (false, rest),
+    };
+    if digits.contains('$') || (row_anchored && letters.is_empty()) {
+        return None;
+    }
+    Some((format!("{}{}", letters, digits), Anchor { col: col_anchored, row: row_anchored }))
+}
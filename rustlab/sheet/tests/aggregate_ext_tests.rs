@@ -0,0 +1,130 @@
+mod common;
+
+use common::{setup_sheet, text};
+use sheet::aggregate_ext::Criteria;
+use sheet::function_ext::{Cell, CellValue, ErrorKind};
+use sheet::parser_ext::parser;
+
+#[test]
+fn test_criteria_parse() {
+    assert_eq!(
+        Criteria::parse(&text(">10").value),
+        Criteria { op: ">", value: CellValue::Int(10) }
+    );
+    assert_eq!(
+        Criteria::parse(&text("<>2.5").value),
+        Criteria { op: "<>", value: CellValue::Float(2.5) }
+    );
+    assert_eq!(
+        Criteria::parse(&text("apples").value),
+        Criteria { op: "=", value: text("apples").value }
+    );
+    assert_eq!(
+        Criteria::parse(&text("=true").value),
+        Criteria { op: "=", value: CellValue::Bool(true) }
+    );
+    assert_eq!(Criteria::parse(&CellValue::Int(3)), Criteria { op: "=", value: CellValue::Int(3) });
+}
+
+#[test]
+fn test_criteria_matches() {
+    let over_ten = Criteria::parse(&text(">10").value);
+    assert!(over_ten.matches(&CellValue::Int(11)));
+    assert!(over_ten.matches(&CellValue::Float(10.5)));
+    assert!(!over_ten.matches(&CellValue::Int(10)));
+    assert!(!over_ten.matches(&text("20").value));
+    assert!(!over_ten.matches(&CellValue::Error(ErrorKind::Div0)));
+
+    let apples = Criteria::parse(&text("Ap*s").value);
+    assert!(apples.matches(&text("apples").value));
+    assert!(apples.matches(&text("APPS").value));
+    assert!(!apples.matches(&text("apple").value));
+    assert!(Criteria::parse(&text("b?t").value).matches(&text("bat").value));
+    assert!(Criteria::parse(&text("<>apples").value).matches(&CellValue::Int(1)));
+
+    assert!(Criteria::parse(&text("").value).matches(&CellValue::Empty));
+    assert!(!Criteria::parse(&text("<>").value).matches(&CellValue::Empty));
+    assert!(Criteria::parse(&text("<>").value).matches(&text("x").value));
+}
+
+#[test]
+fn test_count_functions_skip_other_types() {
+    let (arr, ..) = setup_sheet(&[
+        "A1=1",
+        "A2=2.5",
+        "A3=\"text\"",
+        "A4=TRUE",
+        "A5=1/0",
+        "A6=\"\"",
+        "B1=COUNT(A1:A7)",
+        "B2=COUNTA(A1:A7)",
+        "B3=COUNTBLANK(A1:A7)",
+        "B4=COUNT(A1:A2,7,TRUE,\"x\")",
+    ]);
+    assert_eq!(arr[1], Cell::new_int(2));
+    assert_eq!(arr[11], Cell::new_int(6));
+    assert_eq!(arr[21], Cell::new_int(2));
+    assert_eq!(arr[31], Cell::new_int(4));
+}
+
+#[test]
+fn test_countif_sumif_averageif() {
+    let (arr, ..) = setup_sheet(&[
+        "A1=\"apples\"",
+        "A2=\"pears\"",
+        "A3=\"apples\"",
+        "B1=5",
+        "B2=20",
+        "B3=15",
+        "C1=COUNTIF(B1:B3,\">10\")",
+        "C2=SUMIF(A1:A3,\"apples\",B1:B3)",
+        "C3=AVERAGEIF(B1:B3,\">=15\")",
+        "C4=SUMIF(B1:B3,\"<>20\")",
+        "C5=AVERAGEIF(A1:A3,\"plums\",B1:B3)",
+        "C6=SUMIF(A1:A3,\"apples\",B1:B2)",
+    ]);
+    assert_eq!(arr[2], Cell::new_int(2));
+    assert_eq!(arr[12], Cell::new_int(20));
+    assert_eq!(arr[22], Cell::new_float(17.5));
+    assert_eq!(arr[32], Cell::new_int(20));
+    assert_eq!(arr[42], Cell::error(ErrorKind::Div0));
+    assert_eq!(arr[52], Cell::error(ErrorKind::Value));
+}
+
+#[test]
+fn test_multi_criteria_aggregates() {
+    let (arr, ..) = setup_sheet(&[
+        "A1=\"east\"",
+        "A2=\"west\"",
+        "A3=\"east\"",
+        "B1=10",
+        "B2=30",
+        "B3=50",
+        "C1=COUNTIFS(A1:A3,\"east\",B1:B3,\">20\")",
+        "C2=SUMIFS(B1:B3,A1:A3,\"east\",B1:B3,\"<60\")",
+    ]);
+    assert_eq!(arr[2], Cell::new_int(1));
+    assert_eq!(arr[12], Cell::new_int(60));
+}
+
+#[test]
+fn test_conditional_aggregates_track_both_ranges() {
+    let (mut arr, mut graph, mut formula_array, mut state) = setup_sheet(&[
+        "A1=\"x\"",
+        "A2=\"y\"",
+        "B1=1",
+        "B2=2",
+        "D1=5",
+        "C1=SUMIF(A1:A2,\"x\",B1:B2)",
+        "C2=COUNTIF(A1:A2,D2)",
+    ]);
+    assert_eq!(arr[2], Cell::new_int(1));
+    assert_eq!(arr[12], Cell::new_int(0));
+
+    // Edit the criteria range, the value range and the criteria cell.
+    for input in ["A2=\"x\"", "B2=10", "D2=\"x\""] {
+        parser(input, 10, 10, &mut arr, &mut graph, &mut formula_array, &mut state).unwrap();
+    }
+    assert_eq!(arr[2], Cell::new_int(11));
+    assert_eq!(arr[12], Cell::new_int(2));
+}
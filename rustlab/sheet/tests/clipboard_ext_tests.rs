@@ -0,0 +1,109 @@
+mod common;
+
+use common::TestSheet;
+use sheet::clipboard_ext::{parse_range, shift_reference, Clipboard};
+use sheet::error_ext::SheetError;
+use sheet::function_ext::{Cell, ErrorKind};
+use sheet::util_ext::Anchor;
+
+#[test]
+fn test_parse_range_and_shift_reference() {
+    assert_eq!(parse_range("B5:A1", 10, 10), Ok((0, 41)));
+    assert_eq!(parse_range("C3", 10, 10), Ok((22, 22)));
+    assert!(parse_range("A1:K1", 10, 10).is_err());
+
+    assert_eq!(shift_reference(11, Anchor::RELATIVE, 2, 3, 10, 10), Some(34));
+    assert_eq!(shift_reference(11, Anchor { col: true, row: false }, 2, 3, 10, 10), Some(31));
+    assert_eq!(shift_reference(11, Anchor::ABSOLUTE, 2, 3, 10, 10), Some(11));
+    assert_eq!(shift_reference(11, Anchor::RELATIVE, -2, 0, 10, 10), None);
+    assert_eq!(shift_reference(19, Anchor::RELATIVE, 0, 1, 10, 10), None);
+}
+
+#[test]
+fn test_copy_paste_shifts_relative_references() {
+    let mut sheet = TestSheet::new();
+    for input in ["A1=1", "A2=2", "B1=A1*10", "B2=A2+$A$1", "C1=SUM(A$1:A2)", "copy A1:C2"] {
+        sheet.run(input).unwrap();
+    }
+    assert_eq!(sheet.state.clipboard, Some(Clipboard { start: 0, end: 12, cut: false }));
+
+    sheet.run("paste D3").unwrap();
+    assert_eq!(sheet.text(23).as_deref(), Some("=1"));
+    assert_eq!(sheet.text(24).as_deref(), Some("=D3*10"));
+    assert_eq!(sheet.text(34).as_deref(), Some("=D4+$A$1"));
+    assert_eq!(sheet.text(25).as_deref(), Some("=SUM(D$1:D4)"));
+    assert_eq!(sheet.arr[24], Cell::new_int(10));
+    assert_eq!(sheet.arr[34], Cell::new_int(3));
+    assert_eq!(sheet.arr[25], Cell::new_int(3));
+
+    // The pasted cells are wired into the graph like typed formulas.
+    sheet.run("D4=5").unwrap();
+    assert_eq!(sheet.arr[34], Cell::new_int(6));
+    assert_eq!(sheet.arr[25], Cell::new_int(6));
+
+    // The clipboard stays filled after a copy.
+    sheet.run("paste A9").unwrap();
+    assert_eq!(sheet.text(81).as_deref(), Some("=A9*10"));
+}
+
+#[test]
+fn test_paste_off_the_grid_gives_ref_error() {
+    let mut sheet = TestSheet::new();
+    for input in ["B2=A1+1", "C2=SUM(A1:B1)", "copy B2:C2", "paste A1"] {
+        sheet.run(input).unwrap();
+    }
+    assert_eq!(sheet.text(0).as_deref(), Some("=#REF!+1"));
+    assert_eq!(sheet.arr[0], Cell::error(ErrorKind::Ref));
+    assert_eq!(sheet.text(1).as_deref(), Some("=SUM(#REF!)"));
+    assert_eq!(sheet.arr[1], Cell::error(ErrorKind::Ref));
+
+    // The block itself must fit on the sheet.
+    let range = "J1:K1".to_string();
+    assert_eq!(sheet.run("paste J1"), Err(SheetError::InvalidRange { range }));
+    assert_eq!(TestSheet::new().run("paste A1"), Err(SheetError::command("Nothing to paste")));
+}
+
+#[test]
+fn test_cut_paste_moves_cells_and_rewrites_dependents() {
+    let mut sheet = TestSheet::new();
+    for input in ["A1=2", "A2=A1*3", "B1=A1+A2", "B2=SUM(A1:A2)", "B3=SUM(A1:A3)", "cut A1:A2"] {
+        sheet.run(input).unwrap();
+    }
+    sheet.run("paste C5").unwrap();
+    assert_eq!(sheet.state.clipboard, None);
+
+    // The moved formula follows the cell it read, which moved with it.
+    assert_eq!(sheet.arr[42], Cell::new_int(2));
+    assert_eq!(sheet.text(52).as_deref(), Some("=C5*3"));
+    assert_eq!(sheet.arr[52], Cell::new_int(6));
+    assert_eq!((sheet.text(0), sheet.arr[0].clone()), (None, Cell::empty()));
+    assert_eq!((sheet.text(10), sheet.arr[10].clone()), (None, Cell::empty()));
+
+    // Formulas elsewhere point at the new location; a range only partly moved stays.
+    assert_eq!(sheet.text(1).as_deref(), Some("=C5+C6"));
+    assert_eq!(sheet.text(11).as_deref(), Some("=SUM(C5:C6)"));
+    assert_eq!(sheet.text(21).as_deref(), Some("=SUM(A1:A3)"));
+    assert_eq!(sheet.arr[1], Cell::new_int(8));
+    assert_eq!(sheet.arr[21], Cell::new_int(0));
+
+    sheet.run("C5=4").unwrap();
+    assert_eq!(sheet.arr[1], Cell::new_int(16));
+    assert_eq!(sheet.arr[11], Cell::new_int(16));
+}
+
+#[test]
+fn test_paste_that_creates_a_cycle_is_rolled_back() {
+    let mut sheet = TestSheet::new();
+    for input in ["A1=B2", "A2=5", "copy A2", "cut A1"] {
+        sheet.run(input).unwrap();
+    }
+    let (arr, formula_array) = (sheet.arr.clone(), sheet.formula_array.clone());
+    let path = vec!["B2".to_string(), "B2".to_string()];
+    assert_eq!(sheet.run("paste B2"), Err(SheetError::Cycle { path }));
+    assert_eq!(sheet.arr, arr);
+    assert_eq!(sheet.formula_array, formula_array);
+    assert!(sheet.state.clipboard.is_some());
+
+    sheet.run("A2=7").unwrap();
+    assert_eq!(sheet.arr[10], Cell::new_int(7));
+}
@@ -0,0 +1,67 @@
+//! Fixtures shared by the integration tests of the extended engine. Each test crate uses only
+//! some of them.
+#![allow(dead_code)]
+
+use sheet::error_ext::SheetError;
+use sheet::function_ext::{Cell, CellValue};
+use sheet::graph_ext::{Formula, Graph, State};
+use sheet::grid_ext::Grid;
+use sheet::parser_ext::parser;
+
+/// A blank 10 x 10 sheet driven through [`parser`], with every piece of state exposed.
+pub struct TestSheet {
+    pub arr: Grid<Cell>,
+    pub graph: Graph,
+    pub formula_array: Grid<Formula>,
+    pub state: State,
+}
+
+impl TestSheet {
+    pub fn new() -> Self {
+        TestSheet {
+            arr: Grid::new(100, Cell::empty()),
+            graph: Graph::new(100),
+            formula_array: Grid::new(100, Formula::default()),
+            state: State::new(),
+        }
+    }
+
+    pub fn run(&mut self, input: &str) -> Result<(), SheetError> {
+        let TestSheet { arr, graph, formula_array, state } = self;
+        parser(input, 10, 10, arr, graph, formula_array, state)
+    }
+
+    /// Canonical text of the formula in cell `idx`.
+    pub fn text(&self, idx: usize) -> Option<String> {
+        self.formula_array[idx].to_formula_string(10)
+    }
+}
+
+/// Runs `inputs` on a blank 10 x 10 sheet, panicking if one of them fails.
+pub fn setup_sheet(inputs: &[&str]) -> (Grid<Cell>, Graph, Grid<Formula>, State) {
+    let mut sheet = TestSheet::new();
+    for input in inputs {
+        sheet.run(input).unwrap();
+    }
+    let TestSheet { arr, graph, formula_array, state } = sheet;
+    (arr, graph, formula_array, state)
+}
+
+/// Runs `inputs` on a blank 10 x 10 sheet and returns every cell.
+pub fn eval_all(inputs: &[&str]) -> Vec<Cell> {
+    setup_sheet(inputs).0.iter().cloned().collect()
+}
+
+/// Asserts that `cell` holds a float within `1e-9` of `expected`, relative to its size.
+pub fn assert_float(cell: &Cell, expected: f64) {
+    match cell.value {
+        CellValue::Float(f) => {
+            assert!((f - expected).abs() <= 1e-9 * expected.abs().max(1.0), "{} != {}", f, expected)
+        }
+        ref other => panic!("expected {}, got {:?}", expected, other),
+    }
+}
+
+pub fn text(s: &str) -> Cell {
+    Cell::new_string(s.to_string())
+}
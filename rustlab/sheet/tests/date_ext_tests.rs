@@ -0,0 +1,135 @@
+mod common;
+
+use common::{eval_all, TestSheet};
+use sheet::date_ext::{format_date, parse_iso, FixedClock};
+use sheet::function_ext::{Cell, ErrorKind};
+use std::sync::Arc;
+
+#[test]
+fn test_iso_literals_and_serials() {
+    assert_eq!(parse_iso("2025-04-01"), Some((45748.0, 10)));
+    assert_eq!(parse_iso("1899-12-30"), Some((0.0, 10)));
+    assert_eq!(parse_iso("1900-03-01"), Some((61.0, 10)));
+    assert_eq!(parse_iso("2025-04-01T18:00+1"), Some((45748.75, 16)));
+    assert_eq!(parse_iso("2025-02-29"), None);
+    assert_eq!(parse_iso("2025-4-1"), None);
+    assert_eq!(parse_iso("2025-04-01T24:00"), None);
+    assert_eq!(format_date(45748.0), "2025-04-01");
+    assert_eq!(format_date(45748.0 + 9.5 / 24.0), "2025-04-01T09:30:00");
+
+    let arr = eval_all(&["A1=2025-04-01", "B1=2024-02-29T12:00", "C1=2025-4-1"]);
+    assert_eq!(arr[0], Cell::new_date(45748.0));
+    assert_eq!(arr[1], Cell::new_date(45351.5));
+    // Without the zero padding it is still arithmetic.
+    assert_eq!(arr[2], Cell::new_int(2020));
+}
+
+#[test]
+fn test_date_arithmetic_in_formulas() {
+    let arr = eval_all(&[
+        "A1=2025-04-01",
+        "A2=A1+30",
+        "A3=A2-A1",
+        "A4=A1-1",
+        "A5=2025-04-01<2025-05-01",
+        "A6=\"Due: \"&A2",
+    ]);
+    assert_eq!(arr[10], Cell::new_date(45778.0));
+    assert_eq!(arr[20], Cell::new_int(30));
+    assert_eq!(arr[30], Cell::new_date(45747.0));
+    assert_eq!(arr[40], Cell::new_bool(true));
+    assert_eq!(arr[50], Cell::new_string("Due: 2025-05-01".to_string()));
+}
+
+#[test]
+fn test_date_parts_and_construction() {
+    let arr = eval_all(&[
+        "A1=DATE(2025,4,1)",
+        "B1=YEAR(A1)",
+        "C1=MONTH(A1)",
+        "D1=DAY(A1)",
+        "E1=WEEKDAY(A1)",
+        "F1=WEEKDAY(A1,2)",
+        "G1=WEEKDAY(A1,3)",
+        "H1=WEEKDAY(A1,4)",
+        "A2=DATE(2025,14,1)",
+        "B2=DATE(125,4,1)",
+        "C2=DATE(2025,3,0)",
+        "D2=YEAR(\"2024-12-31\")",
+        "E2=MONTH(\"soon\")",
+        "F2=DAY(-1)",
+        "G2=DATE(10000,1,1)",
+    ]);
+    assert_eq!(arr[0], Cell::new_date(45748.0));
+    assert_eq!(&arr[1..7], &[2025, 4, 1, 3, 2, 1].map(Cell::new_int));
+    assert_eq!(arr[7], Cell::error(ErrorKind::Num));
+    assert_eq!(arr[10], Cell::new_date(parse_iso("2026-02-01").unwrap().0));
+    assert_eq!(arr[11], Cell::new_date(45748.0));
+    assert_eq!(arr[12], Cell::new_date(parse_iso("2025-02-28").unwrap().0));
+    assert_eq!(arr[13], Cell::new_int(2024));
+    assert_eq!(arr[14], Cell::error(ErrorKind::Value));
+    assert_eq!(arr[15], Cell::error(ErrorKind::Num));
+    assert_eq!(arr[16], Cell::error(ErrorKind::Num));
+}
+
+#[test]
+fn test_edate_eomonth_and_datedif() {
+    let arr = eval_all(&[
+        "A1=EDATE(2024-01-31,1)",
+        "B1=EDATE(2024-01-31,-13)",
+        "C1=EOMONTH(2025-01-15,1)",
+        "D1=EOMONTH(2025-01-15,-1)",
+        "A2=DATEDIF(2020-02-15,2025-04-01,\"Y\")",
+        "B2=DATEDIF(2020-02-15,2025-04-01,\"M\")",
+        "C2=DATEDIF(2020-02-15,2025-04-01,\"D\")",
+        "D2=DATEDIF(2020-02-15,2025-04-01,\"MD\")",
+        "E2=DATEDIF(2020-02-15,2025-04-01,\"ym\")",
+        "F2=DATEDIF(2020-02-15,2025-04-01,\"YD\")",
+        "G2=DATEDIF(2025-04-01,2020-02-15,\"D\")",
+        "H2=DATEDIF(2020-02-15,2025-04-01,\"W\")",
+    ]);
+    let date = |s: &str| Cell::new_date(parse_iso(s).unwrap().0);
+    assert_eq!(arr[0], date("2024-02-29"));
+    assert_eq!(arr[1], date("2022-12-31"));
+    assert_eq!(arr[2], date("2025-02-28"));
+    assert_eq!(arr[3], date("2024-12-31"));
+    assert_eq!(&arr[10..16], &[5, 61, 1872, 17, 1, 45].map(Cell::new_int));
+    assert_eq!(arr[16], Cell::error(ErrorKind::Num));
+    assert_eq!(arr[17], Cell::error(ErrorKind::Num));
+}
+
+#[test]
+fn test_networkdays_with_holidays() {
+    let arr = eval_all(&[
+        "A1=2025-04-18",
+        "A2=2025-04-19",
+        "A3=2025-04-18",
+        "B1=NETWORKDAYS(2025-04-01,2025-04-30)",
+        "B2=NETWORKDAYS(2025-04-01,2025-04-30,A1:A4)",
+        "B3=NETWORKDAYS(2025-04-30,2025-04-01)",
+        "B4=NETWORKDAYS(2025-04-05,2025-04-06)",
+        "B5=NETWORKDAYS(2025-01-01,2025-12-31)",
+    ]);
+    assert_eq!(arr[1], Cell::new_int(22));
+    assert_eq!(arr[11], Cell::new_int(21));
+    assert_eq!(arr[21], Cell::new_int(-22));
+    assert_eq!(arr[31], Cell::new_int(0));
+    assert_eq!(arr[41], Cell::new_int(261));
+}
+
+#[test]
+fn test_today_and_now_use_the_clock() {
+    let mut sheet = TestSheet::new();
+    sheet.state.clock = Some(Arc::new(FixedClock(45748.75)));
+    for input in ["A1=TODAY()", "B1=NOW()", "C1=TODAY()+1", "D1=NOW()-TODAY()"] {
+        sheet.run(input).unwrap();
+    }
+    assert_eq!(sheet.arr[0], Cell::new_date(45748.0));
+    assert_eq!(sheet.arr[1], Cell::new_date(45748.75));
+    assert_eq!(sheet.arr[2], Cell::new_date(45749.0));
+    assert_eq!(sheet.arr[3], Cell::new_float(0.75));
+
+    // The clock belongs to the sheet, so another sheet still reads the system clock.
+    let today = eval_all(&["A1=TODAY()"]).remove(0);
+    assert_ne!(today, Cell::new_date(45748.0));
+}
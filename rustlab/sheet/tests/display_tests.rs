@@ -1,18 +1,18 @@
 use sheet::display::{printer, scroller};
 use sheet::graph::Graph;
-use std::i32;
+use sheet::grid_ext::Grid;
 
-fn generate_grid_with_pattern(c: usize, r: usize, err_at: Option<usize>) -> Vec<i32> {
+fn generate_grid_with_pattern(c: usize, r: usize, err_at: Option<usize>) -> Grid<i32> {
     let mut grid = vec![0; c * r];
     if let Some(idx) = err_at {
         grid[idx] = i32::MIN;
     }
-    for i in 0..(c * r) {
+    for (i, val) in grid.iter_mut().enumerate() {
         if Some(i) != err_at {
-            grid[i] = i as i32;
+            *val = i as i32;
         }
     }
-    grid
+    Grid::from(grid)
 }
 
 #[test]
@@ -40,7 +40,7 @@ fn test_scroller_top_left_no_move() {
     let mut y = 0;
     let c = 10;
     let r = 10;
-    let mut arr = vec![0; c * r];
+    let mut arr = Grid::new(c * r, 0);
     let mut graph = Graph::new(c * r);
 
     assert_eq!(scroller("w", &mut arr, &mut x, &mut y, c, r, &mut graph), 0);
@@ -53,7 +53,7 @@ fn test_scroller_down_and_right_partial_and_full_jump() {
     let mut y = 0;
     let c = 15;
     let r = 13;
-    let mut arr = vec![0; c * r];
+    let mut arr = Grid::new(c * r, 0);
     let mut graph = Graph::new(c * r);
 
     assert_eq!(scroller("s", &mut arr, &mut x, &mut y, c, r, &mut graph), 0);
@@ -68,7 +68,7 @@ fn test_scroller_invalid_command() {
     let mut y = 0;
     let c = 10;
     let r = 10;
-    let mut arr = vec![0; c * r];
+    let mut arr = Grid::new(c * r, 0);
     let mut graph = Graph::new(c * r);
 
     assert_eq!(scroller("invalid_command", &mut arr, &mut x, &mut y, c, r, &mut graph), -1);
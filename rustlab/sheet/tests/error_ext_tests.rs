@@ -0,0 +1,73 @@
+use sheet::error_ext::SheetError;
+use sheet::Sheet;
+
+fn cycle(path: &[&str]) -> SheetError {
+    SheetError::Cycle { path: path.iter().map(|cell| cell.to_string()).collect() }
+}
+
+#[test]
+fn test_display_messages() {
+    assert_eq!(SheetError::parse(6, "')'").to_string(), "Parse error at column 7: expected ')'");
+    assert_eq!(
+        SheetError::UnknownFunction { name: "XYZ".to_string() }.to_string(),
+        "Unknown function XYZ"
+    );
+    assert_eq!(SheetError::bad_reference("Z100").to_string(), "Invalid reference Z100");
+    assert_eq!(cycle(&["A1", "B1", "A1"]).to_string(), "Circular dependency: A1 → B1 → A1");
+    assert_eq!(SheetError::command("Nothing to undo").to_string(), "Nothing to undo");
+}
+
+#[test]
+fn test_serializes_tagged_with_kind() {
+    let json = serde_json::to_value(cycle(&["A1", "A1"])).unwrap();
+    assert_eq!(json, serde_json::json!({ "kind": "cycle", "path": ["A1", "A1"] }));
+    let json = serde_json::to_value(SheetError::parse(3, "an expression")).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!({ "kind": "parse", "position": 3, "expected": "an expression" })
+    );
+
+    let error = SheetError::InvalidRange { range: "A1:".to_string() };
+    let text = serde_json::to_string(&error).unwrap();
+    assert_eq!(serde_json::from_str::<SheetError>(&text).unwrap(), error);
+}
+
+#[test]
+fn test_offset_only_moves_parse_errors() {
+    assert_eq!(SheetError::parse(2, "':'").offset(3), SheetError::parse(5, "':'"));
+    let error = SheetError::bad_reference("A0");
+    assert_eq!(error.clone().offset(3), error);
+    assert!(cycle(&[]).is_cycle());
+    assert!(!error.is_cycle());
+}
+
+#[test]
+fn test_cycle_path_follows_references() {
+    let mut sheet = Sheet::new(5, 5);
+    sheet.set("B1", "C1+1").unwrap();
+    sheet.set("C1", "SUM(D1:D3)").unwrap();
+    sheet.set("D2", "A1*2").unwrap();
+    // A1 reads B1, which reads C1, which reads D2 through its range, which reads A1.
+    assert_eq!(sheet.set("A1", "B1"), Err(cycle(&["A1", "B1", "C1", "D2", "A1"])));
+    assert_eq!(sheet.set("A1", "A1"), Err(cycle(&["A1", "A1"])));
+}
+
+#[test]
+fn test_each_kind_of_mistake_has_its_own_error() {
+    let mut sheet = Sheet::new(5, 5);
+    sheet.set("A1", "\"text\"").unwrap();
+    assert_eq!(sheet.run("B1=1+*2"), Err(SheetError::parse(5, "an expression")));
+    assert_eq!(
+        sheet.run("B1=FOO(1)"),
+        Err(SheetError::UnknownFunction { name: "FOO".to_string() })
+    );
+    assert_eq!(sheet.run("B1=A1+A1000000000"), Err(SheetError::bad_reference("A1000000000")));
+    assert_eq!(
+        sheet.run("copy A1:Z9999999"),
+        Err(SheetError::InvalidRange { range: "A1:Z9999999".to_string() })
+    );
+    assert_eq!(
+        sheet.run("=autofill A 10"),
+        Err(SheetError::TypeMismatch { expected: "integers".to_string() })
+    );
+}
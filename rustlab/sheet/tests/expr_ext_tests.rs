@@ -0,0 +1,290 @@
+use sheet::date_ext::DATE_FUNCTIONS;
+use sheet::error_ext::SheetError;
+use sheet::expr_ext::{
+    arity, has_function, parse_formula, tokenize, BinaryOp, Expr, TokenKind, UnaryOp,
+    CORE_FUNCTIONS,
+};
+use sheet::finance_ext::FINANCE_FUNCTIONS;
+use sheet::function_ext::CellValue;
+use sheet::lookup_ext::LOOKUP_FUNCTIONS;
+use sheet::math_ext::MATH_FUNCTIONS;
+use sheet::stats_ext::STAT_FUNCTIONS;
+use sheet::text_ext::TEXT_FUNCTIONS;
+use sheet::util_ext::Anchor;
+
+fn kinds(src: &str) -> Vec<TokenKind> {
+    tokenize(src).unwrap().into_iter().map(|t| t.kind).collect()
+}
+
+#[test]
+fn test_tokenize_arithmetic() {
+    assert_eq!(
+        kinds("A1+2*3.5"),
+        vec![
+            TokenKind::Ref("A1".to_string()),
+            TokenKind::Op('+'),
+            TokenKind::Int(2),
+            TokenKind::Op('*'),
+            TokenKind::Float(3.5),
+        ]
+    );
+}
+
+#[test]
+fn test_tokenize_call_with_range() {
+    assert_eq!(
+        kinds("SUM( B1 : C2 )"),
+        vec![
+            TokenKind::Ident("SUM".to_string()),
+            TokenKind::LParen,
+            TokenKind::Ref("B1".to_string()),
+            TokenKind::Colon,
+            TokenKind::Ref("C2".to_string()),
+            TokenKind::RParen,
+        ]
+    );
+}
+
+#[test]
+fn test_tokenize_string_escape() {
+    assert_eq!(kinds("\"say \"\"hi\"\"\""), vec![TokenKind::Str("say \"hi\"".to_string())]);
+}
+
+#[test]
+fn test_tokenize_errors() {
+    assert!(tokenize("\"open").is_err());
+    assert!(tokenize("A1 # 2").is_err());
+}
+
+#[test]
+fn test_parse_precedence() {
+    // 1+2*3 parses as 1+(2*3)
+    let expr = parse_formula("1+2*3", 10, 10).unwrap();
+    match expr {
+        Expr::Binary { op: BinaryOp::Add, rhs, .. } => {
+            assert!(matches!(*rhs, Expr::Binary { op: BinaryOp::Mul, .. }));
+        }
+        other => panic!("unexpected tree: {:?}", other),
+    }
+}
+
+#[test]
+fn test_parse_left_associative() {
+    // 8-4-2 parses as (8-4)-2
+    let expr = parse_formula("8-4-2", 10, 10).unwrap();
+    match expr {
+        Expr::Binary { op: BinaryOp::Sub, lhs, rhs } => {
+            assert!(matches!(*lhs, Expr::Binary { op: BinaryOp::Sub, .. }));
+            assert_eq!(*rhs, Expr::Literal(CellValue::Int(2)));
+        }
+        other => panic!("unexpected tree: {:?}", other),
+    }
+}
+
+#[test]
+fn test_parse_negative_literal_and_unary() {
+    assert_eq!(parse_formula("-5", 10, 10).unwrap(), Expr::Literal(CellValue::Int(-5)));
+    assert!(matches!(
+        parse_formula("-(A1)", 10, 10).unwrap(),
+        Expr::Unary { op: UnaryOp::Neg, .. }
+    ));
+}
+
+#[test]
+fn test_parse_nested_collects_refs() {
+    let expr = parse_formula("(A1+B2)*2-SUM(C1:C3)", 10, 10).unwrap();
+    let mut cells = Vec::new();
+    let mut ranges = Vec::new();
+    expr.collect_refs(&mut cells, &mut ranges);
+    assert_eq!(cells, vec![0, 11]);
+    assert_eq!(ranges, vec![(2, 22)]);
+}
+
+#[test]
+fn test_parse_errors() {
+    assert_eq!(parse_formula("(A1+B1", 10, 10), Err(SheetError::parse(6, "')'")));
+    assert_eq!(parse_formula("MIN(B1 C1)", 10, 10), Err(SheetError::parse(7, "':'")));
+    assert_eq!(
+        parse_formula("XYZ(B1)", 10, 10),
+        Err(SheetError::UnknownFunction { name: "XYZ".to_string() })
+    );
+    assert_eq!(parse_formula("Z100", 10, 10), Err(SheetError::bad_reference("Z100")));
+    assert!(parse_formula("A1+", 10, 10).is_err());
+    assert!(parse_formula("A1 B1", 10, 10).is_err());
+}
+
+#[test]
+fn test_to_formula_string_round_trip() {
+    for src in [
+        "A1+B2*3",
+        "(A1+B2)*3",
+        "8-(4-2)",
+        "-A1+2.5",
+        "SUM(A1:C3)/AVG(B1:B2)",
+        "\"say \"\"hi\"\"\"",
+        "SLEEP(2)",
+        "3.0*J10",
+        "A1^2*3",
+        "A1&\" \"&B1",
+        "2^(A1+1)",
+        "A1-2025-04-01T09:30:00",
+        "$A$1*B$2+$C3",
+        "SUM($A$1:B$3)",
+        "#REF!+IFERROR(#N/A,#DIV/0!)",
+    ] {
+        let expr = parse_formula(src, 10, 10).unwrap();
+        let text = expr.to_formula_string(10);
+        assert_eq!(text, src);
+        assert_eq!(parse_formula(&text, 10, 10).unwrap(), expr);
+    }
+}
+
+#[test]
+fn test_to_formula_string_adds_needed_parens() {
+    let sum = Expr::Binary {
+        op: BinaryOp::Add,
+        lhs: Box::new(Expr::Cell(0, Anchor::RELATIVE)),
+        rhs: Box::new(Expr::Cell(1, Anchor::RELATIVE)),
+    };
+    let product = Expr::Binary {
+        op: BinaryOp::Mul,
+        lhs: Box::new(sum.clone()),
+        rhs: Box::new(Expr::Literal(CellValue::Int(2))),
+    };
+    assert_eq!(product.to_formula_string(10), "(A1+B1)*2");
+    let difference = Expr::Binary {
+        op: BinaryOp::Sub,
+        lhs: Box::new(Expr::Cell(2, Anchor::RELATIVE)),
+        rhs: Box::new(sum),
+    };
+    assert_eq!(difference.to_formula_string(10), "C1-(A1+B1)");
+}
+
+#[test]
+fn test_tokenize_comparisons() {
+    assert_eq!(
+        kinds("A1<>B1<=1>=2<3>4=5"),
+        vec![
+            TokenKind::Ref("A1".to_string()),
+            TokenKind::Cmp(BinaryOp::Ne),
+            TokenKind::Ref("B1".to_string()),
+            TokenKind::Cmp(BinaryOp::Le),
+            TokenKind::Int(1),
+            TokenKind::Cmp(BinaryOp::Ge),
+            TokenKind::Int(2),
+            TokenKind::Cmp(BinaryOp::Lt),
+            TokenKind::Int(3),
+            TokenKind::Cmp(BinaryOp::Gt),
+            TokenKind::Int(4),
+            TokenKind::Cmp(BinaryOp::Eq),
+            TokenKind::Int(5),
+        ]
+    );
+}
+
+#[test]
+fn test_parse_comparison_binds_loosest() {
+    // A1+1>B1*2 parses as (A1+1)>(B1*2)
+    match parse_formula("A1+1>B1*2", 10, 10).unwrap() {
+        Expr::Binary { op: BinaryOp::Gt, lhs, rhs } => {
+            assert!(matches!(*lhs, Expr::Binary { op: BinaryOp::Add, .. }));
+            assert!(matches!(*rhs, Expr::Binary { op: BinaryOp::Mul, .. }));
+        }
+        other => panic!("unexpected tree: {:?}", other),
+    }
+}
+
+#[test]
+fn test_parse_logical_functions() {
+    assert_eq!(parse_formula("true", 10, 10).unwrap(), Expr::Literal(CellValue::Bool(true)));
+    for src in ["IF(A1>=2,\"big\",\"small\")", "AND(A1:B2,NOT(C1),FALSE)", "IFERROR(A1/B1,0)"] {
+        let expr = parse_formula(src, 10, 10).unwrap();
+        assert_eq!(expr.to_formula_string(10), src);
+    }
+    assert_eq!(
+        parse_formula("IF(A1)", 10, 10),
+        Err(SheetError::parse(5, "2 to 3 arguments to IF"))
+    );
+    assert_eq!(parse_formula("NOT(A1,B1)", 10, 10), Err(SheetError::parse(9, "1 argument to NOT")));
+    assert_eq!(parse_formula("MAYBE", 10, 10), Err(SheetError::bad_reference("MAYBE")));
+}
+
+#[test]
+fn test_parse_variadic_range_function() {
+    let expr = parse_formula("SUM(A1:A3, C5, 10, B1:B2*2)", 10, 10).unwrap();
+    match &expr {
+        Expr::Call { name, args } => {
+            assert_eq!(name, "SUM");
+            assert_eq!(args.len(), 4);
+            assert_eq!(args[0], Expr::Range(0, 20, [Anchor::RELATIVE; 2]));
+            assert_eq!(args[1], Expr::Cell(42, Anchor::RELATIVE));
+        }
+        other => panic!("unexpected tree: {:?}", other),
+    }
+    assert_eq!(expr.to_formula_string(10), "SUM(A1:A3,C5,10,B1:B2*2)");
+    assert_eq!(
+        parse_formula("MAX()", 10, 10),
+        Err(SheetError::parse(4, "at least 1 argument to MAX"))
+    );
+    assert_eq!(parse_formula("MIN(A1:A2,)", 10, 10), Err(SheetError::parse(10, "an expression")));
+}
+
+#[test]
+fn test_parse_concat_precedence() {
+    // "a"&1+2="a3" parses as ("a"&(1+2))="a3"
+    let expr = parse_formula("\"a\"&1+2=\"a3\"", 10, 10).unwrap();
+    match expr {
+        Expr::Binary { op: BinaryOp::Eq, lhs, .. } => match *lhs {
+            Expr::Binary { op: BinaryOp::Concat, rhs, .. } => {
+                assert!(matches!(*rhs, Expr::Binary { op: BinaryOp::Add, .. }));
+            }
+            other => panic!("unexpected tree: {:?}", other),
+        },
+        other => panic!("unexpected tree: {:?}", other),
+    }
+}
+
+#[test]
+fn test_anchored_references() {
+    let anchor = |col, row| Anchor { col, row };
+    let expr = parse_formula("$B$2+SUM(A$1:$C3)", 10, 10).unwrap();
+    assert_eq!(
+        expr,
+        Expr::Binary {
+            op: BinaryOp::Add,
+            lhs: Box::new(Expr::Cell(11, Anchor::ABSOLUTE)),
+            rhs: Box::new(Expr::Call {
+                name: "SUM".to_string(),
+                args: vec![Expr::Range(0, 22, [anchor(false, true), anchor(true, false)])],
+            }),
+        }
+    );
+    assert_eq!(kinds("$A1"), vec![TokenKind::Ref("$A1".to_string())]);
+    assert!(tokenize("A1$").is_err());
+    assert!(tokenize("$SUM(A1)").is_err());
+    assert!(parse_formula("$$A1", 10, 10).is_err());
+}
+
+#[test]
+fn test_arity_comes_from_each_function_table() {
+    assert_eq!(arity("SUM"), Some((1, usize::MAX)));
+    assert_eq!(arity("IF"), Some((2, 3)));
+    assert_eq!(arity("MEDIAN"), Some((1, usize::MAX)));
+    assert_eq!(arity("PI"), Some((0, 0)));
+    assert_eq!(arity("XLOOKUP"), Some((3, 6)));
+    assert_eq!(arity("NOPE"), None);
+    // Every function is evaluated by exactly one module.
+    let tables = [
+        CORE_FUNCTIONS,
+        STAT_FUNCTIONS,
+        MATH_FUNCTIONS,
+        TEXT_FUNCTIONS,
+        DATE_FUNCTIONS,
+        FINANCE_FUNCTIONS,
+        LOOKUP_FUNCTIONS,
+    ];
+    for &(name, min_args, max_args) in tables.iter().flat_map(|table| table.iter()) {
+        assert_eq!(tables.iter().filter(|table| has_function(table, name)).count(), 1, "{}", name);
+        assert!(min_args <= max_args, "{}", name);
+    }
+}
@@ -0,0 +1,126 @@
+mod common;
+
+use common::{assert_float, eval_all};
+use sheet::function_ext::{Cell, CellValue, ErrorKind};
+
+#[test]
+fn test_loan_functions_match_excel() {
+    let arr = eval_all(&[
+        "A1=PMT(0.05/12,360,200000)",
+        "B1=PMT(0,10,1000)",
+        "C1=PMT(0.01,12,1000,0,1)",
+        "A2=FV(0.06/12,10,-200,-500,1)",
+        "B2=FV(0,12,-100)",
+        "A3=PV(0.08/12,240,500)",
+        "B3=PV(0,5,-100,-50)",
+        "A4=NPER(0.12/12,-100,-1000,10000,1)",
+        "B4=NPER(0,-100,1000)",
+        "C4=NPER(0.01,-5,1000)",
+        "D4=PMT(0.05,0,1000)",
+    ]);
+    assert_float(&arr[0], -1073.6432460242795);
+    assert_float(&arr[1], -100.0);
+    assert_float(&arr[2], -87.96909770132838);
+    assert_float(&arr[10], 2581.403374060136);
+    assert_float(&arr[11], 1200.0);
+    assert_float(&arr[20], -59777.1458511878);
+    assert_float(&arr[21], 550.0);
+    assert_float(&arr[30], 59.6738656742946);
+    assert_float(&arr[31], 10.0);
+    assert_eq!(arr[32], Cell::error(ErrorKind::Num));
+    assert_eq!(arr[33], Cell::error(ErrorKind::Num));
+}
+
+#[test]
+fn test_cash_flow_functions_read_ranges() {
+    let arr = eval_all(&[
+        "A1=-70000",
+        "A2=12000",
+        "A3=15000",
+        "A4=18000",
+        "A5=21000",
+        "A6=26000",
+        "B1=NPV(0.1,-10000,3000,4200,6800)",
+        "B2=IRR(A1:A5)",
+        "B3=IRR(A1:A6)",
+        "B4=NPV(0.1,A2:A6,A1)",
+        "B5=IRR(A2:A6)",
+        "B6=NPV(-1,A1:A6)",
+    ]);
+    assert_float(&arr[1], 1188.4434121601413);
+    assert_float(&arr[11], -0.0212448482734104);
+    assert_float(&arr[21], 0.0866309480365318);
+    let discounted: f64 = [12000.0, 15000.0, 18000.0, 21000.0, 26000.0, -70000.0]
+        .iter()
+        .enumerate()
+        .map(|(i, v)| v / 1.1f64.powi(i as i32 + 1))
+        .sum();
+    assert_float(&arr[31], discounted);
+    assert_eq!(arr[41], Cell::error(ErrorKind::Num));
+    assert_eq!(arr[51], Cell::error(ErrorKind::Div0));
+}
+
+#[test]
+fn test_cash_flow_functions_track_their_ranges() {
+    let arr = eval_all(&["A1=-100", "A2=110", "B1=IRR(A1:A2)", "A2=121", "A3=0"]);
+    assert_float(&arr[1], 0.21);
+}
+
+#[test]
+fn test_xnpv_pairs_values_with_dates() {
+    let arr = eval_all(&[
+        "A1=-10000",
+        "A2=2750",
+        "A3=4250",
+        "A4=3250",
+        "A5=2750",
+        "B1=2008-01-01",
+        "B2=2008-03-01",
+        "B3=2008-10-30",
+        "B4=2009-02-15",
+        "B5=\"2009-04-01\"",
+        "C1=XNPV(0.09,A1:A5,B1:B5)",
+        "C2=XNPV(0.09,A1:A5,B1:B4)",
+        "C3=XNPV(0.09,A1,B1)",
+    ]);
+    assert_float(&arr[2], 2086.647602031535);
+    assert_eq!(arr[12], Cell::error(ErrorKind::Num));
+    assert_eq!(arr[22], Cell::new_float(-10000.0));
+}
+
+#[test]
+fn test_xnpv_needs_a_date_for_every_value() {
+    let arr = eval_all(&[
+        "A1=-100",
+        "A2=110",
+        "B1=2008-01-01",
+        "C1=XNPV(0.1,A1:A2,B1:B2)",
+        "B3=\"soon\"",
+        "C2=XNPV(0.1,A1:A2,B3:B4)",
+    ]);
+    assert_eq!(arr[2], Cell::error(ErrorKind::Value));
+    assert_eq!(arr[12], Cell::error(ErrorKind::Value));
+}
+
+#[test]
+fn test_rate_converges_or_gives_num() {
+    let arr = eval_all(&[
+        "A1=RATE(48,-200,8000)",
+        "B1=RATE(10,0,-1000,2000)",
+        "C1=RATE(12,-100,1000,0,1,0.05)",
+        "A2=RATE(10,100,1000)",
+        "B2=RATE(0,-100,1000)",
+        "C2=IRR(A3:A4)",
+    ]);
+    assert_float(&arr[0], 0.007701472488246008);
+    assert_float(&arr[1], 2f64.powf(0.1) - 1.0);
+    let rate = match arr[2].value {
+        CellValue::Float(f) => f,
+        ref other => panic!("RATE did not converge: {:?}", other),
+    };
+    let check = eval_all(&[&format!("A1=PMT({},12,1000,0,1)", rate)]);
+    assert_float(&check[0], -100.0);
+    assert_eq!(arr[10], Cell::error(ErrorKind::Num));
+    assert_eq!(arr[11], Cell::error(ErrorKind::Num));
+    assert_eq!(arr[12], Cell::error(ErrorKind::Num));
+}
@@ -1,4 +1,4 @@
-use sheet::function_ext::{Cell, CellValue};
+use sheet::function_ext::{Cell, CellValue, ErrorKind};
 
 #[test]
 fn test_new_int_cell() {
@@ -24,12 +24,47 @@ fn test_new_string_cell() {
 #[test]
 fn test_invalid_cell() {
     let cell = Cell::invalid();
-    assert_eq!(cell.value, CellValue::Int(0));
+    assert_eq!(cell.value, CellValue::Error(ErrorKind::Value));
     assert!(!cell.is_valid);
 }
 
+#[test]
+fn test_error_cell() {
+    let cell = Cell::error(ErrorKind::Div0);
+    assert_eq!(cell.error_kind(), Some(ErrorKind::Div0));
+    assert!(!cell.is_valid);
+    assert_eq!(Cell::new(CellValue::Error(ErrorKind::Ref)), Cell::error(ErrorKind::Ref));
+    assert!(Cell::new(CellValue::Int(1)).is_valid);
+    assert_eq!(Cell::new_int(1).error_kind(), None);
+}
+
+#[test]
+fn test_error_kind_display() {
+    let shown: Vec<String> = [
+        ErrorKind::Div0,
+        ErrorKind::Value,
+        ErrorKind::Ref,
+        ErrorKind::Name,
+        ErrorKind::Num,
+        ErrorKind::Cycle,
+        ErrorKind::NotAvailable,
+    ]
+    .iter()
+    .map(|kind| kind.to_string())
+    .collect();
+    assert_eq!(shown, ["#DIV/0!", "#VALUE!", "#REF!", "#NAME?", "#NUM!", "#CYCLE!", "#N/A"]);
+}
+
 #[test]
 fn test_default_cell() {
     let default: Cell = Default::default();
     assert_eq!(default, Cell::invalid());
 }
+
+#[test]
+fn test_empty_cell() {
+    let cell = Cell::empty();
+    assert_eq!(cell.value, CellValue::Empty);
+    assert!(cell.is_valid);
+    assert_eq!(cell.error_kind(), None);
+}
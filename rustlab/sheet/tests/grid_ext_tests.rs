@@ -0,0 +1,72 @@
+use sheet::function_ext::Cell;
+use sheet::graph_ext::{Formula, Graph, State};
+use sheet::grid_ext::Grid;
+use sheet::parser_ext::parser;
+use sheet::structure_ext::{MAX_COLS, MAX_ROWS};
+
+#[test]
+fn test_reads_return_fill_without_allocating() {
+    let grid = Grid::new(5000, Cell::empty());
+    assert_eq!((grid.len(), grid.is_empty()), (5000, false));
+    assert_eq!(grid[4999], Cell::empty());
+    assert_eq!(grid.get(5000), None);
+    assert_eq!(grid.occupied().count(), 0);
+    assert_eq!(grid.iter().count(), 5000);
+}
+
+#[test]
+fn test_writes_allocate_only_their_chunk() {
+    let mut grid = Grid::new(5000, 0);
+    grid[3000] = 7;
+    grid[3001] += 1;
+    assert_eq!((grid[3000], grid[3001], grid[2999]), (7, 1, 0));
+    let occupied: Vec<usize> = grid.occupied().map(|(idx, _)| idx).collect();
+    assert_eq!(occupied.len(), 1024);
+    assert!(occupied.contains(&3000));
+
+    // The last chunk is cut off at the length.
+    grid[4999] = 2;
+    assert_eq!(grid.occupied().last(), Some((4999, &2)));
+    assert_eq!(format!("{:?}", grid), "{3000: 7, 3001: 1, 4999: 2}");
+}
+
+#[test]
+#[should_panic(expected = "index out of bounds")]
+fn test_out_of_bounds_write_panics() {
+    let mut grid = Grid::new(10, 0);
+    grid[10] = 1;
+}
+
+#[test]
+fn test_equality_ignores_allocation() {
+    let mut written = Grid::new(2000, 0);
+    written[1500] = 0;
+    assert_eq!(written, Grid::new(2000, 0));
+    assert_ne!(written, Grid::new(2001, 0));
+    assert_eq!(Grid::from(vec![1, 0, 3]), {
+        let mut grid = Grid::new(3, 0);
+        grid[0] = 1;
+        grid[2] = 3;
+        grid
+    });
+}
+
+#[test]
+fn test_largest_sheet_only_pays_for_used_cells() {
+    let num_cells = (MAX_ROWS * MAX_COLS) as usize;
+    let mut arr = Grid::new(num_cells, Cell::empty());
+    let mut graph = Graph::new(num_cells);
+    let mut formula_array = Grid::new(num_cells, Formula::default());
+    let mut state = State::new();
+    for input in ["A1=2", "ZZZ999=A1*3", "A999=SUM(A1:B2)+ZZZ999"] {
+        parser(input, MAX_COLS, MAX_ROWS, &mut arr, &mut graph, &mut formula_array, &mut state)
+            .unwrap();
+    }
+    assert_eq!(arr[num_cells - 1], Cell::new_int(6));
+    assert_eq!(arr[998 * MAX_COLS as usize], Cell::new_int(8));
+
+    // Cloning for undo copies the three written chunks, not 18 million cells.
+    let (arr, formula_array) = (arr.clone(), formula_array.clone());
+    assert!(arr.occupied().count() <= 3 * 1024);
+    assert!(formula_array.occupied().count() <= 3 * 1024);
+}
@@ -0,0 +1,202 @@
+mod common;
+
+use common::{setup_sheet, text};
+use sheet::error_ext::SheetError;
+use sheet::function_ext::{Cell, ErrorKind};
+use sheet::graph_ext::cycle_through;
+use sheet::grid_ext::Grid;
+use sheet::parser_ext::parser;
+
+/// A small price table in A1:C4: name, price, stock.
+const TABLE: [&str; 12] = [
+    "A1=\"apple\"",
+    "A2=\"banana\"",
+    "A3=\"cherry\"",
+    "A4=\"date\"",
+    "B1=10",
+    "B2=20",
+    "B3=30",
+    "B4=40",
+    "C1=5",
+    "C2=0",
+    "C3=7",
+    "C4=2",
+];
+
+#[test]
+fn test_vlookup_hlookup() {
+    let (arr, ..) = setup_sheet(
+        &[
+            &TABLE[..],
+            &[
+                "E1=VLOOKUP(\"Cherry\",A1:C4,2,FALSE)",
+                "E2=VLOOKUP(\"b*\",A1:C4,3,FALSE)",
+                "E3=VLOOKUP(\"fig\",A1:C4,2,FALSE)",
+                "E4=VLOOKUP(25,B1:C4,2)",
+                "E5=VLOOKUP(\"apple\",A1:C4,4,FALSE)",
+                "E6=HLOOKUP(20,B1:B4,1)",
+                "E7=HLOOKUP(\"apple\",A1:C1,3,FALSE)",
+            ],
+        ]
+        .concat(),
+    );
+    assert_eq!(arr[4], Cell::new_int(30));
+    assert_eq!(arr[14], Cell::new_int(0));
+    assert_eq!(arr[24], Cell::error(ErrorKind::NotAvailable));
+    assert_eq!(arr[34], Cell::new_int(0));
+    assert_eq!(arr[44], Cell::error(ErrorKind::Ref));
+    assert_eq!(arr[54], Cell::new_int(10));
+    assert_eq!(arr[64], Cell::error(ErrorKind::Ref));
+}
+
+#[test]
+fn test_index_match_choose() {
+    let (arr, ..) = setup_sheet(
+        &[
+            &TABLE[..],
+            &[
+                "E1=INDEX(A1:C4,3,2)",
+                "E2=INDEX(B1:B4,4)",
+                "E3=MATCH(\"date\",A1:A4,0)",
+                "E4=MATCH(35,B1:B4)",
+                "E5=INDEX(C1:C4,MATCH(\"banana\",A1:A4,0))",
+                "E6=INDEX(A1:C4,5,1)",
+                "E7=CHOOSE(2,\"x\",B1+1,1/0)",
+                "E8=CHOOSE(4,1,2,3)",
+                "E9=MATCH(5,A1:B4,0)",
+            ],
+        ]
+        .concat(),
+    );
+    assert_eq!(arr[4], Cell::new_int(30));
+    assert_eq!(arr[14], Cell::new_int(40));
+    assert_eq!(arr[24], Cell::new_int(4));
+    assert_eq!(arr[34], Cell::new_int(3));
+    assert_eq!(arr[44], Cell::new_int(0));
+    assert_eq!(arr[54], Cell::error(ErrorKind::Ref));
+    assert_eq!(arr[64], Cell::new_int(11));
+    assert_eq!(arr[74], Cell::error(ErrorKind::Value));
+    assert_eq!(arr[84], Cell::error(ErrorKind::NotAvailable));
+}
+
+#[test]
+fn test_xlookup_modes() {
+    let (arr, ..) = setup_sheet(
+        &[
+            &TABLE[..],
+            &[
+                "E1=XLOOKUP(\"date\",A1:A4,B1:B4)",
+                "E2=XLOOKUP(\"fig\",A1:A4,B1:B4,\"none\")",
+                "E3=XLOOKUP(25,B1:B4,A1:A4,\"none\",-1)",
+                "E4=XLOOKUP(25,B1:B4,A1:A4,\"none\",1)",
+                "E5=XLOOKUP(\"?a*\",A1:A4,C1:C4,0,2,-1)",
+                "E6=XLOOKUP(\"b*\",A1:A4,C1:C4)",
+                "E7=XLOOKUP(1,B1:B4,C1:C3)",
+            ],
+        ]
+        .concat(),
+    );
+    assert_eq!(arr[4], Cell::new_int(40));
+    assert_eq!(arr[14], text("none"));
+    assert_eq!(arr[34], text("cherry"));
+    assert_eq!(arr[44], Cell::new_int(2));
+    assert_eq!(arr[54], Cell::error(ErrorKind::NotAvailable));
+    assert_eq!(arr[64], Cell::error(ErrorKind::Value));
+}
+
+#[test]
+fn test_offset_and_indirect_references() {
+    let (arr, ..) = setup_sheet(
+        &[
+            &TABLE[..],
+            &[
+                "E1=OFFSET(A1,2,1)",
+                "E2=SUM(OFFSET(B1,1,0,3,1))",
+                "E3=INDIRECT(\"b\"&2)",
+                "E4=SUM(INDIRECT(\"B1:C2\"))",
+                "E5=OFFSET(A1,-1,0)",
+                "E6=INDIRECT(\"nowhere\")",
+                "E7=OFFSET(A1:B2,0,0)",
+                "E8=COUNTIF(OFFSET(C1,0,0,4,1),\">1\")",
+            ],
+        ]
+        .concat(),
+    );
+    assert_eq!(arr[4], Cell::new_int(30));
+    assert_eq!(arr[14], Cell::new_int(90));
+    assert_eq!(arr[24], Cell::new_int(20));
+    assert_eq!(arr[34], Cell::new_int(35));
+    assert_eq!(arr[44], Cell::error(ErrorKind::Ref));
+    assert_eq!(arr[54], Cell::error(ErrorKind::Ref));
+    assert_eq!(arr[64], Cell::error(ErrorKind::Value));
+    assert_eq!(arr[74], Cell::new_int(3));
+}
+
+#[test]
+fn test_dynamic_dependencies_follow_the_reference() {
+    let (mut arr, mut graph, mut formula_array, mut state) =
+        setup_sheet(&["A1=1", "A2=2", "B1=\"A1\"", "C1=INDIRECT(B1)*10"]);
+    assert_eq!(arr[2], Cell::new_int(10));
+    assert_eq!(graph.dynamic_ranges(2), vec![(0, 0)]);
+
+    let mut run = |input: &str, arr: &mut Grid<Cell>| {
+        parser(input, 10, 10, arr, &mut graph, &mut formula_array, &mut state).unwrap();
+    };
+    // The referenced cell is a dependency...
+    run("A1=5", &mut arr);
+    assert_eq!(arr[2], Cell::new_int(50));
+    // ...until the reference moves to another cell.
+    run("B1=\"A2\"", &mut arr);
+    assert_eq!(arr[2], Cell::new_int(20));
+    run("A1=7", &mut arr);
+    assert_eq!(arr[2], Cell::new_int(20));
+    run("A2=3", &mut arr);
+    assert_eq!(arr[2], Cell::new_int(30));
+}
+
+#[test]
+fn test_dynamic_reference_is_computed_before_it_is_read() {
+    // D1 reads A1 until B1 changes; then it reads A3, which is recalculated in the same pass
+    // and must be up to date by the time D1 is evaluated.
+    let (mut arr, mut graph, mut formula_array, mut state) =
+        setup_sheet(&["B1=0", "A3=B1*100", "D1=OFFSET(A1,B1*2,0)"]);
+    assert_eq!(arr[3], Cell::new_int(0));
+    parser("B1=1", 10, 10, &mut arr, &mut graph, &mut formula_array, &mut state).unwrap();
+    assert_eq!(arr[20], Cell::new_int(100));
+    assert_eq!(arr[3], Cell::new_int(100));
+    assert_eq!(graph.dynamic_ranges(3), vec![(20, 20)]);
+}
+
+#[test]
+fn test_dynamic_cycle_gives_cycle_error() {
+    let (mut arr, mut graph, mut formula_array, mut state) =
+        setup_sheet(&["A1=\"C1\"", "B1=INDIRECT(A1)", "B2=B1+1"]);
+    assert_eq!(arr[11], Cell::new_int(1));
+
+    // A formula closing the cycle through a registered dynamic range is rejected...
+    let mut run = |input: &str, arr: &mut Grid<Cell>| {
+        parser(input, 10, 10, arr, &mut graph, &mut formula_array, &mut state)
+    };
+    let path = ["C1", "B2", "B1", "C1"].map(String::from).to_vec();
+    assert_eq!(run("C1=B2", &mut arr), Err(SheetError::Cycle { path }));
+    // ...while a reference that moves onto the cycle turns the cell into #CYCLE!.
+    run("A1=\"B2\"", &mut arr).unwrap();
+    assert_eq!(arr[1], Cell::error(ErrorKind::Cycle));
+
+    // Pointing the reference elsewhere resolves the cycle.
+    run("A1=\"C1\"", &mut arr).unwrap();
+    assert_eq!(arr[1], Cell::new_int(0));
+    assert_eq!(arr[11], Cell::new_int(1));
+    assert_eq!(graph.dynamic_ranges(1), vec![(2, 2)]);
+}
+
+#[test]
+fn test_cycle_through_follows_resolved_references_and_ranges() {
+    let (arr, _, formula_array, _) =
+        setup_sheet(&["E1=\"D1\"", "D1=SUM(A2:A3)", "A3=INDIRECT(E1)"]);
+    // A3 would read D1, which reads A3 through its range.
+    assert_eq!(arr[20], Cell::error(ErrorKind::Cycle));
+    assert_eq!(cycle_through(20, &arr, &formula_array, 10), Some(vec![20, 3, 20]));
+    assert_eq!(cycle_through(3, &arr, &formula_array, 10), Some(vec![3, 20, 3]));
+    assert_eq!(cycle_through(4, &arr, &formula_array, 10), None);
+}
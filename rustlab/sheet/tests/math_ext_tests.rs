@@ -0,0 +1,81 @@
+mod common;
+
+use common::{assert_float, eval_all};
+use sheet::function_ext::{Cell, ErrorKind};
+
+#[test]
+fn test_rounding_keeps_integer_type() {
+    let arr = eval_all(&[
+        "A1=ROUND(2.345,2)",
+        "A2=ROUND(1234,-2)",
+        "A3=ROUND(-2.5,0)",
+        "A4=ROUND(7,1)",
+        "A5=TRUNC(-2.7)",
+        "A6=INT(-2.7)",
+        "A7=FLOOR(7,3)",
+        "A8=CEILING(2.1,0.5)",
+        "A9=FLOOR(5,0)",
+        "A10=CEILING(5,-1)",
+    ]);
+    assert_float(&arr[0], 2.35);
+    assert_eq!(arr[10], Cell::new_int(1200));
+    assert_float(&arr[20], -3.0);
+    assert_eq!(arr[30], Cell::new_int(7));
+    assert_float(&arr[40], -2.0);
+    assert_eq!(arr[50], Cell::new_int(-3));
+    assert_eq!(arr[60], Cell::new_int(6));
+    assert_float(&arr[70], 2.5);
+    assert_eq!(arr[80], Cell::error(ErrorKind::Div0));
+    assert_eq!(arr[90], Cell::error(ErrorKind::Num));
+}
+
+#[test]
+fn test_abs_sign_mod_power() {
+    let arr = eval_all(&[
+        "A1=-4",
+        "B1=ABS(A1)",
+        "B2=ABS(-1.5)",
+        "B3=SIGN(A1)",
+        "B4=MOD(-3,2)",
+        "B5=MOD(7.5,-2)",
+        "B6=MOD(1,0)",
+        "B7=POWER(A1,2)",
+        "B8=A1^3",
+        "B9=2^3^2",
+    ]);
+    assert_eq!(arr[1], Cell::new_int(4));
+    assert_float(&arr[11], 1.5);
+    assert_eq!(arr[21], Cell::new_int(-1));
+    assert_eq!(arr[31], Cell::new_int(1));
+    assert_float(&arr[41], -0.5);
+    assert_eq!(arr[51], Cell::error(ErrorKind::Div0));
+    assert_eq!(arr[61], Cell::new_int(16));
+    assert_eq!(arr[71], Cell::new_int(-64));
+    assert_eq!(arr[81], Cell::new_int(64));
+}
+
+#[test]
+fn test_roots_logs_and_trigonometry() {
+    let arr = eval_all(&[
+        "A1=SQRT(16)",
+        "A2=SQRT(-1)",
+        "A3=LOG(1000)",
+        "A4=LOG(8,2)",
+        "A5=LN(0)",
+        "A6=EXP(1)",
+        "A7=SIN(PI()/2)",
+        "A8=DEGREES(ATAN2(1,1))",
+        "A9=ACOS(2)",
+        "A10=SQRT(\"x\")",
+    ]);
+    assert_float(&arr[0], 4.0);
+    assert_eq!(arr[10], Cell::error(ErrorKind::Num));
+    assert_float(&arr[20], 3.0);
+    assert_float(&arr[30], 3.0);
+    assert_eq!(arr[40], Cell::error(ErrorKind::Num));
+    assert_float(&arr[50], std::f64::consts::E);
+    assert_float(&arr[60], 1.0);
+    assert_float(&arr[70], 45.0);
+    assert_eq!(arr[80], Cell::error(ErrorKind::Num));
+    assert_eq!(arr[90], Cell::error(ErrorKind::Value));
+}
@@ -1,7 +1,12 @@
-use sheet::function_ext::{Cell, CellValue};
-use sheet::graph_ext::{Formula, Graph, State};
-use sheet::parser_ext::{autofill, cell_parser, detect_pattern, generate_sequence, parser};
-use sheet::util_ext::{arithmetic_eval, return_optype};
+use sheet::error_ext::SheetError;
+use sheet::expr_ext::BinaryOp;
+use sheet::function_ext::{Cell, CellValue, ErrorKind};
+use sheet::graph_ext::{Formula, Graph, Operand, RangeFunc, State};
+use sheet::grid_ext::Grid;
+use sheet::parser_ext::{
+    anchored_cell_parser, autofill, cell_parser, detect_pattern, generate_sequence, parser,
+};
+use sheet::util_ext::{arithmetic_eval, return_optype, Anchor};
 // use sheet::function_ext::{Cell, CellValue};
 
 #[test]
@@ -131,7 +136,7 @@ fn test_arithmetic_eval_mixed_float_int_div_zero() {
 fn test_arithmetic_eval_float_int_invalid_op() {
     let c1 = Cell::new_float(2.5);
     let c2 = Cell::new_int(3);
-    let result = arithmetic_eval(c1, c2, '^');
+    let result = arithmetic_eval(c1, c2, '%');
     assert!(!result.is_valid);
 }
 
@@ -181,6 +186,7 @@ fn test_arithmetic_eval_float_plus_float() {
 }
 
 #[test]
+#[allow(clippy::approx_constant)]
 fn test_arithmetic_eval_string_add_float_should_fail() {
     let c1 = Cell::new_string("Number: ".into());
     let c2 = Cell::new_float(3.14);
@@ -229,6 +235,18 @@ fn test_cell_parser_invalid_characters() {
     assert!(result.is_err());
 }
 
+#[test]
+fn test_cell_parser_anchors() {
+    assert_eq!(cell_parser("$B$2", 10, 10, 0, 3), Ok(11));
+    assert_eq!(anchored_cell_parser("B2", 10, 10), Ok((11, Anchor::RELATIVE)));
+    assert_eq!(anchored_cell_parser("$B2", 10, 10), Ok((11, Anchor { col: true, row: false })));
+    assert_eq!(anchored_cell_parser("B$2", 10, 10), Ok((11, Anchor { col: false, row: true })));
+    assert_eq!(anchored_cell_parser("$B$2", 10, 10), Ok((11, Anchor::ABSOLUTE)));
+    assert!(anchored_cell_parser("B2$", 10, 10).is_err());
+    assert!(anchored_cell_parser("B$$2", 10, 10).is_err());
+    assert!(anchored_cell_parser("$$B2", 10, 10).is_err());
+}
+
 #[test]
 fn test_cell_parser_out_of_bounds() {
     let result = cell_parser("Z100", 5, 5, 0, 3); // Assuming Z100 > max rows
@@ -283,14 +301,14 @@ fn test_generate_sequence_const() {
 }
 #[test]
 fn test_autofill_invalid_column() {
-    let mut arr = vec![Cell::default(); 100];
+    let mut arr = Grid::new(100, Cell::default());
     let result = autofill("ZZ", 5, 5, 5, &mut arr);
     assert!(result.is_err());
 }
 
 #[test]
 fn test_autofill_insufficient_data() {
-    let mut arr = vec![Cell::default(); 100];
+    let mut arr = Grid::new(100, Cell::default());
     arr[0] = Cell::new_int(1);
     arr[5] = Cell::new_int(2);
     let result = autofill("A", 10, 10, 10, &mut arr);
@@ -299,7 +317,7 @@ fn test_autofill_insufficient_data() {
 
 #[test]
 fn test_autofill_invalid_type_in_column() {
-    let mut arr = vec![Cell::default(); 100];
+    let mut arr = Grid::new(100, Cell::default());
     arr[0] = Cell::new_string("bad".to_string());
     arr[10] = Cell::new_int(2);
     let result = autofill("A", 10, 10, 10, &mut arr);
@@ -353,7 +371,7 @@ fn test_generate_sequence_invalid_pattern() {
 
 #[test]
 fn test_autofill_ap_valid() {
-    let mut arr = vec![Cell::default(); 100];
+    let mut arr = Grid::new(100, Cell::default());
     arr[0] = Cell::new_int(1);
     arr[10] = Cell::new_int(2);
     arr[20] = Cell::new_int(3);
@@ -365,9 +383,9 @@ fn test_autofill_ap_valid() {
 }
 #[test]
 fn test_parser_valid_expression() {
-    let mut arr = vec![Cell::default(); 100];
+    let mut arr = Grid::new(100, Cell::default());
     let mut graph = Graph::new(100);
-    let mut formula_array = vec![Formula::default(); 100];
+    let mut formula_array = Grid::new(100, Formula::default());
     let mut state = State::new();
     state.num_cells = 100;
 
@@ -377,7 +395,7 @@ fn test_parser_valid_expression() {
 }
 #[test]
 fn test_autofill_gp_valid() {
-    let mut arr = vec![Cell::default(); 100];
+    let mut arr = Grid::new(100, Cell::default());
     arr[0] = Cell::new_int(2);
     arr[10] = Cell::new_int(4);
     arr[20] = Cell::new_int(8);
@@ -390,7 +408,7 @@ fn test_autofill_gp_valid() {
 
 #[test]
 fn test_autofill_const_valid() {
-    let mut arr = vec![Cell::default(); 100];
+    let mut arr = Grid::new(100, Cell::default());
     arr[0] = Cell::new_int(5);
     arr[10] = Cell::new_int(5);
     arr[20] = Cell::new_int(5);
@@ -403,7 +421,7 @@ fn test_autofill_const_valid() {
 
 #[test]
 fn test_autofill_fib_valid() {
-    let mut arr = vec![Cell::default(); 100];
+    let mut arr = Grid::new(100, Cell::default());
     arr[0] = Cell::new_int(1);
     arr[10] = Cell::new_int(1);
     arr[20] = Cell::new_int(2);
@@ -415,9 +433,9 @@ fn test_autofill_fib_valid() {
 }
 #[test]
 fn test_parser_const_assignment() {
-    let mut arr = vec![Cell::default(); 100];
+    let mut arr = Grid::new(100, Cell::default());
     let mut graph = Graph::new(100);
-    let mut formula_array = vec![Formula::default(); 100];
+    let mut formula_array = Grid::new(100, Formula::default());
     let result =
         parser("A1=5", 10, 10, &mut arr, &mut graph, &mut formula_array, &mut State::new());
     assert!(result.is_ok());
@@ -426,9 +444,9 @@ fn test_parser_const_assignment() {
 }
 #[test]
 fn test_parser_binary_op() {
-    let mut arr = vec![Cell::default(); 100];
+    let mut arr = Grid::new(100, Cell::default());
     let mut graph = Graph::new(100);
-    let mut formula_array = vec![Formula::default(); 100];
+    let mut formula_array = Grid::new(100, Formula::default());
     arr[1] = Cell::new_int(4);
     let result =
         parser("A1=A2+3", 10, 10, &mut arr, &mut graph, &mut formula_array, &mut State::new());
@@ -436,9 +454,9 @@ fn test_parser_binary_op() {
 }
 #[test]
 fn test_parser_sum_function() {
-    let mut arr = vec![Cell::default(); 100];
+    let mut arr = Grid::new(100, Cell::default());
     let mut graph = Graph::new(100);
-    let mut formula_array = vec![Formula::default(); 100];
+    let mut formula_array = Grid::new(100, Formula::default());
     arr[1] = Cell::new_int(4);
     arr[2] = Cell::new_int(5);
     arr[3] = Cell::new_int(6);
@@ -455,9 +473,9 @@ fn test_parser_sum_function() {
 }
 #[test]
 fn test_parser_string_assignment() {
-    let mut arr = vec![Cell::default(); 100];
+    let mut arr = Grid::new(100, Cell::default());
     let mut graph = Graph::new(100);
-    let mut formula_array = vec![Formula::default(); 100];
+    let mut formula_array = Grid::new(100, Formula::default());
     let result =
         parser("A1=\"hello\"", 10, 10, &mut arr, &mut graph, &mut formula_array, &mut State::new());
     assert!(result.is_ok());
@@ -465,8 +483,8 @@ fn test_parser_string_assignment() {
 }
 #[test]
 fn test_parser_invalid_function_name() {
-    let mut arr = vec![Cell::default(); 100];
-    let mut formula_array = vec![Formula::default(); 100];
+    let mut arr = Grid::new(100, Cell::default());
+    let mut formula_array = Grid::new(100, Formula::default());
     let mut graph = Graph::new(100);
     let mut state = State::new();
 
@@ -475,9 +493,10 @@ fn test_parser_invalid_function_name() {
     assert!(result.is_err());
 }
 #[test]
+#[allow(clippy::approx_constant)]
 fn test_parser_float_assignment() {
-    let mut arr = vec![Cell::default(); 100];
-    let mut formula_array = vec![Formula::default(); 100];
+    let mut arr = Grid::new(100, Cell::default());
+    let mut formula_array = Grid::new(100, Formula::default());
     let mut graph = Graph::new(100);
     let mut state = State::new();
 
@@ -488,8 +507,8 @@ fn test_parser_float_assignment() {
 
 #[test]
 fn test_parser_const_division_formula() {
-    let mut arr = vec![Cell::default(); 100];
-    let mut formula_array = vec![Formula::default(); 100];
+    let mut arr = Grid::new(100, Cell::default());
+    let mut formula_array = Grid::new(100, Formula::default());
     let mut graph = Graph::new(100);
     let mut state = State::new();
 
@@ -499,8 +518,8 @@ fn test_parser_const_division_formula() {
 }
 #[test]
 fn test_parser_float_division_formula() {
-    let mut arr = vec![Cell::default(); 100];
-    let mut formula_array = vec![Formula::default(); 100];
+    let mut arr = Grid::new(100, Cell::default());
+    let mut formula_array = Grid::new(100, Formula::default());
     let mut graph = Graph::new(100);
     let mut state = State::new();
 
@@ -510,24 +529,24 @@ fn test_parser_float_division_formula() {
 }
 #[test]
 fn test_parser_stdev_with_invalid_type() {
-    let mut arr = vec![Cell::default(); 100];
+    let mut arr = Grid::new(100, Cell::default());
     arr[1] = Cell::new_string("invalid".into());
     arr[2] = Cell::new_int(5);
-    let mut formula_array = vec![Formula::default(); 100];
+    let mut formula_array = Grid::new(100, Formula::default());
     let mut graph = Graph::new(100);
     let mut state = State::new();
 
     let result =
         parser("A1=STDEV(B1:B2)", 10, 10, &mut arr, &mut graph, &mut formula_array, &mut state);
     assert!(result.is_ok());
-    assert_eq!(arr[0].is_valid, false);
+    assert!(!arr[0].is_valid);
 }
 
 #[test]
 fn test_parser_sleep_self_value_cycle() {
-    let mut arr = vec![Cell::default(); 100];
+    let mut arr = Grid::new(100, Cell::default());
     arr[0] = Cell::new_int(1);
-    let mut formula_array = vec![Formula::default(); 100];
+    let mut formula_array = Grid::new(100, Formula::default());
     let mut graph = Graph::new(100);
     let mut state = State::new();
     state.num_cells = 100;
@@ -540,8 +559,8 @@ fn test_parser_sleep_self_value_cycle() {
 
 // #[test]
 // fn test_parser_trimmed_input() {
-//     let mut arr = vec![Cell::default(); 100];
-//     let mut formula_array = vec![Formula::default(); 100];
+//     let mut arr = Grid::new(100, Cell::default());
+//     let mut formula_array = Grid::new(100, Formula::default());
 //     let mut graph = Graph::new(100);
 //     let mut state = State::new();
 
@@ -551,8 +570,8 @@ fn test_parser_sleep_self_value_cycle() {
 // }
 #[test]
 fn test_parser_max_empty_range() {
-    let mut arr = vec![Cell::default(); 100];
-    let mut formula_array = vec![Formula::default(); 100];
+    let mut arr = Grid::new(100, Cell::default());
+    let mut formula_array = Grid::new(100, Formula::default());
     let mut graph = Graph::new(100);
     let mut state = State::new();
 
@@ -563,8 +582,8 @@ fn test_parser_max_empty_range() {
 }
 #[test]
 fn test_parser_avg_empty_range() {
-    let mut arr = vec![Cell::default(); 100];
-    let mut formula_array = vec![Formula::default(); 100];
+    let mut arr = Grid::new(100, Cell::default());
+    let mut formula_array = Grid::new(100, Formula::default());
     let mut graph = Graph::new(100);
     let mut state = State::new();
 
@@ -575,9 +594,9 @@ fn test_parser_avg_empty_range() {
 }
 #[test]
 fn test_parser_cell_to_cell_assignment() {
-    let mut arr = vec![Cell::default(); 100];
+    let mut arr = Grid::new(100, Cell::default());
     arr[11] = Cell::new_int(99); // B2
-    let mut formula_array = vec![Formula::default(); 100];
+    let mut formula_array = Grid::new(100, Formula::default());
     let mut graph = Graph::new(100);
     let mut state = State::new();
 
@@ -587,9 +606,9 @@ fn test_parser_cell_to_cell_assignment() {
 }
 #[test]
 fn test_parser_sleep_indirect_cell() {
-    let mut arr = vec![Cell::default(); 100];
+    let mut arr = Grid::new(100, Cell::default());
     arr[1] = Cell::new_int(0); // B1
-    let mut formula_array = vec![Formula::default(); 100];
+    let mut formula_array = Grid::new(100, Formula::default());
     let mut graph = Graph::new(100);
     let mut state = State::new();
 
@@ -601,11 +620,11 @@ fn test_parser_sleep_indirect_cell() {
 
 #[test]
 fn test_parser_min_range() {
-    let mut arr = vec![Cell::default(); 100];
+    let mut arr = Grid::new(100, Cell::default());
     arr[1] = Cell::new_int(10);
     arr[2] = Cell::new_int(5);
     arr[3] = Cell::new_int(15);
-    let mut formula_array = vec![Formula::default(); 100];
+    let mut formula_array = Grid::new(100, Formula::default());
     let mut graph = Graph::new(100);
     let mut state = State::new();
 
@@ -617,11 +636,11 @@ fn test_parser_min_range() {
 
 #[test]
 fn test_parser_avg_range() {
-    let mut arr = vec![Cell::default(); 100];
+    let mut arr = Grid::new(100, Cell::default());
     arr[1] = Cell::new_int(10);
     arr[2] = Cell::new_int(20);
     arr[3] = Cell::new_int(30);
-    let mut formula_array = vec![Formula::default(); 100];
+    let mut formula_array = Grid::new(100, Formula::default());
     let mut graph = Graph::new(100);
     let mut state = State::new();
 
@@ -633,7 +652,7 @@ fn test_parser_avg_range() {
 
 #[test]
 fn test_parser_stdev_valid_range() {
-    let mut arr = vec![Cell::default(); 100];
+    let mut arr = Grid::new(100, Cell::default());
     arr[1] = Cell::new_int(2);
     arr[2] = Cell::new_int(4);
     arr[3] = Cell::new_int(4);
@@ -642,7 +661,7 @@ fn test_parser_stdev_valid_range() {
     arr[6] = Cell::new_int(5);
     arr[7] = Cell::new_int(7);
     arr[8] = Cell::new_int(9);
-    let mut formula_array = vec![Formula::default(); 100];
+    let mut formula_array = Grid::new(100, Formula::default());
     let mut graph = Graph::new(100);
     let mut state = State::new();
 
@@ -654,8 +673,8 @@ fn test_parser_stdev_valid_range() {
 }
 #[test]
 fn test_parser_invalid_cell_assignment() {
-    let mut arr = vec![Cell::default(); 100];
-    let mut formula_array = vec![Formula::default(); 100];
+    let mut arr = Grid::new(100, Cell::default());
+    let mut formula_array = Grid::new(100, Formula::default());
     let mut graph = Graph::new(100);
     let mut state = State::new();
     let result = parser("5=5", 10, 10, &mut arr, &mut graph, &mut formula_array, &mut state);
@@ -664,8 +683,8 @@ fn test_parser_invalid_cell_assignment() {
 
 #[test]
 fn test_parser_negative_constant() {
-    let mut arr = vec![Cell::default(); 100];
-    let mut formula_array = vec![Formula::default(); 100];
+    let mut arr = Grid::new(100, Cell::default());
+    let mut formula_array = Grid::new(100, Formula::default());
     let mut graph = Graph::new(100);
     let mut state = State::new();
     let result = parser("A1=-5", 10, 10, &mut arr, &mut graph, &mut formula_array, &mut state);
@@ -675,8 +694,8 @@ fn test_parser_negative_constant() {
 
 #[test]
 fn test_parser_positive_constant() {
-    let mut arr = vec![Cell::default(); 100];
-    let mut formula_array = vec![Formula::default(); 100];
+    let mut arr = Grid::new(100, Cell::default());
+    let mut formula_array = Grid::new(100, Formula::default());
     let mut graph = Graph::new(100);
     let mut state = State::new();
     let result = parser("A1=+5", 10, 10, &mut arr, &mut graph, &mut formula_array, &mut state); // Lines 204, 207, 210
@@ -686,8 +705,8 @@ fn test_parser_positive_constant() {
 
 #[test]
 fn test_parser_empty_string() {
-    let mut arr = vec![Cell::default(); 100];
-    let mut formula_array = vec![Formula::default(); 100];
+    let mut arr = Grid::new(100, Cell::default());
+    let mut formula_array = Grid::new(100, Formula::default());
     let mut graph = Graph::new(100);
     let mut state = State::new();
     let result = parser("A1=\"\"", 10, 10, &mut arr, &mut graph, &mut formula_array, &mut state); // Lines 234-237
@@ -697,20 +716,20 @@ fn test_parser_empty_string() {
 
 #[test]
 fn test_parser_autofill_missing_length() {
-    let mut arr = vec![Cell::default(); 100];
-    let mut formula_array = vec![Formula::default(); 100];
+    let mut arr = Grid::new(100, Cell::default());
+    let mut formula_array = Grid::new(100, Formula::default());
     let mut graph = Graph::new(100);
     let mut state = State::new();
     let result =
         parser("=autofill A", 10, 10, &mut arr, &mut graph, &mut formula_array, &mut state); // Lines 493-498
     assert!(result.is_err());
-    assert_eq!(result.unwrap_err(), "Usage: autofill <column> <length>");
+    assert_eq!(result.unwrap_err(), SheetError::command("Usage: autofill <column> <length>"));
 }
 
 #[test]
 fn test_parser_no_digit_before_operator() {
-    let mut arr = vec![Cell::default(); 100];
-    let mut formula_array = vec![Formula::default(); 100];
+    let mut arr = Grid::new(100, Cell::default());
+    let mut formula_array = Grid::new(100, Formula::default());
     let mut graph = Graph::new(100);
     let mut state = State::new();
     let result = parser("A1=+B1", 10, 10, &mut arr, &mut graph, &mut formula_array, &mut state); // Lines 521-524, 526-530
@@ -719,19 +738,23 @@ fn test_parser_no_digit_before_operator() {
 
 #[test]
 fn test_parser_unknown_function() {
-    let mut arr = vec![Cell::default(); 100];
-    let mut formula_array = vec![Formula::default(); 100];
+    let mut arr = Grid::new(100, Cell::default());
+    let mut formula_array = Grid::new(100, Formula::default());
     let mut graph = Graph::new(100);
     let mut state = State::new();
     let result = parser("A1=XYZ(B1)", 10, 10, &mut arr, &mut graph, &mut formula_array, &mut state); // Lines 550, 552-555
     assert!(result.is_err());
-    assert_eq!(result.unwrap_err(), "Unknown function");
+    assert_eq!(result.unwrap_err(), SheetError::UnknownFunction { name: "XYZ".to_string() });
 }
 #[test]
 fn test_parser_sleep_with_prior_formula() {
-    let mut arr = vec![Cell::default(); 100];
-    let mut formula_array = vec![Formula::default(); 100];
-    formula_array[0] = Formula { op_type: 1, op_info1: 1, op_info2: 2 };
+    let mut arr = Grid::new(100, Cell::default());
+    let mut formula_array = Grid::new(100, Formula::default());
+    formula_array[0] = Formula::Arith {
+        lhs: Operand::Cell(1),
+        op: BinaryOp::Add,
+        rhs: Operand::Const(CellValue::Int(2)),
+    };
     let mut graph = Graph::new(100);
     let mut state = State::new();
     let result =
@@ -741,19 +764,19 @@ fn test_parser_sleep_with_prior_formula() {
 
 #[test]
 fn test_parser_sleep_missing_paren() {
-    let mut arr = vec![Cell::default(); 100];
-    let mut formula_array = vec![Formula::default(); 100];
+    let mut arr = Grid::new(100, Cell::default());
+    let mut formula_array = Grid::new(100, Formula::default());
     let mut graph = Graph::new(100);
     let mut state = State::new();
     let result = parser("A1=SLEEP(1", 10, 10, &mut arr, &mut graph, &mut formula_array, &mut state); // Lines 472-473
     assert!(result.is_err());
-    assert_eq!(result.unwrap_err(), "Missing closing parenthesis");
+    assert_eq!(result.unwrap_err(), SheetError::parse(10, "')'"));
 }
 
 // #[test]
 // fn test_parser_sleep_invalid_cell() {
-//     let mut arr = vec![Cell::default(); 100];
-//     let mut formula_array = vec![Formula::default(); 100];
+//     let mut arr = Grid::new(100, Cell::default());
+//     let mut formula_array = Grid::new(100, Formula::default());
 //     let mut graph = Graph::new(100);
 //     let mut state = State::new();
 //     let result = parser("A1=SLEEP(Z1a)", 10, 10, &mut arr, &mut graph, &mut formula_array, &mut state); // Line 476
@@ -762,8 +785,8 @@ fn test_parser_sleep_missing_paren() {
 
 #[test]
 fn test_parser_sleep_constant() {
-    let mut arr = vec![Cell::default(); 100];
-    let mut formula_array = vec![Formula::default(); 100];
+    let mut arr = Grid::new(100, Cell::default());
+    let mut formula_array = Grid::new(100, Formula::default());
     let mut graph = Graph::new(100);
     let mut state = State::new();
     let result =
@@ -774,9 +797,13 @@ fn test_parser_sleep_constant() {
 
 #[test]
 fn test_parser_range_with_prior_formula() {
-    let mut arr = vec![Cell::default(); 100];
-    let mut formula_array = vec![Formula::default(); 100];
-    formula_array[0] = Formula { op_type: 1, op_info1: 1, op_info2: 2 };
+    let mut arr = Grid::new(100, Cell::default());
+    let mut formula_array = Grid::new(100, Formula::default());
+    formula_array[0] = Formula::Arith {
+        lhs: Operand::Cell(1),
+        op: BinaryOp::Add,
+        rhs: Operand::Const(CellValue::Int(2)),
+    };
     let mut graph = Graph::new(100);
     let mut state = State::new();
     let result =
@@ -786,31 +813,31 @@ fn test_parser_range_with_prior_formula() {
 
 #[test]
 fn test_parser_missing_paren() {
-    let mut arr = vec![Cell::default(); 100];
-    let mut formula_array = vec![Formula::default(); 100];
+    let mut arr = Grid::new(100, Cell::default());
+    let mut formula_array = Grid::new(100, Formula::default());
     let mut graph = Graph::new(100);
     let mut state = State::new();
     let result =
         parser("A1=MIN(B1:C1", 10, 10, &mut arr, &mut graph, &mut formula_array, &mut state); // Lines 443-444
     assert!(result.is_err());
-    assert_eq!(result.unwrap_err(), "Missing closing parenthesis");
+    assert_eq!(result.unwrap_err(), SheetError::parse(12, "')'"));
 }
 
 #[test]
 fn test_parser_missing_colon() {
-    let mut arr = vec![Cell::default(); 100];
-    let mut formula_array = vec![Formula::default(); 100];
+    let mut arr = Grid::new(100, Cell::default());
+    let mut formula_array = Grid::new(100, Formula::default());
     let mut graph = Graph::new(100);
     let mut state = State::new();
     let result =
         parser("A1=MIN(B1 C1)", 10, 10, &mut arr, &mut graph, &mut formula_array, &mut state); // Line 445
     assert!(result.is_err());
-    assert_eq!(result.unwrap_err(), "Missing colon");
+    assert_eq!(result.unwrap_err(), SheetError::parse(10, "':'"));
 }
 // #[test]
 // fn test_parser_multiple_operators() {
-//     let mut arr = vec![Cell::default(); 100];
-//     let mut formula_array = vec![Formula::default(); 100];
+//     let mut arr = Grid::new(100, Cell::default());
+//     let mut formula_array = Grid::new(100, Formula::default());
 //     let mut graph = Graph::new(100);
 //     let mut state = State::new();
 //     let result = parser("A1=2++3", 10, 10, &mut arr, &mut graph, &mut formula_array, &mut state); // Lines 345-346, 348
@@ -820,8 +847,8 @@ fn test_parser_missing_colon() {
 
 #[test]
 fn test_parser_positive_first_operand() {
-    let mut arr = vec![Cell::default(); 100];
-    let mut formula_array = vec![Formula::default(); 100];
+    let mut arr = Grid::new(100, Cell::default());
+    let mut formula_array = Grid::new(100, Formula::default());
     let mut graph = Graph::new(100);
     let mut state = State::new();
     let result = parser("A1=+2+3", 10, 10, &mut arr, &mut graph, &mut formula_array, &mut state); // Lines 364, 366, 368
@@ -831,8 +858,8 @@ fn test_parser_positive_first_operand() {
 
 #[test]
 fn test_parser_positive_second_operand() {
-    let mut arr = vec![Cell::default(); 100];
-    let mut formula_array = vec![Formula::default(); 100];
+    let mut arr = Grid::new(100, Cell::default());
+    let mut formula_array = Grid::new(100, Formula::default());
     let mut graph = Graph::new(100);
     let mut state = State::new();
     let result = parser("A1=2+3", 10, 10, &mut arr, &mut graph, &mut formula_array, &mut state); // Lines 378-380, 382-385
@@ -843,9 +870,9 @@ fn test_parser_positive_second_operand() {
 
 #[test]
 fn test_parser_constant_cell_division() {
-    let mut arr = vec![Cell::default(); 100];
+    let mut arr = Grid::new(100, Cell::default());
     arr[1] = Cell::new_int(5);
-    let mut formula_array = vec![Formula::default(); 100];
+    let mut formula_array = Grid::new(100, Formula::default());
     let mut graph = Graph::new(100);
     let mut state = State::new();
     let result = parser("A1=10/B1", 10, 10, &mut arr, &mut graph, &mut formula_array, &mut state); // Lines 423, 425-426
@@ -855,10 +882,10 @@ fn test_parser_constant_cell_division() {
 
 #[test]
 fn test_parser_cell_cell_subtraction() {
-    let mut arr = vec![Cell::default(); 100];
+    let mut arr = Grid::new(100, Cell::default());
     arr[1] = Cell::new_int(10);
     arr[2] = Cell::new_int(3);
-    let mut formula_array = vec![Formula::default(); 100];
+    let mut formula_array = Grid::new(100, Formula::default());
     let mut graph = Graph::new(100);
     let mut state = State::new();
     let result = parser("A1=B1-C1", 10, 10, &mut arr, &mut graph, &mut formula_array, &mut state); // Line 429
@@ -868,11 +895,269 @@ fn test_parser_cell_cell_subtraction() {
 
 #[test]
 fn test_parser_invalid_cell_reference() {
-    let mut arr = vec![Cell::default(); 100];
-    let mut formula_array = vec![Formula::default(); 100];
+    let mut arr = Grid::new(100, Cell::default());
+    let mut formula_array = Grid::new(100, Formula::default());
     let mut graph = Graph::new(100);
     let mut state = State::new();
     let result = parser("A1=Z100", 10, 10, &mut arr, &mut graph, &mut formula_array, &mut state); // Lines 274-275
     assert!(result.is_err());
-    assert_eq!(result.unwrap_err(), "Cell reference out of bounds");
+    assert_eq!(result.unwrap_err(), SheetError::bad_reference("Z100"));
+}
+
+#[test]
+fn test_parser_nested_expression() {
+    let mut arr = Grid::new(100, Cell::default());
+    let mut graph = Graph::new(100);
+    let mut formula_array = Grid::new(100, Formula::default());
+    let mut state = State::new();
+    arr[1] = Cell::new_int(4);
+    arr[2] = Cell::new_int(6);
+    let result =
+        parser("A1=(B1+C1)*2-1", 10, 10, &mut arr, &mut graph, &mut formula_array, &mut state);
+    assert_eq!(result, Ok(()));
+    assert_eq!(arr[0], Cell::new_int(19));
+
+    let result =
+        parser("A2=SUM(B1:C1)/4+A1", 10, 10, &mut arr, &mut graph, &mut formula_array, &mut state);
+    assert_eq!(result, Ok(()));
+    assert_eq!(arr[10], Cell::new_float(21.5));
+}
+
+#[test]
+fn test_parser_nested_expression_propagates() {
+    let mut arr = Grid::new(100, Cell::default());
+    let mut graph = Graph::new(100);
+    let mut formula_array = Grid::new(100, Formula::default());
+    let mut state = State::new();
+    parser("A1=B1*2+C1*3", 10, 10, &mut arr, &mut graph, &mut formula_array, &mut state).unwrap();
+    parser("B1=2", 10, 10, &mut arr, &mut graph, &mut formula_array, &mut state).unwrap();
+    parser("C1=1", 10, 10, &mut arr, &mut graph, &mut formula_array, &mut state).unwrap();
+    assert_eq!(arr[0], Cell::new_int(7));
+}
+
+#[test]
+fn test_parser_nested_expression_cycle() {
+    let mut arr = Grid::new(100, Cell::default());
+    let mut graph = Graph::new(100);
+    let mut formula_array = Grid::new(100, Formula::default());
+    let mut state = State::new();
+    parser("A1=B1+1", 10, 10, &mut arr, &mut graph, &mut formula_array, &mut state).unwrap();
+    let result =
+        parser("B1=(A1+2)*3", 10, 10, &mut arr, &mut graph, &mut formula_array, &mut state);
+    assert!(result.is_err());
+    assert!(state.has_cycle);
+    assert_eq!(formula_array[1], Formula::Empty);
+}
+
+#[test]
+fn test_parser_stores_typed_formulas() {
+    let mut arr = Grid::new(100, Cell::default());
+    let mut graph = Graph::new(100);
+    let mut formula_array = Grid::new(100, Formula::default());
+    let mut state = State::new();
+    for input in ["A1=2.5", "A2=B1", "A3=B1*3", "A4=SUM(B1:C2)", "A5=SLEEP(0)", "A6=(B1+1)*2"] {
+        parser(input, 10, 10, &mut arr, &mut graph, &mut formula_array, &mut state).unwrap();
+    }
+    assert_eq!(formula_array[0], Formula::Literal(CellValue::Float(2.5)));
+    assert_eq!(formula_array[10], Formula::Ref(1));
+    assert_eq!(
+        formula_array[20],
+        Formula::Arith {
+            lhs: Operand::Cell(1),
+            op: BinaryOp::Mul,
+            rhs: Operand::Const(CellValue::Int(3))
+        }
+    );
+    assert_eq!(formula_array[30], Formula::Range { func: RangeFunc::Sum, start: 1, end: 12 });
+    assert_eq!(formula_array[40], Formula::Sleep(Operand::Const(CellValue::Int(0))));
+    assert!(matches!(formula_array[50], Formula::Expr(_)));
+}
+
+#[test]
+fn test_parser_literals_survive_recalc() {
+    let mut arr = Grid::new(100, Cell::default());
+    let mut graph = Graph::new(100);
+    let mut formula_array = Grid::new(100, Formula::default());
+    let mut state = State::new();
+    parser("A1=2.5", 10, 10, &mut arr, &mut graph, &mut formula_array, &mut state).unwrap();
+    parser("B1=\"hi\"", 10, 10, &mut arr, &mut graph, &mut formula_array, &mut state).unwrap();
+    parser("C1=A1*2", 10, 10, &mut arr, &mut graph, &mut formula_array, &mut state).unwrap();
+
+    arr[0] = Cell::invalid();
+    arr[1] = Cell::invalid();
+    graph.recalc(10, &mut arr, 0, &formula_array, &mut state).unwrap();
+    graph.recalc(10, &mut arr, 1, &formula_array, &mut state).unwrap();
+    assert_eq!(arr[0], Cell::new_float(2.5));
+    assert_eq!(arr[1], Cell::new_string("hi".to_string()));
+    assert_eq!(arr[2], Cell::new_float(5.0));
+}
+
+#[test]
+fn test_parser_formula_text() {
+    let mut arr = Grid::new(100, Cell::default());
+    let mut graph = Graph::new(100);
+    let mut formula_array = Grid::new(100, Formula::default());
+    let mut state = State::new();
+    for input in ["A1=2.5", "A2=B1", "A3=B1*3", "A4=SUM(B1:C2)", "A5=( B1 + 1 ) * 2", "A6=\"x\""] {
+        parser(input, 10, 10, &mut arr, &mut graph, &mut formula_array, &mut state).unwrap();
+    }
+    let text = |idx: usize| formula_array[idx].to_formula_string(10);
+    assert_eq!(text(0).as_deref(), Some("=2.5"));
+    assert_eq!(text(10).as_deref(), Some("=B1"));
+    assert_eq!(text(20).as_deref(), Some("=B1*3"));
+    assert_eq!(text(30).as_deref(), Some("=SUM(B1:C2)"));
+    assert_eq!(text(40).as_deref(), Some("=(B1+1)*2"));
+    assert_eq!(text(50).as_deref(), Some("=\"x\""));
+    assert_eq!(text(60), None);
+}
+
+#[test]
+fn test_parser_keeps_reference_anchors() {
+    let mut arr = Grid::new(100, Cell::empty());
+    let mut graph = Graph::new(100);
+    let mut formula_array = Grid::new(100, Formula::default());
+    let mut state = State::new();
+    for input in ["B1=3", "C2=4", "A1=$B$1", "A2=B$1*$C2", "A3=SUM($B$1:C$2)", "A4=$B1+1", "A5=B1"]
+    {
+        parser(input, 10, 10, &mut arr, &mut graph, &mut formula_array, &mut state).unwrap();
+    }
+    let text = |idx: usize| formula_array[idx].to_formula_string(10);
+    assert_eq!(text(0).as_deref(), Some("=$B$1"));
+    assert_eq!(text(10).as_deref(), Some("=B$1*$C2"));
+    assert_eq!(text(20).as_deref(), Some("=SUM($B$1:C$2)"));
+    assert_eq!(text(30).as_deref(), Some("=$B1+1"));
+    assert_eq!(formula_array[40], Formula::Ref(1));
+    assert_eq!(
+        arr.iter().take(31).step_by(10).cloned().collect::<Vec<_>>(),
+        [3, 12, 7, 4].map(Cell::new_int)
+    );
+
+    // Anchored references are still dependencies.
+    parser("B1=5", 10, 10, &mut arr, &mut graph, &mut formula_array, &mut state).unwrap();
+    assert_eq!(arr[0], Cell::new_int(5));
+    assert_eq!(arr[20], Cell::new_int(9));
+    assert!(
+        parser("A6=$B$1$", 10, 10, &mut arr, &mut graph, &mut formula_array, &mut state).is_err()
+    );
+}
+
+#[test]
+fn test_parser_error_values_propagate() {
+    let mut arr = Grid::new(100, Cell::new_int(0));
+    let mut graph = Graph::new(100);
+    let mut formula_array = Grid::new(100, Formula::default());
+    let mut state = State::new();
+    for input in ["A1=1/0", "B1=A1+1", "C1=SUM(A1:B1)", "D1=\"x\"", "E1=SUM(C2:D2)+MAX(D1:D1)"] {
+        parser(input, 10, 10, &mut arr, &mut graph, &mut formula_array, &mut state).unwrap();
+    }
+    assert_eq!(arr[0], Cell::error(ErrorKind::Div0));
+    assert_eq!(arr[1], Cell::error(ErrorKind::Div0));
+    assert_eq!(arr[2], Cell::error(ErrorKind::Div0));
+    assert_eq!(arr[4], Cell::error(ErrorKind::Value));
+
+    // Fixing the source clears the error downstream.
+    parser("A1=2", 10, 10, &mut arr, &mut graph, &mut formula_array, &mut state).unwrap();
+    assert_eq!(arr[1], Cell::new_int(3));
+    assert_eq!(arr[2], Cell::new_int(5));
+}
+
+#[test]
+fn test_parser_logical_functions() {
+    let mut arr = Grid::new(100, Cell::new_int(0));
+    let mut graph = Graph::new(100);
+    let mut formula_array = Grid::new(100, Formula::default());
+    let mut state = State::new();
+    for input in [
+        "A1=5",
+        "B1=A1>3",
+        "C1=AND(B1,A1<10)",
+        "D1=OR(A1=1,NOT(B1))",
+        "E1=IFERROR(10/(A1-5),-1)",
+        "F1=AND(A1:B1)",
+    ] {
+        parser(input, 10, 10, &mut arr, &mut graph, &mut formula_array, &mut state).unwrap();
+    }
+    assert_eq!(arr[1], Cell::new_bool(true));
+    assert_eq!(arr[2], Cell::new_bool(true));
+    assert_eq!(arr[3], Cell::new_bool(false));
+    assert_eq!(arr[4], Cell::new_int(-1));
+    assert_eq!(arr[5], Cell::new_bool(true));
+}
+
+#[test]
+fn test_parser_if_follows_condition_changes() {
+    let mut arr = Grid::new(100, Cell::new_int(0));
+    let mut graph = Graph::new(100);
+    let mut formula_array = Grid::new(100, Formula::default());
+    let mut state = State::new();
+    for input in ["A1=0", "B1=10", "C1=IF(A1<>0,B1/A1,\"n/a\")"] {
+        parser(input, 10, 10, &mut arr, &mut graph, &mut formula_array, &mut state).unwrap();
+    }
+    // The division is never evaluated while A1 is zero.
+    assert_eq!(arr[2], Cell::new_string("n/a".to_string()));
+
+    parser("A1=4", 10, 10, &mut arr, &mut graph, &mut formula_array, &mut state).unwrap();
+    assert_eq!(arr[2], Cell::new_float(2.5));
+
+    // Cells used only by one branch are still tracked.
+    parser("B1=20", 10, 10, &mut arr, &mut graph, &mut formula_array, &mut state).unwrap();
+    assert_eq!(arr[2], Cell::new_int(5));
+
+    // A branch that references the IF cell itself is still a cycle.
+    assert!(parser("B1=C1", 10, 10, &mut arr, &mut graph, &mut formula_array, &mut state).is_err());
+}
+
+#[test]
+fn test_parser_variadic_range_functions() {
+    let mut arr = Grid::new(100, Cell::new_int(0));
+    let mut graph = Graph::new(100);
+    let mut formula_array = Grid::new(100, Formula::default());
+    let mut state = State::new();
+    for input in ["A1=1", "A2=2", "A3=3", "C5=4", "B1=5", "B2=6", "J1=SUM(A1:A3,C5,10,B1:B2)"] {
+        parser(input, 10, 10, &mut arr, &mut graph, &mut formula_array, &mut state).unwrap();
+    }
+    assert_eq!(arr[9], Cell::new_int(31));
+
+    // Each argument is tracked: a single cell, and both ranges.
+    parser("C5=14", 10, 10, &mut arr, &mut graph, &mut formula_array, &mut state).unwrap();
+    assert_eq!(arr[9], Cell::new_int(41));
+    parser("B2=16", 10, 10, &mut arr, &mut graph, &mut formula_array, &mut state).unwrap();
+    assert_eq!(arr[9], Cell::new_int(51));
+
+    for input in ["J2=MAX(A1:A3,C5*2)", "J3=MIN(A2,A1:A3)", "J4=AVG(1,2,A3)", "J5=SUM(A1,TRUE)"] {
+        parser(input, 10, 10, &mut arr, &mut graph, &mut formula_array, &mut state).unwrap();
+    }
+    assert_eq!(arr[19], Cell::new_int(28));
+    assert_eq!(arr[29], Cell::new_int(1));
+    assert_eq!(arr[39], Cell::new_int(2));
+    assert_eq!(arr[49], Cell::new_int(2));
+
+    // A range argument covering the cell itself is a cycle.
+    assert!(parser(
+        "J1=SUM(A1,J1:J2)",
+        10,
+        10,
+        &mut arr,
+        &mut graph,
+        &mut formula_array,
+        &mut state
+    )
+    .is_err());
+}
+
+#[test]
+fn test_parser_blank_cells() {
+    let mut arr = Grid::new(100, Cell::empty());
+    let mut graph = Graph::new(100);
+    let mut formula_array = Grid::new(100, Formula::default());
+    let mut state = State::new();
+    for input in ["A1=B1", "A2=B1+3", "A3=AVG(B1:B3,4)", "A4=SUM(B1:B3)", "A5=IF(B1=0,1,2)"] {
+        parser(input, 10, 10, &mut arr, &mut graph, &mut formula_array, &mut state).unwrap();
+    }
+    assert_eq!(arr[0], Cell::new_int(0));
+    assert_eq!(arr[10], Cell::new_int(3));
+    assert_eq!(arr[20], Cell::new_int(4));
+    assert_eq!(arr[30], Cell::new_int(0));
+    assert_eq!(arr[40], Cell::new_int(1));
+    assert_eq!(arr[1], Cell::empty());
 }
@@ -1,15 +1,16 @@
 use sheet::graph::{Formula, Graph};
-use std::i32;
+use sheet::grid_ext::Grid;
+use sheet::parser::ParseError;
 
-fn setup(cells: usize) -> (Graph, Vec<i32>, Vec<Formula>) {
-    (Graph::new(cells), vec![0; cells], vec![Formula::default(); cells])
+fn setup(cells: usize) -> (Graph, Grid<i32>, Grid<Formula>) {
+    (Graph::new(cells), Grid::new(cells, 0), Grid::new(cells, Formula::default()))
 }
 
 #[test]
 fn test_parser_const_assignment() {
     let (mut graph, mut arr, mut formulas) = setup(5);
     let status = sheet::parser::parser("A1=42", 5, 1, &mut arr, &mut graph, &mut formulas);
-    assert_eq!(status, 1);
+    assert_eq!(status, Ok(()));
     assert_eq!(arr[0], 42);
 }
 
@@ -18,7 +19,7 @@ fn test_parser_cell_reference() {
     let (mut graph, mut arr, mut formulas) = setup(5);
     arr[1] = 30; // B1
     let status = sheet::parser::parser("A1=B1", 5, 1, &mut arr, &mut graph, &mut formulas);
-    assert_eq!(status, 1);
+    assert_eq!(status, Ok(()));
     assert_eq!(arr[0], 30);
 }
 
@@ -26,7 +27,7 @@ fn test_parser_cell_reference() {
 fn test_parser_arithmetic_const_expr() {
     let (mut graph, mut arr, mut formulas) = setup(5);
     let status = sheet::parser::parser("A1=3+7", 5, 1, &mut arr, &mut graph, &mut formulas);
-    assert_eq!(status, 1);
+    assert_eq!(status, Ok(()));
     assert_eq!(arr[0], 10);
 }
 
@@ -35,7 +36,7 @@ fn test_parser_arithmetic_cell_const_expr() {
     let (mut graph, mut arr, mut formulas) = setup(5);
     arr[1] = 9; // B1
     let status = sheet::parser::parser("A1=B1+6", 5, 1, &mut arr, &mut graph, &mut formulas);
-    assert_eq!(status, 1);
+    assert_eq!(status, Ok(()));
     assert_eq!(arr[0], 15);
 }
 
@@ -44,7 +45,7 @@ fn test_parser_arithmetic_const_cell_expr() {
     let (mut graph, mut arr, mut formulas) = setup(5);
     arr[2] = 3; // C1
     let status = sheet::parser::parser("A1=6/C1", 5, 1, &mut arr, &mut graph, &mut formulas);
-    assert_eq!(status, 1);
+    assert_eq!(status, Ok(()));
     assert_eq!(arr[0], 2);
 }
 
@@ -54,7 +55,7 @@ fn test_parser_arithmetic_cell_cell_expr_multiplication() {
     arr[1] = 5; // B1
     arr[2] = 2; // C1
     let status = sheet::parser::parser("A1=B1*C1", 5, 1, &mut arr, &mut graph, &mut formulas);
-    assert_eq!(status, 1);
+    assert_eq!(status, Ok(()));
     assert_eq!(arr[0], 10);
 }
 
@@ -64,7 +65,7 @@ fn test_parser_arithmetic_cell_cell_expr_addition() {
     arr[1] = 5; // B1
     arr[2] = 2; // C1
     let status = sheet::parser::parser("A1=B1+C1", 5, 1, &mut arr, &mut graph, &mut formulas);
-    assert_eq!(status, 1);
+    assert_eq!(status, Ok(()));
     assert_eq!(arr[0], 7);
 }
 
@@ -74,7 +75,7 @@ fn test_parser_arithmetic_cell_cell_expr_subtraction() {
     arr[1] = 5; // B1
     arr[2] = 2; // C1
     let status = sheet::parser::parser("A1=B1-C1", 5, 1, &mut arr, &mut graph, &mut formulas);
-    assert_eq!(status, 1);
+    assert_eq!(status, Ok(()));
     assert_eq!(arr[0], 3);
 }
 
@@ -84,7 +85,7 @@ fn test_parser_arithmetic_cell_cell_expr_division() {
     arr[1] = 10; // B1
     arr[2] = 2; // C1
     let status = sheet::parser::parser("A1=B1/C1", 5, 1, &mut arr, &mut graph, &mut formulas);
-    assert_eq!(status, 1);
+    assert_eq!(status, Ok(()));
     assert_eq!(arr[0], 5);
 }
 
@@ -92,7 +93,7 @@ fn test_parser_arithmetic_cell_cell_expr_division() {
 fn test_parser_arithmetic_cell_negative_constant() {
     let (mut graph, mut arr, mut formulas) = setup(5);
     let status = sheet::parser::parser("A1=-10", 5, 1, &mut arr, &mut graph, &mut formulas);
-    assert_eq!(status, 1);
+    assert_eq!(status, Ok(()));
     assert_eq!(arr[0], -10);
 }
 
@@ -100,7 +101,7 @@ fn test_parser_arithmetic_cell_negative_constant() {
 fn test_parser_arithmetic_const_addition() {
     let (mut graph, mut arr, mut formulas) = setup(5);
     let status = sheet::parser::parser("A1=10+5", 5, 1, &mut arr, &mut graph, &mut formulas);
-    assert_eq!(status, 1);
+    assert_eq!(status, Ok(()));
     assert_eq!(arr[0], 15);
 }
 
@@ -108,7 +109,7 @@ fn test_parser_arithmetic_const_addition() {
 fn test_parser_arithmetic_const_subtraction() {
     let (mut graph, mut arr, mut formulas) = setup(5);
     let status = sheet::parser::parser("A1=10-5", 5, 1, &mut arr, &mut graph, &mut formulas);
-    assert_eq!(status, 1);
+    assert_eq!(status, Ok(()));
     assert_eq!(arr[0], 5);
 }
 
@@ -116,7 +117,7 @@ fn test_parser_arithmetic_const_subtraction() {
 fn test_parser_arithmetic_const_multiplication() {
     let (mut graph, mut arr, mut formulas) = setup(5);
     let status = sheet::parser::parser("A1=10*5", 5, 1, &mut arr, &mut graph, &mut formulas);
-    assert_eq!(status, 1);
+    assert_eq!(status, Ok(()));
     assert_eq!(arr[0], 50);
 }
 
@@ -124,7 +125,7 @@ fn test_parser_arithmetic_const_multiplication() {
 fn test_parser_arithmetic_const_division() {
     let (mut graph, mut arr, mut formulas) = setup(5);
     let status = sheet::parser::parser("A1=10/5", 5, 1, &mut arr, &mut graph, &mut formulas);
-    assert_eq!(status, 1);
+    assert_eq!(status, Ok(()));
     assert_eq!(arr[0], 2);
 }
 
@@ -132,14 +133,14 @@ fn test_parser_arithmetic_const_division() {
 fn test_parser_arithmetic_cell_unknown_operator() {
     let (mut graph, mut arr, mut formulas) = setup(5);
     let status = sheet::parser::parser("A1=10@5", 5, 1, &mut arr, &mut graph, &mut formulas);
-    assert_eq!(status, -1);
+    assert_eq!(status, Err(ParseError::Unrecognized));
 }
 
 #[test]
 fn test_parser_arithmetic_divide_by_zero() {
     let (mut graph, mut arr, mut formulas) = setup(5);
     let status = sheet::parser::parser("A1=10/0", 5, 1, &mut arr, &mut graph, &mut formulas);
-    assert_eq!(status, 1);
+    assert_eq!(status, Ok(()));
     assert_eq!(arr[0], i32::MIN);
 }
 
@@ -147,7 +148,7 @@ fn test_parser_arithmetic_divide_by_zero() {
 fn test_parser_sleep_with_value() {
     let (mut graph, mut arr, mut formulas) = setup(5);
     let status = sheet::parser::parser("A1=SLEEP(0)", 5, 1, &mut arr, &mut graph, &mut formulas);
-    assert_eq!(status, 1);
+    assert_eq!(status, Ok(()));
     assert_eq!(arr[0], 0);
 }
 
@@ -156,7 +157,7 @@ fn test_parser_sleep_with_cell_ref() {
     let (mut graph, mut arr, mut formulas) = setup(5);
     arr[1] = 3; // B1
     let status = sheet::parser::parser("A1=SLEEP(B1)", 5, 1, &mut arr, &mut graph, &mut formulas);
-    assert_eq!(status, 1);
+    assert_eq!(status, Ok(()));
     assert_eq!(arr[0], 3);
 }
 
@@ -164,21 +165,21 @@ fn test_parser_sleep_with_cell_ref() {
 fn test_parser_invalid_sleep_format() {
     let (mut graph, mut arr, mut formulas) = setup(5);
     let status = sheet::parser::parser("A1=SLEEP(1", 5, 1, &mut arr, &mut graph, &mut formulas);
-    assert_eq!(status, -1);
+    assert_eq!(status, Err(ParseError::Unrecognized));
 }
 
 #[test]
 fn test_parser_invalid_cell_name() {
     let (mut graph, mut arr, mut formulas) = setup(5);
     let status = sheet::parser::parser("A1=XX", 5, 1, &mut arr, &mut graph, &mut formulas);
-    assert_eq!(status, -1);
+    assert_eq!(status, Err(ParseError::Unrecognized));
 }
 
 #[test]
 fn test_parser_invalid_syntax() {
     let (mut graph, mut arr, mut formulas) = setup(5);
     let status = sheet::parser::parser("=42", 5, 1, &mut arr, &mut graph, &mut formulas);
-    assert_eq!(status, -1);
+    assert_eq!(status, Err(ParseError::Unrecognized));
 }
 
 // #[test]
@@ -196,7 +197,7 @@ fn test_topo_sort_with_range_dependencies() {
     arr[6] = 10;
     arr[11] = 14;
     Graph::add_formula(&mut graph, 0, 6, 11, 12, &mut formulas);
-    graph.add_range_to_graph(6, 11, 0);
+    graph.add_range_to_graph(6, 11, 0, 5);
     graph.recalc(5, &mut arr, 0, &mut formulas, &mut false);
     assert_eq!(arr[0], 24);
 }
@@ -204,21 +205,21 @@ fn test_topo_sort_with_range_dependencies() {
 // fn test_parser_lowercase_cell_name() {
 //     let (mut graph, mut arr, mut formulas) = setup(5);
 //     let status = sheet::parser::parser("a1=42", 5, 1, &mut arr, &mut graph, &mut formulas); // Lines 11, 84-86, 100
-//     assert_eq!(status, -1);
+//     assert_eq!(status, Err(ParseError::Unrecognized));
 // }
 
 #[test]
 fn test_parser_invalid_char_in_cell() {
     let (mut graph, mut arr, mut formulas) = setup(5);
     let status = sheet::parser::parser("A@=42", 5, 1, &mut arr, &mut graph, &mut formulas); // Lines 84-86, 100
-    assert_eq!(status, -1);
+    assert_eq!(status, Err(ParseError::Unrecognized));
 }
 
 #[test]
 fn test_parser_letter_after_digit() {
     let (mut graph, mut arr, mut formulas) = setup(5);
     let status = sheet::parser::parser("A1B=42", 5, 1, &mut arr, &mut graph, &mut formulas); // Lines 84-86
-    assert_eq!(status, -1);
+    assert_eq!(status, Err(ParseError::Unrecognized));
 }
 
 #[test]
@@ -226,7 +227,7 @@ fn test_parser_multi_digit_row() {
     let (mut graph, mut arr, mut formulas) = setup(25);
     arr[5 * 2] = 99; // A3 (row 3, index 10)
     let status = sheet::parser::parser("A1=A3", 5, 5, &mut arr, &mut graph, &mut formulas); // Lines 96, 98
-    assert_eq!(status, 1);
+    assert_eq!(status, Ok(()));
     assert_eq!(arr[0], 99);
 }
 
@@ -234,34 +235,34 @@ fn test_parser_multi_digit_row() {
 fn test_parser_out_of_bounds_cell() {
     let (mut graph, mut arr, mut formulas) = setup(5);
     let status = sheet::parser::parser("F1=42", 5, 1, &mut arr, &mut graph, &mut formulas); // Lines 113-117
-    assert_eq!(status, -1);
+    assert_eq!(status, Err(ParseError::Unrecognized));
 }
 
 #[test]
 fn test_parser_negative_row() {
     let (mut graph, mut arr, mut formulas) = setup(5);
     let status = sheet::parser::parser("A0=42", 5, 1, &mut arr, &mut graph, &mut formulas); // Lines 113-117
-    assert_eq!(status, -1);
+    assert_eq!(status, Err(ParseError::Unrecognized));
 }
 
 #[test]
 fn test_parser_invalid_range() {
     let (mut graph, mut arr, mut formulas) = setup(5);
     let status = sheet::parser::parser("A1=A2", 5, 1, &mut arr, &mut graph, &mut formulas); // Lines 41, 61, 131, 133-136
-    assert_eq!(status, -1); // A2 out of bounds (row=2 > r=1)
+    assert_eq!(status, Err(ParseError::Unrecognized)); // A2 out of bounds (row=2 > r=1)
 }
 #[test]
 fn test_parser_empty_input() {
     let (mut graph, mut arr, mut formulas) = setup(5);
     let status = sheet::parser::parser("", 5, 1, &mut arr, &mut graph, &mut formulas); // Lines 166-167
-    assert_eq!(status, -1);
+    assert_eq!(status, Err(ParseError::Unrecognized));
 }
 
 #[test]
 fn test_parser_no_equals_sign() {
     let (mut graph, mut arr, mut formulas) = setup(5);
     let status = sheet::parser::parser("A1 42", 5, 1, &mut arr, &mut graph, &mut formulas); // Lines 169-170, 183
-    assert_eq!(status, -1);
+    assert_eq!(status, Err(ParseError::Unrecognized));
 }
 
 // #[test]
@@ -276,7 +277,7 @@ fn test_parser_no_equals_sign() {
 //     let (mut graph, mut arr, mut formulas) = setup(5);
 //     arr[1] = 10; // B1
 //     let status = sheet::parser::parser("A1=-B1", 5, 1, &mut arr, &mut graph, &mut formulas); // Lines 205, 207, 209, 226, 230, 233
-//     assert_eq!(status, 1);
+//     assert_eq!(status, Ok(()));
 //     assert_eq!(arr[0], -10);
 // }
 
@@ -284,7 +285,7 @@ fn test_parser_no_equals_sign() {
 fn test_parser_arithmetic_subtraction() {
     let (mut graph, mut arr, mut formulas) = setup(5);
     let status = sheet::parser::parser("A1=10-3", 5, 1, &mut arr, &mut graph, &mut formulas); // Lines 241, 243-246, 249
-    assert_eq!(status, 1);
+    assert_eq!(status, Ok(()));
     assert_eq!(arr[0], 7);
 }
 
@@ -292,7 +293,7 @@ fn test_parser_arithmetic_subtraction() {
 fn test_parser_arithmetic_multiplication() {
     let (mut graph, mut arr, mut formulas) = setup(5);
     let status = sheet::parser::parser("A1=4*5", 5, 1, &mut arr, &mut graph, &mut formulas); // Lines 241, 243-246, 253-254
-    assert_eq!(status, 1);
+    assert_eq!(status, Ok(()));
     assert_eq!(arr[0], 20);
 }
 
@@ -300,41 +301,41 @@ fn test_parser_arithmetic_multiplication() {
 fn test_parser_invalid_operator() {
     let (mut graph, mut arr, mut formulas) = setup(5);
     let status = sheet::parser::parser("A1=2^3", 5, 1, &mut arr, &mut graph, &mut formulas); // Lines 241, 243-246, 258-259
-    assert_eq!(status, -1);
+    assert_eq!(status, Err(ParseError::Unrecognized));
 }
 
 #[test]
 fn test_parser_sleep_missing_paren() {
     let (mut graph, mut arr, mut formulas) = setup(5);
     let status = sheet::parser::parser("A1=SLEEP(1", 5, 1, &mut arr, &mut graph, &mut formulas); // Lines 261-263
-    assert_eq!(status, -1);
+    assert_eq!(status, Err(ParseError::Unrecognized));
 }
 
 #[test]
 fn test_parser_unknown_function() {
     let (mut graph, mut arr, mut formulas) = setup(5);
     let status = sheet::parser::parser("A1=XYZ(1)", 5, 1, &mut arr, &mut graph, &mut formulas); // Lines 261, 267-268
-    assert_eq!(status, -1);
+    assert_eq!(status, Err(ParseError::Unrecognized));
 }
 #[test]
 fn test_parser_sum_missing_colon() {
     let (mut graph, mut arr, mut formulas) = setup(5);
     let status = sheet::parser::parser("A1=SUM(B1 C1)", 5, 1, &mut arr, &mut graph, &mut formulas); // Lines 289-293
-    assert_eq!(status, -1);
+    assert_eq!(status, Err(ParseError::InvalidRange));
 }
 
 #[test]
 fn test_parser_sum_invalid_range() {
     let (mut graph, mut arr, mut formulas) = setup(5);
     let status = sheet::parser::parser("A1=SUM(B1:Z1)", 5, 1, &mut arr, &mut graph, &mut formulas); // Lines 294-296
-    assert_eq!(status, -1);
+    assert_eq!(status, Err(ParseError::InvalidRange));
 }
 
 #[test]
 fn test_parser_sum_empty_range() {
     let (mut graph, mut arr, mut formulas) = setup(5);
     let status = sheet::parser::parser("A1=SUM(B1:B1)", 5, 1, &mut arr, &mut graph, &mut formulas); // Lines 289-296, 300-301
-    assert_eq!(status, 1);
+    assert_eq!(status, Ok(()));
     assert_eq!(arr[0], 0);
 }
 
@@ -344,7 +345,7 @@ fn test_parser_valid_sum_range() {
     arr[1] = 10;
     arr[2] = 20;
     let status = sheet::parser::parser("A1=SUM(B1:C1)", 5, 1, &mut arr, &mut graph, &mut formulas);
-    assert_eq!(status, 1);
+    assert_eq!(status, Ok(()));
     assert_eq!(arr[0], 30);
 }
 
@@ -355,7 +356,7 @@ fn test_parser_valid_stdev_range() {
     arr[2] = 20;
     let status =
         sheet::parser::parser("A1=STDEV(B1:C1)", 5, 1, &mut arr, &mut graph, &mut formulas);
-    assert_eq!(status, 1);
+    assert_eq!(status, Ok(()));
 }
 
 #[test]
@@ -364,7 +365,7 @@ fn test_parser_valid_average_range() {
     arr[1] = 10;
     arr[2] = 20;
     let status = sheet::parser::parser("A1=AVG(B1:C1)", 5, 1, &mut arr, &mut graph, &mut formulas);
-    assert_eq!(status, 1);
+    assert_eq!(status, Ok(()));
 }
 
 #[test]
@@ -373,7 +374,7 @@ fn test_parser_valid_min_range() {
     arr[1] = 10;
     arr[2] = 20;
     let status = sheet::parser::parser("A1=MIN(B1:C1)", 5, 1, &mut arr, &mut graph, &mut formulas);
-    assert_eq!(status, 1);
+    assert_eq!(status, Ok(()));
 }
 
 #[test]
@@ -382,7 +383,7 @@ fn test_parser_valid_max_range() {
     arr[1] = 10;
     arr[2] = 20;
     let status = sheet::parser::parser("A1=MAX(B1:C1)", 5, 1, &mut arr, &mut graph, &mut formulas);
-    assert_eq!(status, 1);
+    assert_eq!(status, Ok(()));
 }
 
 // #[test]
@@ -393,6 +394,124 @@ fn test_parser_valid_max_range() {
 //     Graph::add_formula(&mut graph, 6, 0, 0, -1, &mut formulas); // B2 = A1
 //     graph.add_edge(6, 0);
 //     let status = sheet::parser::parser("A1=SUM(B2:B3)", 5, 5, &mut arr, &mut graph, &mut formulas); // Lines 304-310
-//     assert_eq!(status, -1);
+//     assert_eq!(status, Err(ParseError::Unrecognized));
 //     assert_eq!(arr[0], 0); // Cycle resets A1
 // }
+
+#[test]
+fn test_formula_to_formula_string() {
+    let (mut graph, mut arr, mut formulas) = setup(9);
+    for input in ["B1=5", "A1=B1*2", "C1=A1+B1", "A2=SUM(A1:C1)", "B2=B1", "C2=SLEEP(0)"] {
+        assert_eq!(sheet::parser::parser(input, 3, 3, &mut arr, &mut graph, &mut formulas), Ok(()));
+    }
+    assert_eq!(formulas[1].to_formula_string(1, 3), "=5");
+    assert_eq!(formulas[0].to_formula_string(0, 3), "=B1*2");
+    assert_eq!(formulas[2].to_formula_string(2, 3), "=A1+B1");
+    assert_eq!(formulas[3].to_formula_string(3, 3), "=SUM(A1:C1)");
+    assert_eq!(formulas[4].to_formula_string(4, 3), "=B1");
+    assert_eq!(formulas[5].to_formula_string(5, 3), "=SLEEP(0)");
+}
+
+#[test]
+fn test_parser_keeps_reference_anchors() {
+    let (mut graph, mut arr, mut formulas) = setup(9);
+    for input in ["A1=4", "B1=$A$1*2", "C1=A$1+$B1", "A2=SUM($A$1:C$1)", "B2=8/$A1", "C2=$B$2"] {
+        assert_eq!(sheet::parser::parser(input, 3, 3, &mut arr, &mut graph, &mut formulas), Ok(()));
+    }
+    assert_eq!(arr.iter().skip(1).take(5).copied().collect::<Vec<_>>(), [8, 12, 24, 2, 2]);
+    assert_eq!(formulas[1].to_formula_string(1, 3), "=$A$1*2");
+    assert_eq!(formulas[2].to_formula_string(2, 3), "=A$1+$B1");
+    assert_eq!(formulas[3].to_formula_string(3, 3), "=SUM($A$1:C$1)");
+    assert_eq!(formulas[4].to_formula_string(4, 3), "=8/$A1");
+    assert_eq!(formulas[5].to_formula_string(5, 3), "=$B$2");
+
+    // Anchors only go before the column letters and the row digits.
+    assert_eq!(
+        sheet::parser::parser("C3=A1$", 3, 3, &mut arr, &mut graph, &mut formulas),
+        Err(ParseError::Unrecognized)
+    );
+    assert_eq!(
+        sheet::parser::parser("C3=$$A1", 3, 3, &mut arr, &mut graph, &mut formulas),
+        Err(ParseError::Unrecognized)
+    );
+}
+
+#[test]
+fn test_parser_cycle_is_rolled_back() {
+    let (mut graph, mut arr, mut formulas) = setup(5);
+    let status = sheet::parser::parser("A1=B1+1", 5, 1, &mut arr, &mut graph, &mut formulas);
+    assert_eq!(status, Ok(()));
+    let status = sheet::parser::parser("B1=A1", 5, 1, &mut arr, &mut graph, &mut formulas);
+    assert_eq!(status, Err(ParseError::CircularDependency));
+    assert_eq!(arr[0], 1);
+    assert_eq!(sheet::parser::parser("B1=4", 5, 1, &mut arr, &mut graph, &mut formulas), Ok(()));
+    assert_eq!(arr[0], 5);
+}
+
+#[test]
+fn test_parser_rejected_formula_keeps_old_edges() {
+    let (mut graph, mut arr, mut formulas) = setup(5);
+    for input in ["A1=MIN(B1:C1)", "D1=B1*2", "E1=5/C1"] {
+        assert_eq!(sheet::parser::parser(input, 5, 1, &mut arr, &mut graph, &mut formulas), Ok(()));
+    }
+    for (input, error) in [
+        ("A1=MIN(", ParseError::InvalidRange),
+        ("D1=MIN(", ParseError::InvalidRange),
+        ("D1=SLEEP(", ParseError::Unrecognized),
+        ("E1=foo", ParseError::Unrecognized),
+    ] {
+        let status = sheet::parser::parser(input, 5, 1, &mut arr, &mut graph, &mut formulas);
+        assert_eq!(status, Err(error));
+    }
+    // The cells still update when their precedents change.
+    assert_eq!(sheet::parser::parser("B1=-3", 5, 1, &mut arr, &mut graph, &mut formulas), Ok(()));
+    assert_eq!(sheet::parser::parser("C1=5", 5, 1, &mut arr, &mut graph, &mut formulas), Ok(()));
+    assert_eq!((arr[0], arr[3], arr[4]), (-3, -6, 1));
+}
+
+#[test]
+fn test_parser_rejects_empty_operands() {
+    let (mut graph, mut arr, mut formulas) = setup(5);
+    assert_eq!(sheet::parser::parser("C1=A1+1", 5, 1, &mut arr, &mut graph, &mut formulas), Ok(()));
+    for input in ["C1=A1+", "C1=+5", "C1=A1 * ", "C1=", "C1=SLEEP()"] {
+        let status = sheet::parser::parser(input, 5, 1, &mut arr, &mut graph, &mut formulas);
+        assert_eq!(status, Err(ParseError::Unrecognized), "{}", input);
+    }
+    assert_eq!(sheet::parser::parser("A1=4", 5, 1, &mut arr, &mut graph, &mut formulas), Ok(()));
+    assert_eq!(arr[2], 5);
+}
+
+#[test]
+fn test_parser_sheets_in_parallel_threads() {
+    let handles: Vec<_> = (0..4)
+        .map(|i| {
+            std::thread::spawn(move || {
+                let (mut graph, mut arr, mut formulas) = setup(5);
+                for _ in 0..1000 {
+                    let good = sheet::parser::parser(
+                        &format!("B1={}", i),
+                        5,
+                        1,
+                        &mut arr,
+                        &mut graph,
+                        &mut formulas,
+                    );
+                    assert_eq!(good, Ok(()));
+                    let bad = sheet::parser::parser(
+                        "A1=SUM(C1:B1)",
+                        5,
+                        1,
+                        &mut arr,
+                        &mut graph,
+                        &mut formulas,
+                    );
+                    assert_eq!(bad, Err(ParseError::InvalidRange));
+                }
+                arr[1]
+            })
+        })
+        .collect();
+    for (i, handle) in handles.into_iter().enumerate() {
+        assert_eq!(handle.join().unwrap(), i as i32);
+    }
+}
@@ -0,0 +1,119 @@
+use sheet::function_ext::Cell;
+use sheet::graph_ext::{Formula, Graph, State};
+use sheet::grid_ext::Grid;
+use sheet::parser_ext::parser;
+use sheet::range_index_ext::{Range, RangeIndex};
+
+fn range(start_cell: i32, end_cell: i32, dependent_cell: i32) -> Range {
+    Range { start_cell, end_cell, dependent_cell, dynamic: false }
+}
+
+fn dependents(index: &RangeIndex, cell: i32, cols: i32) -> Vec<i32> {
+    let mut cells: Vec<i32> = index.covering(cell, cols).iter().map(|r| r.dependent_cell).collect();
+    cells.sort_unstable();
+    cells
+}
+
+#[test]
+fn test_covering_across_tiles() {
+    let cols = 100;
+    let mut index = RangeIndex::new();
+    // AD31:AH33 straddles the tile corner at row 32, column 32; the corners are swapped.
+    index.insert(range(32 * cols + 33, 30 * cols + 29, 1), cols);
+    index.insert(range(0, 99 * cols + 99, 2), cols);
+    index.insert(range(5, 5, 3), cols);
+
+    assert_eq!(dependents(&index, 31 * cols + 31, cols), vec![1, 2]);
+    assert_eq!(dependents(&index, 32 * cols + 32, cols), vec![1, 2]);
+    assert_eq!(dependents(&index, 32 * cols + 34, cols), vec![2]);
+    assert_eq!(dependents(&index, 5, cols), vec![2, 3]);
+    assert_eq!(index.len(), 3);
+}
+
+#[test]
+fn test_remove_keeps_other_ranges_and_reuses_slots() {
+    let mut index = RangeIndex::new();
+    index.insert(range(0, 11, 7), 10);
+    index.insert(Range { dynamic: true, ..range(20, 20, 7) }, 10);
+    index.insert(range(0, 0, 8), 10);
+
+    index.remove_where(7, |range| range.dynamic);
+    assert_eq!(index.of_dependent(7).cloned().collect::<Vec<_>>(), vec![range(0, 11, 7)]);
+    assert!(index.covering(20, 10).is_empty());
+
+    index.remove_dependent(7);
+    assert_eq!(dependents(&index, 0, 10), vec![8]);
+    index.insert(range(1, 1, 9), 10);
+    assert_eq!(index.len(), 2);
+    assert_eq!(index.iter().count(), 2);
+}
+
+#[test]
+fn test_other_widths_are_still_answered() {
+    let mut index = RangeIndex::new();
+    index.insert(range(0, 11, 5), 10);
+    // Queried for another width, the index falls back to checking every range.
+    assert_eq!(dependents(&index, 6, 5), vec![5]);
+    assert!(index.covering(12, 5).is_empty());
+    // Inserting for another width recomputes the tiles.
+    index.insert(range(40, 40, 6), 20);
+    assert_eq!(dependents(&index, 1, 20), vec![5]);
+    assert!(index.covering(12, 20).is_empty());
+    assert_eq!(dependents(&index, 40, 20), vec![6]);
+}
+
+#[test]
+fn test_standard_engine_uses_the_index() {
+    use sheet::graph::{Formula, Graph};
+    let (cols, rows) = (40, 40);
+    let mut graph = Graph::new(cols * rows);
+    let mut arr = Grid::new(cols * rows, 0);
+    let mut formulas = Grid::new(cols * rows, Formula::default());
+    for input in ["AH33=4", "AM40=1", "A1=SUM(AG32:AH33)", "B1=MAX(AH33:AN40)"] {
+        assert_eq!(
+            sheet::parser::parser(input, cols, rows, &mut arr, &mut graph, &mut formulas),
+            Ok(())
+        );
+    }
+    sheet::parser::parser("AH33=9", cols, rows, &mut arr, &mut graph, &mut formulas).unwrap();
+    assert_eq!((arr[0], arr[1]), (9, 9));
+    sheet::parser::parser("A1=5", cols, rows, &mut arr, &mut graph, &mut formulas).unwrap();
+    assert_eq!(graph.ranges.len(), 1);
+}
+
+/// 10k range formulas: every one of rows 1..=1000 has values in A..J and ten formulas summing
+/// parts of them. The index finds the same formulas as a scan of all ranges; the timing
+/// comparison lives in `benches/range_index_bench.rs`.
+#[test]
+fn test_10k_range_formulas() {
+    let (cols, rows) = (26, 1000);
+    let num_cells = (cols * rows) as usize;
+    let mut arr = Grid::new(num_cells, Cell::empty());
+    let mut graph = Graph::new(num_cells);
+    let mut formula_array = Grid::new(num_cells, Formula::default());
+    let mut state = State::new();
+    let mut run = |input: &str, arr: &mut Grid<Cell>, graph: &mut Graph| {
+        parser(input, cols, rows, arr, graph, &mut formula_array, &mut state).unwrap();
+    };
+    for row in 1..=rows {
+        run(&format!("A{}=1", row), &mut arr, &mut graph);
+        for (i, col) in ('K'..='T').enumerate() {
+            let last = (b'A' + i as u8) as char;
+            run(&format!("{}{}=SUM(A{}:{}{})", col, row, row, last, row), &mut arr, &mut graph);
+        }
+    }
+    assert_eq!(graph.ranges.len(), 10_000);
+
+    let queries: Vec<i32> = (0..rows).map(|row| row * cols).collect();
+    let indexed: usize = queries.iter().map(|&cell| graph.ranges.covering(cell, cols).len()).sum();
+    let scanned: usize = queries
+        .iter()
+        .map(|&cell| graph.ranges.iter().filter(|range| range.contains(cell, cols)).count())
+        .sum();
+    assert_eq!(indexed, scanned);
+    assert_eq!(indexed, 10_000);
+
+    run("A500=3", &mut arr, &mut graph);
+    assert_eq!(arr[(499 * cols + 10) as usize], Cell::new_int(3));
+    assert_eq!(arr[(499 * cols + 19) as usize], Cell::new_int(3));
+}
@@ -0,0 +1,153 @@
+use sheet::date_ext::FixedClock;
+use sheet::error_ext::SheetError;
+use sheet::function_ext::{Cell, ErrorKind};
+use sheet::structure_ext::insert_row;
+use sheet::Sheet;
+
+#[test]
+fn test_set_get_and_formula() {
+    let mut sheet = Sheet::new(5, 5);
+    sheet.set("A1", "4").unwrap();
+    sheet.set("$B$2", "SUM(A1:A3)*2").unwrap();
+    sheet.set("C1", "\"text\"").unwrap();
+    assert_eq!(sheet.get("B2"), Ok(&Cell::new_int(8)));
+    assert_eq!(sheet.formula("B2").unwrap().as_deref(), Some("=SUM(A1:A3)*2"));
+    assert_eq!(sheet.get("C1"), Ok(&Cell::new_string("text".to_string())));
+    assert_eq!(sheet.index("B2"), Ok(6));
+
+    assert!(sheet.get("F1").is_err());
+    assert!(sheet.formula("a1").is_err());
+    // A command can't be smuggled in as the cell label.
+    assert!(sheet.set("copy A1", "A1:B2").is_err());
+}
+
+#[test]
+fn test_failed_steps_leave_sheet_and_history_alone() {
+    let mut sheet = Sheet::new(5, 5);
+    sheet.set("A1", "B1+1").unwrap();
+    let path = ["B1", "A1", "B1"].map(String::from).to_vec();
+    assert_eq!(sheet.set("B1", "A1"), Err(SheetError::Cycle { path }));
+    assert_eq!(sheet.run("B1=SUM("), Err(SheetError::parse(7, "an expression")));
+    // Positions in errors of `set` count from the start of the formula.
+    assert_eq!(sheet.set("B1", "SUM("), Err(SheetError::parse(4, "an expression")));
+    assert!(sheet.run("insert_row 9").is_err());
+    assert_eq!(sheet.formula("B1"), Ok(None));
+    assert_eq!(sheet.get("A1"), Ok(&Cell::new_int(1)));
+
+    // Only the first assignment was recorded.
+    sheet.undo().unwrap();
+    assert_eq!(sheet.formula("A1"), Ok(None));
+    assert_eq!(sheet.undo(), Err(SheetError::command("Nothing to undo")));
+}
+
+#[test]
+fn test_undo_and_redo_with_limit() {
+    let mut sheet = Sheet::new(5, 5).with_undo_limit(2);
+    for value in ["1", "2", "3"] {
+        sheet.set("A1", value).unwrap();
+    }
+    sheet.undo().unwrap();
+    sheet.undo().unwrap();
+    assert_eq!(sheet.get("A1"), Ok(&Cell::new_int(1)));
+    assert!(sheet.undo().is_err());
+
+    sheet.redo().unwrap();
+    assert_eq!(sheet.get("A1"), Ok(&Cell::new_int(2)));
+    // Scrolling is not a step, so it keeps what could be redone.
+    for input in ["s", "d", "scroll_to B2"] {
+        sheet.run(input).unwrap();
+    }
+    sheet.redo().unwrap();
+    assert_eq!(sheet.get("A1"), Ok(&Cell::new_int(3)));
+    sheet.undo().unwrap();
+    // A new step drops what could be redone.
+    sheet.set("A2", "7").unwrap();
+    assert_eq!(sheet.redo(), Err(SheetError::command("Nothing to redo")));
+}
+
+#[test]
+fn test_undo_restores_replaced_cells_and_their_dependents() {
+    let mut sheet = Sheet::new(3, 3);
+    for input in ["A1=2", "B1=A1*10", "C1=SUM(A1:B1)", "A1=B2+1", "copy A1:A1", "paste B2"] {
+        sheet.run(input).unwrap();
+    }
+    assert_eq!(sheet.formula("B2").unwrap().as_deref(), Some("=C3+1"));
+    assert_eq!(sheet.get("C1"), Ok(&Cell::new_int(22)));
+
+    sheet.undo().unwrap();
+    assert_eq!(sheet.formula("B2"), Ok(None));
+    assert_eq!(sheet.get("C1"), Ok(&Cell::new_int(11)));
+    sheet.undo().unwrap();
+    assert_eq!(sheet.formula("A1").unwrap().as_deref(), Some("=2"));
+    assert_eq!(sheet.get("C1"), Ok(&Cell::new_int(22)));
+    // The restored formula is wired up again.
+    sheet.set("B1", "A1*3").unwrap();
+    assert_eq!(sheet.get("C1"), Ok(&Cell::new_int(8)));
+
+    sheet.undo().unwrap();
+    sheet.redo().unwrap();
+    assert_eq!(sheet.get("C1"), Ok(&Cell::new_int(8)));
+}
+
+#[test]
+fn test_run_commands_and_structural_edits() {
+    let mut sheet = Sheet::new(3, 3);
+    for input in ["A1=1", "B1=A1+1", "copy A1:B1"] {
+        sheet.run(input).unwrap();
+    }
+    sheet.run("paste A2").unwrap();
+    assert_eq!(sheet.formula("B2").unwrap().as_deref(), Some("=A2+1"));
+
+    // Writing past the edge grows the sheet; undo shrinks it again.
+    sheet.run("E6=B2*10").unwrap();
+    assert_eq!((sheet.rows(), sheet.cols()), (6, 5));
+    assert_eq!(sheet.get("E6"), Ok(&Cell::new_int(20)));
+    sheet.run("undo").unwrap();
+    assert_eq!((sheet.rows(), sheet.cols()), (3, 3));
+
+    sheet.edit_line(insert_row, 0).unwrap();
+    assert_eq!(sheet.formula("B3").unwrap().as_deref(), Some("=A3+1"));
+    sheet.run("resize 2 2").unwrap();
+    assert_eq!(sheet.cells().len(), 4);
+    for bad in ["resize 2", "resize 2 x", "resize 2 2 2", "resize 3 x 4"] {
+        assert_eq!(sheet.run(bad), Err(SheetError::command("Usage: resize <rows> <columns>")));
+    }
+    assert_eq!(sheet.cells().len(), 4);
+    sheet.run("undo").unwrap();
+    sheet.run("undo").unwrap();
+    assert_eq!(sheet.formula("B2").unwrap().as_deref(), Some("=A2+1"));
+}
+
+#[test]
+fn test_recalc_all_refreshes_volatile_formulas() {
+    let mut sheet = Sheet::new(3, 3);
+    sheet.set_clock(FixedClock(45000.0));
+    sheet.set("A1", "NOW()").unwrap();
+    sheet.set("B1", "A1+1").unwrap();
+
+    sheet.set_clock(FixedClock(45001.5));
+    assert_eq!(sheet.get("B1"), Ok(&Cell::new_date(45001.0)));
+    sheet.recalc_all().unwrap();
+    assert_eq!(sheet.get("A1"), Ok(&Cell::new_date(45001.5)));
+    assert_eq!(sheet.get("B1"), Ok(&Cell::new_date(45002.5)));
+}
+
+#[test]
+fn test_trace_lists_the_loop_behind_a_cycle_error() {
+    let mut sheet = Sheet::new(5, 5);
+    for (cell, formula) in [("A1", "\"C1\""), ("B1", "INDIRECT(A1)"), ("B2", "B1+1")] {
+        sheet.set(cell, formula).unwrap();
+    }
+    sheet.set("C3", "SUM(B1:B2)").unwrap();
+    assert_eq!(sheet.trace("B1"), Ok(None));
+
+    // Pointing B1 at B2 closes a loop, so B1 shows #CYCLE! instead.
+    sheet.set("A1", "\"B2\"").unwrap();
+    assert_eq!(sheet.get("B1"), Ok(&Cell::error(ErrorKind::Cycle)));
+    let path = |cells: &[&str]| Some(cells.iter().map(|cell| cell.to_string()).collect());
+    assert_eq!(sheet.trace("B1"), Ok(path(&["B1", "B2", "B1"])));
+    assert_eq!(sheet.trace("B2"), Ok(path(&["B2", "B1", "B2"])));
+    assert_eq!(sheet.trace("C3"), Ok(None));
+    assert_eq!(sheet.trace("A1"), Ok(None));
+    assert!(sheet.trace("F9").is_err());
+}
@@ -0,0 +1,97 @@
+mod common;
+
+use common::{assert_float, eval_all};
+use sheet::function_ext::{Cell, ErrorKind};
+
+const DATA: [&str; 6] = ["A1=2", "A2=4", "A3=4", "A4=4", "A5=5", "A6=5"];
+
+#[test]
+fn test_variance_and_stdev() {
+    let arr = eval_all(
+        &[
+            &DATA[..],
+            &["B1=VAR.P(A1:A6)", "B2=VAR.S(A1:A6)", "B3=STDEV.P(A1:A6)", "B4=STDEV.S(A1:A6)"],
+        ]
+        .concat(),
+    );
+    assert_float(&arr[1], 1.0);
+    assert_float(&arr[11], 1.2);
+    assert_float(&arr[21], 1.0);
+    assert_float(&arr[31], 1.2f64.sqrt());
+}
+
+#[test]
+fn test_median_mode_percentile_quartile() {
+    let arr = eval_all(
+        &[
+            &DATA[..],
+            &[
+                "B1=MEDIAN(A1:A6)",
+                "B2=MEDIAN(A1:A2,A5:A6)",
+                "B3=MODE(A1:A6)",
+                "B4=PERCENTILE(A1:A6,0.3)",
+                "B5=QUARTILE(A1:A6,4)",
+                "B6=QUARTILE(A1:A6,5)",
+                "B7=MODE(1,2,3)",
+            ],
+        ]
+        .concat(),
+    );
+    assert_eq!(arr[1], Cell::new_int(4));
+    assert_eq!(arr[11], Cell::new_float(4.5));
+    assert_eq!(arr[21], Cell::new_int(4));
+    assert_float(&arr[31], 4.0);
+    assert_float(&arr[41], 5.0);
+    assert_eq!(arr[51], Cell::error(ErrorKind::Num));
+    assert_eq!(arr[61], Cell::error(ErrorKind::NotAvailable));
+}
+
+#[test]
+fn test_rank() {
+    let arr = eval_all(
+        &[&DATA[..], &["B1=RANK(5,A1:A6)", "B2=RANK(4,A1:A6,1)", "B3=RANK(3,A1:A6)"]].concat(),
+    );
+    assert_eq!(arr[1], Cell::new_int(1));
+    assert_eq!(arr[11], Cell::new_int(2));
+    assert_eq!(arr[21], Cell::error(ErrorKind::NotAvailable));
+}
+
+#[test]
+fn test_paired_statistics() {
+    let arr = eval_all(&[
+        "A1=1",
+        "A2=2",
+        "A3=3",
+        "A4=4",
+        "B1=3",
+        "B2=5",
+        "B3=7",
+        "B4=9",
+        "C1=SLOPE(B1:B4,A1:A4)",
+        "C2=INTERCEPT(B1:B4,A1:A4)",
+        "C3=CORREL(A1:A4,B1:B4)",
+        "C4=COVAR(A1:A4,B1:B4)",
+        "C5=LINEST(B1:B4)",
+        "C6=LINEST(B1:B4,A1:A4)",
+        "C7=CORREL(A1:A4,B1:B3)",
+        "C8=SLOPE(A1:A4,D1:D4)",
+    ]);
+    assert_float(&arr[2], 2.0);
+    assert_float(&arr[12], 1.0);
+    assert_float(&arr[22], 1.0);
+    assert_float(&arr[32], 2.5);
+    assert_float(&arr[42], 2.0);
+    assert_float(&arr[52], 2.0);
+    assert_eq!(arr[62], Cell::error(ErrorKind::NotAvailable));
+    assert_eq!(arr[72], Cell::error(ErrorKind::Div0));
+}
+
+#[test]
+fn test_statistics_need_enough_values() {
+    let arr =
+        eval_all(&["B1=VAR.S(A1:A3)", "B2=STDEV.S(5)", "B3=MEDIAN(A1:A3)", "B4=VAR.P(\"x\")"]);
+    assert_eq!(arr[1], Cell::error(ErrorKind::Div0));
+    assert_eq!(arr[11], Cell::error(ErrorKind::Div0));
+    assert_eq!(arr[21], Cell::error(ErrorKind::Num));
+    assert_eq!(arr[31], Cell::error(ErrorKind::Value));
+}
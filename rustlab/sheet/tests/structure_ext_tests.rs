@@ -0,0 +1,172 @@
+mod common;
+
+use common::TestSheet;
+use sheet::error_ext::SheetError;
+use sheet::function_ext::{Cell, ErrorKind};
+use sheet::graph_ext::{Formula, State};
+use sheet::grid_ext::Grid;
+use sheet::parser_ext::parser;
+use sheet::structure_ext::{grow_to_fit, insert_row, parse_line, resize, Axis};
+
+#[test]
+fn test_parse_line() {
+    assert_eq!(parse_line("3", Axis::Row, 10, 10), Ok(2));
+    assert_eq!(parse_line("C", Axis::Col, 10, 10), Ok(2));
+    assert!(parse_line("0", Axis::Row, 10, 10).is_err());
+    assert!(parse_line("11", Axis::Row, 10, 10).is_err());
+    assert_eq!(parse_line("K", Axis::Col, 10, 10), Err(SheetError::bad_reference("K")));
+    assert!(parse_line("c", Axis::Col, 10, 10).is_err());
+}
+
+#[test]
+fn test_insert_row_shifts_cells_and_references() {
+    let mut sheet = TestSheet::new();
+    for input in ["A1=1", "A2=2", "A3=3", "B1=A2*10", "B2=SUM(A1:A3)", "B3=$A$3+A1"] {
+        sheet.run(input).unwrap();
+    }
+    sheet.run("insert_row 2").unwrap();
+
+    assert_eq!(sheet.arr[10], Cell::empty());
+    assert_eq!(
+        (sheet.arr[20].clone(), sheet.arr[30].clone()),
+        (Cell::new_int(2), Cell::new_int(3))
+    );
+    assert_eq!(sheet.text(1).as_deref(), Some("=A3*10"));
+    // The range grows to include the new row; anchored references move too.
+    assert_eq!(sheet.text(21).as_deref(), Some("=SUM(A1:A4)"));
+    assert_eq!(sheet.text(31).as_deref(), Some("=$A$4+A1"));
+    assert_eq!(sheet.arr[21], Cell::new_int(6));
+
+    // The graph follows the new positions.
+    sheet.run("A2=10").unwrap();
+    assert_eq!(sheet.arr[21], Cell::new_int(16));
+    sheet.run("A3=5").unwrap();
+    assert_eq!(sheet.arr[1], Cell::new_int(50));
+}
+
+#[test]
+fn test_delete_row_gives_ref_errors_and_shrinks_ranges() {
+    let mut sheet = TestSheet::new();
+    for input in ["A1=1", "A2=2", "A3=3", "B1=A2+1", "B4=SUM(A1:A3)", "C5=SUM(A2:A2)", "D5=A3"] {
+        sheet.run(input).unwrap();
+    }
+    sheet.run("delete_row 2").unwrap();
+
+    assert_eq!(sheet.text(1).as_deref(), Some("=#REF!+1"));
+    assert_eq!(sheet.arr[1], Cell::error(ErrorKind::Ref));
+    assert_eq!(sheet.text(21).as_deref(), Some("=SUM(A1:A2)"));
+    assert_eq!(sheet.arr[21], Cell::new_int(4));
+    assert_eq!(sheet.text(32).as_deref(), Some("=SUM(#REF!)"));
+    assert_eq!(sheet.text(33).as_deref(), Some("=A2"));
+    assert_eq!(sheet.arr[33], Cell::new_int(3));
+    // A blank row is added at the bottom.
+    assert!(sheet.formula_array.iter().skip(90).all(Formula::is_empty));
+}
+
+#[test]
+fn test_insert_and_delete_columns() {
+    let mut sheet = TestSheet::new();
+    for input in ["A1=1", "B1=2", "C1=3", "A2=SUM(A1:C1)", "B2=C1-B1"] {
+        sheet.run(input).unwrap();
+    }
+    sheet.run("insert_col B").unwrap();
+    assert_eq!(sheet.text(10).as_deref(), Some("=SUM(A1:D1)"));
+    assert_eq!(sheet.text(12).as_deref(), Some("=D1-C1"));
+    assert_eq!(sheet.arr[10], Cell::new_int(6));
+
+    sheet.run("delete_col D").unwrap();
+    assert_eq!(sheet.text(10).as_deref(), Some("=SUM(A1:C1)"));
+    assert_eq!(sheet.text(12).as_deref(), Some("=#REF!-C1"));
+    assert_eq!(sheet.arr[10], Cell::new_int(3));
+}
+
+#[test]
+fn test_insert_pushes_references_off_the_sheet() {
+    let mut sheet = TestSheet::new();
+    for input in ["B1=A10", "C1=SUM(A9:A10)", "A9=1"] {
+        sheet.run(input).unwrap();
+    }
+    let (c, r) = (10, 10);
+    let TestSheet { arr, graph, formula_array, state } = &mut sheet;
+    insert_row(0, c, r, arr, graph, formula_array, state).unwrap();
+    assert_eq!(sheet.text(11).as_deref(), Some("=#REF!"));
+    assert_eq!(sheet.text(12).as_deref(), Some("=SUM(A10:A10)"));
+    assert_eq!(sheet.arr[12], Cell::new_int(1));
+}
+
+#[test]
+fn test_insert_refuses_to_drop_the_last_line() {
+    let mut sheet = TestSheet::new();
+    for input in ["A10=4", "B1=A10", "J2=A1"] {
+        sheet.run(input).unwrap();
+    }
+    assert_eq!(
+        sheet.run("insert_row 1"),
+        Err(SheetError::command("Row 10 is not empty; resize the sheet before inserting"))
+    );
+    assert_eq!(
+        sheet.run("insert_col B"),
+        Err(SheetError::command("Column J is not empty; resize the sheet before inserting"))
+    );
+    // Nothing moved.
+    assert_eq!(sheet.arr[90], Cell::new_int(4));
+    assert_eq!(sheet.text(1).as_deref(), Some("=A10"));
+    assert_eq!(sheet.text(19).as_deref(), Some("=A1"));
+
+    // Once the last row is clear again, the insert goes through.
+    sheet.run("delete_row 10").unwrap();
+    sheet.run("insert_row 1").unwrap();
+    assert_eq!(sheet.text(11).as_deref(), Some("=#REF!"));
+}
+
+#[test]
+fn test_resize_remaps_flat_indices() {
+    let mut sheet = TestSheet::new();
+    for input in ["B2=5", "C3=B2*2", "J10=SUM(B2:C3)", "A1=J10+C3"] {
+        sheet.run(input).unwrap();
+    }
+    let TestSheet { arr, graph, formula_array, state } = &mut sheet;
+    let (mut arr, mut formula_array) = (arr.clone(), formula_array.clone());
+    resize(12, 15, 10, &mut arr, graph, &mut formula_array, state).unwrap();
+    assert_eq!((arr.len(), formula_array.len()), (180, 180));
+    let text = |idx: usize| formula_array[idx].to_formula_string(12);
+    // C3 is now 2 * 12 + 2 = 26 instead of 22, and the formulas still read it.
+    assert_eq!(arr[26], Cell::new_int(10));
+    assert_eq!(text(26).as_deref(), Some("=B2*2"));
+    assert_eq!(text(9 * 12 + 9).as_deref(), Some("=SUM(B2:C3)"));
+    assert_eq!(arr[0], Cell::new_int(25));
+
+    // The graph is rebuilt for the new width.
+    let mut state = State::new();
+    parser("B2=1", 12, 15, &mut arr, graph, &mut formula_array, &mut state).unwrap();
+    assert_eq!(arr[0], Cell::new_int(5));
+    assert!(parser("L15=1", 12, 15, &mut arr, graph, &mut formula_array, &mut state).is_ok());
+
+    // Shrinking drops cells; references to them become #REF! and ranges are cut off.
+    resize(3, 3, 12, &mut arr, graph, &mut formula_array, &mut state).unwrap();
+    assert_eq!(arr.len(), 9);
+    assert_eq!(formula_array[0].to_formula_string(3).as_deref(), Some("=#REF!+C3"));
+    assert_eq!(arr[0], Cell::error(ErrorKind::Ref));
+    assert!(resize(0, 3, 3, &mut arr, graph, &mut formula_array, &mut state).is_err());
+}
+
+#[test]
+fn test_assigning_past_the_edge_grows_the_sheet() {
+    let mut sheet = TestSheet::new();
+    sheet.run("B2=7").unwrap();
+    let TestSheet { arr, graph, formula_array, state } = &mut sheet;
+    let (mut arr, mut formula_array) = (arr.clone(), formula_array.clone());
+    let mut grow = |input: &str, c, r, arr: &mut Grid<Cell>, formulas: &mut Grid<Formula>| {
+        grow_to_fit(input, c, r, arr, graph, formulas, state)
+    };
+
+    assert_eq!(grow("C3=1", 10, 10, &mut arr, &mut formula_array), Ok((10, 10)));
+    assert_eq!(grow("L20=B2+1", 10, 10, &mut arr, &mut formula_array), Ok((12, 20)));
+    assert_eq!(arr.len(), 240);
+    assert_eq!(arr[12 + 1], Cell::new_int(7));
+    // Invalid formulas and targets past the limits don't grow the sheet.
+    assert_eq!(grow("Z99=SUM(", 12, 20, &mut arr, &mut formula_array), Ok((12, 20)));
+    assert_eq!(grow("A1000=1", 12, 20, &mut arr, &mut formula_array), Ok((12, 20)));
+    assert_eq!(grow("scroll_to L20", 12, 20, &mut arr, &mut formula_array), Ok((12, 20)));
+    assert_eq!(arr.len(), 240);
+}
@@ -0,0 +1,96 @@
+mod common;
+
+use common::{eval_all, text};
+use sheet::function_ext::{Cell, ErrorKind};
+
+#[test]
+fn test_functions_count_characters_not_bytes() {
+    let arr = eval_all(&[
+        "A1=\"héllo wörld\"",
+        "B1=LEN(A1)",
+        "B2=LEFT(A1,2)",
+        "B3=RIGHT(A1,5)",
+        "B4=MID(A1,2,4)",
+        "B5=FIND(\"ö\",A1)",
+        "B6=UPPER(A1)",
+        "B7=REPLACE(A1,1,5,\"ça\")",
+        "B8=LEFT(A1)",
+        "B9=MID(A1,0,1)",
+    ]);
+    assert_eq!(arr[1], Cell::new_int(11));
+    assert_eq!(arr[11], text("hé"));
+    assert_eq!(arr[21], text("wörld"));
+    assert_eq!(arr[31], text("éllo"));
+    assert_eq!(arr[41], Cell::new_int(8));
+    assert_eq!(arr[51], text("HÉLLO WÖRLD"));
+    assert_eq!(arr[61], text("ça wörld"));
+    assert_eq!(arr[71], text("h"));
+    assert_eq!(arr[81], Cell::error(ErrorKind::Value));
+}
+
+#[test]
+fn test_trim_substitute_rept_find() {
+    let arr = eval_all(&[
+        "A1=TRIM(\"  a   b  \")",
+        "A2=SUBSTITUTE(\"a-b-c\",\"-\",\"+\")",
+        "A3=SUBSTITUTE(\"a-b-c\",\"-\",\"+\",2)",
+        "A4=REPT(\"ab\",3)",
+        "A5=REPT(\"x\",-1)",
+        "A6=FIND(\"b\",\"abcabc\",3)",
+        "A7=FIND(\"B\",\"abc\")",
+        "A8=LOWER(TRUE)",
+    ]);
+    assert_eq!(arr[0], text("a b"));
+    assert_eq!(arr[10], text("a+b+c"));
+    assert_eq!(arr[20], text("a-b+c"));
+    assert_eq!(arr[30], text("ababab"));
+    assert_eq!(arr[40], Cell::error(ErrorKind::Value));
+    assert_eq!(arr[50], Cell::new_int(5));
+    assert_eq!(arr[60], Cell::error(ErrorKind::Value));
+    assert_eq!(arr[70], text("true"));
+}
+
+#[test]
+fn test_concat_and_textjoin_over_ranges() {
+    let arr = eval_all(&[
+        "A1=\"a\"",
+        "A2=1",
+        "A3=2.5",
+        "B1=CONCAT(A1:A4,\"!\")",
+        "B2=TEXTJOIN(\", \",TRUE,A1:A4)",
+        "B3=TEXTJOIN(\"-\",FALSE,A1:A4)",
+        "B4=A1&A2&\" \"&TRUE",
+        "B5=A1&1/0",
+    ]);
+    assert_eq!(arr[1], text("a12.5!"));
+    assert_eq!(arr[11], text("a, 1, 2.5"));
+    assert_eq!(arr[21], text("a-1-2.5-"));
+    assert_eq!(arr[31], text("a1 TRUE"));
+    assert_eq!(arr[41], Cell::error(ErrorKind::Div0));
+}
+
+#[test]
+fn test_value_and_text_conversions() {
+    let arr = eval_all(&[
+        "A1=VALUE(\" 42 \")",
+        "A2=VALUE(\"2.5\")",
+        "A3=VALUE(\"50%\")",
+        "A4=VALUE(\"abc\")",
+        "A5=TEXT(1234.567,\"#,##0.00\")",
+        "A6=TEXT(0.256,\"0.0%\")",
+        "A7=TEXT(-5,\"$0\")",
+        "A8=TEXT(3.5,\"000\")",
+        "A9=TEXT(2.5,\"General\")",
+        "A10=TEXT(1234567,\"#,##0\")",
+    ]);
+    assert_eq!(arr[0], Cell::new_int(42));
+    assert_eq!(arr[10], Cell::new_float(2.5));
+    assert_eq!(arr[20], Cell::new_float(0.5));
+    assert_eq!(arr[30], Cell::error(ErrorKind::Value));
+    assert_eq!(arr[40], text("1,234.57"));
+    assert_eq!(arr[50], text("25.6%"));
+    assert_eq!(arr[60], text("-$5"));
+    assert_eq!(arr[70], text("004"));
+    assert_eq!(arr[80], text("2.5"));
+    assert_eq!(arr[90], text("1,234,567"));
+}
@@ -1,5 +1,8 @@
-use sheet::function_ext::{Cell, CellValue};
-use sheet::util_ext::{arithmetic_eval, return_optype};
+use sheet::function_ext::{Cell, CellValue, ErrorKind};
+use sheet::util_ext::{
+    arithmetic_eval, cell_label, column_label, compare_eval, concat_eval, return_optype,
+    text_value, truth_value,
+};
 
 #[test]
 fn test_arithmetic_eval_int_sub() {
@@ -128,7 +131,7 @@ fn test_arithmetic_eval_mixed_float_int_div_zero() {
 fn test_arithmetic_eval_float_int_invalid_op() {
     let c1 = Cell::new_float(2.5);
     let c2 = Cell::new_int(3);
-    let result = arithmetic_eval(c1, c2, '^');
+    let result = arithmetic_eval(c1, c2, '%');
     assert!(!result.is_valid);
 }
 #[test]
@@ -144,7 +147,7 @@ fn test_arithmetic_eval_int_mul() {
 fn test_arithmetic_eval_int_invalid_op() {
     let c1 = Cell::new_int(3);
     let c2 = Cell::new_int(4);
-    let result = arithmetic_eval(c1, c2, '^');
+    let result = arithmetic_eval(c1, c2, '%');
     assert!(!result.is_valid);
 }
 
@@ -200,6 +203,7 @@ fn test_arithmetic_eval_int_string() {
 }
 
 #[test]
+#[allow(clippy::approx_constant)]
 fn test_arithmetic_eval_float_string() {
     let c1 = Cell::new_float(3.14);
     let c2 = Cell::new_string("bad".into());
@@ -214,3 +218,142 @@ fn test_arithmetic_eval_debug_log_on_invalid_combo() {
     let result = arithmetic_eval(c1, c2, '+');
     assert!(!result.is_valid);
 }
+
+#[test]
+fn test_column_and_cell_labels() {
+    assert_eq!(column_label(0), "A");
+    assert_eq!(column_label(25), "Z");
+    assert_eq!(column_label(26), "AA");
+    assert_eq!(column_label(701), "ZZ");
+    assert_eq!(column_label(702), "AAA");
+    assert_eq!(cell_label(0, 10), "A1");
+    assert_eq!(cell_label(12, 10), "C2");
+}
+
+#[test]
+fn test_arithmetic_eval_error_kinds() {
+    assert_eq!(
+        arithmetic_eval(Cell::new_int(1), Cell::new_int(0), '/'),
+        Cell::error(ErrorKind::Div0)
+    );
+    assert_eq!(
+        arithmetic_eval(Cell::new_float(1.0), Cell::new_int(0), '/'),
+        Cell::error(ErrorKind::Div0)
+    );
+    assert_eq!(
+        arithmetic_eval(Cell::new_string("a".into()), Cell::new_int(1), '+'),
+        Cell::error(ErrorKind::Value)
+    );
+    assert_eq!(
+        arithmetic_eval(Cell::new_float(f64::MAX), Cell::new_float(f64::MAX), '*'),
+        Cell::error(ErrorKind::Num)
+    );
+}
+
+#[test]
+fn test_arithmetic_eval_propagates_first_error() {
+    let result = arithmetic_eval(Cell::error(ErrorKind::Ref), Cell::error(ErrorKind::Div0), '+');
+    assert_eq!(result, Cell::error(ErrorKind::Ref));
+    let result = arithmetic_eval(Cell::new_int(1), Cell::error(ErrorKind::Name), '*');
+    assert_eq!(result, Cell::error(ErrorKind::Name));
+}
+
+#[test]
+fn test_arithmetic_eval_int_overflow_promotes_to_float() {
+    let result = arithmetic_eval(Cell::new_int(i32::MAX), Cell::new_int(1), '+');
+    assert_eq!(result, Cell::new_float(i32::MAX as f64 + 1.0));
+    let result = arithmetic_eval(Cell::new_int(i32::MIN), Cell::new_int(-1), '/');
+    assert_eq!(result, Cell::new_float(-(i32::MIN as f64)));
+}
+
+#[test]
+fn test_compare_eval_numbers_and_strings() {
+    let yes = Cell::new_bool(true);
+    let no = Cell::new_bool(false);
+    assert_eq!(compare_eval(Cell::new_int(2), Cell::new_float(2.0), "="), yes);
+    assert_eq!(compare_eval(Cell::new_int(2), Cell::new_float(2.5), "<"), yes);
+    assert_eq!(compare_eval(Cell::new_int(3), Cell::new_int(3), "<>"), no);
+    assert_eq!(
+        compare_eval(Cell::new_string("Apple".into()), Cell::new_string("apple".into()), "="),
+        yes
+    );
+    assert_eq!(
+        compare_eval(Cell::new_string("apple".into()), Cell::new_string("banana".into()), ">="),
+        no
+    );
+    // Mixed types: numbers < strings < booleans.
+    assert_eq!(compare_eval(Cell::new_int(1), Cell::new_string("1".into()), "="), no);
+    assert_eq!(compare_eval(Cell::new_string("z".into()), Cell::new_bool(false), "<"), yes);
+    assert_eq!(
+        compare_eval(Cell::error(ErrorKind::Div0), Cell::new_int(1), "="),
+        Cell::error(ErrorKind::Div0)
+    );
+}
+
+#[test]
+fn test_bool_arithmetic_and_truth_value() {
+    assert_eq!(arithmetic_eval(Cell::new_bool(true), Cell::new_int(1), '+'), Cell::new_int(2));
+    assert_eq!(truth_value(&Cell::new_float(0.5)), Ok(true));
+    assert_eq!(truth_value(&Cell::new_int(0)), Ok(false));
+    assert_eq!(truth_value(&Cell::new_string("yes".into())), Err(ErrorKind::Value));
+    assert_eq!(truth_value(&Cell::error(ErrorKind::Ref)), Err(ErrorKind::Ref));
+}
+
+#[test]
+fn test_arithmetic_eval_power() {
+    assert_eq!(arithmetic_eval(Cell::new_int(2), Cell::new_int(10), '^'), Cell::new_int(1024));
+    assert_eq!(arithmetic_eval(Cell::new_int(2), Cell::new_int(-1), '^'), Cell::new_float(0.5));
+    assert_eq!(
+        arithmetic_eval(Cell::new_float(9.0), Cell::new_float(0.5), '^'),
+        Cell::new_float(3.0)
+    );
+    assert_eq!(
+        arithmetic_eval(Cell::new_int(2), Cell::new_int(40), '^'),
+        Cell::new_float(2f64.powi(40))
+    );
+    assert_eq!(
+        arithmetic_eval(Cell::new_int(0), Cell::new_int(-1), '^'),
+        Cell::error(ErrorKind::Div0)
+    );
+    assert_eq!(
+        arithmetic_eval(Cell::new_int(0), Cell::new_int(0), '^'),
+        Cell::error(ErrorKind::Num)
+    );
+    assert_eq!(
+        arithmetic_eval(Cell::new_int(-8), Cell::new_float(0.5), '^'),
+        Cell::error(ErrorKind::Num)
+    );
+}
+
+#[test]
+fn test_text_value_and_concat_eval() {
+    assert_eq!(text_value(&Cell::new_float(2.5)), Ok("2.5".to_string()));
+    assert_eq!(text_value(&Cell::new_float(3.0)), Ok("3".to_string()));
+    assert_eq!(text_value(&Cell::new_bool(true)), Ok("TRUE".to_string()));
+    assert_eq!(text_value(&Cell::empty()), Ok(String::new()));
+    assert_eq!(
+        concat_eval(Cell::new_string("n=".into()), Cell::new_int(4)),
+        Cell::new_string("n=4".into())
+    );
+    assert_eq!(
+        concat_eval(Cell::new_int(1), Cell::error(ErrorKind::Ref)),
+        Cell::error(ErrorKind::Ref)
+    );
+}
+
+#[test]
+fn test_arithmetic_eval_dates() {
+    let date = Cell::new_date(45748.0);
+    assert_eq!(arithmetic_eval(date.clone(), Cell::new_int(30), '+'), Cell::new_date(45778.0));
+    assert_eq!(arithmetic_eval(Cell::new_int(1), date.clone(), '+'), Cell::new_date(45749.0));
+    assert_eq!(arithmetic_eval(date.clone(), Cell::new_float(0.5), '-'), Cell::new_date(45747.5));
+    assert_eq!(arithmetic_eval(Cell::new_date(45778.0), date.clone(), '-'), Cell::new_int(30));
+    assert_eq!(arithmetic_eval(date.clone(), Cell::new_int(2), '*'), Cell::new_int(91496));
+    assert_eq!(
+        arithmetic_eval(date.clone(), Cell::new_string("x".into()), '+'),
+        Cell::error(ErrorKind::Value)
+    );
+    assert_eq!(compare_eval(date.clone(), Cell::new_int(45748), "="), Cell::new_bool(true));
+    assert_eq!(text_value(&date), Ok("2025-04-01".to_string()));
+    assert_eq!(text_value(&Cell::new_date(45748.75)), Ok("2025-04-01T18:00:00".to_string()));
+}
@@ -9,6 +9,8 @@ use crdt::{CLIENT_LIST, ClientListEvent, Column, Event, GRID_UPDATE, GridUpdateE
 use leptos::wasm_bindgen::JsCast;
 use leptos::{ev::SubmitEvent, html::Input, *};
 use rand::prelude::*;
+use sheet::structure_ext::{MAX_COLS, MAX_ROWS};
+use sheet::util_ext::column_label;
 use web_sys::WebSocket;
 
 /// Represents a change event in the grid.
@@ -37,7 +39,7 @@ pub fn App() -> impl IntoView {
     let (ws, set_ws) = create_signal::<Option<WebSocket>>(None);
     let (clients, set_clients) = create_signal(vec![]);
     let (data_change, set_data_change) = create_signal::<Option<ChangeEvent>>(None);
-    let (data, set_data) = create_signal(init_data());
+    let (data, set_data) = create_signal(init_data(DEFAULT_ROWS, DEFAULT_COLS));
     let (name, set_name) = create_signal(String::default());
 
     // Initialize WebSocket connection
@@ -51,42 +53,40 @@ pub fn App() -> impl IntoView {
             let set_data_clone = set_data;
 
             let onmessage = Closure::wrap(Box::new(move |ev: web_sys::MessageEvent| {
-                if let Some(msg) = ev.data().as_string() {
-                    if let Ok(evt) = serde_json::from_str::<Event>(&msg) {
-                        if evt.event_type == CLIENT_LIST {
-                            if let Ok(cl) = serde_json::from_value::<ClientListEvent>(evt.data) {
-                                set_clients_clone.update(|c| {
-                                    *c = cl
-                                        .clients
-                                        .into_iter()
-                                        .map(|c| c.name)
-                                        .collect::<Vec<String>>()
-                                });
-                            }
-                        } else if evt.event_type == GRID_UPDATE {
-                            if let Ok(m) = serde_json::from_value::<GridUpdateEvent>(evt.data) {
-                                // simple last-write-wins CRDT merge logic
-                                set_data_clone.update(|d| {
-                                    for i in 0..d.len() {
-                                        for j in 0..d[0].columns.len() {
-                                            let local = &d[i].columns[j];
-                                            let remote = &m.grid[i].columns[j];
-
-                                            if local.timestamp > remote.timestamp {
-                                                continue; // local version is newer - nothing to update
-                                            }
-
-                                            if local.timestamp == remote.timestamp && random() {
-                                                continue; // timestamps are the same, use one at random
-                                            }
+                if let Some(msg) = ev.data().as_string()
+                    && let Ok(evt) = serde_json::from_str::<Event>(&msg)
+                {
+                    if evt.event_type == CLIENT_LIST {
+                        if let Ok(cl) = serde_json::from_value::<ClientListEvent>(evt.data) {
+                            set_clients_clone.update(|c| {
+                                *c = cl.clients.into_iter().map(|c| c.name).collect::<Vec<String>>()
+                            });
+                        }
+                    } else if evt.event_type == GRID_UPDATE
+                        && let Ok(m) = serde_json::from_value::<GridUpdateEvent>(evt.data)
+                    {
+                        // simple last-write-wins CRDT merge logic
+                        set_data_clone.update(|d| {
+                            // a peer may have grown the grid; blank cells are older than any edit
+                            let cols = m.grid.first().map_or(0, |row| row.columns.len());
+                            grow_data(d, m.grid.len(), cols);
+                            for (local_row, remote_row) in d.iter_mut().zip(&m.grid) {
+                                for (local, remote) in
+                                    local_row.columns.iter_mut().zip(&remote_row.columns)
+                                {
+                                    if local.timestamp > remote.timestamp {
+                                        continue; // local version is newer - nothing to update
+                                    }
 
-                                            // overwrite local with remote
-                                            d[i].columns[j] = m.grid[i].columns[j].clone();
-                                        }
+                                    if local.timestamp == remote.timestamp && random() {
+                                        continue; // timestamps are the same, use one at random
                                     }
-                                });
+
+                                    // overwrite local with remote
+                                    *local = remote.clone();
+                                }
                             }
-                        }
+                        });
                     }
                 }
             }) as Box<dyn FnMut(_)>);
@@ -122,10 +122,10 @@ pub fn App() -> impl IntoView {
             })
             .expect("can be serialized");
 
-            if let Some(ws) = ws.get() {
-                if ws.ready_state() == WebSocket::OPEN {
-                    ws.send_with_str(&serialized).expect("Failed to send message");
-                }
+            if let Some(ws) = ws.get()
+                && ws.ready_state() == WebSocket::OPEN
+            {
+                ws.send_with_str(&serialized).expect("Failed to send message");
             }
         }
     });
@@ -136,7 +136,7 @@ pub fn App() -> impl IntoView {
                 <span class="hidden">{move || data_change.get().is_some()}</span>
                 <Connect ws={ws} set_name={set_name} />
                 <Clients clients={clients} />
-                <Grid data={data} set_data_change={set_data_change} />
+                <Grid data={data} set_data={set_data} set_data_change={set_data_change} />
             </div>
         </div>
     }
@@ -160,16 +160,16 @@ pub fn Connect(ws: ReadSignal<Option<WebSocket>>, set_name: WriteSignal<String>)
     let submit_handler = move |ev: SubmitEvent| {
         ev.prevent_default();
         let name = name_input.get().expect("input exists").value();
-        if let Some(ws) = ws.get() {
-            if ws.ready_state() == WebSocket::OPEN {
-                ws.send_with_str(&format!(
-                    r#"{{ "event_type": "INIT", "data": {{ "name": "{}" }} }}"#,
-                    name
-                ))
-                .expect("Failed to send message");
-                set_connected.update(|c| *c = true);
-                set_name.update(|n| *n = name);
-            }
+        if let Some(ws) = ws.get()
+            && ws.ready_state() == WebSocket::OPEN
+        {
+            ws.send_with_str(&format!(
+                r#"{{ "event_type": "INIT", "data": {{ "name": "{}" }} }}"#,
+                name
+            ))
+            .expect("Failed to send message");
+            set_connected.update(|c| *c = true);
+            set_name.update(|n| *n = name);
         }
     };
 
@@ -210,26 +210,47 @@ pub fn Clients(clients: ReadSignal<Vec<String>>) -> impl IntoView {
 /// A component that renders and manages the interactive grid.
 ///
 /// This component:
-/// 1. Renders a 10x10 grid of input cells
+/// 1. Renders the grid of input cells, whatever its current size
 /// 2. Handles user input and cell value changes
 /// 3. Manages the grid's state and updates
+/// 4. Grows the grid by a row or a column on request; peers pick up the new size with the
+///    next update
 ///
 /// # Props
 /// * `data` - A signal containing the grid data
+/// * `set_data` - A signal setter for the grid data
 /// * `set_data_change` - A signal setter for grid change events
 #[component]
 pub fn Grid(
     data: ReadSignal<Vec<Row>>,
+    set_data: WriteSignal<Vec<Row>>,
     set_data_change: WriteSignal<Option<ChangeEvent>>,
 ) -> impl IntoView {
+    let size = move || {
+        let d = data.get();
+        (d.len(), d.first().map_or(0, |row| row.columns.len()))
+    };
+    let add_row = move |_| {
+        let (rows, cols) = size();
+        set_data.update(|d| grow_data(d, rows + 1, cols));
+    };
+    let add_column = move |_| {
+        let (rows, cols) = size();
+        set_data.update(|d| grow_data(d, rows, cols + 1));
+    };
+
     view! {
         <div class="grid-container">
+            <div class="grid-actions">
+                <button on:click=add_row>"Add row"</button>
+                <button on:click=add_column>"Add column"</button>
+            </div>
             <table class="grid">
                 <thead>
                     <tr>
                         <th></th> // Empty corner cell
-                        {(0..10).map(|i| view! {
-                            <th>{char::from_u32(65 + i as u32).unwrap()}</th>
+                        {move || (0..size().1).map(|i| view! {
+                            <th>{column_label(i as i32)}</th>
                         }).collect_view()}
                     </tr>
                 </thead>
@@ -279,12 +300,33 @@ fn init_column(idx: usize) -> Column {
     Column { idx, value: String::default(), timestamp: 0, peer: String::default() }
 }
 
+/// Number of rows of a new grid.
+pub const DEFAULT_ROWS: usize = 10;
+
+/// Number of columns of a new grid.
+pub const DEFAULT_COLS: usize = 10;
+
 /// Initializes the grid data structure.
 ///
-/// Creates a 10x10 grid with default values for all cells.
+/// Creates a `rows` x `cols` grid with default values for all cells.
 ///
 /// # Returns
 /// A vector of `Row` instances representing the initial grid state
-pub fn init_data() -> Vec<Row> {
-    (0..10).map(|i| Row { idx: i, columns: (0..10).map(init_column).collect() }).collect()
+pub fn init_data(rows: usize, cols: usize) -> Vec<Row> {
+    (0..rows).map(|i| Row { idx: i, columns: (0..cols).map(init_column).collect() }).collect()
+}
+
+/// Grows the grid to at least `rows` x `cols` by appending blank rows and columns, up to the
+/// [`MAX_ROWS`] x [`MAX_COLS`] the engine supports.
+///
+/// Existing cells are kept; the grid never shrinks.
+pub fn grow_data(data: &mut Vec<Row>, rows: usize, cols: usize) {
+    let (rows, cols) = (rows.min(MAX_ROWS as usize), cols.min(MAX_COLS as usize));
+    let cols = cols.max(data.first().map_or(0, |row| row.columns.len()));
+    for row in data.iter_mut() {
+        let len = row.columns.len();
+        row.columns.extend((len..cols).map(init_column));
+    }
+    let len = data.len();
+    data.extend((len..rows).map(|i| Row { idx: i, columns: (0..cols).map(init_column).collect() }));
 }